@@ -0,0 +1,68 @@
+//! A visualizer plugin: outlined bars along the bottom of the frame that turn white on beats.
+//!
+//! Build it with `cargo build --release --example outline --features plugins` and copy the library from
+//! `target/release/examples/` (`liboutline.so`, `.dylib` or `outline.dll`) into the plugins directory, then render
+//! with `--plugin outline`.
+
+use std::ffi::{c_char, c_void};
+
+use audio_spectrum_generator::render::{PluginFrame, PLUGIN_ABI_VERSION};
+
+/// Read-only while drawing: frames are drawn on several threads at once.
+struct Outline {
+    color: [u8; 4],
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn asg_plugin_abi_version() -> u32 {
+    PLUGIN_ABI_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn asg_plugin_name() -> *const c_char {
+    c"outline".as_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn asg_plugin_create(_width: u32, _height: u32, _bars: u32) -> *mut c_void {
+    Box::into_raw(Box::new(Outline { color: [0, 200, 255, 255] })).cast()
+}
+
+/// # Safety
+/// Called by the host with the state from `asg_plugin_create`, `width * height` RGBA pixels and a valid frame.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn asg_plugin_draw(
+    state: *mut c_void,
+    rgba: *mut u8,
+    width: u32,
+    height: u32,
+    frame: *const PluginFrame,
+) {
+    let (outline, frame) = unsafe { (&*state.cast::<Outline>(), &*frame) };
+    let pixels = unsafe { std::slice::from_raw_parts_mut(rgba, width as usize * height as usize * 4) };
+    let bars = unsafe { std::slice::from_raw_parts(frame.bars, frame.bar_count) };
+    let color = if frame.beat { [255; 4] } else { outline.color };
+    let slot = width as usize / bars.len().max(1);
+    if slot < 2 || height == 0 {
+        return;
+    }
+    for (i, &value) in bars.iter().enumerate() {
+        let top = (height as usize - (value.clamp(0.0, 1.0) * height as f32 / 2.0) as usize).min(height as usize - 1);
+        let (left, right) = (i * slot, (i + 1) * slot - 1);
+        for y in top..height as usize {
+            for x in [left, right] {
+                pixels[(y * width as usize + x) * 4..][..4].copy_from_slice(&color);
+            }
+        }
+        for x in left..=right {
+            pixels[(top * width as usize + x) * 4..][..4].copy_from_slice(&color);
+        }
+    }
+}
+
+/// # Safety
+/// Called once with the state from `asg_plugin_create`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn asg_plugin_destroy(state: *mut c_void) {
+    drop(unsafe { Box::from_raw(state.cast::<Outline>()) });
+}
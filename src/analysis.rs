@@ -0,0 +1,136 @@
+//! Analysis of a whole audio file in one call, for apps that want the spectra, levels and beats without rendering
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::{Config, SmoothingKernel};
+use crate::decode::{decode_mp3, DecodedAudio};
+use crate::envelope::compute_frame_envelopes;
+use crate::export::{frame_analyses, FrameAnalysis};
+use crate::level::{compute_frame_levels, compute_track_levels, TrackLevels};
+use crate::spectrum::{bar_heights, compute_all_spectrums, spectrum_index};
+
+/// What `analyze_file` computes, with the command line's defaults.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalysisOptions {
+    /// Video frames per second the per-frame results are sampled at.
+    pub fps: u32,
+    pub bars: usize,
+    /// FFT window in samples, a power of two from 64.
+    pub fft_size: usize,
+    /// Fraction of each FFT window shared with the next (0.0 up to, not including, 1.0).
+    pub overlap: f32,
+    /// Spectral tilt compensation (dB per octave, relative to 1 kHz).
+    pub tilt: f32,
+    /// Power-law exponent applied to the normalized bars.
+    pub amp_gamma: f32,
+    /// Radius (in bars) of smoothing across neighboring bars (0 = off).
+    pub bar_smoothing: usize,
+    pub bar_smoothing_kernel: SmoothingKernel,
+    /// Lowest bars whose level beats are detected on.
+    pub bass_bars: usize,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        let config = Config::default();
+        Self {
+            fps: config.fps,
+            bars: config.bars,
+            fft_size: config.fft_size,
+            overlap: config.overlap,
+            tilt: config.tilt,
+            amp_gamma: config.amp_gamma,
+            bar_smoothing: config.bar_smoothing,
+            bar_smoothing_kernel: config.bar_smoothing_kernel,
+            bass_bars: 4,
+        }
+    }
+}
+
+/// Everything `analyze_file` found.
+#[derive(Debug, Serialize)]
+pub struct AnalysisResult {
+    pub sample_rate: u32,
+    /// Length of the decoded audio in seconds.
+    pub duration_sec: f32,
+    pub fps: u32,
+    /// Raw bar magnitudes of every FFT window (`compute_all_spectrums`), lowest frequency first.
+    pub spectra: Vec<Vec<f32>>,
+    /// Largest magnitude in `spectra`, which the frames' bars are normalized by.
+    pub max_magnitude: f32,
+    /// One per video frame: normalized and smoothed bars as they would be drawn, levels and beats.
+    pub frames: Vec<FrameAnalysis>,
+    /// Whole-track peak, RMS and loudness (over both channels of a stereo file).
+    pub track: TrackLevels,
+}
+
+/// Decode `path` and analyze it as a render with `options` would, without drawing anything.
+pub fn analyze_file(
+    path: &Path,
+    options: &AnalysisOptions,
+) -> Result<AnalysisResult, Box<dyn std::error::Error + Send + Sync>> {
+    let config = analysis_config(options)?;
+    let decoded = decode_mp3(path, true, None, |_, _| {})?;
+    Ok(analyze_decoded(&decoded, &config, options.bass_bars))
+}
+
+/// The `Config` the analysis functions take, with `options`' values checked by the builder's setters.
+fn analysis_config(options: &AnalysisOptions) -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+    let builder = Config::builder()
+        .fps(options.fps)?
+        .bars(options.bars)?
+        .fft_size(options.fft_size)?
+        .overlap(options.overlap)?
+        .amp_gamma(options.amp_gamma)?
+        .with(|config| {
+            config.tilt = options.tilt;
+            config.bar_smoothing = options.bar_smoothing;
+            config.bar_smoothing_kernel = options.bar_smoothing_kernel;
+        });
+    Ok(builder.build_unplaced())
+}
+
+fn analyze_decoded(decoded: &DecodedAudio, config: &Config, bass_bars: usize) -> AnalysisResult {
+    let (samples, sample_rate, fps) = (&decoded.samples, decoded.sample_rate, config.fps);
+    let (spectra, max_magnitude) =
+        compute_all_spectrums(samples, sample_rate, config.fft_size, config.overlap, config.bars, config.tilt);
+    let duration_sec = samples.len() as f32 / sample_rate as f32;
+    let total_frames = (duration_sec * fps as f32).ceil().max(1.0) as usize;
+    let norm = if max_magnitude > 0.0 { max_magnitude } else { 1.0 };
+    let levels = compute_frame_levels(samples, sample_rate, fps, total_frames);
+    let envelopes = compute_frame_envelopes(samples, sample_rate, fps, total_frames);
+    let bars_at = |frame| bar_heights(&spectra, norm, spectrum_index(frame, spectra.len(), total_frames), config);
+    let frames = frame_analyses(total_frames, fps, bass_bars, bars_at, &levels, &envelopes);
+    let channels: Vec<&[f32]> = match decoded.stereo {
+        Some((ref left, ref right)) => vec![left, right],
+        None => vec![samples],
+    };
+    let track = compute_track_levels(&channels, sample_rate);
+    AnalysisResult { sample_rate, duration_sec, fps, spectra, max_magnitude, frames, track }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analysis_config, analyze_decoded, AnalysisOptions};
+    use crate::decode::DecodedAudio;
+
+    #[test]
+    fn a_tone_is_analyzed_per_frame_and_bad_options_are_rejected() {
+        let sample_rate = 8000;
+        let samples: Vec<f32> = (0..sample_rate * 2)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+        let decoded = DecodedAudio { samples, sample_rate, stereo: None, tags: Default::default() };
+        let options = AnalysisOptions { fps: 10, bars: 16, ..Default::default() };
+        let result = analyze_decoded(&decoded, &analysis_config(&options).unwrap(), options.bass_bars);
+        assert_eq!(result.frames.len(), 20);
+        assert!(result.spectra.iter().all(|spectrum| spectrum.len() == 16));
+        assert!(result.frames[10].bars.iter().any(|&bar| bar > 0.5));
+        assert!((result.track.peak - 0.5).abs() < 0.01);
+
+        let err = analysis_config(&AnalysisOptions { fft_size: 1000, ..Default::default() }).unwrap_err();
+        assert_eq!(err.to_string(), "fft_size must be a power of two from 64, got 1000");
+    }
+}
@@ -0,0 +1,17 @@
+//! Analysis of decoded samples: spectra, the bar heights drawn from them, levels and beats. The `*Stream` types take
+//! the samples chunk by chunk, as `decode::AudioDecoder::stream` hands them over.
+
+#[cfg(feature = "symphonia")]
+pub use crate::analysis::{analyze_file, AnalysisOptions, AnalysisResult};
+pub use crate::beat::BeatDetector;
+pub use crate::envelope::{
+    compute_frame_envelopes, frame_window, resample_envelope, EnvelopePoint, EnvelopeStream, FrameWindows,
+};
+pub use crate::export::{frame_analyses, FrameAnalysis};
+pub use crate::level::{
+    compute_frame_levels, compute_track_levels, FrameLevel, FrameLevelStream, TrackLevelStream, TrackLevels,
+};
+pub use crate::spectrum::{
+    band_energy_shares, bar_heights, compute_all_spectrums, spectrum_index, BandEnergyStream, SpectrumAnalysis,
+    SpectrumStream, FREQUENCY_BANDS,
+};
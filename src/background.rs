@@ -0,0 +1,395 @@
+//! Per-frame background rendering (slow zoom/pan of the background image, background video frames, bass pulsing)
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::JoinHandle;
+
+use image::imageops::FilterType;
+use image::RgbaImage;
+
+use crate::beat::bass_energy;
+use crate::error::SpectrumError;
+use crate::logging::warning;
+use crate::output::ffprobe_path;
+
+/// Slow camera motion over the background image across the video's duration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum BgMotion {
+    /// Zoom in toward the center.
+    Zoom,
+    /// Pan from left to right at a fixed zoom.
+    Pan,
+    /// Zoom in while drifting from the upper left toward the lower right.
+    Kenburns,
+}
+
+/// How much larger than the frame the background source is prepared, i.e. the maximum zoom factor.
+pub const MOTION_SCALE: f32 = 1.2;
+
+/// Size (w, h) to prepare the background image at when a motion is used, given the output frame size.
+pub fn motion_source_size(width: u32, height: u32) -> (u32, u32) {
+    (
+        (width as f32 * MOTION_SCALE).round() as u32,
+        (height as f32 * MOTION_SCALE).round() as u32,
+    )
+}
+
+/// Crop rectangle (x, y, w, h) within a `src_w` x `src_h` source for the frame at `progress` (0.0–1.0).
+/// The rectangle keeps the frame's aspect ratio (`out_w` x `out_h`) and always lies inside the source.
+pub fn motion_crop(
+    motion: BgMotion,
+    src_w: u32,
+    src_h: u32,
+    out_w: u32,
+    out_h: u32,
+    progress: f32,
+) -> (u32, u32, u32, u32) {
+    let t = if progress.is_finite() { progress.clamp(0.0, 1.0) } else { 0.0 };
+    // Ease in and out so the motion starts and stops gently.
+    let t = t * t * (3.0 - 2.0 * t);
+    let (sw, sh) = (src_w as f32, src_h as f32);
+    // Largest frame-aspect rectangle that fits in the source.
+    let fit = (sw / out_w.max(1) as f32).min(sh / out_h.max(1) as f32);
+    let (full_w, full_h) = (out_w as f32 * fit, out_h as f32 * fit);
+    let (zoomed_w, zoomed_h) = (full_w / MOTION_SCALE, full_h / MOTION_SCALE);
+
+    // (crop size, crop offset as a fraction of the free space on each axis)
+    let ((w, h), (fx, fy)) = match motion {
+        BgMotion::Zoom => ((lerp(full_w, zoomed_w, t), lerp(full_h, zoomed_h, t)), (0.5, 0.5)),
+        BgMotion::Pan => ((zoomed_w, zoomed_h), (t, 0.5)),
+        BgMotion::Kenburns => ((lerp(full_w, zoomed_w, t), lerp(full_h, zoomed_h, t)), (t, t)),
+    };
+    let w = w.round().clamp(1.0, sw);
+    let h = h.round().clamp(1.0, sh);
+    let x = ((sw - w) * fx).round();
+    let y = ((sh - h) * fy).round();
+    (x as u32, y as u32, w as u32, h as u32)
+}
+
+/// Background for the frame at `progress`: the `motion` crop of `source`, scaled to `width` x `height`.
+pub fn render_background(source: &RgbaImage, motion: BgMotion, width: u32, height: u32, progress: f32) -> RgbaImage {
+    let (x, y, w, h) = motion_crop(motion, source.width(), source.height(), width, height, progress);
+    let crop = image::imageops::crop_imm(source, x, y, w, h).to_image();
+    if w == width && h == height {
+        crop
+    } else {
+        image::imageops::resize(&crop, width, height, FilterType::Triangle)
+    }
+}
+
+/// How the background reacts to bass energy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum BgPulse {
+    /// Zoom into the background's center.
+    Scale,
+    /// Brighten the background.
+    Brightness,
+}
+
+/// Per-frame decay of the followed bass level (fast attack, slow release).
+const BASS_RELEASE: f32 = 0.85;
+
+/// Follows low-frequency energy across frames: the mean of the lowest bars, rising instantly and decaying
+/// by `BASS_RELEASE` per frame so the background does not flicker.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BassFollower {
+    level: f32,
+}
+
+impl BassFollower {
+    /// Feed one frame's normalized bar heights (low to high frequency) and return the level (0.0–1.0).
+    pub fn update(&mut self, bar_heights: &[f32], bass_bars: usize) -> f32 {
+        self.level = bass_energy(bar_heights, bass_bars).max(self.level * BASS_RELEASE);
+        self.level
+    }
+}
+
+/// Apply `pulse` to `bg` for bass `level` (0.0–1.0); `amount` is the effect at full level
+/// (extra zoom for `Scale`, e.g. 0.05 = 5%; brightness gain for `Brightness`, e.g. 0.2 = +20%).
+pub fn pulse_background(bg: &RgbaImage, pulse: BgPulse, amount: f32, level: f32) -> RgbaImage {
+    let k = amount.max(0.0) * level.clamp(0.0, 1.0);
+    match pulse {
+        BgPulse::Scale => {
+            let (w, h) = bg.dimensions();
+            let (cw, ch) = (
+                ((w as f32 / (1.0 + k)).round() as u32).clamp(1, w.max(1)),
+                ((h as f32 / (1.0 + k)).round() as u32).clamp(1, h.max(1)),
+            );
+            if (cw, ch) == (w, h) {
+                return bg.clone();
+            }
+            let crop = image::imageops::crop_imm(bg, (w - cw) / 2, (h - ch) / 2, cw, ch).to_image();
+            image::imageops::resize(&crop, w, h, FilterType::Triangle)
+        }
+        BgPulse::Brightness => {
+            let mut out = bg.clone();
+            for p in out.pixels_mut() {
+                for c in &mut p.0[..3] {
+                    *c = (*c as f32 * (1.0 + k)).round().min(255.0) as u8;
+                }
+            }
+            out
+        }
+    }
+}
+
+/// How a background video is matched to the audio's duration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BgVideoFit {
+    /// Play the clip repeatedly from the start.
+    #[default]
+    Loop,
+    /// Slow down or speed up the clip so it spans the whole audio once.
+    Stretch,
+}
+
+/// Frames of a background video decoded by an ffmpeg subprocess, scaled and cropped to fill the frame and
+/// resampled to the output fps. Raw RGBA frames are read from ffmpeg's stdout one at a time.
+pub struct VideoFrames {
+    child: Child,
+    stdout: ChildStdout,
+    /// ffmpeg's error output, collected on a thread until it exits.
+    stderr: Option<JoinHandle<String>>,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    /// The first frame, read by `open` to check the clip decodes.
+    first: Option<RgbaImage>,
+    last: Option<RgbaImage>,
+}
+
+impl VideoFrames {
+    /// Start decoding `path` with the `ffmpeg` binary at `size` and `fps`, fitted to `duration_sec` of output by
+    /// `fit`. `blur` applies a Gaussian blur (sigma, pixels) in ffmpeg. Fails with ffmpeg's message when the clip
+    /// yields no frame.
+    pub fn open(
+        ffmpeg: &Path,
+        path: &Path,
+        (width, height): (u32, u32),
+        fps: u32,
+        fit: BgVideoFit,
+        duration_sec: f32,
+        blur: Option<f32>,
+    ) -> Result<Self, SpectrumError> {
+        let mut filters = Vec::new();
+        if fit == BgVideoFit::Stretch {
+            let clip_sec = probe_duration(&ffprobe_path(ffmpeg), path)?;
+            filters.push(format!("setpts=PTS*{}", duration_sec as f64 / clip_sec));
+        }
+        filters.push(format!("fps={}", fps));
+        filters.push(format!(
+            "scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h}",
+            w = width,
+            h = height
+        ));
+        if let Some(sigma) = blur {
+            filters.push(format!("gblur=sigma={}", sigma));
+        }
+
+        let mut cmd = Command::new(ffmpeg);
+        cmd.args(["-v", "error"]);
+        if fit == BgVideoFit::Loop {
+            cmd.args(["-stream_loop", "-1"]);
+        }
+        let mut child = cmd
+            .arg("-i")
+            .arg(path)
+            .args(["-an", "-vf", &filters.join(","), "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| start_error(ffmpeg, path, e))?;
+        let stdout = child.stdout.take().expect("ffmpeg stdout is piped");
+        let mut stderr = child.stderr.take().expect("ffmpeg stderr is piped");
+        let stderr = std::thread::spawn(move || {
+            let mut log = String::new();
+            let _ = stderr.read_to_string(&mut log);
+            log
+        });
+        let mut video = Self {
+            child,
+            stdout,
+            stderr: Some(stderr),
+            path: path.to_path_buf(),
+            width,
+            height,
+            first: None,
+            last: None,
+        };
+        video.first = video.read_frame();
+        if video.first.is_none() {
+            let log = video.ffmpeg_log();
+            let reason = if log.is_empty() { "no frames decoded".to_string() } else { log };
+            return Err(video_error(path, reason));
+        }
+        Ok(video)
+    }
+
+    fn read_frame(&mut self) -> Option<RgbaImage> {
+        let mut buf = vec![0u8; self.width as usize * self.height as usize * 4];
+        self.stdout.read_exact(&mut buf).ok()?;
+        RgbaImage::from_raw(self.width, self.height, buf)
+    }
+
+    /// ffmpeg's error output, trimmed, once it has exited; empty after the first call.
+    fn ffmpeg_log(&mut self) -> String {
+        let _ = self.child.wait();
+        let log = self.stderr.take().and_then(|handle| handle.join().ok()).unwrap_or_default();
+        log.trim().to_string()
+    }
+
+    /// Next video frame. Once the clip runs out (e.g. a stretched clip a frame short), the last frame is repeated,
+    /// with a warning if ffmpeg stopped on an error.
+    pub fn next_frame(&mut self) -> Option<RgbaImage> {
+        match self.first.take().or_else(|| self.read_frame()) {
+            Some(frame) => self.last = Some(frame),
+            None if self.stderr.is_some() => {
+                let log = self.ffmpeg_log();
+                if !log.is_empty() {
+                    warning!("background video {:?} stopped early, repeating its last frame: {}", self.path, log);
+                }
+            }
+            None => {}
+        }
+        self.last.clone()
+    }
+}
+
+impl Drop for VideoFrames {
+    fn drop(&mut self) {
+        // A looping clip never ends on its own.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Container duration of a media file in seconds, via `ffprobe`.
+fn probe_duration(ffprobe: &Path, path: &Path) -> Result<f64, SpectrumError> {
+    let output = Command::new(ffprobe)
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .map_err(|e| start_error(ffprobe, path, e))?;
+    parse_duration(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| video_error(path, "ffprobe can't read its duration".into()))
+}
+
+/// The background video at `path` can't be used, for `reason`.
+fn video_error(path: &Path, reason: String) -> SpectrumError {
+    SpectrumError::AssetLoad { what: "background video", path: path.to_path_buf(), source: reason.into() }
+}
+
+/// `FfmpegNotFound` when `program` isn't there, a `video_error` for anything else keeping it from starting.
+fn start_error(program: &Path, path: &Path, e: std::io::Error) -> SpectrumError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => SpectrumError::FfmpegNotFound(program.to_path_buf()),
+        _ => video_error(path, format!("failed to start {:?}: {}", program, e)),
+    }
+}
+
+/// Parse ffprobe's duration output; None unless it is a positive number of seconds.
+fn parse_duration(s: &str) -> Option<f64> {
+    s.trim().parse::<f64>().ok().filter(|d| d.is_finite() && *d > 0.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        motion_crop, motion_source_size, parse_duration, pulse_background, render_background, BassFollower, BgMotion,
+        BgPulse, BgVideoFit, VideoFrames,
+    };
+    use image::{ImageBuffer, Rgba};
+
+    #[cfg(unix)]
+    #[test]
+    fn a_clip_ffmpeg_cannot_read_fails_with_its_message() {
+        use std::os::unix::fs::PermissionsExt;
+        let ffmpeg = std::env::temp_dir().join(format!("asg-bg-ffmpeg-{}", std::process::id()));
+        std::fs::write(&ffmpeg, "#!/bin/sh\necho 'clip.mp4: Invalid data found when processing input' >&2\nexit 1\n")
+            .unwrap();
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let opened = VideoFrames::open(&ffmpeg, "clip.mp4".as_ref(), (4, 4), 30, BgVideoFit::Loop, 1.0, None);
+        std::fs::remove_file(&ffmpeg).ok();
+        let error = opened.err().unwrap().to_string();
+        assert!(error.contains("Invalid data found"), "{}", error);
+    }
+
+    #[test]
+    fn motion_source_size_adds_headroom() {
+        assert_eq!(motion_source_size(100, 50), (120, 60));
+    }
+
+    #[test]
+    fn zoom_starts_full_and_ends_centered() {
+        assert_eq!(motion_crop(BgMotion::Zoom, 120, 60, 100, 50, 0.0), (0, 0, 120, 60));
+        assert_eq!(motion_crop(BgMotion::Zoom, 120, 60, 100, 50, 1.0), (10, 5, 100, 50));
+    }
+
+    #[test]
+    fn pan_moves_left_to_right_at_fixed_size() {
+        let (x0, y0, w0, h0) = motion_crop(BgMotion::Pan, 120, 60, 100, 50, 0.0);
+        let (x1, y1, w1, h1) = motion_crop(BgMotion::Pan, 120, 60, 100, 50, 1.0);
+        assert_eq!((w0, h0), (w1, h1));
+        assert_eq!(x0, 0);
+        assert_eq!(x1 + w1, 120);
+        assert_eq!(y0, y1);
+    }
+
+    #[test]
+    fn kenburns_crop_stays_inside_source() {
+        for i in 0..=10 {
+            let (x, y, w, h) = motion_crop(BgMotion::Kenburns, 120, 60, 100, 50, i as f32 / 10.0);
+            assert!(x + w <= 120 && y + h <= 60, "step {}", i);
+        }
+        assert_eq!(motion_crop(BgMotion::Kenburns, 120, 60, 100, 50, 1.0), (20, 10, 100, 50));
+    }
+
+    #[test]
+    fn render_background_outputs_frame_size() {
+        let source = ImageBuffer::from_pixel(120, 60, Rgba([10, 20, 30, 255]));
+        let bg = render_background(&source, BgMotion::Zoom, 100, 50, 0.3);
+        assert_eq!(bg.dimensions(), (100, 50));
+        assert_eq!(bg.get_pixel(50, 25).0, [10, 20, 30, 255]);
+    }
+
+    #[test]
+    fn parse_duration_accepts_positive_seconds() {
+        assert_eq!(parse_duration("12.500000\n"), Some(12.5));
+        assert_eq!(parse_duration("N/A"), None);
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn bass_follower_attacks_fast_and_releases_slowly() {
+        let mut f = BassFollower::default();
+        assert_eq!(f.update(&[1.0, 1.0, 0.0, 0.0], 2), 1.0);
+        let decayed = f.update(&[0.0; 4], 2);
+        assert!(decayed < 1.0 && decayed > 0.5, "decayed = {}", decayed);
+        // A new peak above the decaying level takes over immediately.
+        assert_eq!(f.update(&[0.9, 0.9, 0.0, 0.0], 2), 0.9);
+        assert_eq!(BassFollower::default().update(&[], 4), 0.0);
+    }
+
+    #[test]
+    fn pulse_background_brightness_scales_rgb() {
+        let bg = ImageBuffer::from_pixel(2, 2, Rgba([100, 200, 0, 255]));
+        let out = pulse_background(&bg, BgPulse::Brightness, 0.5, 1.0);
+        assert_eq!(out.get_pixel(0, 0).0, [150, 255, 0, 255]);
+        assert_eq!(pulse_background(&bg, BgPulse::Brightness, 0.5, 0.0).get_pixel(0, 0).0, [100, 200, 0, 255]);
+    }
+
+    #[test]
+    fn pulse_background_scale_zooms_center() {
+        let bg = ImageBuffer::from_fn(20, 20, |x, _| if x < 2 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 255]) });
+        let out = pulse_background(&bg, BgPulse::Scale, 0.5, 1.0);
+        assert_eq!(out.dimensions(), (20, 20));
+        // The red left edge is cropped away when zoomed in.
+        assert_eq!(out.get_pixel(0, 10).0, [0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Beat (onset) detection on the per-frame bass energy
+
+use std::collections::VecDeque;
+
+/// Length of the recent-energy window a beat must stand out from (seconds).
+const HISTORY_SEC: f32 = 1.0;
+
+/// A frame is a beat when its bass energy exceeds the recent average by this factor.
+const BEAT_RATIO: f32 = 1.4;
+
+/// Bass energy (0.0–1.0) below which no beat fires, so quiet passages stay calm.
+const MIN_ENERGY: f32 = 0.15;
+
+/// Shortest time between beats (seconds), i.e. at most 240 BPM.
+const MIN_INTERVAL_SEC: f32 = 0.25;
+
+/// Mean of the lowest `bass_bars` normalized bar heights (0.0–1.0).
+pub fn bass_energy(bar_heights: &[f32], bass_bars: usize) -> f32 {
+    let n = bass_bars.min(bar_heights.len());
+    if n == 0 {
+        return 0.0;
+    }
+    bar_heights[..n]
+        .iter()
+        .map(|h| if h.is_finite() { h.clamp(0.0, 1.0) } else { 0.0 })
+        .sum::<f32>()
+        / n as f32
+}
+
+/// Detects beats as sudden rises of bass energy over its average across the last `HISTORY_SEC`.
+#[derive(Clone, Debug)]
+pub struct BeatDetector {
+    history: VecDeque<f32>,
+    capacity: usize,
+    min_gap_frames: usize,
+    frames_since_beat: usize,
+}
+
+impl BeatDetector {
+    pub fn new(fps: u32) -> Self {
+        let capacity = ((fps as f32 * HISTORY_SEC).round() as usize).max(1);
+        let min_gap_frames = (fps as f32 * MIN_INTERVAL_SEC).round() as usize;
+        Self {
+            history: VecDeque::with_capacity(capacity),
+            capacity,
+            min_gap_frames,
+            frames_since_beat: usize::MAX,
+        }
+    }
+
+    /// Feed one frame's normalized bar heights (low to high frequency); true when a beat starts on this frame.
+    pub fn update(&mut self, bar_heights: &[f32], bass_bars: usize) -> bool {
+        let energy = bass_energy(bar_heights, bass_bars);
+        let average = if self.history.is_empty() {
+            0.0
+        } else {
+            self.history.iter().sum::<f32>() / self.history.len() as f32
+        };
+        let beat = energy >= MIN_ENERGY
+            && energy > average * BEAT_RATIO
+            && self.frames_since_beat >= self.min_gap_frames;
+        self.frames_since_beat = if beat { 0 } else { self.frames_since_beat.saturating_add(1) };
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(energy);
+        beat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BeatDetector;
+
+    #[test]
+    fn steady_level_fires_only_at_onset() {
+        let mut beats = BeatDetector::new(30);
+        let fired: Vec<bool> = (0..30).map(|_| beats.update(&[0.8; 4], 4)).collect();
+        assert!(fired[0]);
+        assert!(fired[1..].iter().all(|&b| !b));
+    }
+
+    #[test]
+    fn kicks_over_quiet_floor_fire_with_min_interval() {
+        let mut beats = BeatDetector::new(20);
+        let mut fired = Vec::new();
+        for frame in 0..60 {
+            // A kick every 10 frames (0.5 s), plus one 2 frames after the second kick that is too soon to count.
+            let kick = frame % 10 == 0 || frame == 12;
+            if beats.update(&[if kick { 0.9 } else { 0.2 }; 4], 4) {
+                fired.push(frame);
+            }
+        }
+        assert_eq!(fired, vec![0, 10, 20, 30, 40, 50]);
+    }
+
+    #[test]
+    fn silence_never_fires() {
+        let mut beats = BeatDetector::new(30);
+        assert!((0..10).all(|_| !beats.update(&[0.1; 4], 4)));
+    }
+}
@@ -0,0 +1,201 @@
+//! Chapter markers from a CUE sheet or a timestamp list, and their FFMETADATA form for ffmpeg
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::duration::parse_clock;
+use crate::error::SpectrumError;
+use crate::subtitles::Cue;
+
+/// Read chapters from `path`: a CUE sheet for a `.cue` extension, otherwise a timestamp list. Each chapter lasts
+/// until the next one starts; the last until `duration_sec`.
+pub fn load_chapters(path: &Path, duration_sec: f32) -> Result<Vec<Cue>, SpectrumError> {
+    let failed = |source| SpectrumError::AssetLoad { what: "chapters", path: path.to_path_buf(), source };
+    let content = std::fs::read_to_string(path).map_err(|e| failed(e.into()))?;
+    let content = content.trim_start_matches('\u{feff}');
+    let is_cue = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cue"));
+    let starts = if is_cue { parse_cue_sheet(content) } else { parse_timestamp_list(content) };
+    let starts = starts.map_err(|e| failed(e.into()))?;
+    if starts.is_empty() {
+        return Err(failed("no chapters found".into()));
+    }
+    Ok(chapters_from_starts(starts, duration_sec))
+}
+
+/// Turn `(start, title)` pairs into cues that end where the next chapter starts.
+fn chapters_from_starts(mut starts: Vec<(f32, String)>, duration_sec: f32) -> Vec<Cue> {
+    starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+    (0..starts.len())
+        .map(|i| {
+            let (start, ref title) = starts[i];
+            let end = starts.get(i + 1).map_or(duration_sec, |(t, _)| *t);
+            Cue { start, end: end.max(start), text: title.clone() }
+        })
+        .collect()
+}
+
+/// A `TRACK` of a CUE sheet while it is being read.
+struct CueTrack {
+    number: u32,
+    title: Option<String>,
+    start: Option<f32>,
+}
+
+impl CueTrack {
+    fn into_chapter(self) -> Result<(f32, String), String> {
+        let start = self.start.ok_or_else(|| format!("track {} has no INDEX 01", self.number))?;
+        Ok((start, self.title.unwrap_or_else(|| format!("Track {}", self.number))))
+    }
+}
+
+/// Parse a single-file CUE sheet: each `TRACK` takes its `TITLE` (or "Track N") and its `INDEX 01 mm:ss:ff` time
+/// (75 frames per second).
+pub fn parse_cue_sheet(content: &str) -> Result<Vec<(f32, String)>, String> {
+    let mut chapters = Vec::new();
+    let mut files = 0;
+    let mut track: Option<CueTrack> = None;
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                files += 1;
+                if files > 1 {
+                    return Err(format!("line {}: cue sheets with more than one FILE are not supported", n + 1));
+                }
+            }
+            "TRACK" => {
+                if let Some(track) = track.take() {
+                    chapters.push(track.into_chapter()?);
+                }
+                let number = rest.split_whitespace().next().and_then(|n| n.parse().ok());
+                let number = number.ok_or_else(|| format!("line {}: invalid TRACK {:?}", n + 1, rest))?;
+                track = Some(CueTrack { number, title: None, start: None });
+            }
+            // A TITLE before the first TRACK is the album title.
+            "TITLE" => {
+                if let Some(ref mut track) = track {
+                    track.title = Some(rest.trim_matches('"').to_string());
+                }
+            }
+            "INDEX" => {
+                let (index, time) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if index == "01"
+                    && let Some(ref mut track) = track
+                {
+                    let time = time.trim();
+                    let start = parse_cue_time(time).ok_or_else(|| format!("line {}: invalid time {:?}", n + 1, time))?;
+                    track.start = Some(start);
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(track) = track {
+        chapters.push(track.into_chapter()?);
+    }
+    Ok(chapters)
+}
+
+/// Parse a timestamp list: one chapter per line, a time (`mm:ss`, `h:mm:ss`, optionally with a fraction) followed by
+/// the title, as in video descriptions (`0:00 Intro`, `3:25 - Second song`). Blank lines and `#` comments are
+/// skipped.
+pub fn parse_timestamp_list(content: &str) -> Result<Vec<(f32, String)>, String> {
+    let mut chapters = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (time, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let start = parse_clock(time).ok_or_else(|| format!("line {}: invalid time {:?}", n + 1, time))?;
+        let title = title.trim().trim_start_matches(['-', '–', '—']).trim();
+        if title.is_empty() {
+            return Err(format!("line {}: missing chapter title", n + 1));
+        }
+        chapters.push((start, title.to_string()));
+    }
+    Ok(chapters)
+}
+
+/// `mm:ss:ff` with 75 frames per second.
+fn parse_cue_time(s: &str) -> Option<f32> {
+    let mut parts = s.splitn(3, ':');
+    let m: u32 = parts.next()?.parse().ok()?;
+    let sec: u32 = parts.next()?.parse().ok()?;
+    let frames: u32 = parts.next()?.parse().ok()?;
+    Some(m as f32 * 60.0 + sec as f32 + frames as f32 / 75.0)
+}
+
+/// The chapters as an FFMETADATA file, read by ffmpeg as an extra input (`-map_chapters`).
+pub fn ffmetadata(chapters: &[Cue]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        let ms = |t: f32| (t * 1000.0).round() as u64;
+        let _ = write!(
+            out,
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            ms(chapter.start),
+            ms(chapter.end),
+            escape_metadata(&chapter.text)
+        );
+    }
+    out
+}
+
+/// Backslash-escape the characters FFMETADATA treats as syntax.
+fn escape_metadata(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chapters_from_starts, ffmetadata, parse_cue_sheet, parse_timestamp_list};
+
+    #[test]
+    fn cue_sheet_tracks_become_chapters() {
+        let cue = [
+            "PERFORMER \"Band\"",
+            "TITLE \"Album\"",
+            "FILE \"album.mp3\" MP3",
+            "  TRACK 01 AUDIO",
+            "    TITLE \"Opening\"",
+            "    INDEX 01 00:00:00",
+            "  TRACK 02 AUDIO",
+            "    INDEX 00 03:10:00",
+            "    INDEX 01 03:12:37",
+        ]
+        .join("\n");
+        let chapters = parse_cue_sheet(&cue).unwrap();
+        assert_eq!(chapters, [(0.0, "Opening".to_string()), (192.0 + 37.0 / 75.0, "Track 2".to_string())]);
+        assert!(parse_cue_sheet("FILE \"a.mp3\" MP3\nFILE \"b.mp3\" MP3\n").is_err());
+        assert!(parse_cue_sheet("TRACK 01 AUDIO\nTITLE \"No index\"\n").is_err());
+    }
+
+    #[test]
+    fn timestamp_list_reads_description_style_lines() {
+        let list = "# Tracklist\n0:00 Intro\n3:25 - Second song\n\n1:02:03.5 Finale\n";
+        let chapters = parse_timestamp_list(list).unwrap();
+        let expected = [(0.0, "Intro"), (205.0, "Second song"), (3723.5, "Finale")];
+        assert_eq!(chapters, expected.map(|(t, title)| (t, title.to_string())));
+        assert!(parse_timestamp_list("Intro 0:00\n").is_err());
+        assert!(parse_timestamp_list("0:00\n").is_err());
+    }
+
+    #[test]
+    fn chapters_end_at_the_next_start_and_escape_metadata() {
+        let chapters = chapters_from_starts(vec![(10.0, "B=2".into()), (0.0, "A".into())], 25.0);
+        assert_eq!((chapters[0].end, chapters[1].end), (10.0, 25.0));
+        let meta = ffmetadata(&chapters);
+        assert!(meta.starts_with(";FFMETADATA1\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=10000\ntitle=A\n"));
+        assert!(meta.ends_with("START=10000\nEND=25000\ntitle=B\\=2\n"));
+    }
+}
@@ -0,0 +1,1963 @@
+//! The command line: `Cli`'s options and subcommands, and `run`, which takes a render through every stage
+
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cli")]
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::builder::Resettable;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+#[cfg(feature = "cli")]
+use crate::{interrupt, wizard};
+use crate::{config_file, error, logging};
+#[cfg(feature = "plugins")]
+use crate::plugin::{default_plugin_dir, discover_plugins, find_plugin, PluginVisualizer};
+#[cfg(feature = "script")]
+use crate::script::ScriptVisualizer;
+#[cfg(feature = "script")]
+use crate::text::load_font;
+use crate::background::{BgMotion, BgPulse, BgVideoFit};
+use crate::colormap::{parse_hex_color, Colormap};
+use crate::config::{
+    BarAnchor, BarShape, BarStroke, Config, DotMode, HAlign, Orientation, SmoothingKernel, StereoSplit, Style, Symmetry,
+    VAlign,
+};
+use crate::decode::{AudioDecoder, DecoderKind, Ffmpeg, RawPcm, Symphonia};
+use crate::duration::{parse_duration, parse_positive_duration};
+use crate::effects::{Glow, Reflection, Shadow};
+use crate::error::SpectrumError::{self, InvalidConfig};
+use crate::frame::{content_inset, Border};
+pub use crate::interrupt::CancelToken;
+use crate::level::amplitude_to_db;
+#[cfg(feature = "plugins")]
+use crate::logging::{verbose, warning};
+use crate::logging::info;
+use crate::memory::format_size;
+use crate::meter::{MeterBallistics, MeterStyle};
+use crate::overlay::{OverlayPos, TimeDisplay};
+use crate::output::{
+    split_args, writes_to_stdout, AudioCodec, AudioSettings, Backend, Codec, Container, EncodeSettings, HwAccel,
+    StreamFormat, VideoSettings, X264Preset,
+};
+use crate::palette::Palette;
+use crate::preset::{parse_preset, parse_saved_name, saved_preset_path, Preset, PresetChoice, PresetSettings};
+use crate::progress::{ProgressFormat, ProgressSink};
+use crate::renderer::{write_failed, FrameOutput, Output, RenderPlan, RenderSettings, Renderer, TrackStats, VideoOutput};
+pub use crate::renderer::{FrameContext, FrameHook, RunOptions};
+use crate::spectrum::FREQUENCY_BANDS;
+use crate::theme::{Layer, Theme};
+use crate::visualizer::Visualizer;
+
+#[derive(Parser, Debug)]
+#[command(name = "audio-spectrum-generator")]
+#[command(about = "Generate an audio spectrum video (MP4 or WebM) from an MP3 file")]
+#[command(subcommand_negates_reqs = true)]
+#[command(after_long_help = error::EXIT_CODES_HELP)]
+struct Cli {
+    /// Without a subcommand, the options render a video as `render` does
+    #[command(flatten)]
+    args: Args,
+
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+/// The rendering options, taken by the bare command and by `render`, `preview` and `analyze`.
+#[derive(clap::Args, Debug)]
+#[command(mut_args(with_env))]
+struct Args {
+    /// Input MP3 file (or, with --decoder, any audio ffmpeg reads or raw PCM)
+    #[arg(required = true)]
+    input: Option<PathBuf>,
+
+    /// How the input is decoded: symphonia (MP3, built in), ffmpeg (any format ffmpeg reads, with ffprobe for its
+    /// length and tags) or raw (headerless 16-bit little-endian PCM laid out as --raw-format says)
+    #[arg(long, value_enum, default_value_t = DecoderKind::Symphonia)]
+    decoder: DecoderKind,
+
+    /// Sample rate and channel count of --decoder raw input, as RATE,CHANNELS
+    #[arg(long, value_parser = parse_raw_format, default_value = "44100,2")]
+    raw_format: RawPcm,
+
+    /// Output video file: WebM (VP9/Opus) for a .webm extension, otherwise MP4 (H.264/AAC). `-` streams the video
+    /// to stdout in the --format container
+    #[arg(short, long, required_unless_present_any = ["frames_only", "snapshot", "preview", "export_spectrum"])]
+    output: Option<PathBuf>,
+
+    /// Container of the video streamed to stdout with `-o -`: mp4 (fragmented), mov (fragmented) or webm. Files
+    /// take theirs from the extension
+    #[arg(long, value_enum)]
+    format: Option<StreamFormat>,
+
+    /// Video codec: h264 (libx264), hevc (libx265) or av1 (libsvtav1). Default: h264, or VP9 for .webm (which also
+    /// takes av1)
+    #[arg(long, value_enum)]
+    codec: Option<Codec>,
+
+    /// Encode H.264/HEVC on the GPU with this hardware encoder instead of libx264/libx265 (.mp4, or .mov without
+    /// --transparent)
+    #[arg(long, value_enum, conflicts_with = "frames_only")]
+    hwaccel: Option<HwAccel>,
+
+    /// Video constant rate factor: lower is higher quality and larger (libx264 0-51, default 23; VP9 0-63,
+    /// default 32)
+    #[arg(long, conflicts_with = "video_bitrate")]
+    crf: Option<u32>,
+
+    /// Target video bitrate instead of constant quality, e.g. 8M or 2500k
+    #[arg(long, value_parser = parse_bitrate)]
+    video_bitrate: Option<String>,
+
+    /// libx264 speed preset: slower presets give smaller files at the same quality (ffmpeg default: medium)
+    #[arg(long, value_enum)]
+    x264_preset: Option<X264Preset>,
+
+    /// Encoder for the output video: ffmpeg, ffmpeg reading PNG frames saved to the temp dir, or the built-in H.264
+    /// encoder (MP4 without audio; needs a build with the native-encoder feature)
+    #[arg(long, value_enum, default_value_t = Backend::Ffmpeg)]
+    encoder: Backend,
+
+    /// ffmpeg binary to run (ffprobe is looked up next to it)
+    #[arg(long, env = "FFMPEG_PATH", default_value = "ffmpeg")]
+    ffmpeg_path: PathBuf,
+
+    /// Audio codec: aac, opus, mp3 or flac (lossless). Default: aac, or opus for .webm (the only one WebM takes)
+    #[arg(long, value_enum)]
+    audio_codec: Option<AudioCodec>,
+
+    /// Audio bitrate, e.g. 192k (default: 128k)
+    #[arg(long, value_parser = parse_bitrate)]
+    audio_bitrate: Option<String>,
+
+    /// Extra ffmpeg output options appended to the encode command after the built-in codec settings (which they
+    /// override), e.g. "-movflags +faststart". Quote arguments containing spaces
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true, conflicts_with = "frames_only")]
+    ffmpeg_args: Option<String>,
+
+    /// HLS segment length for a .m3u8 output, e.g. 6, 2.5s or 0:10 (default 6 seconds)
+    #[arg(long, value_name = "TIME", value_parser = parse_positive_duration)]
+    hls_segment: Option<f32>,
+
+    /// Don't tag the output with the title, artist and album (ID3, or --title/--artist) and the embedded cover art
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Write the rendered PNG frames (frame_000000.png, ...) to this directory and skip ffmpeg, instead of encoding
+    /// a video
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    frames_only: Option<PathBuf>,
+
+    /// Also write the per-frame analysis (bar values, RMS, peak, loudness, beats) to this file: CSV for a .csv
+    /// extension, otherwise JSON. Without -o, --frames-only or --snapshot, only the analysis is written
+    #[arg(long, value_name = "FILE")]
+    export_spectrum: Option<PathBuf>,
+
+    /// Render only the frame at TIME (90, 1:30, 1m30s or 00:01:30.500) to the PNG file, without making a video.
+    /// Effects that build up over time (spectrogram history, particles, peak hold) start empty
+    #[arg(long, num_args = 2, value_names = ["TIME", "PNG"], conflicts_with_all = ["output", "frames_only"])]
+    snapshot: Vec<String>,
+
+    /// Render one frame to a PNG next to the -o file (NAME-preview.png, or preview.png) instead of encoding, at TIME
+    /// or the middle of the track. Audio is decoded only up to that frame, so levels are scaled to that part
+    #[arg(long, value_name = "TIME", conflicts_with_all = ["snapshot", "frames_only", "export_spectrum"])]
+    preview: Option<Option<String>>,
+
+    /// Check the options and report the duration, frame count, estimated render time, temporary disk use and the
+    /// ffmpeg command, reading only the MP3 headers; nothing is rendered or written
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Keep the temporary WAV (and chapters and cover art) after encoding, and the frames an interrupted or failed
+    /// --frames-only run wrote. An unfinished video is removed either way
+    #[arg(long)]
+    keep_temp: bool,
+
+    /// Keep the render's working memory (the frames in flight) under SIZE (e.g. 2G or 512M) with fewer render
+    /// workers. The spectra grow with the track and come on top; --dry-run shows both
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_memory: Option<u64>,
+
+    /// Leave the background transparent and keep the alpha channel: ProRes 4444 for a .mov output, VP9 with alpha
+    /// for .webm. Ignores --bg-color
+    #[arg(long)]
+    transparent: bool,
+
+    /// TOML file of default options, keyed by long flag name (`bars = 64`, `bar-color = "ff6600"`). Flags given on
+    /// the command line or through environment variables take precedence
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print only warnings and errors: no status messages or progress bars
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Print more: -v adds input details and how long each stage took, -vv also the ffmpeg command and worker count
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// How to report progress: bars (terminal progress bars, hidden with -q), json (one JSON object per line on
+    /// stderr, such as {"event":"update","phase":"rendering","position":120}) or none
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Bars)]
+    progress: ProgressFormat,
+
+    /// Platform preset setting resolution, fps, spectrum layout and overlay margins (youtube, shorts, tiktok,
+    /// instagram-square, podcast-audiogram), or the name of a preset saved with `preset save`. Flags given explicitly
+    /// override the preset's values
+    #[arg(long, value_parser = parse_preset)]
+    preset: Option<PresetChoice>,
+
+    /// Resolution (e.g. 1920x1080). Overrides --width / --height when set
+    #[arg(long, value_parser = parse_resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Video width (pixels)
+    #[arg(long, default_value_t = 1920)]
+    width: u32,
+
+    /// Video height (pixels)
+    #[arg(long, default_value_t = 1080)]
+    height: u32,
+
+    /// Frame rate (fps)
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Number of spectrum bars
+    #[arg(long, default_value_t = 128)]
+    bars: usize,
+
+    /// Spectrum area height (pixels)
+    #[arg(long, default_value_t = 200)]
+    spectrum_height: u32,
+
+    /// Visualization style
+    #[arg(long, value_enum, default_value_t = Style::Bars)]
+    style: Style,
+
+    /// Draw the visualization with a Rhai script defining fn frame(f) instead of --style (see the README). Needs a
+    /// build with the script feature
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// Draw the visualization with the plugin NAME from the plugins directory instead of --style (see the README).
+    /// Needs a build with the plugins feature
+    #[arg(long, value_name = "NAME", conflicts_with = "script")]
+    plugin: Option<String>,
+
+    /// Directory the plugins are loaded from. Default: audio-spectrum-generator/plugins in the user config
+    /// directory (next to the saved presets)
+    #[arg(long, value_name = "DIR")]
+    plugin_dir: Option<PathBuf>,
+
+    /// Opacity of the lower (mirrored) half for --style mirror (0.0–1.0)
+    #[arg(long, default_value_t = 0.6, value_parser = parse_unit_f32)]
+    mirror_opacity: f32,
+
+    /// Center of the circle for --style radial and ring as X,Y (pixels). Default: frame center
+    #[arg(long, value_parser = parse_point)]
+    radial_center: Option<(u32, u32)>,
+
+    /// Inner radius of the circle for --style radial, or resting radius for --style ring (pixels). Default: 1/6 of the shorter frame side
+    #[arg(long)]
+    radial_inner_radius: Option<u32>,
+
+    /// Angle of the first bar for --style radial (degrees clockwise from 12 o'clock)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    radial_rotation: f32,
+
+    /// Image shown inside the circle for --style radial and ring (PNG/JPEG etc.). Cropped to a circle
+    #[arg(long)]
+    radial_image: Option<PathBuf>,
+
+    /// Show the MP3's embedded album art inside the circle for --style radial and ring (ignored when --radial-image is set)
+    #[arg(long)]
+    radial_art: bool,
+
+    /// Stroke width (pixels) for --style line, --style waveform and --style ring
+    #[arg(long, default_value_t = 2.0, value_parser = parse_positive_f32)]
+    stroke_width: f32,
+
+    /// Fill the area under the curve for --style line
+    #[arg(long)]
+    line_fill: bool,
+
+    /// Gradient end color for --style area, in hex RGB (fades from --bar-color at the top to this at the baseline)
+    #[arg(long, value_parser = parse_hex_color)]
+    area_gradient: Option<[u8; 4]>,
+
+    /// Reflect the area around the center line for --style area
+    #[arg(long)]
+    area_mirror: bool,
+
+    /// What the dots track for --style dots: size (radius) or position (height)
+    #[arg(long, value_enum, default_value_t = DotMode::Size)]
+    dot_mode: DotMode,
+
+    /// Number of previous frames left as fading trails for --style dots (0 = off)
+    #[arg(long, default_value_t = 0)]
+    dot_trail: usize,
+
+    /// Height of one segment for --style led (pixels)
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u32).range(1..))]
+    led_segment_height: u32,
+
+    /// Gap between segments for --style led (pixels)
+    #[arg(long, default_value_t = 2)]
+    led_segment_gap: u32,
+
+    /// Color --style led segments green, yellow, then red from the bottom up, like a hardware level meter
+    #[arg(long)]
+    led_ramp: bool,
+
+    /// Where bars are anchored in the band for --style bars: center (grow both ways), bottom (grow up), or top (grow down)
+    #[arg(long, value_enum, default_value_t = BarAnchor::Center)]
+    bar_anchor: BarAnchor,
+
+    /// Analyze the left and right channels separately and show both in --style bars: left-right (left channel grows
+    /// left from the center, right grows right) or top-bottom (left grows up, right grows down)
+    #[arg(long, value_enum)]
+    stereo_split: Option<StereoSplit>,
+
+    /// Gap between adjacent bars (pixels)
+    #[arg(long, default_value_t = 1)]
+    bar_gap: u32,
+
+    /// Shortest drawn bar (pixels), so every bar keeps a visible stub through silence (bars, mirror, led and radial
+    /// styles; mirror halves get half each). With --bar-shape capsule, short stubs become dots
+    #[arg(long, default_value_t = 0)]
+    bar_min_height: u32,
+
+    /// Mirror the frequency axis around the horizontal center: low-center (lows in the middle, highs at both edges)
+    /// or high-center. Each of the --bars bands is drawn twice
+    #[arg(long, value_enum)]
+    symmetric: Option<Symmetry>,
+
+    /// Bar outline for --style bars, mirror and led: rect (corners rounded by --bar-radius), capsule, triangle
+    /// (pointing the way the bar grows), or diamond
+    #[arg(long, value_enum, default_value_t = BarShape::Rect)]
+    bar_shape: BarShape,
+
+    /// Corner radius of bars in pixels (0 = square, large values = pill-shaped). Default: half the bar width, clamped to 1–4
+    #[arg(long)]
+    bar_radius: Option<u32>,
+
+    /// Color palette preset setting bar, background and gradient colors; individual color flags override it
+    #[arg(long, value_enum)]
+    palette: Option<Palette>,
+
+    /// Bar color in hex RGB or RGBA (e.g. 000000, #ff6600 or ff660080 for 50% opacity). Default: black
+    #[arg(long, value_parser = parse_hex_color)]
+    bar_color: Option<[u8; 4]>,
+
+    /// Color bars by frequency position with a colormap (overrides --bar-color)
+    #[arg(long, value_enum)]
+    bar_colormap: Option<Colormap>,
+
+    /// Comma-separated hex colors for --bar-colormap custom, low to high frequency (e.g. 0000ff,ff00ff,ff0000)
+    #[arg(long, value_delimiter = ',', value_parser = parse_hex_color)]
+    colormap_stops: Vec<[u8; 4]>,
+
+    /// Color of full-height bars in hex RGB; bars fade from their base color (quiet) to this (loud) with their height
+    #[arg(long, value_parser = parse_hex_color)]
+    bar_color_loud: Option<[u8; 4]>,
+
+    /// Slowly turn the hue of the bar colors (and colormap) full circle once every TIME, e.g. 30 or 1m30s
+    #[arg(long, value_name = "TIME", value_parser = parse_positive_duration)]
+    color_cycle: Option<f32>,
+
+    /// Outline around each bar as COLOR,WIDTH (e.g. ffffff,2): hex RGB and width in pixels
+    #[arg(long, value_parser = parse_bar_stroke)]
+    bar_stroke: Option<BarStroke>,
+
+    /// Draw bars as outlines only (requires --bar-stroke)
+    #[arg(long)]
+    bar_stroke_only: bool,
+
+    /// Soft halo around the spectrum as RADIUS,INTENSITY (e.g. 12,1.5): blur radius in pixels and halo opacity multiplier
+    #[arg(long, value_parser = parse_glow)]
+    glow: Option<Glow>,
+
+    /// Flipped, fading copy of the spectrum below the band as OPACITY,HEIGHT (e.g. 0.4,120): opacity at the baseline (0.0–1.0) and height in pixels
+    #[arg(long, value_parser = parse_reflection)]
+    reflection: Option<Reflection>,
+
+    /// Soft drop shadow beneath bars, text and logos as OFFSET,BLUR,COLOR (e.g. 4,8,00000099): shift right and
+    /// down in pixels, blur radius in pixels, and hex RGB or RGBA color
+    #[arg(long, value_parser = parse_shadow)]
+    shadow: Option<Shadow>,
+
+    /// Background color in hex RGB (e.g. ffffff or #1a1a2e). Default: white
+    #[arg(long, value_parser = parse_hex_color)]
+    bg_color: Option<[u8; 4]>,
+
+    /// Color of the played part and playhead for --style timeline, in hex RGB. Default: orange
+    #[arg(long, default_value = "ff6600", value_parser = parse_hex_color)]
+    played_color: [u8; 4],
+
+    /// Background image path (PNG/JPEG etc.). Resized to video size if needed. Overrides --bg-color when set
+    #[arg(long)]
+    bg_image: Option<PathBuf>,
+
+    /// Gaussian blur sigma (pixels) applied to --bg-image once before rendering, or to each --bg-video frame
+    #[arg(long, value_parser = parse_positive_f32)]
+    bg_blur: Option<f32>,
+
+    /// Darken --bg-image or --bg-video toward black (0.0 = unchanged, 1.0 = black)
+    #[arg(long, default_value_t = 0.0, value_parser = parse_unit_f32)]
+    bg_dim: f32,
+
+    /// Slow camera motion over --bg-image across the video: zoom, pan, or kenburns (zoom + drift)
+    #[arg(long, value_enum)]
+    bg_motion: Option<BgMotion>,
+
+    /// Background video path; its frames are scaled to fill the frame and drawn behind the spectrum. Overrides --bg-color
+    #[arg(long, conflicts_with = "bg_image")]
+    bg_video: Option<PathBuf>,
+
+    /// How --bg-video is matched to the audio length: loop (repeat the clip) or stretch (play it once across the audio)
+    #[arg(long, value_enum, default_value_t = BgVideoFit::Loop)]
+    bg_video_fit: BgVideoFit,
+
+    /// Make the background react to bass: scale (zoom in) or brightness
+    #[arg(long, value_enum)]
+    bg_pulse: Option<BgPulse>,
+
+    /// Strength of --bg-pulse at full bass (extra zoom for scale, e.g. 0.05 = 5%; brightness gain for brightness)
+    #[arg(long, default_value_t = 0.1, value_parser = parse_positive_f32)]
+    bg_pulse_amount: f32,
+
+    /// Number of lowest bars averaged as the bass level for --bg-pulse and --particles beat detection
+    #[arg(long, default_value_t = 4)]
+    bass_bars: usize,
+
+    /// Distance from bottom of frame to the bottom edge of the spectrum band (pixels)
+    #[arg(long, default_value_t = 0)]
+    spectrum_y_from_bottom: u32,
+
+    /// Horizontal width of the spectrum band (pixels). When not set, uses full frame width
+    #[arg(long)]
+    spectrum_width: Option<u32>,
+
+    /// X coordinate (pixels) where the band's --spectrum-align edge or center is placed. Default: the matching
+    /// frame edge or center
+    #[arg(long)]
+    spectrum_x: Option<u32>,
+
+    /// Y coordinate (pixels from the top) where the band's --spectrum-valign edge or middle is placed. Default: the
+    /// frame top or middle, or --spectrum-y-from-bottom above the frame bottom
+    #[arg(long, conflicts_with = "spectrum_y_from_bottom")]
+    spectrum_y: Option<u32>,
+
+    /// Which part of the band sits at --spectrum-x: left, center, or right
+    #[arg(long, value_enum, default_value_t = HAlign::Center)]
+    spectrum_align: HAlign,
+
+    /// Which part of the band sits at --spectrum-y: top, middle, or bottom
+    #[arg(long, value_enum, default_value_t = VAlign::Bottom)]
+    spectrum_valign: VAlign,
+
+    /// Direction of the spectrum: horizontal, vertical (low frequencies at the bottom, band along the frame height),
+    /// or rotate:DEG (turned DEG degrees clockwise around the band center, e.g. rotate:-15)
+    #[arg(long, default_value = "horizontal", value_parser = parse_orientation)]
+    orientation: Orientation,
+
+    /// Amplitude gamma applied after normalization (x^gamma). < 1.0 boosts quiet bars, > 1.0 emphasizes peaks
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive_f32)]
+    amp_gamma: f32,
+
+    /// Spectral tilt compensation in dB per octave (relative to 1 kHz). Positive values boost high frequencies (e.g. 3 for pink-noise balance)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    tilt: f32,
+
+    /// Smooth bar heights across neighboring bars with this radius (in bars). 0 = off
+    #[arg(long, default_value_t = 0)]
+    bar_smoothing: usize,
+
+    /// Kernel for --bar-smoothing
+    #[arg(long, value_enum, default_value_t = SmoothingKernel::Gaussian)]
+    bar_smoothing_kernel: SmoothingKernel,
+
+    /// Draw a vertical marker at the spectral centroid ("brightness") of each frame
+    #[arg(long)]
+    centroid_marker: bool,
+
+    /// Centroid marker color in hex RGB (e.g. ff0000). Default: red
+    #[arg(long, default_value = "ff0000", value_parser = parse_hex_color)]
+    centroid_color: [u8; 4],
+
+    /// On each detected beat, burst particles from the tops of the loudest bars (bars, mirror and led styles)
+    #[arg(long)]
+    particles: bool,
+
+    /// Seed for the randomized effects (--particles). Renders are repeatable either way; another seed gives another,
+    /// equally repeatable, pattern
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    seed: u64,
+
+    /// Draw a level meter driven by each frame's RMS level: vertical (bar) or needle (analog dial)
+    #[arg(long, value_enum)]
+    meter: Option<MeterStyle>,
+
+    /// Meter response and scale: vu (~300 ms, -20 to +3 VU) or ppm (fast rise, slow fall, -50 to 0 dBFS)
+    #[arg(long, value_enum, default_value_t = MeterBallistics::Vu)]
+    meter_ballistics: MeterBallistics,
+
+    /// Where the meter is placed
+    #[arg(long, value_enum, default_value_t = OverlayPos::CenterRight)]
+    meter_pos: OverlayPos,
+
+    /// Meter size in pixels: height of the vertical meter, width of the needle dial
+    #[arg(long, default_value_t = 240)]
+    meter_size: u32,
+
+    /// Draw the meter instead of the spectrum
+    #[arg(long, requires = "meter")]
+    meter_only: bool,
+
+    /// Draw the track title and artist as text, taken from the MP3's ID3 tags unless --title/--artist are given
+    #[arg(long)]
+    show_title: bool,
+
+    /// Title text to draw (overrides the ID3 title; implies --show-title)
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Artist text drawn below the title (overrides the ID3 artist; implies --show-title)
+    #[arg(long)]
+    artist: Option<String>,
+
+    /// Where the title/artist text is placed
+    #[arg(long, value_enum, default_value_t = OverlayPos::TopLeft)]
+    title_pos: OverlayPos,
+
+    /// Draw a playback time counter (m:ss): elapsed, remaining, or both
+    #[arg(long, value_enum)]
+    show_time: Option<TimeDisplay>,
+
+    /// Where the time counter is placed
+    #[arg(long, value_enum, default_value_t = OverlayPos::BottomRight)]
+    time_pos: OverlayPos,
+
+    /// Subtitles or lyrics file (.srt, or .lrc for timed lyrics); the active cue is drawn as text on each frame
+    #[arg(long)]
+    subtitles: Option<PathBuf>,
+
+    /// Where subtitles are placed; long cues are wrapped to fit the frame width
+    #[arg(long, value_enum, default_value_t = OverlayPos::BottomCenter)]
+    subtitle_pos: OverlayPos,
+
+    /// Chapter markers embedded into the output: a .cue sheet, or a timestamp list with one "0:00 Title" per line
+    #[arg(long)]
+    chapters: Option<PathBuf>,
+
+    /// Draw the current chapter's title as text
+    #[arg(long, requires = "chapters")]
+    show_chapter: bool,
+
+    /// Where the chapter title is placed
+    #[arg(long, value_enum, default_value_t = OverlayPos::TopRight)]
+    chapter_pos: OverlayPos,
+
+    /// TrueType/OpenType font file for text overlays. Default: a common system sans-serif font
+    #[arg(long)]
+    font: Option<PathBuf>,
+
+    /// Text size in pixels (the artist line is drawn smaller)
+    #[arg(long, default_value_t = 48.0, value_parser = parse_positive_f32)]
+    font_size: f32,
+
+    /// Text color in hex RGB or RGBA (e.g. ffffff). Default: the bar color
+    #[arg(long, value_parser = parse_hex_color)]
+    text_color: Option<[u8; 4]>,
+
+    /// Logo/watermark image (PNG with alpha recommended) composited onto every frame
+    #[arg(long)]
+    logo: Option<PathBuf>,
+
+    /// Where the logo is placed
+    #[arg(long, value_enum, default_value_t = OverlayPos::TopRight)]
+    logo_pos: OverlayPos,
+
+    /// Logo width as a fraction of the frame width (height follows the image's aspect ratio)
+    #[arg(long, default_value_t = 0.1, value_parser = parse_positive_f32)]
+    logo_scale: f32,
+
+    /// Logo opacity (0.0–1.0)
+    #[arg(long, default_value_t = 0.8, value_parser = parse_unit_f32)]
+    logo_opacity: f32,
+
+    /// Distance between overlays and the frame edges (pixels)
+    #[arg(long, default_value_t = 40)]
+    overlay_margin: u32,
+
+    /// Empty space between the border (or the frame edges) and everything drawn, in --bg-color (pixels)
+    #[arg(long, default_value_t = 0)]
+    padding: u32,
+
+    /// Border around the whole video as WIDTH,COLOR or WIDTH,TOP_COLOR,BOTTOM_COLOR for a vertical gradient
+    /// (e.g. 24,1a1a2e or 24,ff6600,6a00ff)
+    #[arg(long, value_parser = parse_border)]
+    border: Option<Border>,
+
+    /// Layout file (TOML, or JSON with a .json extension) listing background, spectrum, text, logo and progress
+    /// layers drawn in order. Its spectrum layers replace the band set by the spectrum flags
+    #[arg(long)]
+    theme: Option<PathBuf>,
+}
+
+fn parse_positive_f32(s: &str) -> Result<f32, String> {
+    let v: f32 = s.trim().parse().map_err(|_| format!("invalid number: {:?}", s))?;
+    if !v.is_finite() || v <= 0.0 {
+        return Err(format!("value must be a positive number, got {:?}", s));
+    }
+    Ok(v)
+}
+
+fn parse_unit_f32(s: &str) -> Result<f32, String> {
+    let v: f32 = s.trim().parse().map_err(|_| format!("invalid number: {:?}", s))?;
+    if !(0.0..=1.0).contains(&v) {
+        return Err(format!("value must be between 0.0 and 1.0, got {:?}", s));
+    }
+    Ok(v)
+}
+
+fn parse_point(s: &str) -> Result<(u32, u32), String> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| "point must be X,Y (e.g. 960,540)".to_string())?;
+    let x: u32 = x.trim().parse().map_err(|_| format!("invalid x: {:?}", x))?;
+    let y: u32 = y.trim().parse().map_err(|_| format!("invalid y: {:?}", y))?;
+    Ok((x, y))
+}
+
+fn parse_raw_format(s: &str) -> Result<RawPcm, String> {
+    let (rate, channels) = s
+        .split_once(',')
+        .ok_or_else(|| "raw format must be RATE,CHANNELS (e.g. 48000,2)".to_string())?;
+    let sample_rate: u32 = rate.trim().parse().map_err(|_| format!("invalid sample rate: {:?}", rate))?;
+    let channels: usize = channels.trim().parse().map_err(|_| format!("invalid channel count: {:?}", channels))?;
+    if sample_rate == 0 || channels == 0 {
+        return Err("sample rate and channel count must be positive".to_string());
+    }
+    Ok(RawPcm { sample_rate, channels })
+}
+
+/// The `AudioDecoder` `--decoder` picks.
+fn input_decoder(args: &Args) -> Box<dyn AudioDecoder> {
+    match args.decoder {
+        DecoderKind::Symphonia => Box::new(Symphonia),
+        DecoderKind::Ffmpeg => Box::new(Ffmpeg { ffmpeg: args.ffmpeg_path.clone() }),
+        DecoderKind::Raw => Box::new(args.raw_format),
+    }
+}
+
+fn parse_bar_stroke(s: &str) -> Result<BarStroke, String> {
+    let (color, width) = s
+        .split_once(',')
+        .ok_or_else(|| "bar stroke must be COLOR,WIDTH (e.g. ffffff,2)".to_string())?;
+    let color = parse_hex_color(color.trim())?;
+    let width: u32 = width.trim().parse().map_err(|_| format!("invalid stroke width: {:?}", width))?;
+    if width == 0 {
+        return Err("stroke width must be positive".to_string());
+    }
+    Ok(BarStroke { color, width })
+}
+
+fn parse_border(s: &str) -> Result<Border, String> {
+    let mut parts = s.split(',');
+    let (Some(width), Some(color), end_color, None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err("border must be WIDTH,COLOR or WIDTH,TOP_COLOR,BOTTOM_COLOR (e.g. 24,1a1a2e)".to_string());
+    };
+    let width: u32 = width.trim().parse().map_err(|_| format!("invalid border width: {:?}", width))?;
+    if width == 0 {
+        return Err("border width must be positive".to_string());
+    }
+    let color = parse_hex_color(color.trim())?;
+    let end_color = end_color.map(|c| parse_hex_color(c.trim())).transpose()?;
+    Ok(Border { width, color, end_color })
+}
+
+fn parse_glow(s: &str) -> Result<Glow, String> {
+    let (radius, intensity) = s
+        .split_once(',')
+        .ok_or_else(|| "glow must be RADIUS,INTENSITY (e.g. 12,1.5)".to_string())?;
+    let radius: u32 = radius.trim().parse().map_err(|_| format!("invalid glow radius: {:?}", radius))?;
+    let intensity = parse_positive_f32(intensity)?;
+    Ok(Glow { radius, intensity })
+}
+
+fn parse_reflection(s: &str) -> Result<Reflection, String> {
+    let (opacity, height) = s
+        .split_once(',')
+        .ok_or_else(|| "reflection must be OPACITY,HEIGHT (e.g. 0.4,120)".to_string())?;
+    let opacity = parse_unit_f32(opacity)?;
+    let height: u32 = height.trim().parse().map_err(|_| format!("invalid reflection height: {:?}", height))?;
+    Ok(Reflection { opacity, height })
+}
+
+fn parse_shadow(s: &str) -> Result<Shadow, String> {
+    let mut parts = s.splitn(3, ',');
+    let (Some(offset), Some(blur), Some(color)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("shadow must be OFFSET,BLUR,COLOR (e.g. 4,8,00000099)".to_string());
+    };
+    let offset: i32 = offset.trim().parse().map_err(|_| format!("invalid shadow offset: {:?}", offset))?;
+    let blur: u32 = blur.trim().parse().map_err(|_| format!("invalid shadow blur: {:?}", blur))?;
+    let color = parse_hex_color(color.trim())?;
+    Ok(Shadow { offset, blur, color })
+}
+
+pub(crate) fn parse_orientation(s: &str) -> Result<Orientation, String> {
+    match s.trim() {
+        "horizontal" => Ok(Orientation::Horizontal),
+        "vertical" => Ok(Orientation::Vertical),
+        other => {
+            let degrees = other
+                .strip_prefix("rotate:")
+                .ok_or_else(|| format!("orientation must be horizontal, vertical, or rotate:DEG, got {:?}", s))?;
+            let degrees: f32 = degrees
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid rotation angle: {:?}", degrees))?;
+            if !degrees.is_finite() {
+                return Err(format!("invalid rotation angle: {:?}", degrees));
+            }
+            Ok(Orientation::Rotate(degrees))
+        }
+    }
+}
+
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let parts: Vec<&str> = s.split('x').collect();
+    if parts.len() != 2 {
+        return Err("resolution must be WIDTHxHEIGHT (e.g. 1920x1080)".to_string());
+    }
+    let w: u32 = parts[0].trim().parse().map_err(|_| "invalid width")?;
+    let h: u32 = parts[1].trim().parse().map_err(|_| "invalid height")?;
+    if w == 0 || h == 0 {
+        return Err("width and height must be positive".to_string());
+    }
+    Ok((w, h))
+}
+
+/// Parse a video bitrate in ffmpeg's notation: a positive number with an optional k or M suffix (e.g. 8M, 2500k).
+fn parse_bitrate(s: &str) -> Result<String, String> {
+    let s = s.trim();
+    let number = s.strip_suffix(['k', 'K', 'm', 'M']).unwrap_or(s);
+    match number.parse::<f32>() {
+        Ok(value) if value > 0.0 => Ok(s.to_string()),
+        _ => Err("bitrate must be a positive number with an optional k or M suffix (e.g. 8M)".to_string()),
+    }
+}
+
+/// Parse a memory size: a positive number of bytes with an optional K, M or G suffix, in units of 1024 (e.g. 2G).
+fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1u64 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    match number.parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok((value * unit as f64).ceil() as u64),
+        _ => Err("size must be a positive number with an optional K, M or G suffix (e.g. 2G)".to_string()),
+    }
+}
+
+/// PNG written by --preview: `NAME-preview.png` next to the video `output` would have been, or `preview.png`.
+fn preview_path(output: Option<&Path>) -> PathBuf {
+    match output.filter(|output| !writes_to_stdout(output)) {
+        Some(output) => {
+            let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+            output.with_file_name(format!("{}-preview.png", stem))
+        }
+        None => PathBuf::from("preview.png"),
+    }
+}
+
+/// `seconds` as `m:ss`, or `h:mm:ss` from an hour up.
+fn format_clock(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// Apply `preset` to every setting whose flag was not given on the command line or through its variable.
+fn apply_preset(args: &mut Args, matches: &clap::ArgMatches, preset: PresetSettings) {
+    let unset = |id: &str| !given(matches, id);
+    if unset("resolution") {
+        if unset("width") {
+            args.width = preset.resolution.0;
+        }
+        if unset("height") {
+            args.height = preset.resolution.1;
+        }
+    }
+    if unset("fps") {
+        args.fps = preset.fps;
+    }
+    if unset("style") {
+        args.style = preset.style;
+    }
+    if unset("bars") {
+        args.bars = preset.bars;
+    }
+    if unset("spectrum_height") {
+        args.spectrum_height = preset.spectrum_height;
+    }
+    if unset("spectrum_width") {
+        args.spectrum_width = preset.spectrum_width;
+    }
+    if unset("spectrum_y_from_bottom") && unset("spectrum_y") {
+        args.spectrum_y_from_bottom = preset.spectrum_y_from_bottom;
+    }
+    if unset("overlay_margin") {
+        args.overlay_margin = preset.overlay_margin;
+    }
+    if unset("show_title") {
+        args.show_title = preset.show_title;
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Render the video (the same as giving the options without a subcommand)
+    Render(Args),
+    /// Render one frame to a PNG without encoding: NAME-preview.png next to the -o file, or preview.png
+    #[command(mut_arg("output", |arg| arg.required_unless_present(Resettable::Reset)))]
+    #[command(mut_args(hide_output_modes))]
+    Preview {
+        /// Time of the frame (90, 1:30, 1m30s or 00:01:30.500). Default: the middle of the track
+        #[arg(long, value_name = "TIME")]
+        at: Option<String>,
+        #[command(flatten)]
+        args: Args,
+    },
+    /// Report peak level, RMS, integrated loudness, clipped samples and the dominant frequency bands without
+    /// rendering; with -o, also write the per-frame spectrum and levels
+    #[command(mut_arg("output", |arg| {
+        arg.required_unless_present(Resettable::Reset).help("JSON (or .csv) file for the per-frame spectrum and levels")
+    }))]
+    #[command(mut_args(hide_output_modes))]
+    Analyze(Args),
+    /// Show what the input's headers say: length, sample rate and tags, read by the --decoder given before `probe`
+    Probe {
+        /// Input MP3 file (or, with --decoder, any audio ffmpeg reads or raw PCM)
+        input: PathBuf,
+    },
+    /// Pick the input, style, colors and resolution in the terminal with a live sketch of the layout, then print
+    /// the command line or save the options to a config file
+    #[cfg(feature = "cli")]
+    Wizard,
+    /// Manage saved presets
+    Preset {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+    /// List the visualizer plugins in the plugins directory (--plugin-dir) by the names --plugin takes
+    #[cfg(feature = "plugins")]
+    Plugins,
+}
+
+#[derive(Subcommand, Debug)]
+enum PresetAction {
+    /// Save the options given before `preset` (and those from --config and --preset) as NAME, to recall with
+    /// --preset NAME
+    Save {
+        #[arg(value_parser = parse_saved_name)]
+        name: String,
+    },
+}
+
+/// Subcommands that take the rendering options, like the bare command.
+const OPTION_SUBCOMMANDS: [&str; 3] = ["render", "preview", "analyze"];
+
+/// Options that pick another kind of output than a video; `preview` and `analyze` set their own, so only the bare
+/// command and `render` take them.
+const OUTPUT_MODE_OPTIONS: [&str; 4] = ["frames_only", "snapshot", "preview", "export_spectrum"];
+
+/// Prefix of the environment variables that set options: `ASG_BAR_COLOR` for `--bar-color`, `ASG_INPUT` for the
+/// input file.
+const ENV_PREFIX: &str = "ASG_";
+
+/// Let `arg` be set from its `ENV_PREFIX` variable too, unless it has a variable of its own (`FFMPEG_PATH`) or
+/// doesn't take a single value (`-v`, `--snapshot`). Flags take `1`/`0`, `yes`/`no`, `on`/`off` as well as
+/// `true`/`false`. The variables are listed in the README rather than in --help.
+fn with_env(arg: clap::Arg) -> clap::Arg {
+    let single = arg.get_num_args().is_none_or(|n| n.max_values() <= 1);
+    if arg.get_env().is_some() || !single || matches!(arg.get_action(), clap::ArgAction::Count) {
+        return arg;
+    }
+    let name = format!("{}{}", ENV_PREFIX, arg.get_id().as_str().to_uppercase());
+    let arg = arg.env(name).hide_env(true);
+    match arg.get_action() {
+        clap::ArgAction::SetTrue => arg.value_parser(clap::builder::BoolishValueParser::new()),
+        _ => arg,
+    }
+}
+
+/// Whether option `id` was given on the command line or through its environment variable, so that config files and
+/// presets leave it alone.
+fn given(matches: &clap::ArgMatches, id: &str) -> bool {
+    matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+}
+
+/// Hide the `OUTPUT_MODE_OPTIONS` from a subcommand's help.
+fn hide_output_modes(arg: clap::Arg) -> clap::Arg {
+    if OUTPUT_MODE_OPTIONS.contains(&arg.get_id().as_str()) { arg.hide(true) } else { arg }
+}
+
+/// The matches holding the rendering options: a `render`, `preview` or `analyze` subcommand's, else the command's.
+fn option_matches(matches: &clap::ArgMatches) -> &clap::ArgMatches {
+    match matches.subcommand() {
+        Some((name, sub)) if OPTION_SUBCOMMANDS.contains(&name) => sub,
+        _ => matches,
+    }
+}
+
+/// Options that name this run's files rather than a look, left out of saved presets.
+const UNSAVED_OPTIONS: [&str; 10] = [
+    "output",
+    "frames_only",
+    "snapshot",
+    "preview",
+    "dry_run",
+    "keep_temp",
+    "export_spectrum",
+    "config",
+    "quiet",
+    "verbose",
+];
+
+/// Parse the command line, with the options of a --config file and then those of a saved --preset inserted before
+/// its own options (after a `render`, `preview` or `analyze` subcommand's name), so the command line wins over the
+/// config file, and the config file over the preset. The first passes ignore errors, since the files may supply
+/// required options like --output. Also returns the platform preset a saved preset was based on, which cannot be
+/// given as a second --preset.
+fn parse_matches(
+    mut argv: Vec<std::ffi::OsString>,
+) -> Result<(clap::ArgMatches, Option<Preset>), SpectrumError> {
+    let command = Cli::command();
+    let options_start = |matches: &clap::ArgMatches, argv: &[std::ffi::OsString]| match matches.subcommand_name() {
+        Some(name) if OPTION_SUBCOMMANDS.contains(&name) => {
+            argv.iter().position(|arg| arg == name).map_or(1, |i| i + 1)
+        }
+        _ => 1,
+    };
+    let matches = command.clone().ignore_errors(true).try_get_matches_from(&argv)?;
+    let cli = option_matches(&matches);
+    if let Some(path) = cli.get_one::<PathBuf>("config") {
+        let file_args = config_file::config_args(path, &command, |id| given(cli, id))
+            .map_err(|e| InvalidConfig(e.to_string()))?;
+        let start = options_start(&matches, &argv);
+        argv.splice(start..start, file_args);
+    }
+    let matches = command.clone().ignore_errors(true).try_get_matches_from(&argv)?;
+    let cli = option_matches(&matches);
+    let mut platform = None;
+    if let Some(PresetChoice::Saved(name)) = cli.get_one::<PresetChoice>("preset") {
+        let path = saved_preset_path(name).map_err(InvalidConfig)?;
+        if !path.exists() {
+            let message = format!("no saved preset {:?} (looked for {:?}); save one with `preset save`", name, path);
+            return Err(InvalidConfig(message));
+        }
+        let mut preset_args = Vec::new();
+        let file_args = config_file::config_args(&path, &command, |id| id != "preset" && given(cli, id))
+            .map_err(|e| InvalidConfig(e.to_string()))?;
+        for arg in file_args {
+            match arg.to_str().and_then(|arg| arg.strip_prefix("--preset=")) {
+                Some(value) => match parse_preset(value).map_err(InvalidConfig)? {
+                    PresetChoice::Platform(preset) => platform = Some(preset),
+                    PresetChoice::Saved(_) => {
+                        return Err(InvalidConfig(format!("saved preset {:?} names another saved preset", name)));
+                    }
+                },
+                None => preset_args.push(arg),
+            }
+        }
+        let start = options_start(&matches, &argv);
+        argv.splice(start..start, preset_args);
+    }
+    Ok((command.try_get_matches_from(argv)?, platform))
+}
+
+/// `preset save NAME`: write the options given on the command line to the saved preset NAME. A saved --preset's own
+/// options are already among them, so only the platform preset is kept.
+fn save_preset(
+    name: &str,
+    matches: &clap::ArgMatches,
+    platform: Option<Preset>,
+) -> Result<(), SpectrumError> {
+    let skip = [&UNSAVED_OPTIONS[..], &["preset"]].concat();
+    let mut table = config_file::options_table(&Cli::command(), matches, &skip);
+    if let Some(value) = platform.and_then(|preset| preset.to_possible_value()) {
+        table.insert("preset".to_string(), value.get_name().into());
+    }
+    let path = saved_preset_path(name).map_err(InvalidConfig)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| write_failed(dir, e))?;
+    }
+    let contents = toml::to_string(&table).map_err(|e| write_failed(&path, e))?;
+    std::fs::write(&path, contents).map_err(|e| write_failed(&path, e))?;
+    info!("Saved preset {:?} ({} options): {:?}", name, table.len(), path);
+    Ok(())
+}
+
+/// Longest tag value `probe` prints; lyrics and comments are cut there.
+const PROBE_TAG_CHARS: usize = 100;
+
+/// A tag value on one line: whitespace runs (newlines included) become single spaces, and a long value is cut to
+/// `PROBE_TAG_CHARS`.
+fn one_line(value: &str) -> String {
+    let line = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(PROBE_TAG_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line,
+    }
+}
+
+/// The `--script` or `--plugin` visualizer, loaded and checked, for frames of `config`'s size and bar count.
+#[cfg_attr(not(any(feature = "script", feature = "plugins")), allow(unused_variables))]
+fn custom_visualizer(
+    args: &Args,
+    config: &Config,
+) -> Result<Option<Box<dyn Visualizer>>, SpectrumError> {
+    #[cfg(feature = "script")]
+    if let Some(ref path) = args.script {
+        // The script's own font: a missing system font only matters once the script calls text().
+        let font = if args.font.is_some() { Some(load_font(args.font.as_deref())?) } else { load_font(None).ok() };
+        let script = ScriptVisualizer::load(path, font)?;
+        script.check((config.width, config.height), config.bars)?;
+        return Ok(Some(Box::new(script)));
+    }
+    #[cfg(feature = "plugins")]
+    if let Some(ref name) = args.plugin {
+        let dir = match args.plugin_dir {
+            Some(ref dir) => dir.clone(),
+            None => default_plugin_dir().map_err(InvalidConfig)?,
+        };
+        let plugin = find_plugin(&dir, name)?;
+        verbose!("Loaded plugin {:?} from {:?}", name, plugin.path());
+        return Ok(Some(Box::new(PluginVisualizer::new(plugin, (config.width, config.height), config.bars))));
+    }
+    Ok(None)
+}
+
+/// `plugins`: list the plugins in `dir` (the default plugins directory when None) with their files.
+#[cfg(feature = "plugins")]
+fn list_plugins(dir: Option<&Path>) -> Result<(), SpectrumError> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => default_plugin_dir().map_err(InvalidConfig)?,
+    };
+    let plugins = discover_plugins(&dir);
+    if plugins.is_empty() {
+        println!("No plugins in {:?}", dir);
+    }
+    for plugin in plugins {
+        match plugin {
+            Ok(plugin) => println!("{}\t{}", plugin.name(), plugin.path().display()),
+            Err(e) => warning!("{}", e),
+        }
+    }
+    Ok(())
+}
+
+/// `probe INPUT`: print what the input's headers say, through the `--decoder` given before `probe`, without decoding
+/// it. The bitrate is the file's average, tags and pictures included.
+fn probe(decoder: &dyn AudioDecoder, input: &Path) -> Result<(), SpectrumError> {
+    let info = decoder.probe(input)?;
+    println!("Input: {:?}", input);
+    println!("Codec: {}", info.codec.unwrap_or("unknown"));
+    println!("Sample rate: {} Hz", info.sample_rate);
+    match info.channels {
+        Some(channels) => println!("Channels: {}", channels),
+        None => println!("Channels: unknown (not in the headers)"),
+    }
+    match info.duration_sec.filter(|&sec| sec > 0.0) {
+        Some(sec) => {
+            println!("Duration: {} ({:.1} s)", format_clock(sec as f64), sec);
+            let bytes = std::fs::metadata(input)
+                .map_err(|e| SpectrumError::DecodeError { path: input.to_path_buf(), message: e.to_string() })?
+                .len();
+            println!("Bitrate: {:.0} kb/s (average)", bytes as f64 * 8.0 / sec as f64 / 1e3);
+        }
+        None => println!("Duration: unknown (not in the headers)"),
+    }
+    // Only symphonia lists every tag; the other decoders know the title, artist and album.
+    let tags = &info.tags;
+    let known = [("Title", &tags.title), ("Artist", &tags.artist), ("Album", &tags.album)];
+    let known: Vec<_> = known.into_iter().filter_map(|(key, value)| Some((key, value.as_deref()?))).collect();
+    if info.all_tags.is_empty() && known.is_empty() {
+        println!("Tags: none");
+    } else {
+        println!("Tags:");
+    }
+    for tag in &info.all_tags {
+        match tag.std_key {
+            Some(std_key) => println!("  {:?} ({}): {}", std_key, tag.key, one_line(&tag.value)),
+            None => println!("  {}: {}", tag.key, one_line(&tag.value)),
+        }
+    }
+    if info.all_tags.is_empty() {
+        for (key, value) in known {
+            println!("  {}: {}", key, one_line(value));
+        }
+    }
+    if info.pictures.is_empty() {
+        println!("Pictures: none");
+    } else {
+        println!("Pictures:");
+    }
+    for picture in &info.pictures {
+        let usage = picture.usage.map_or("Other".to_string(), |usage| format!("{:?}", usage));
+        let size = match image::ImageReader::new(std::io::Cursor::new(&picture.data)).with_guessed_format() {
+            Ok(reader) => reader.into_dimensions().map_or("unreadable".to_string(), |(w, h)| format!("{}x{}", w, h)),
+            Err(_) => "unreadable".to_string(),
+        };
+        let kb = picture.data.len() as f64 / 1e3;
+        println!("  {}, {}, {}, {:.1} kB", usage, picture.media_type, size, kb);
+    }
+    Ok(())
+}
+
+/// How many of the `FREQUENCY_BANDS` `analyze` lists, by their share of the energy.
+const DOMINANT_BANDS: usize = 3;
+
+/// `analyze`: print the whole-track levels and the frequency bands holding the most energy.
+fn print_track_stats(input: &Path, stats: &TrackStats) {
+    let (levels, shares) = (&stats.levels, stats.band_shares);
+    let seconds = stats.samples as f64 / stats.sample_rate as f64;
+    let (channels, layout) = if stats.stereo { (2, "stereo") } else { (1, "mono") };
+    println!("Input: {:?}, {} at {} Hz, {}", input, format_clock(seconds), stats.sample_rate, layout);
+    println!("Peak: {:.1} dBFS", amplitude_to_db(levels.peak));
+    println!("RMS: {:.1} dBFS", amplitude_to_db(levels.rms));
+    println!("Integrated loudness: {:.1} LUFS", levels.integrated_lufs);
+    let total_samples = (stats.samples * channels).max(1);
+    let clipped_percent = 100.0 * levels.clipped_samples as f64 / total_samples as f64;
+    println!("Clipped samples: {} ({:.3}%)", levels.clipped_samples, clipped_percent);
+    let mut bands: Vec<_> = FREQUENCY_BANDS.iter().zip(shares).filter(|&(_, share)| share > 0.0).collect();
+    bands.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let dominant: Vec<String> = bands
+        .iter()
+        .take(DOMINANT_BANDS)
+        .map(|((name, low, high), share)| format!("{} ({}-{} Hz) {:.0}%", name, low, high, share * 100.0))
+        .collect();
+    if dominant.is_empty() {
+        println!("Dominant bands: none (silent)");
+    } else {
+        println!("Dominant bands: {}", dominant.join(", "));
+    }
+}
+
+/// Run the program with the process's arguments; `main.rs` is just this. Ctrl-C cancels the run, and clap's errors
+/// (including `--help`) print and exit the way clap does.
+#[cfg(feature = "cli")]
+pub fn main() -> ExitCode {
+    let cancel = CancelToken::new();
+    interrupt::install(cancel.clone());
+    let options = RunOptions { cancel, ..RunOptions::default() };
+    match run(std::env::args_os().collect(), options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(SpectrumError::Usage(e)) => e.exit(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Run the command line `args` (program name first) as the binary does, with what `options` adds. Each run has its
+/// own temporary directory, log level and stage timings, so runs can share a process. Invalid arguments and
+/// `--help` are returned as `SpectrumError::Usage` rather than printed.
+pub fn run_with<I, T>(args: I, options: RunOptions) -> Result<(), SpectrumError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString>,
+{
+    run(args.into_iter().map(Into::into).collect(), options)
+}
+
+/// `run_with`, calling `on_frame` on every rendered frame after the overlays and before `--padding`/`--border` go
+/// around it, so it draws in the content area's coordinates (the whole frame without those). Render workers call it
+/// in parallel and out of order.
+pub fn run_with_hook<I, T>(
+    args: I,
+    on_frame: impl Fn(&mut image::RgbaImage, &FrameContext) + Sync,
+) -> Result<(), SpectrumError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString>,
+{
+    run_with(args, RunOptions { on_frame: Some(&on_frame), ..RunOptions::default() })
+}
+
+/// `run_with_hook` reporting progress to `progress` in place of what `--progress` picks, so the terminal stays
+/// untouched by bars.
+pub fn run_with_progress<I, T>(
+    args: I,
+    on_frame: impl Fn(&mut image::RgbaImage, &FrameContext) + Sync,
+    progress: Arc<dyn ProgressSink>,
+) -> Result<(), SpectrumError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString>,
+{
+    run_with(args, RunOptions { on_frame: Some(&on_frame), progress: Some(progress), ..RunOptions::default() })
+}
+
+fn run(argv: Vec<std::ffi::OsString>, options: RunOptions) -> Result<(), SpectrumError> {
+    let RunOptions { on_frame, progress, cancel, visualizer } = options;
+    let (matches, saved_platform) = parse_matches(argv)?;
+    let Cli { args, action } = Cli::from_arg_matches(&matches)?;
+    if let Some((name @ ("preview" | "analyze"), sub)) = matches.subcommand()
+        && let Some(id) = OUTPUT_MODE_OPTIONS.iter().find(|id| sub.value_source(id).is_some())
+    {
+        return Err(InvalidConfig(format!("--{} goes with render, not {}", id.replace('_', "-"), name)));
+    }
+    let matches = option_matches(&matches);
+    let (mut args, save_as, report_stats) = match action {
+        Some(Action::Probe { input }) => return probe(input_decoder(&args).as_ref(), &input),
+        #[cfg(feature = "cli")]
+        Some(Action::Wizard) => return wizard::run(parse_hex_color, parse_resolution),
+        #[cfg(feature = "plugins")]
+        Some(Action::Plugins) => return list_plugins(args.plugin_dir.as_deref()),
+        Some(Action::Preset { action: PresetAction::Save { name } }) => (args, Some(name), false),
+        Some(Action::Render(args)) => (args, None, false),
+        Some(Action::Preview { at, mut args }) => {
+            args.preview = Some(at);
+            (args, None, false)
+        }
+        Some(Action::Analyze(mut args)) => {
+            args.export_spectrum = args.output.take();
+            (args, None, true)
+        }
+        None => (args, None, false),
+    };
+    let _log = logging::init(logging::Level::from_flags(args.quiet, args.verbose));
+    let platform = match args.preset {
+        Some(PresetChoice::Platform(preset)) => Some(preset),
+        _ => saved_platform,
+    };
+    if let Some(ref name) = save_as {
+        return save_preset(name, matches, platform);
+    }
+    if let Some(preset) = platform {
+        apply_preset(&mut args, matches, preset.settings());
+    }
+    let input = args.input.clone().ok_or_else(|| InvalidConfig("an input MP3 file is required".into()))?;
+    // Checked first, so a missing input isn't reported as a missing ffmpeg or a bad option.
+    if !input.exists() {
+        return Err(SpectrumError::InputNotFound(input));
+    }
+    check_features(&args)?;
+    if visualizer.is_some() && (args.script.is_some() || args.plugin.is_some()) {
+        let message = "--script and --plugin can't replace a visualizer passed to run_with";
+        return Err(InvalidConfig(message.into()));
+    }
+
+    let theme = args.theme.as_deref().map(Theme::load).transpose()?;
+    if let Some(background) = theme.as_ref().map(Theme::background).transpose().map_err(InvalidConfig)?.flatten() {
+        args.bg_color = background.color.or(args.bg_color);
+        args.bg_image = background.image.or(args.bg_image);
+        args.bg_dim = background.dim.unwrap_or(args.bg_dim);
+        args.bg_blur = background.blur.or(args.bg_blur);
+    }
+    let (frame_width, frame_height) = args.resolution.unwrap_or((args.width, args.height));
+    // Everything is laid out in the content area inside the padding and border, then framed at the end.
+    let inset = content_inset(args.padding, args.border);
+    if inset * 2 >= frame_width.min(frame_height) {
+        return Err(InvalidConfig("--padding and --border leave no room inside the frame".into()));
+    }
+    let config = spectrum_config(&args, (frame_width - inset * 2, frame_height - inset * 2));
+    let layers = match theme {
+        Some(ref theme) => theme.layers(&config).map_err(InvalidConfig)?,
+        None => vec![Layer::Spectrum(Box::new(config.clone()))],
+    };
+    let renderer = Renderer::new(config, layers, render_settings(&args))?;
+    let output = output(&args, &renderer, report_stats)?;
+    let decoder = input_decoder(&args);
+
+    if args.dry_run {
+        let plan = renderer.plan(decoder.as_ref(), &input, &output)?;
+        print_plan(&renderer, &plan, &input, &output);
+        return Ok(());
+    }
+    let loaded_visualizer = match visualizer {
+        Some(_) => None,
+        None => custom_visualizer(&args, renderer.config())?,
+    };
+    let options = RunOptions {
+        on_frame,
+        progress: Some(progress.unwrap_or_else(|| args.progress.sink())),
+        cancel,
+        visualizer: visualizer.or(loaded_visualizer.as_deref()),
+    };
+    let summary = renderer.run(decoder.as_ref(), &input, &output, options)?;
+    if let Some(ref stats) = summary.track_stats {
+        print_track_stats(&input, stats);
+    }
+    Ok(())
+}
+
+/// Check that the build has the features the options ask for.
+fn check_features(args: &Args) -> Result<(), SpectrumError> {
+    if args.encoder == Backend::Native && !cfg!(feature = "native-encoder") {
+        let message = "--encoder native needs a build with the native-encoder feature \
+                       (cargo build --release --features native-encoder)";
+        return Err(InvalidConfig(message.into()));
+    }
+    if args.script.is_some() && !cfg!(feature = "script") {
+        let message = "--script needs a build with the script feature (cargo build --release --features script)";
+        return Err(InvalidConfig(message.into()));
+    }
+    if args.plugin.is_some() && !cfg!(feature = "plugins") {
+        let message = "--plugin needs a build with the plugins feature (cargo build --release --features plugins)";
+        return Err(InvalidConfig(message.into()));
+    }
+    Ok(())
+}
+
+/// The spectrum band the flags describe, laid out in the `(width, height)` content area.
+fn spectrum_config(args: &Args, (width, height): (u32, u32)) -> Config {
+    let palette = args.palette.map(Palette::colors);
+    Config {
+        width,
+        height,
+        fps: args.fps,
+        bars: args.bars,
+        spectrum_height: args.spectrum_height,
+        spectrum_y_from_bottom: args.spectrum_y_from_bottom,
+        spectrum_width: args.spectrum_width,
+        spectrum_x: args.spectrum_x,
+        spectrum_y: args.spectrum_y,
+        spectrum_align: args.spectrum_align,
+        spectrum_valign: args.spectrum_valign,
+        orientation: args.orientation,
+        style: args.style,
+        mirror_opacity: args.mirror_opacity,
+        radial_center: args.radial_center,
+        radial_inner_radius: args.radial_inner_radius,
+        radial_rotation: args.radial_rotation,
+        stroke_width: args.stroke_width,
+        line_fill: args.line_fill,
+        area_gradient: args.area_gradient.or(palette.and_then(|p| p.gradient)),
+        area_mirror: args.area_mirror,
+        dot_mode: args.dot_mode,
+        dot_trail: args.dot_trail,
+        led_segment_height: args.led_segment_height,
+        led_segment_gap: args.led_segment_gap,
+        led_ramp: args.led_ramp,
+        bar_anchor: args.bar_anchor,
+        stereo_split: args.stereo_split,
+        bar_gap: args.bar_gap,
+        bar_min_height: args.bar_min_height,
+        bar_shape: args.bar_shape,
+        bar_radius: args.bar_radius,
+        bar_color: args.bar_color.or(palette.map(|p| p.bar)).unwrap_or([0, 0, 0, 255]),
+        bar_colormap: args.bar_colormap,
+        colormap_stops: args.colormap_stops.clone(),
+        bar_color_loud: args.bar_color_loud.or(palette.and_then(|p| p.bar_loud)),
+        bar_stroke: args.bar_stroke,
+        bar_stroke_only: args.bar_stroke_only,
+        glow: args.glow,
+        shadow: args.shadow,
+        reflection: args.reflection,
+        bg_color: if args.transparent {
+            [0, 0, 0, 0]
+        } else {
+            args.bg_color.or(palette.map(|p| p.bg)).unwrap_or([255, 255, 255, 255])
+        },
+        played_color: args.played_color,
+        amp_gamma: args.amp_gamma,
+        tilt: args.tilt,
+        bar_smoothing: args.bar_smoothing,
+        bar_smoothing_kernel: args.bar_smoothing_kernel,
+        centroid_marker: args.centroid_marker,
+        centroid_color: args.centroid_color,
+        particles: args.particles,
+        ..Config::default()
+    }
+}
+
+/// What the flags draw around the layers and how the render runs.
+fn render_settings(args: &Args) -> RenderSettings {
+    RenderSettings {
+        bg_image: args.bg_image.clone(),
+        bg_blur: args.bg_blur,
+        bg_dim: args.bg_dim,
+        bg_motion: args.bg_motion,
+        bg_video: args.bg_video.clone(),
+        bg_video_fit: args.bg_video_fit,
+        bg_pulse: args.bg_pulse,
+        bg_pulse_amount: args.bg_pulse_amount,
+        bass_bars: args.bass_bars,
+        symmetric: args.symmetric,
+        color_cycle: args.color_cycle,
+        seed: args.seed,
+        radial_image: args.radial_image.clone(),
+        radial_art: args.radial_art,
+        meter: args.meter,
+        meter_ballistics: args.meter_ballistics,
+        meter_pos: args.meter_pos,
+        meter_size: args.meter_size,
+        meter_only: args.meter_only,
+        show_title: args.show_title,
+        title: args.title.clone(),
+        artist: args.artist.clone(),
+        title_pos: args.title_pos,
+        show_time: args.show_time,
+        time_pos: args.time_pos,
+        subtitles: args.subtitles.clone(),
+        subtitle_pos: args.subtitle_pos,
+        chapters: args.chapters.clone(),
+        show_chapter: args.show_chapter,
+        chapter_pos: args.chapter_pos,
+        font: args.font.clone(),
+        font_size: args.font_size,
+        text_color: args.text_color,
+        logo: args.logo.clone(),
+        logo_pos: args.logo_pos,
+        logo_scale: args.logo_scale,
+        logo_opacity: args.logo_opacity,
+        overlay_margin: args.overlay_margin,
+        padding: args.padding,
+        border: args.border,
+        ffmpeg_path: args.ffmpeg_path.clone(),
+        keep_temp: args.keep_temp,
+        max_memory: args.max_memory,
+    }
+}
+
+/// What the flags write: a video for -o, PNG frames, a snapshot or a preview, and the analysis. A preview checks the
+/// -o video's options but renders a PNG in its place.
+fn output(args: &Args, renderer: &Renderer, report_stats: bool) -> Result<Output, SpectrumError> {
+    let path = args.output.as_deref();
+    if args.format.is_some() && !path.is_some_and(writes_to_stdout) {
+        let message = "--format applies to -o - only; a file's container comes from its extension";
+        return Err(InvalidConfig(message.into()));
+    }
+    let video = path
+        .map(|path| {
+            let video = VideoOutput {
+                path: path.to_path_buf(),
+                container: match writes_to_stdout(path) {
+                    true => args.format.unwrap_or_default().container(),
+                    false => Container::from_path(path),
+                },
+                encoder: args.encoder,
+                settings: encode_settings(args)?,
+                metadata: !args.no_metadata,
+            };
+            renderer.check_video(&video)?;
+            Ok::<_, SpectrumError>(video)
+        })
+        .transpose()?;
+    let frames = match (video, &args.snapshot[..], &args.frames_only) {
+        (video, _, _) if args.preview.is_some() => FrameOutput::Preview {
+            time: match args.preview {
+                Some(Some(ref time)) => Some(parse_duration(time).map_err(InvalidConfig)?),
+                _ => None,
+            },
+            path: preview_path(video.as_ref().map(|video| video.path.as_path())),
+        },
+        (Some(video), _, _) => FrameOutput::Video(video),
+        (None, [time, path], _) => {
+            FrameOutput::Snapshot { time: parse_duration(time).map_err(InvalidConfig)?, path: PathBuf::from(path) }
+        }
+        (None, _, Some(dir)) => FrameOutput::Frames(dir.clone()),
+        (None, _, None) => FrameOutput::None,
+    };
+    Ok(Output { frames, export_spectrum: args.export_spectrum.clone(), track_stats: report_stats })
+}
+
+/// The codec, quality and muxer flags.
+fn encode_settings(args: &Args) -> Result<EncodeSettings, SpectrumError> {
+    Ok(EncodeSettings {
+        video: VideoSettings {
+            alpha: args.transparent,
+            codec: args.codec,
+            hwaccel: args.hwaccel,
+            crf: args.crf,
+            bitrate: args.video_bitrate.clone(),
+            x264_preset: args.x264_preset,
+        },
+        audio: AudioSettings { codec: args.audio_codec, bitrate: args.audio_bitrate.clone() },
+        extra_args: args.ffmpeg_args.as_deref().map(split_args).transpose().map_err(InvalidConfig)?.unwrap_or_default(),
+        hls_segment: args.hls_segment,
+        ..EncodeSettings::default()
+    })
+}
+
+/// `--dry-run`: print what rendering `input` into `output` would take.
+fn print_plan(renderer: &Renderer, plan: &RenderPlan, input: &Path, output: &Output) {
+    let ((width, height), workers) = (plan.frame_size, plan.workers);
+    let fps = renderer.config().fps;
+    println!("Input: {:?}, {:.1} s at {} Hz", input, plan.duration_sec, plan.sample_rate);
+    println!("Frames: {} of {} at {} fps, {}x{}", plan.rendered_frames, plan.total_frames, fps, width, height);
+    println!(
+        "Estimated render time: {} ({} worker{}; varies with style and effects)",
+        format_clock(plan.render_sec),
+        workers,
+        if workers == 1 { "" } else { "s" }
+    );
+    let working = match renderer.settings().max_memory {
+        Some(budget) => format!("{} of --max-memory {}", format_size(plan.working_bytes), format_size(budget)),
+        None => format_size(plan.working_bytes),
+    };
+    println!(
+        "Peak memory: about {} (spectra {}, working {})",
+        format_size(plan.peak_bytes),
+        format_size(plan.track_bytes),
+        working
+    );
+    match (&output.frames, &plan.encode) {
+        (FrameOutput::Video(_), Some(encode)) => {
+            if let Some(chapters) = encode.chapters {
+                println!("Chapters: {}", chapters);
+            }
+            let (megabytes, dir) = (encode.temp_bytes as f64 / 1e6, &encode.temp_dir);
+            match encode.temp_frames {
+                Some(ref frames) => println!(
+                    "Temporary disk: {:.1} MB in {:?} plus {} PNG frames in {:?}",
+                    megabytes, dir, plan.total_frames, frames
+                ),
+                None => println!("Temporary disk: {:.1} MB in {:?}", megabytes, dir),
+            }
+            println!("ffmpeg command: {}", encode.ffmpeg_command);
+            if !encode.ffmpeg_found {
+                let ffmpeg = &renderer.settings().ffmpeg_path;
+                println!("ffmpeg not found at {:?}: install it or set --ffmpeg-path before running", ffmpeg);
+            }
+        }
+        (FrameOutput::Video(video), None) => {
+            println!("Output: {:?}, encoded with the built-in H.264 encoder (no temporary files)", video.path);
+        }
+        (FrameOutput::Frames(dir), _) => println!("Output: {} PNG files in {:?}", plan.total_frames, dir),
+        (FrameOutput::Snapshot { path, .. } | FrameOutput::Preview { path, .. }, _) => println!("Output: {:?}", path),
+        (FrameOutput::None, _) => {}
+    }
+    if let Some(ref path) = output.export_spectrum {
+        println!("Analysis: {:?}", path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_preset, parse_bar_stroke, parse_bitrate, parse_byte_size, parse_border, parse_glow,
+        parse_hex_color, parse_orientation, parse_raw_format,
+        parse_point, parse_positive_f32, parse_resolution, parse_reflection, parse_shadow, parse_unit_f32,
+        format_clock, one_line, preview_path, Action, Args, Cli, BarStroke, Border,
+        Glow, Orientation, PresetChoice, RawPcm, Reflection, Shadow, Preset, Style,
+    };
+    use clap::{CommandFactory, FromArgMatches};
+    use std::path::Path;
+
+    fn args_with_preset(argv: &[&str]) -> Args {
+        let matches = Cli::command().try_get_matches_from(argv).unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let Some(PresetChoice::Platform(preset)) = args.preset else { panic!("no platform preset") };
+        apply_preset(&mut args, &matches, preset.settings());
+        args
+    }
+
+    #[test]
+    fn apply_preset_fills_unset_flags() {
+        let args = args_with_preset(&["asg", "in.mp3", "-o", "out.mp4", "--preset", "podcast-audiogram"]);
+        assert_eq!((args.width, args.height, args.fps), (1080, 1080, 24));
+        assert_eq!(args.style, Style::Timeline);
+        assert!(args.show_title);
+        assert_eq!(args.overlay_margin, Preset::PodcastAudiogram.settings().overlay_margin);
+    }
+
+    #[test]
+    fn apply_preset_keeps_explicit_flags() {
+        let args = args_with_preset(&[
+            "asg", "in.mp3", "-o", "out.mp4", "--preset", "shorts", "--fps", "60", "--style", "mirror", "--height",
+            "1350", "--spectrum-y", "900",
+        ]);
+        assert_eq!((args.width, args.height, args.fps), (1080, 1350, 60));
+        assert_eq!(args.style, Style::Mirror);
+        assert_eq!((args.spectrum_y, args.spectrum_y_from_bottom), (Some(900), 0));
+        assert_eq!(args.spectrum_width, Some(840));
+    }
+
+    #[test]
+    fn parse_bitrate_accepts_suffixes() {
+        assert_eq!(parse_bitrate("8M").unwrap(), "8M");
+        assert_eq!(parse_bitrate("2500k").unwrap(), "2500k");
+        assert_eq!(parse_bitrate("1.5M").unwrap(), "1.5M");
+        assert!(parse_bitrate("fast").is_err());
+        assert!(parse_bitrate("0").is_err());
+        assert!(parse_bitrate("8G").is_err());
+    }
+
+    #[test]
+    fn parse_byte_size_counts_in_units_of_1024() {
+        assert_eq!(parse_byte_size("2G").unwrap(), 2 << 30);
+        assert_eq!(parse_byte_size("512m").unwrap(), 512 << 20);
+        assert_eq!(parse_byte_size("1.5K").unwrap(), 1536);
+        assert_eq!(parse_byte_size("4096").unwrap(), 4096);
+        assert!(parse_byte_size("0").is_err());
+        assert!(parse_byte_size("2T").is_err());
+        assert!(parse_byte_size("G").is_err());
+    }
+
+    #[test]
+    fn snapshot_takes_time_and_png() {
+        let matches = Cli::command().try_get_matches_from(["asg", "in.mp3", "--snapshot", "1:05", "a.png"]).unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        assert_eq!(args.snapshot, ["1:05", "a.png"]);
+        assert!(Cli::command().try_get_matches_from(["asg", "in.mp3", "--snapshot", "1:05"]).is_err());
+    }
+
+    #[test]
+    fn probe_prints_tags_on_one_line() {
+        assert_eq!(one_line("Verse one\nVerse  two\r\n"), "Verse one Verse two");
+        let long = "la ".repeat(60);
+        assert_eq!(one_line(&long).len(), super::PROBE_TAG_CHARS + 3);
+        assert!(one_line(&long).ends_with("..."));
+    }
+
+    #[test]
+    fn format_clock_adds_hours_when_needed() {
+        assert_eq!(format_clock(0.4), "0:00");
+        assert_eq!(format_clock(65.0), "1:05");
+        assert_eq!(format_clock(3723.0), "1:02:03");
+    }
+
+    #[test]
+    fn preview_time_is_optional_and_output_may_stay() {
+        let parse = |argv: &[&str]| Args::from_arg_matches(&Cli::command().try_get_matches_from(argv).unwrap());
+        assert_eq!(parse(&["asg", "in.mp3", "--preview"]).unwrap().preview, Some(None));
+        let args = parse(&["asg", "in.mp3", "-o", "out/video.mp4", "--preview", "1:05"]).unwrap();
+        assert_eq!(args.preview, Some(Some("1:05".to_string())));
+        assert_eq!(preview_path(args.output.as_deref()), Path::new("out/video-preview.png"));
+        assert_eq!(preview_path(Some(Path::new("-"))), Path::new("preview.png"));
+        assert!(Cli::command().try_get_matches_from(["asg", "in.mp3", "--preview", "--frames-only", "f"]).is_err());
+    }
+
+    #[test]
+    fn subcommands_take_the_rendering_options() {
+        let parse = |argv: &[&str]| Cli::command().try_get_matches_from(argv);
+        let cli = Cli::from_arg_matches(&parse(&["asg", "render", "in.mp3", "-o", "a.mp4", "--bars", "64"]).unwrap());
+        let Some(Action::Render(args)) = cli.unwrap().action else { panic!("not render") };
+        assert_eq!((args.output, args.bars), (Some("a.mp4".into()), 64));
+        let cli = Cli::from_arg_matches(&parse(&["asg", "preview", "in.mp3", "--at", "1:05"]).unwrap());
+        let Some(Action::Preview { at, args }) = cli.unwrap().action else { panic!("not preview") };
+        assert_eq!((at.as_deref(), args.output), (Some("1:05"), None));
+        assert!(parse(&["asg", "analyze", "in.mp3"]).is_ok());
+        assert!(parse(&["asg", "analyze", "in.mp3", "-o", "a.json", "--fps", "60"]).is_ok());
+        assert!(parse(&["asg", "probe", "in.mp3"]).is_ok());
+        let cli = Cli::from_arg_matches(&parse(&["asg", "--decoder", "raw", "probe", "in.pcm"]).unwrap()).unwrap();
+        assert!(matches!((cli.args.decoder, cli.action), (super::DecoderKind::Raw, Some(Action::Probe { .. }))));
+        assert!(parse(&["asg", "render", "in.mp3"]).is_err());
+    }
+
+    #[test]
+    fn options_take_asg_variables_named_after_them() {
+        let command = Cli::command();
+        let env = |command: &clap::Command, id: &str| {
+            let arg = command.get_arguments().find(|arg| arg.get_id() == id).unwrap();
+            arg.get_env().map(|name| name.to_string_lossy().into_owned())
+        };
+        assert_eq!(env(&command, "bar_color").as_deref(), Some("ASG_BAR_COLOR"));
+        assert_eq!(env(&command, "input").as_deref(), Some("ASG_INPUT"));
+        assert_eq!(env(&command, "ffmpeg_path").as_deref(), Some("FFMPEG_PATH"));
+        assert_eq!((env(&command, "snapshot"), env(&command, "verbose")), (None, None));
+        let render = command.find_subcommand("render").unwrap();
+        assert_eq!(env(render, "dry_run").as_deref(), Some("ASG_DRY_RUN"));
+    }
+
+    #[test]
+    fn verbose_counts_and_conflicts_with_quiet() {
+        let parse = |argv: &[&str]| Cli::command().try_get_matches_from(argv);
+        let args = Args::from_arg_matches(&parse(&["asg", "in.mp3", "-o", "a.mp4", "-vv"]).unwrap()).unwrap();
+        assert_eq!((args.quiet, args.verbose), (false, 2));
+        let args = Args::from_arg_matches(&parse(&["asg", "in.mp3", "-o", "a.mp4", "-q"]).unwrap()).unwrap();
+        assert_eq!((args.quiet, args.verbose), (true, 0));
+        assert!(parse(&["asg", "in.mp3", "-o", "a.mp4", "-q", "-v"]).is_err());
+    }
+
+    #[test]
+    fn frames_only_replaces_output() {
+        let matches = Cli::command().try_get_matches_from(["asg", "in.mp3", "--frames-only", "frames"]).unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        assert_eq!((args.output, args.frames_only), (None, Some("frames".into())));
+        assert!(Cli::command().try_get_matches_from(["asg", "in.mp3"]).is_err());
+        assert!(Cli::command().try_get_matches_from(["asg", "in.mp3", "-o", "a.mp4", "--frames-only", "f"]).is_err());
+    }
+
+    #[test]
+    fn parse_hex_color_with_hash() {
+        let got = parse_hex_color("#ff6600").unwrap();
+        assert_eq!(got, [255, 102, 0, 255]);
+    }
+
+    #[test]
+    fn parse_hex_color_without_hash() {
+        let got = parse_hex_color("000000").unwrap();
+        assert_eq!(got, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn parse_hex_color_white() {
+        let got = parse_hex_color("ffffff").unwrap();
+        assert_eq!(got, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn parse_hex_color_with_alpha() {
+        let got = parse_hex_color("#ff660080").unwrap();
+        assert_eq!(got, [255, 102, 0, 128]);
+    }
+
+    #[test]
+    fn parse_hex_color_too_short() {
+        let err = parse_hex_color("ff00").unwrap_err();
+        assert!(err.contains("6 hex digits"));
+    }
+
+    #[test]
+    fn parse_hex_color_too_long() {
+        let err = parse_hex_color("1234567").unwrap_err();
+        assert!(err.contains("6 hex digits"));
+    }
+
+    #[test]
+    fn parse_hex_color_invalid_char() {
+        let err = parse_hex_color("ff00gg").unwrap_err();
+        assert!(err.contains("invalid hex"));
+    }
+
+    #[test]
+    fn parse_bar_stroke_ok() {
+        let got = parse_bar_stroke("#ffffff, 2").unwrap();
+        assert_eq!(got, BarStroke { color: [255, 255, 255, 255], width: 2 });
+    }
+
+    #[test]
+    fn parse_bar_stroke_invalid() {
+        assert!(parse_bar_stroke("ffffff").unwrap_err().contains("COLOR,WIDTH"));
+        assert!(parse_bar_stroke("ffffff,0").unwrap_err().contains("positive"));
+        assert!(parse_bar_stroke("fff,2").is_err());
+    }
+
+    #[test]
+    fn parse_border_solid_and_gradient() {
+        let solid = parse_border("24, 1a1a2e").unwrap();
+        assert_eq!(solid, Border { width: 24, color: [0x1a, 0x1a, 0x2e, 255], end_color: None });
+        let gradient = parse_border("8,ff6600,6a00ff").unwrap();
+        assert_eq!(gradient.end_color, Some([0x6a, 0x00, 0xff, 255]));
+    }
+
+    #[test]
+    fn parse_border_invalid() {
+        assert!(parse_border("24").unwrap_err().contains("WIDTH,COLOR"));
+        assert!(parse_border("0,ffffff").unwrap_err().contains("positive"));
+        assert!(parse_border("8,ffffff,000000,ff0000").is_err());
+        assert!(parse_border("8,white").is_err());
+    }
+
+    #[test]
+    fn parse_glow_ok() {
+        let got = parse_glow("12, 1.5").unwrap();
+        assert_eq!(got, Glow { radius: 12, intensity: 1.5 });
+    }
+
+    #[test]
+    fn parse_glow_missing_intensity() {
+        let err = parse_glow("12").unwrap_err();
+        assert!(err.contains("RADIUS,INTENSITY"));
+    }
+
+    #[test]
+    fn parse_glow_rejects_non_positive_intensity() {
+        assert!(parse_glow("12,0").is_err());
+        assert!(parse_glow("-1,1.0").is_err());
+    }
+
+    #[test]
+    fn parse_shadow_ok() {
+        let got = parse_shadow("4, 8, 00000099").unwrap();
+        assert_eq!(got, Shadow { offset: 4, blur: 8, color: [0, 0, 0, 0x99] });
+        assert_eq!(parse_shadow("-2,0,#ff0000").unwrap().offset, -2);
+    }
+
+    #[test]
+    fn parse_shadow_rejects_malformed() {
+        assert!(parse_shadow("4,8").is_err());
+        assert!(parse_shadow("4,-1,000000").is_err());
+        assert!(parse_shadow("4,8,black").is_err());
+    }
+
+    #[test]
+    fn parse_reflection_ok() {
+        let got = parse_reflection("0.4,120").unwrap();
+        assert_eq!(got, Reflection { opacity: 0.4, height: 120 });
+    }
+
+    #[test]
+    fn parse_reflection_invalid() {
+        assert!(parse_reflection("0.4").unwrap_err().contains("OPACITY,HEIGHT"));
+        assert!(parse_reflection("1.5,120").unwrap_err().contains("between 0.0 and 1.0"));
+        assert!(parse_reflection("0.4,-1").is_err());
+    }
+
+    #[test]
+    fn parse_resolution_ok() {
+        let got = parse_resolution("1920x1080").unwrap();
+        assert_eq!(got, (1920, 1080));
+    }
+
+    #[test]
+    fn parse_resolution_with_spaces() {
+        let got = parse_resolution(" 640 x 480 ").unwrap();
+        assert_eq!(got, (640, 480));
+    }
+
+    #[test]
+    fn parse_resolution_zero_width() {
+        let err = parse_resolution("0x1080").unwrap_err();
+        assert!(err.contains("positive"));
+    }
+
+    #[test]
+    fn parse_resolution_zero_height() {
+        let err = parse_resolution("1920x0").unwrap_err();
+        assert!(err.contains("positive"));
+    }
+
+    #[test]
+    fn parse_resolution_invalid_format() {
+        let err = parse_resolution("1920").unwrap_err();
+        assert!(err.contains("WIDTHxHEIGHT"));
+    }
+
+    #[test]
+    fn parse_resolution_invalid_number() {
+        let err = parse_resolution("axb").unwrap_err();
+        assert!(err.contains("invalid"));
+    }
+
+    #[test]
+    fn parse_positive_f32_ok() {
+        assert_eq!(parse_positive_f32("0.5").unwrap(), 0.5);
+        assert_eq!(parse_positive_f32(" 2 ").unwrap(), 2.0);
+    }
+
+    #[test]
+    fn parse_positive_f32_rejects_zero_and_negative() {
+        assert!(parse_positive_f32("0").unwrap_err().contains("positive"));
+        assert!(parse_positive_f32("-1.5").unwrap_err().contains("positive"));
+        assert!(parse_positive_f32("inf").unwrap_err().contains("positive"));
+    }
+
+    #[test]
+    fn parse_positive_f32_rejects_non_number() {
+        assert!(parse_positive_f32("abc").unwrap_err().contains("invalid number"));
+    }
+
+    #[test]
+    fn parse_unit_f32_ok() {
+        assert_eq!(parse_unit_f32("0").unwrap(), 0.0);
+        assert_eq!(parse_unit_f32("0.6").unwrap(), 0.6);
+        assert_eq!(parse_unit_f32("1").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn parse_unit_f32_out_of_range() {
+        assert!(parse_unit_f32("1.5").unwrap_err().contains("between 0.0 and 1.0"));
+        assert!(parse_unit_f32("-0.1").unwrap_err().contains("between 0.0 and 1.0"));
+        assert!(parse_unit_f32("nan").unwrap_err().contains("between 0.0 and 1.0"));
+    }
+
+    #[test]
+    fn parse_raw_format_takes_rate_and_channels() {
+        assert_eq!(parse_raw_format("48000, 1").unwrap(), RawPcm { sample_rate: 48000, channels: 1 });
+        assert!(parse_raw_format("48000").unwrap_err().contains("RATE,CHANNELS"));
+        assert!(parse_raw_format("0,2").unwrap_err().contains("positive"));
+    }
+
+    #[test]
+    fn parse_point_ok() {
+        assert_eq!(parse_point("960,540").unwrap(), (960, 540));
+        assert_eq!(parse_point(" 10 , 20 ").unwrap(), (10, 20));
+    }
+
+    #[test]
+    fn parse_point_invalid() {
+        assert!(parse_point("960x540").unwrap_err().contains("X,Y"));
+        assert!(parse_point("a,1").unwrap_err().contains("invalid x"));
+        assert!(parse_point("1,-2").unwrap_err().contains("invalid y"));
+    }
+
+    #[test]
+    fn parse_orientation_ok() {
+        assert_eq!(parse_orientation("horizontal").unwrap(), Orientation::Horizontal);
+        assert_eq!(parse_orientation("vertical").unwrap(), Orientation::Vertical);
+        assert_eq!(parse_orientation("rotate:-15").unwrap(), Orientation::Rotate(-15.0));
+        assert_eq!(parse_orientation("rotate: 30.5").unwrap(), Orientation::Rotate(30.5));
+    }
+
+    #[test]
+    fn parse_orientation_invalid() {
+        assert!(parse_orientation("diagonal").unwrap_err().contains("rotate:DEG"));
+        assert!(parse_orientation("rotate:abc").unwrap_err().contains("invalid rotation angle"));
+        assert!(parse_orientation("rotate:inf").unwrap_err().contains("invalid rotation angle"));
+    }
+
+    // Decodes a WAV file with symphonia.
+    #[cfg(feature = "wav")]
+    #[test]
+    fn a_visualizer_passed_to_run_with_draws_every_frame_with_its_samples() {
+        use crate::draw::FrameData;
+        use crate::export::FrameAnalysis;
+
+        struct Samples;
+        impl crate::visualizer::Visualizer for Samples {
+            fn draw(&self, frame: &mut image::RgbaImage, _: &FrameAnalysis, data: &FrameData) {
+                let loud = data.waveform.iter().any(|&s| s > 0.5);
+                frame.put_pixel(0, 0, image::Rgba(if loud { [255, 0, 0, 255] } else { [0, 0, 255, 255] }));
+            }
+        }
+        let dir = std::env::temp_dir().join(format!("asg-run-with-visualizer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (wav, png) = (dir.join("in.wav"), dir.join("out.png"));
+        crate::wav::write_wav(&wav, &vec![0.9; 8_000], 8_000).unwrap();
+        let argv = ["asg".as_ref(), wav.as_os_str(), "--snapshot".as_ref(), "0".as_ref(), png.as_os_str()];
+        let options = || super::RunOptions { visualizer: Some(&Samples), ..Default::default() };
+        super::run_with(argv, options()).unwrap();
+        let frame = image::open(&png).unwrap().to_rgba8();
+        let with_script = argv.iter().copied().chain(["--script".as_ref(), "viz.rhai".as_ref()]);
+        let error = super::run_with(with_script, options()).unwrap_err().to_string();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert!(error.contains("--script"), "{}", error);
+    }
+
+    #[cfg(feature = "wav")]
+    #[test]
+    fn a_frame_that_panics_while_drawing_ends_the_run_with_an_error() {
+        let dir = std::env::temp_dir().join(format!("asg-run-with-panic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (wav, frames) = (dir.join("in.wav"), dir.join("frames"));
+        crate::wav::write_wav(&wav, &vec![0.5; 8_000], 8_000).unwrap();
+        let argv = ["asg".as_ref(), wav.as_os_str(), "--frames-only".as_ref(), frames.as_os_str()];
+        let size = ["--width", "64", "--height", "36", "--spectrum-height", "20", "--bars", "16"];
+        let size = size.map(std::ffi::OsStr::new);
+        let result = super::run_with_hook(argv.into_iter().chain(size), |_, frame| {
+            if frame.index == 2 {
+                panic!("no pixels for you");
+            }
+        });
+        std::fs::remove_dir_all(&dir).ok();
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("frame 2") && error.contains("no pixels for you"), "{}", error);
+    }
+}
@@ -0,0 +1,219 @@
+//! Colormaps for coloring bars by their frequency position
+
+/// Built-in colormaps, sampled from low (0.0) to high (1.0) frequency.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Colormap {
+    /// Hue sweep from red through yellow, green and blue to violet.
+    Rainbow,
+    /// Perceptually uniform dark blue → green → yellow (matplotlib's viridis).
+    Viridis,
+    /// Perceptually uniform dark blue → magenta → yellow (matplotlib's plasma).
+    Plasma,
+    /// Evenly spaced user-supplied color stops.
+    Custom,
+}
+
+/// Anchor points of viridis at t = 0, 1/8, …, 1.
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [71, 44, 122],
+    [59, 81, 139],
+    [44, 113, 142],
+    [33, 144, 141],
+    [39, 173, 129],
+    [92, 200, 99],
+    [170, 220, 50],
+    [253, 231, 37],
+];
+
+/// Anchor points of plasma at t = 0, 1/8, …, 1.
+const PLASMA: [[u8; 3]; 9] = [
+    [13, 8, 135],
+    [75, 3, 161],
+    [125, 3, 168],
+    [168, 34, 150],
+    [203, 70, 121],
+    [229, 107, 93],
+    [248, 148, 65],
+    [253, 195, 40],
+    [240, 249, 33],
+];
+
+/// Hue (degrees) reached by the rainbow map at t = 1.0; stops short of wrapping back to red.
+const RAINBOW_MAX_HUE: f32 = 270.0;
+
+impl Colormap {
+    /// Color at `t` (0.0–1.0, clamped; NaN is treated as 0.0). `stops` is only used by `Colormap::Custom`;
+    /// with no stops it yields opaque black.
+    pub fn sample(self, t: f32, stops: &[[u8; 4]]) -> [u8; 4] {
+        let t = if t.is_finite() { t.clamp(0.0, 1.0) } else { 0.0 };
+        match self {
+            Colormap::Rainbow => {
+                let [r, g, b] = hsv_to_rgb(t * RAINBOW_MAX_HUE, 1.0, 1.0);
+                [r, g, b, 255]
+            }
+            Colormap::Viridis => interpolate_stops(&VIRIDIS.map(|[r, g, b]| [r, g, b, 255]), t),
+            Colormap::Plasma => interpolate_stops(&PLASMA.map(|[r, g, b]| [r, g, b, 255]), t),
+            Colormap::Custom => interpolate_stops(stops, t),
+        }
+    }
+
+    /// The map as evenly spaced color stops: `stops` itself for `Colormap::Custom`, or `BUILTIN_STOPS` samples.
+    pub fn to_stops(self, stops: &[[u8; 4]]) -> Vec<[u8; 4]> {
+        match self {
+            Colormap::Custom => stops.to_vec(),
+            _ => (0..BUILTIN_STOPS).map(|i| self.sample(i as f32 / (BUILTIN_STOPS - 1) as f32, stops)).collect(),
+        }
+    }
+}
+
+/// Number of stops `Colormap::to_stops` samples from a built-in map.
+const BUILTIN_STOPS: usize = 17;
+
+/// `color` turned `degrees` around the hue circle, keeping its saturation, value and alpha.
+#[cfg(feature = "runner")]
+pub fn rotate_hue(color: [u8; 4], degrees: f32) -> [u8; 4] {
+    let [r, g, b, a] = color.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    if delta <= 0.0 {
+        return color;
+    }
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let [r, g, b] = hsv_to_rgb(hue + degrees, delta / max, max);
+    [r, g, b, (a * 255.0).round() as u8]
+}
+
+/// A color as `RRGGBB` or `RRGGBBAA` hex, with or without a leading `#`: the color flags' value parser, also used for
+/// theme and script colors.
+#[cfg(any(feature = "runner", feature = "script"))]
+pub fn parse_hex_color(s: &str) -> Result<[u8; 4], String> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 && s.len() != 8 {
+        return Err(format!(
+            "color must be 6 hex digits, or 8 with alpha (e.g. ff6600 or ff660080), got {:?}",
+            s
+        ));
+    }
+    let channel = |i: usize| {
+        s.get(i..i + 2)
+            .and_then(|h| u8::from_str_radix(h, 16).ok())
+            .ok_or_else(|| format!("invalid hex in color: {:?}", s))
+    };
+    let a = if s.len() == 8 { channel(6)? } else { 255 };
+    Ok([channel(0)?, channel(2)?, channel(4)?, a])
+}
+
+/// Linear interpolation between evenly spaced `stops` at `t` (0.0–1.0).
+fn interpolate_stops(stops: &[[u8; 4]], t: f32) -> [u8; 4] {
+    match stops.len() {
+        0 => [0, 0, 0, 255],
+        1 => stops[0],
+        n => {
+            let pos = t * (n - 1) as f32;
+            let i = (pos.floor() as usize).min(n - 2);
+            let frac = pos - i as f32;
+            let (a, b) = (stops[i], stops[i + 1]);
+            let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * frac).round() as u8;
+            [mix(a[0], b[0]), mix(a[1], b[1]), mix(a[2], b[2]), mix(a[3], b[3])]
+        }
+    }
+}
+
+/// HSV to RGB with hue in degrees and saturation/value in 0.0–1.0.
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    let to_u8 = |f: f32| ((f + m) * 255.0).round() as u8;
+    [to_u8(r), to_u8(g), to_u8(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hsv_to_rgb, interpolate_stops, Colormap, BUILTIN_STOPS};
+
+    #[test]
+    fn rainbow_starts_red_and_moves_through_hues() {
+        assert_eq!(Colormap::Rainbow.sample(0.0, &[]), [255, 0, 0, 255]);
+        let mid = Colormap::Rainbow.sample(0.5, &[]);
+        assert!(mid[1] > mid[0] && mid[1] > mid[2], "mid = {:?}", mid);
+        let end = Colormap::Rainbow.sample(1.0, &[]);
+        assert!(end[2] > end[1], "end = {:?}", end);
+    }
+
+    #[test]
+    fn viridis_and_plasma_hit_their_endpoints() {
+        assert_eq!(Colormap::Viridis.sample(0.0, &[]), [68, 1, 84, 255]);
+        assert_eq!(Colormap::Viridis.sample(1.0, &[]), [253, 231, 37, 255]);
+        assert_eq!(Colormap::Plasma.sample(0.0, &[]), [13, 8, 135, 255]);
+        assert_eq!(Colormap::Plasma.sample(1.0, &[]), [240, 249, 33, 255]);
+    }
+
+    #[test]
+    fn sample_clamps_out_of_range_and_nan() {
+        assert_eq!(Colormap::Viridis.sample(-1.0, &[]), Colormap::Viridis.sample(0.0, &[]));
+        assert_eq!(Colormap::Viridis.sample(2.0, &[]), Colormap::Viridis.sample(1.0, &[]));
+        assert_eq!(Colormap::Plasma.sample(f32::NAN, &[]), Colormap::Plasma.sample(0.0, &[]));
+    }
+
+    #[test]
+    fn custom_interpolates_evenly_spaced_stops() {
+        let stops = [[0, 0, 0, 255], [200, 0, 0, 255], [200, 100, 0, 255]];
+        assert_eq!(Colormap::Custom.sample(0.0, &stops), [0, 0, 0, 255]);
+        assert_eq!(Colormap::Custom.sample(0.25, &stops), [100, 0, 0, 255]);
+        assert_eq!(Colormap::Custom.sample(0.5, &stops), [200, 0, 0, 255]);
+        assert_eq!(Colormap::Custom.sample(1.0, &stops), [200, 100, 0, 255]);
+    }
+
+    #[test]
+    fn interpolate_stops_handles_few_stops() {
+        assert_eq!(interpolate_stops(&[], 0.5), [0, 0, 0, 255]);
+        assert_eq!(interpolate_stops(&[[1, 2, 3, 255]], 0.7), [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn hsv_to_rgb_primaries() {
+        assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb(120.0, 1.0, 1.0), [0, 255, 0]);
+        assert_eq!(hsv_to_rgb(240.0, 1.0, 1.0), [0, 0, 255]);
+        assert_eq!(hsv_to_rgb(0.0, 0.0, 0.5), [128, 128, 128]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn rotate_hue_turns_color_and_keeps_grays() {
+        use super::rotate_hue;
+
+        assert_eq!(rotate_hue([255, 0, 0, 200], 120.0), [0, 255, 0, 200]);
+        assert_eq!(rotate_hue([0, 0, 255, 255], 120.0), [255, 0, 0, 255]);
+        assert_eq!(rotate_hue([255, 128, 0, 255], 360.0), [255, 128, 0, 255]);
+        assert_eq!(rotate_hue([90, 90, 90, 255], 45.0), [90, 90, 90, 255]);
+    }
+
+    #[test]
+    fn to_stops_samples_builtin_maps() {
+        let stops = Colormap::Viridis.to_stops(&[]);
+        assert_eq!(stops.len(), BUILTIN_STOPS);
+        assert_eq!(stops[0], [68, 1, 84, 255]);
+        assert_eq!(stops[BUILTIN_STOPS - 1], [253, 231, 37, 255]);
+        assert_eq!(Colormap::Custom.to_stops(&[[1, 2, 3, 255]]), vec![[1, 2, 3, 255]]);
+    }
+}
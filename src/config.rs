@@ -1,7 +1,170 @@
 //! Configuration for resolution, fps, bar count, spectrum height, etc.
 
-/// Application configuration.
-#[derive(Clone, Debug)]
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::colormap::Colormap;
+use crate::effects::{Glow, Reflection, Shadow};
+
+/// Kernel used for smoothing bar values across neighboring bars.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum SmoothingKernel {
+    /// Gaussian weights (sigma = radius / 2).
+    #[default]
+    Gaussian,
+    /// Equal weights (moving average).
+    Box,
+}
+
+/// Frequency axis mirrored around the strip's horizontal center (`--symmetric`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Symmetry {
+    /// Lowest frequencies in the middle, highest at both edges.
+    LowCenter,
+    /// Highest frequencies in the middle, lowest at both edges.
+    HighCenter,
+}
+
+/// What the dot of each bar tracks in `Style::Dots`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum DotMode {
+    /// Radius grows with the value; dots sit on the center line.
+    #[default]
+    Size,
+    /// Fixed-size dots rise from the bottom of the band with the value.
+    Position,
+}
+
+/// Which edge (or the center) of the spectrum band sits at its horizontal anchor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum HAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// Which edge (or the middle) of the spectrum band sits at its vertical anchor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum VAlign {
+    Top,
+    Middle,
+    #[default]
+    Bottom,
+}
+
+/// Direction the spectrum runs in the frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Orientation {
+    /// Low to high frequency from left to right.
+    #[default]
+    Horizontal,
+    /// Turned 90° counter-clockwise: low to high frequency from bottom to top, with the band laid out along the
+    /// frame's height (the band's bottom edge becomes the frame's right side).
+    Vertical,
+    /// The horizontal layout turned by this many degrees clockwise around the band's center.
+    Rotate(f32),
+}
+
+/// Where bars of `Style::Bars` are anchored inside the spectrum band.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum BarAnchor {
+    /// Bars grow symmetrically around the band's center line.
+    #[default]
+    Center,
+    /// Bars sit on the bottom of the band and grow upward.
+    Bottom,
+    /// Bars hang from the top of the band and grow downward.
+    Top,
+}
+
+/// How `Style::Bars` shows the two channels when stereo analysis is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum StereoSplit {
+    /// Horizontal bars stacked low to high frequency (bottom to top); the left channel grows left from the center,
+    /// the right channel grows right.
+    LeftRight,
+    /// Left channel grows up from the band's center line, right channel grows down.
+    TopBottom,
+}
+
+/// Spectrum visualization style.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum Style {
+    /// Rounded bars, vertically centered in the spectrum band.
+    #[default]
+    Bars,
+    /// Bars grow up from a center line and are mirrored below it (lower half dimmed by `mirror_opacity`).
+    Mirror,
+    /// Bars arranged around a circle, pointing outward.
+    Radial,
+    /// Time-domain waveform (oscilloscope) of the current frame's samples.
+    Waveform,
+    /// Whole-track waveform timeline with a moving playhead ("audiogram").
+    Timeline,
+    /// Scrolling heat map of frequency over time.
+    Spectrogram,
+    /// Smooth curve through the bar values.
+    Line,
+    /// Filled area under the smooth spectrum contour.
+    Area,
+    /// One circle per bar, optionally leaving fading trails.
+    Dots,
+    /// Time-domain waveform of the current frame wrapped around a circle, its radius modulated by the amplitude.
+    Ring,
+    /// Bars built from discrete segments rising from the band's bottom, like a hardware LED meter.
+    Led,
+    /// Left against right samples of the current frame as a phosphor-style scatter (goniometer / Lissajous).
+    Vectorscope,
+}
+
+/// Outline of each bar in the bar styles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+#[serde(rename_all = "kebab-case")]
+pub enum BarShape {
+    /// Rectangle with corners rounded by `bar_radius`.
+    #[default]
+    Rect,
+    /// Rectangle with fully rounded ends (pill).
+    Capsule,
+    /// Triangle pointing in the direction the bar grows.
+    Triangle,
+    /// Diamond spanning the bar's width and length.
+    Diamond,
+}
+
+/// Outline drawn around each bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BarStroke {
+    /// Outline color as RGBA.
+    pub color: [u8; 4],
+    /// Outline width (pixels), measured inward from the bar's edge.
+    pub width: u32,
+}
+
+/// Application configuration. Serialized with kebab-case keys; missing keys take their default values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Config {
     /// Output video width (pixels).
     pub width: u32,
@@ -15,16 +178,366 @@ pub struct Config {
     pub spectrum_height: u32,
     /// Distance from bottom of frame to the bottom edge of the spectrum band (pixels).
     pub spectrum_y_from_bottom: u32,
-    /// Horizontal width of the spectrum band (pixels). When None, uses full frame width; when Some(w), band is centered.
+    /// Horizontal width of the spectrum band (pixels). When None, uses full frame width.
     pub spectrum_width: Option<u32>,
+    /// Horizontal anchor of the band (pixels). When None, the frame's left edge, center or right edge per `spectrum_align`.
+    pub spectrum_x: Option<u32>,
+    /// Vertical anchor of the band (pixels from the top). When None, the frame's top, middle, or
+    /// `spectrum_y_from_bottom` above its bottom per `spectrum_valign`.
+    pub spectrum_y: Option<u32>,
+    /// Which part of the band is placed at the horizontal anchor.
+    pub spectrum_align: HAlign,
+    /// Which part of the band is placed at the vertical anchor.
+    pub spectrum_valign: VAlign,
+    /// Direction of the spectrum. Placement options apply to the horizontal layout before it is turned.
+    pub orientation: Orientation,
     /// FFT window size (number of samples).
     pub fft_size: usize,
     /// Overlap ratio (0.0–1.0, e.g. 0.5 = 50%).
     pub overlap: f32,
+    /// Visualization style.
+    pub style: Style,
+    /// Opacity (0.0–1.0) of the lower, mirrored half in `Style::Mirror`.
+    pub mirror_opacity: f32,
+    /// Opacity of the whole band over the layers below it (0.0–1.0), e.g. to dim a spectrogram behind other bands.
+    pub opacity: f32,
+    /// Center of the circle for `Style::Radial` and `Style::Ring` (pixels). When None, the frame center.
+    pub radial_center: Option<(u32, u32)>,
+    /// Inner radius of the circle for `Style::Radial` (pixels), or the resting radius of `Style::Ring`. When None,
+    /// 1/6 of the shorter frame side.
+    pub radial_inner_radius: Option<u32>,
+    /// Angle of the first bar for `Style::Radial`, or of the first sample for `Style::Ring` (degrees clockwise from
+    /// 12 o'clock).
+    pub radial_rotation: f32,
+    /// Stroke width (pixels) for line-drawn styles (`Style::Line`, `Style::Waveform`, `Style::Ring`).
+    pub stroke_width: f32,
+    /// Fill the area under the curve in `Style::Line`.
+    pub line_fill: bool,
+    /// Gradient end color for `Style::Area` (at the baseline). When None, the area is filled solid with `bar_color`.
+    pub area_gradient: Option<[u8; 4]>,
+    /// Reflect the area of `Style::Area` around the band's center line.
+    pub area_mirror: bool,
+    /// What the dots of `Style::Dots` track: radius or vertical position.
+    pub dot_mode: DotMode,
+    /// Number of previous frames kept as fading trails in `Style::Dots` (0 = no trails).
+    pub dot_trail: usize,
+    /// Height of one segment of `Style::Led` (pixels).
+    pub led_segment_height: u32,
+    /// Gap between segments of `Style::Led` (pixels).
+    pub led_segment_gap: u32,
+    /// Color `Style::Led` segments green, yellow, then red by their height instead of by `bar_color`.
+    pub led_ramp: bool,
+    /// Where bars are anchored in the band for `Style::Bars`.
+    pub bar_anchor: BarAnchor,
+    /// Draw the left and right channels separately in `Style::Bars`. When None, bars show the mono downmix.
+    pub stereo_split: Option<StereoSplit>,
+    /// Gap between adjacent bars (pixels).
+    pub bar_gap: u32,
+    /// Shortest drawn bar (pixels), so bars stay visible as stubs through silence (bar, mirror, LED and radial styles).
+    pub bar_min_height: u32,
+    /// Outline of bars in `Style::Bars`, `Style::Mirror` and `Style::Led`.
+    pub bar_shape: BarShape,
+    /// Corner radius of bars (pixels; 0 = square, large values give pill shapes). When None, half the bar width clamped to 1–4.
+    pub bar_radius: Option<u32>,
     /// Bar color as RGBA (default: black).
     pub bar_color: [u8; 4],
+    /// Colormap sampled by each bar's frequency position. When None, all bars use `bar_color`.
+    pub bar_colormap: Option<Colormap>,
+    /// Color stops for `Colormap::Custom`, low to high frequency.
+    pub colormap_stops: Vec<[u8; 4]>,
+    /// Color of a full-height bar. When Some, each bar fades from its base color (quiet) to this color (loud) with its height.
+    pub bar_color_loud: Option<[u8; 4]>,
+    /// Soft halo around the spectrum in its own colors. When None, no glow is drawn.
+    pub glow: Option<Glow>,
+    /// Soft drop shadow beneath the bars. When None, no shadow is drawn.
+    pub shadow: Option<Shadow>,
+    /// Outline around each bar in `Style::Bars` and `Style::Mirror`. When None, bars are filled only.
+    pub bar_stroke: Option<BarStroke>,
+    /// Draw only the outline of bars (requires `bar_stroke`).
+    pub bar_stroke_only: bool,
+    /// Flipped, fading copy of the spectrum below the band. When None, no reflection is drawn.
+    pub reflection: Option<Reflection>,
     /// Background color as RGBA (default: white).
     pub bg_color: [u8; 4],
+    /// Color of the already-played part and the playhead in `Style::Timeline`.
+    pub played_color: [u8; 4],
+    /// Power-law exponent applied to normalized bar values (1.0 = unchanged).
+    pub amp_gamma: f32,
+    /// Spectral tilt compensation (dB per octave, relative to 1 kHz; 0.0 = off).
+    pub tilt: f32,
+    /// Radius (in bars) of spatial smoothing across neighboring bars (0 = off).
+    pub bar_smoothing: usize,
+    /// Kernel used for bar smoothing.
+    pub bar_smoothing_kernel: SmoothingKernel,
+    /// Draw a marker at the spectral centroid ("brightness") of each frame.
+    pub centroid_marker: bool,
+    /// Centroid marker color as RGBA (default: red).
+    pub centroid_color: [u8; 4],
+    /// Burst particles from the loudest bar tops on each detected beat (bars, mirror and LED styles).
+    pub particles: bool,
+}
+
+/// Room the bar styles keep free in the band beyond the tallest bar (pixels).
+pub const BAND_MARGIN: u32 = 4;
+
+impl Config {
+    /// Check for settings that can't be drawn as asked, before any work is done. Each message names the options
+    /// involved and how to fix them. Placement is checked in the horizontal layout, which `Orientation::Vertical`
+    /// lays out along the frame's height.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fps == 0 {
+            return Err("--fps must be at least 1".into());
+        }
+        if self.bars == 0 {
+            return Err("--bars must be at least 1".into());
+        }
+        if !(0.0..1.0).contains(&self.overlap) {
+            return Err(format!("overlap {} must be at least 0 and below 1.0 (0.5 for half)", self.overlap));
+        }
+        if matches!(self.style, Style::Radial | Style::Ring) {
+            return match self.radial_center {
+                Some((x, y)) if x > self.width || y > self.height => Err(format!(
+                    "--radial-center {},{} is outside the {}x{} frame: use a point inside it, such as the center {},{}",
+                    x,
+                    y,
+                    self.width,
+                    self.height,
+                    self.width / 2,
+                    self.height / 2
+                )),
+                _ => Ok(()),
+            };
+        }
+        let (width, height, across) = match self.orientation {
+            Orientation::Vertical => (self.height, self.width, " (turned on its side by --orientation vertical)"),
+            _ => (self.width, self.height, ""),
+        };
+        let h = self.spectrum_height;
+        if h == 0 {
+            return Err("--spectrum-height must be at least 1".into());
+        }
+        if h > height {
+            return Err(format!(
+                "--spectrum-height {} is more than the frame's {} px{}: use --spectrum-height {} or less",
+                h, height, across, height
+            ));
+        }
+        if let Some(w) = self.spectrum_width.filter(|&w| w > width) {
+            return Err(format!(
+                "--spectrum-width {} is more than the frame's {} px{}: use --spectrum-width {} or less, or leave it \
+                 out for the full width",
+                w, width, across, width
+            ));
+        }
+        if let Some(x) = self.spectrum_x.filter(|&x| x > width) {
+            return Err(format!("--spectrum-x {} is off the {} px frame{}: use 0 to {}", x, width, across, width));
+        }
+        if let Some(y) = self.spectrum_y.filter(|&y| y > height) {
+            return Err(format!("--spectrum-y {} is off the {} px frame{}: use 0 to {}", y, height, across, height));
+        }
+        // The band's bottom edge, kept inside the frame as `draw::band_bottom` does; a band reaching above the top is
+        // cut off there, but one with no rows left in the frame is a mistake.
+        let anchor = match (self.spectrum_y, self.spectrum_valign) {
+            (Some(y), _) => y as i64,
+            (None, VAlign::Top) => 0,
+            (None, VAlign::Middle) => height as i64 / 2,
+            (None, VAlign::Bottom) => height as i64 - self.spectrum_y_from_bottom as i64,
+        };
+        let bottom = match self.spectrum_valign {
+            VAlign::Top => anchor + h as i64,
+            VAlign::Middle => anchor + h as i64 / 2,
+            VAlign::Bottom => anchor,
+        };
+        if bottom.min(height as i64) <= 0 {
+            return Err(match self.spectrum_y {
+                None => format!(
+                    "--spectrum-y-from-bottom {} puts the band above the {} px frame{}: use \
+                     --spectrum-y-from-bottom {} or less to show all of it",
+                    self.spectrum_y_from_bottom,
+                    height,
+                    across,
+                    height - h
+                ),
+                Some(y) => format!(
+                    "--spectrum-y {} puts the band above the frame{}: use --spectrum-y {} or more to show all of it",
+                    y,
+                    across,
+                    y as i64 + h as i64 - bottom
+                ),
+            });
+        }
+        if !matches!(self.style, Style::Bars | Style::Mirror | Style::Led | Style::Dots | Style::Line | Style::Area) {
+            return Ok(());
+        }
+        // Left-right stereo bars are stacked up the band instead of across it.
+        let (room, room_name) = match self.stereo_split {
+            Some(StereoSplit::LeftRight) => (h.saturating_sub(BAND_MARGIN), "band's height less its 4 px margin"),
+            _ => (self.spectrum_width.unwrap_or(width), "spectrum's width"),
+        };
+        let (bars, gap) = (self.bars as u64, self.bar_gap as u64);
+        let needed = bars + (bars - 1) * gap;
+        if needed > room as u64 {
+            let most = (room as u64 + gap) / (1 + gap);
+            return Err(if gap == 0 || bars > room as u64 {
+                format!(
+                    "--bars {} is more than the {} px of the {}{}: use --bars {} or fewer",
+                    bars, room, room_name, across, most
+                )
+            } else {
+                format!(
+                    "--bars {} with --bar-gap {} needs {} px, more than the {} px of the {}{}: use --bars {} or \
+                     fewer, or a smaller --bar-gap",
+                    bars, gap, needed, room, room_name, across, most
+                )
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Why `ConfigBuilder` rejected a value.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ConfigError {
+    /// A setter's value is out of range on its own.
+    #[error("{name} must be {expected}, got {value}")]
+    OutOfRange { name: &'static str, value: String, expected: &'static str },
+    /// The values don't fit together, e.g. more bars than the band is wide (`Config::validate`'s message, which names
+    /// the command-line options).
+    #[error("{0}")]
+    Layout(String),
+}
+
+/// Builds a `Config` from the defaults, checking each value as it is set and the layout as a whole in `build`.
+/// Setters that can fail return `Result`, so they chain with `?`.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+/// `ConfigError::OutOfRange` for `name` unless `ok`.
+fn check(name: &'static str, value: impl Display, ok: bool, expected: &'static str) -> Result<(), ConfigError> {
+    if ok { Ok(()) } else { Err(ConfigError::OutOfRange { name, value: value.to_string(), expected }) }
+}
+
+impl Config {
+    /// A builder starting from `Config::default()`.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+impl ConfigBuilder {
+    /// Frame size in pixels.
+    pub fn size(mut self, width: u32, height: u32) -> Result<Self, ConfigError> {
+        check("width", width, width > 0, "at least 1")?;
+        check("height", height, height > 0, "at least 1")?;
+        (self.config.width, self.config.height) = (width, height);
+        Ok(self)
+    }
+
+    /// Video frame rate.
+    pub fn fps(mut self, fps: u32) -> Result<Self, ConfigError> {
+        check("fps", fps, fps > 0, "at least 1")?;
+        self.config.fps = fps;
+        Ok(self)
+    }
+
+    /// Number of bars the spectrum is split into.
+    pub fn bars(mut self, bars: usize) -> Result<Self, ConfigError> {
+        check("bars", bars, bars > 0, "at least 1")?;
+        self.config.bars = bars;
+        Ok(self)
+    }
+
+    /// FFT window in samples, a power of two.
+    pub fn fft_size(mut self, fft_size: usize) -> Result<Self, ConfigError> {
+        check("fft_size", fft_size, fft_size >= 64 && fft_size.is_power_of_two(), "a power of two from 64")?;
+        self.config.fft_size = fft_size;
+        Ok(self)
+    }
+
+    /// Fraction of each FFT window shared with the next.
+    pub fn overlap(mut self, overlap: f32) -> Result<Self, ConfigError> {
+        check("overlap", overlap, (0.0..1.0).contains(&overlap), "at least 0 and below 1.0")?;
+        self.config.overlap = overlap;
+        Ok(self)
+    }
+
+    /// Height of the spectrum band; whether it fits the frame is checked by `build`.
+    pub fn spectrum_height(mut self, height: u32) -> Result<Self, ConfigError> {
+        check("spectrum_height", height, height > 0, "at least 1")?;
+        self.config.spectrum_height = height;
+        Ok(self)
+    }
+
+    /// Width of the spectrum band instead of the frame's.
+    pub fn spectrum_width(mut self, width: u32) -> Result<Self, ConfigError> {
+        check("spectrum_width", width, width > 0, "at least 1")?;
+        self.config.spectrum_width = Some(width);
+        Ok(self)
+    }
+
+    /// Top-left corner of the band instead of its alignment.
+    pub fn spectrum_position(mut self, x: u32, y: u32) -> Self {
+        (self.config.spectrum_x, self.config.spectrum_y) = (Some(x), Some(y));
+        self
+    }
+
+    /// Space between bars (pixels).
+    pub fn bar_gap(mut self, gap: u32) -> Self {
+        self.config.bar_gap = gap;
+        self
+    }
+
+    /// Visualization style.
+    pub fn style(mut self, style: Style) -> Self {
+        self.config.style = style;
+        self
+    }
+
+    /// Direction the band runs in.
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.config.orientation = orientation;
+        self
+    }
+
+    /// Bar and background colors as RGBA.
+    pub fn colors(mut self, bar: [u8; 4], background: [u8; 4]) -> Self {
+        (self.config.bar_color, self.config.bg_color) = (bar, background);
+        self
+    }
+
+    /// Opacity of the whole band, 0.0–1.0.
+    pub fn opacity(mut self, opacity: f32) -> Result<Self, ConfigError> {
+        check("opacity", opacity, (0.0..=1.0).contains(&opacity), "between 0.0 and 1.0")?;
+        self.config.opacity = opacity;
+        Ok(self)
+    }
+
+    /// Exponent shaping the normalized bar heights (below 1.0 lifts quiet bars).
+    pub fn amp_gamma(mut self, gamma: f32) -> Result<Self, ConfigError> {
+        check("amp_gamma", gamma, gamma.is_finite() && gamma > 0.0, "a positive number")?;
+        self.config.amp_gamma = gamma;
+        Ok(self)
+    }
+
+    /// Set any other field directly; `build` still checks the result.
+    pub fn with(mut self, set: impl FnOnce(&mut Config)) -> Self {
+        set(&mut self.config);
+        self
+    }
+
+    /// The config, once its values fit together (`Config::validate`).
+    pub fn build(self) -> Result<Config, ConfigError> {
+        self.config.validate().map_err(ConfigError::Layout)?;
+        Ok(self.config)
+    }
+
+    /// The config without `build`'s layout checks, for analysis that draws nothing.
+    #[cfg(feature = "symphonia")]
+    pub(crate) fn build_unplaced(self) -> Config {
+        self.config
+    }
 }
 
 impl Default for Config {
@@ -37,10 +550,128 @@ impl Default for Config {
             spectrum_height: 200,
             spectrum_y_from_bottom: 0,
             spectrum_width: None,
+            spectrum_x: None,
+            spectrum_y: None,
+            spectrum_align: HAlign::default(),
+            spectrum_valign: VAlign::default(),
+            orientation: Orientation::default(),
             fft_size: 2048,
             overlap: 0.5,
+            style: Style::Bars,
+            mirror_opacity: 0.6,
+            opacity: 1.0,
+            radial_center: None,
+            radial_inner_radius: None,
+            radial_rotation: 0.0,
+            stroke_width: 2.0,
+            line_fill: false,
+            area_gradient: None,
+            area_mirror: false,
+            dot_mode: DotMode::default(),
+            dot_trail: 0,
+            led_segment_height: 6,
+            led_segment_gap: 2,
+            led_ramp: false,
+            bar_anchor: BarAnchor::default(),
+            stereo_split: None,
+            bar_gap: 1,
+            bar_shape: BarShape::default(),
+            bar_min_height: 0,
+            bar_radius: None,
             bar_color: [0, 0, 0, 255],
+            bar_colormap: None,
+            colormap_stops: Vec::new(),
+            bar_color_loud: None,
+            glow: None,
+            shadow: None,
+            bar_stroke: None,
+            bar_stroke_only: false,
+            reflection: None,
             bg_color: [255, 255, 255, 255],
+            played_color: [255, 102, 0, 255],
+            amp_gamma: 1.0,
+            tilt: 0.0,
+            bar_smoothing: 0,
+            bar_smoothing_kernel: SmoothingKernel::Gaussian,
+            centroid_marker: false,
+            centroid_color: [255, 0, 0, 255],
+            particles: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BarStroke, Config, ConfigError, Orientation, StereoSplit, Style};
+    use crate::colormap::Colormap;
+    use crate::effects::Glow;
+
+    #[test]
+    fn validate_suggests_what_fits() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        let err = |config: Config| config.validate().unwrap_err();
+        let narrow = Config { width: 300, ..Config::default() };
+        assert!(err(Config { bars: 400, ..narrow.clone() }).ends_with("use --bars 150 or fewer"));
+        assert!(err(Config { bar_gap: 2, ..narrow.clone() }).contains("needs 382 px, more than the 300 px"));
+        assert!(err(Config { spectrum_height: 1200, ..Config::default() }).ends_with("--spectrum-height 1080 or less"));
+        // A band partly above the frame is cut off; one entirely above it is rejected.
+        assert!(Config { spectrum_y_from_bottom: 900, ..Config::default() }.validate().is_ok());
+        assert!(err(Config { spectrum_y_from_bottom: 1080, ..Config::default() }).contains("-from-bottom 880 or"));
+        assert!(err(Config { spectrum_y: Some(0), ..Config::default() }).contains("--spectrum-y 200 or more"));
+        assert!(err(Config { spectrum_x: Some(2000), ..Config::default() }).contains("use 0 to 1920"));
+        // Stacked left-right bars need room up the band; radial bars go around the circle.
+        let stacked = Config { stereo_split: Some(StereoSplit::LeftRight), bars: 256, bar_gap: 0, ..Config::default() };
+        assert!(err(stacked).ends_with("use --bars 196 or fewer"));
+        assert_eq!(Config { style: Style::Radial, bars: 4000, ..Config::default() }.validate(), Ok(()));
+        let off_center = Config { style: Style::Ring, radial_center: Some((0, 2000)), ..Config::default() };
+        assert!(err(off_center).contains("such as the center 960,540"));
+    }
+
+    #[test]
+    fn vertical_bands_are_checked_along_the_height() {
+        let vertical = Config { orientation: Orientation::Vertical, ..Config::default() };
+        assert!(Config { spectrum_height: 1500, ..vertical.clone() }.validate().is_ok());
+        assert!(Config { bars: 1500, bar_gap: 0, ..vertical.clone() }.validate().unwrap_err().contains("1080 px"));
+        assert!(Config { overlap: 1.0, ..Config::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn the_builder_rejects_bad_values_and_layouts() {
+        let config = Config::builder().size(1280, 720).unwrap().bars(64).unwrap().style(Style::Mirror).build().unwrap();
+        assert_eq!((config.width, config.bars, config.style), (1280, 64, Style::Mirror));
+        assert_eq!(
+            Config::builder().bars(0).unwrap_err(),
+            ConfigError::OutOfRange { name: "bars", value: "0".into(), expected: "at least 1" }
+        );
+        let error = Config::builder().fft_size(1000).unwrap_err();
+        assert_eq!(error.to_string(), "fft_size must be a power of two from 64, got 1000");
+        assert!(Config::builder().overlap(1.0).is_err() && Config::builder().amp_gamma(f32::NAN).is_err());
+        // Each value is fine alone, but 400 bars don't fit across 300 px.
+        let narrow = Config::builder().size(300, 720).unwrap().bars(400).unwrap().bar_gap(0).build();
+        assert!(matches!(narrow, Err(ConfigError::Layout(message)) if message.ends_with("use --bars 300 or fewer")));
+        assert!(Config::builder().with(|config| config.spectrum_height = 2000).build().is_err());
+    }
+
+    #[test]
+    fn configs_round_trip_through_toml_and_json() {
+        let config = Config {
+            style: Style::Radial,
+            orientation: Orientation::Rotate(-12.5),
+            radial_center: Some((640, 360)),
+            stereo_split: Some(StereoSplit::TopBottom),
+            bar_colormap: Some(Colormap::Custom),
+            colormap_stops: vec![[255, 0, 0, 255], [0, 0, 255, 128]],
+            glow: Some(Glow { radius: 12, intensity: 1.5 }),
+            bar_stroke: Some(BarStroke { color: [255, 255, 255, 255], width: 2 }),
+            ..Config::default()
+        };
+        let toml = toml::to_string(&config).unwrap();
+        assert!(toml.contains("style = \"radial\"") && toml.contains("stereo-split = \"top-bottom\""), "{}", toml);
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+        assert_eq!(serde_json::from_str::<Config>(&serde_json::to_string(&config).unwrap()).unwrap(), config);
+        // Missing keys keep their defaults; unknown ones are rejected.
+        let partial: Config = toml::from_str("bars = 64\nbar-color = [255, 0, 0, 255]\n").unwrap();
+        assert_eq!(partial, Config { bars: 64, bar_color: [255, 0, 0, 255], ..Config::default() });
+        assert!(toml::from_str::<Config>("bar-colour = [255, 0, 0, 255]\n").is_err());
+    }
+}
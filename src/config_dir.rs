@@ -0,0 +1,16 @@
+//! Where saved presets (`preset save`) and plugins (`--plugin`) live: the user config directory
+
+use std::path::PathBuf;
+
+/// Directory `audio-spectrum-generator/<name>` in `$XDG_CONFIG_HOME` (default `~/.config`), or in `%APPDATA%` on
+/// Windows. `what` names its contents for the error when there is no such directory.
+pub fn user_config_dir(name: &str, what: &str) -> Result<PathBuf, String> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    };
+    let base = base.ok_or_else(|| format!("cannot find the user config directory for {}", what))?;
+    Ok(base.join("audio-spectrum-generator").join(name))
+}
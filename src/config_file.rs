@@ -0,0 +1,164 @@
+//! Command-line options read from a TOML file (--config, saved presets), and written back (`preset save`)
+
+use std::ffi::OsString;
+use std::path::Path;
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use toml::Value;
+
+/// Read the options in the TOML file at `path` as command-line arguments for `command`. Keys are long flag names
+/// (`bar-color`, or `bar_color`); options for which `is_set` returns true (given on the command line) are skipped
+/// so flags take precedence over the file.
+pub fn config_args(
+    path: &Path,
+    command: &Command,
+    is_set: impl Fn(&str) -> bool,
+) -> Result<Vec<OsString>, Box<dyn std::error::Error + Send + Sync>> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read config {:?}: {}", path, e))?;
+    let table: toml::Table = content.parse().map_err(|e| format!("failed to parse config {:?}: {}", path, e))?;
+    let mut args = Vec::new();
+    for (key, value) in &table {
+        let long = key.replace('_', "-");
+        let arg = command
+            .get_arguments()
+            .filter(|arg| arg.get_id() != "config")
+            .filter(|arg| arg.get_action().takes_values() || matches!(arg.get_action(), ArgAction::SetTrue))
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+            .ok_or_else(|| format!("config {:?}: unknown option {:?}", path, key))?;
+        if is_set(arg.get_id().as_str()) {
+            continue;
+        }
+        let values = option_args(&long, arg, value).map_err(|e| format!("config {:?}: {}: {}", path, key, e))?;
+        args.extend(values.into_iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+/// The arguments that set `value` for `arg`: a flag for `true`, `--name=VALUE` for a value, and one flag per item
+/// for an array (or all items after one flag for options taking several values, like `--snapshot`).
+fn option_args(long: &str, arg: &Arg, value: &Value) -> Result<Vec<String>, String> {
+    let flag = format!("--{}", long);
+    if matches!(arg.get_action(), ArgAction::SetTrue) {
+        return match value {
+            Value::Boolean(true) => Ok(vec![flag]),
+            Value::Boolean(false) => Ok(Vec::new()),
+            _ => Err("expected true or false".into()),
+        };
+    }
+    match value {
+        Value::Array(items) => {
+            let items = items.iter().map(scalar).collect::<Result<Vec<_>, _>>()?;
+            if arg.get_num_args().is_some_and(|n| n.max_values() > 1) {
+                Ok(std::iter::once(flag).chain(items).collect())
+            } else {
+                Ok(items.into_iter().map(|item| format!("{}={}", flag, item)).collect())
+            }
+        }
+        _ => Ok(vec![format!("{}={}", flag, scalar(value)?)]),
+    }
+}
+
+/// The options given on the command line in `matches` (other than the `skip` IDs) as a table `config_args` reads
+/// back. Values are kept as the strings that were typed.
+pub fn options_table(command: &Command, matches: &ArgMatches, skip: &[&str]) -> toml::Table {
+    let mut table = toml::Table::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else { continue };
+        if skip.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let value = if matches!(arg.get_action(), ArgAction::SetTrue) {
+            Value::Boolean(true)
+        } else {
+            let Some(raw) = matches.get_raw(id) else { continue };
+            let mut values: Vec<Value> = raw.map(|v| Value::String(v.to_string_lossy().into_owned())).collect();
+            let multiple = matches!(arg.get_action(), ArgAction::Append)
+                || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+            match values.len() {
+                1 if !multiple => values.remove(0),
+                _ => Value::Array(values),
+            }
+        };
+        table.insert(long.to_string(), value);
+    }
+    table
+}
+
+fn scalar(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::Float(f) => Ok(f.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err("expected a string, number, boolean or array of them".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{config_args, options_table};
+    use clap::{Arg, ArgAction, Command};
+    use std::ffi::OsString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn command() -> Command {
+        Command::new("test")
+            .arg(Arg::new("config").long("config"))
+            .arg(Arg::new("bar_color").long("bar-color"))
+            .arg(Arg::new("bars").long("bars"))
+            .arg(Arg::new("show_title").long("show-title").action(ArgAction::SetTrue))
+            .arg(Arg::new("stops").long("colormap-stops").action(ArgAction::Append).value_delimiter(','))
+            .arg(Arg::new("snapshot").long("snapshot").num_args(2))
+    }
+
+    fn args_for(toml: &str, is_set: impl Fn(&str) -> bool) -> Result<Vec<OsString>, String> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("asg-config-test-{}-{}.toml", std::process::id(), n));
+        std::fs::write(&path, toml).unwrap();
+        let args = config_args(&path, &command(), is_set).map_err(|e| e.to_string());
+        let _ = std::fs::remove_file(&path);
+        args
+    }
+
+    #[test]
+    fn keys_become_long_flags() {
+        let toml = "bar_color = \"ff6600\"\nbars = 64\nshow-title = true\ncolormap-stops = [\"000000\", \"ffffff\"]\n\
+                    snapshot = [\"1.5\", \"a.png\"]\n";
+        let args = args_for(toml, |_| false).unwrap();
+        let expected = [
+            "--bar-color=ff6600",
+            "--bars=64",
+            "--colormap-stops=000000",
+            "--colormap-stops=ffffff",
+            "--show-title",
+            "--snapshot",
+            "1.5",
+            "a.png",
+        ];
+        assert_eq!(args, expected.map(OsString::from));
+    }
+
+    #[test]
+    fn command_line_wins_and_unknown_keys_fail() {
+        let args = args_for("bars = 64\nshow_title = false\n", |id| id == "bars").unwrap();
+        assert!(args.is_empty());
+        assert!(args_for("colour = \"fff\"\n", |_| false).unwrap_err().contains("unknown option"));
+        assert!(args_for("config = \"other.toml\"\n", |_| false).is_err());
+        assert!(args_for("show_title = \"yes\"\n", |_| false).is_err());
+        assert!(args_for("bars = { n = 1 }\n", |_| false).is_err());
+    }
+
+    #[test]
+    fn saved_options_read_back_as_the_same_arguments() {
+        let argv = ["--bars=64", "--colormap-stops=000000,ffffff", "--show-title", "--snapshot", "1.5", "a.png"];
+        let matches = command().get_matches_from(std::iter::once("test").chain(argv));
+        let table = options_table(&command(), &matches, &["snapshot"]);
+        assert_eq!(table.keys().collect::<Vec<_>>(), ["bars", "colormap-stops", "show-title"]);
+        let args = args_for(&toml::to_string(&table).unwrap(), |_| false).unwrap();
+        let expected = ["--bars=64", "--colormap-stops=000000", "--colormap-stops=ffffff", "--show-title"];
+        assert_eq!(args, expected.map(OsString::from));
+    }
+}
@@ -1,12 +1,27 @@
-//! MP3 → PCM decoding (symphonia)
+//! Input → PCM decoding: MP3 with symphonia, or any format through ffmpeg, or raw PCM, behind `AudioDecoder`. A
+//! decoder streams the PCM chunk by chunk and keeps none of it; `decode` collects it for callers that want it whole.
 
-use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
-use symphonia::core::formats::FormatOptions;
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
-use symphonia::default::get_codecs;
-use symphonia::default::get_probe;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::Path;
+#[cfg(feature = "ffmpeg")]
+use std::path::PathBuf;
+#[cfg(feature = "ffmpeg")]
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "symphonia")]
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{DecoderOptions, CODEC_TYPE_NULL},
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey},
+    probe::ProbeResult,
+};
+#[cfg(feature = "symphonia")]
+use symphonia::default::{get_codecs, get_probe};
+
+use crate::error::SpectrumError;
 
 /// Decoded audio (mono PCM and sample rate).
 pub struct DecodedAudio {
@@ -14,12 +29,490 @@ pub struct DecodedAudio {
     pub samples: Vec<f32>,
     /// Sample rate (Hz).
     pub sample_rate: u32,
+    /// Separate left and right channel PCM, when requested and the source has two or more channels.
+    pub stereo: Option<(Vec<f32>, Vec<f32>)>,
+    pub tags: Tags,
 }
 
-/// Decode an MP3 file and return mono PCM.
-/// For stereo, left and right are averaged to mono.
-pub fn decode_mp3(path: &std::path::Path) -> Result<DecodedAudio, Box<dyn std::error::Error + Send + Sync>> {
-    let src = std::fs::File::open(path)?;
+/// One decoded packet's worth of audio, as `AudioDecoder::stream` hands it over.
+pub struct AudioChunk<'a> {
+    /// Sample rate (Hz).
+    pub sample_rate: u32,
+    /// Mono PCM samples (f32, -1.0 to 1.0).
+    pub samples: &'a [f32],
+    /// The first two channels, when asked for and the source has two or more.
+    pub stereo: Option<(&'a [f32], &'a [f32])>,
+}
+
+/// What a stream delivered, once it has ended: everything about the audio except the samples themselves.
+pub struct StreamedAudio {
+    /// Sample rate (Hz).
+    pub sample_rate: u32,
+    /// Mono samples decoded.
+    pub samples: usize,
+    /// Whether the chunks carried left and right.
+    pub stereo: bool,
+    pub tags: Tags,
+}
+
+/// Tags and embedded picture of the input.
+#[derive(Default)]
+pub struct Tags {
+    /// Encoded bytes (PNG/JPEG) of the first embedded picture (e.g. ID3 APIC album art), if any.
+    pub cover_art: Option<Vec<u8>>,
+    /// Track title tag (e.g. ID3 TIT2), if any.
+    pub title: Option<String>,
+    /// Artist tag (e.g. ID3 TPE1), if any.
+    pub artist: Option<String>,
+    /// Album tag (e.g. ID3 TALB), if any.
+    pub album: Option<String>,
+}
+
+/// What the input's headers say, read without decoding it.
+pub struct TrackInfo {
+    /// Track length in seconds (a Xing/VBRI frame count, or estimated from the bitrate), when the file says.
+    pub duration_sec: Option<f32>,
+    /// Sample rate (Hz).
+    pub sample_rate: u32,
+    /// Codec name from symphonia's registry (e.g. "MPEG Audio Layer 3"), when the backend knows it.
+    pub codec: Option<&'static str>,
+    /// Number of channels, when the headers say.
+    pub channels: Option<usize>,
+    pub tags: Tags,
+    /// Every tag, in file order: its standard name when symphonia knows one (`TrackTitle`), its own key (the ID3
+    /// frame ID, `TIT2`) and its value.
+    #[cfg(feature = "symphonia")]
+    pub all_tags: Vec<TagEntry>,
+    /// Every embedded picture.
+    #[cfg(feature = "symphonia")]
+    pub pictures: Vec<Picture>,
+}
+
+/// One tag of an MP3 file, for `probe`.
+#[cfg(feature = "symphonia")]
+pub struct TagEntry {
+    pub std_key: Option<StandardTagKey>,
+    pub key: String,
+    pub value: String,
+}
+
+/// An embedded picture of an MP3 file, for `probe`.
+#[cfg(feature = "symphonia")]
+pub struct Picture {
+    /// MIME type as tagged (e.g. `image/jpeg`).
+    pub media_type: String,
+    /// What the picture shows (e.g. `FrontCover`), when tagged.
+    pub usage: Option<StandardVisualKey>,
+    pub data: Vec<u8>,
+}
+
+/// A backend turning an input file into PCM: `Symphonia` (MP3, the default), `Ffmpeg` (anything ffmpeg reads) or
+/// `RawPcm` (headerless samples). A live source would implement it the same way, calling `on_chunk` as audio arrives.
+/// Decoders are shared with the thread that reads the input a second time while rendering, hence `Send + Sync`.
+pub trait AudioDecoder: Send + Sync {
+    /// Length, sample rate and tags, read without decoding as far as the backend can.
+    fn probe(&self, path: &Path) -> Result<TrackInfo, SpectrumError>;
+
+    /// Decode chunk by chunk, keeping nothing: `on_chunk` gets each chunk as it is decoded (left and right too with
+    /// `keep_stereo`) and ends the stream early with `ControlFlow::Break`. With `stop_after_sec`, decoding ends once
+    /// that much audio has been decoded.
+    fn stream(
+        &self,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        on_chunk: &mut dyn FnMut(AudioChunk<'_>) -> ControlFlow<()>,
+    ) -> Result<StreamedAudio, SpectrumError>;
+
+    /// Decode to mono PCM, as `decode_mp3` describes: `stream`, collecting the samples.
+    fn decode(
+        &self,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        on_chunk: &mut dyn FnMut(u32, &[f32]),
+    ) -> Result<DecodedAudio, SpectrumError> {
+        let (mut samples, mut left, mut right) = (Vec::new(), Vec::new(), Vec::new());
+        let streamed = self.stream(path, keep_stereo, stop_after_sec, &mut |chunk| {
+            samples.extend_from_slice(chunk.samples);
+            if let Some((l, r)) = chunk.stereo {
+                left.extend_from_slice(l);
+                right.extend_from_slice(r);
+            }
+            on_chunk(chunk.sample_rate, chunk.samples);
+            ControlFlow::Continue(())
+        })?;
+        let StreamedAudio { sample_rate, stereo, tags, .. } = streamed;
+        Ok(DecodedAudio { samples, sample_rate, stereo: stereo.then_some((left, right)), tags })
+    }
+}
+
+/// Which `AudioDecoder` reads the input (`--decoder`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+pub enum DecoderKind {
+    /// MP3 through symphonia, built in.
+    #[default]
+    Symphonia,
+    /// Any format ffmpeg reads (WAV, FLAC, AAC, a video's audio, ...), through ffmpeg and ffprobe.
+    Ffmpeg,
+    /// Headerless 16-bit little-endian PCM, laid out as --raw-format says.
+    Raw,
+}
+
+/// MP3 through symphonia (`probe_track`, and the decoding behind `decode_mp3`).
+#[cfg(feature = "symphonia")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Symphonia;
+
+#[cfg(feature = "symphonia")]
+impl AudioDecoder for Symphonia {
+    fn probe(&self, path: &Path) -> Result<TrackInfo, SpectrumError> {
+        probe_track(path)
+    }
+
+    fn stream(
+        &self,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        on_chunk: &mut dyn FnMut(AudioChunk<'_>) -> ControlFlow<()>,
+    ) -> Result<StreamedAudio, SpectrumError> {
+        let mut probe_result = probe_file(path)?;
+        let tags = read_tags(&mut probe_result);
+
+        let track = probe_result
+            .format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| decode_error(path, "no audio track found"))?;
+
+        let track_id = track.id;
+        let codec_params = track.codec_params.clone();
+        let mut decoder = get_codecs()
+            .make(&codec_params, &DecoderOptions::default())
+            .map_err(|e| decode_error(path, format!("decoder creation error: {}", e)))?;
+
+        let sample_rate = codec_params.sample_rate.ok_or_else(|| decode_error(path, "missing sample rate"))?;
+        let channels = codec_params.channels.ok_or_else(|| decode_error(path, "missing channel count"))?.count();
+        let stop_after = stop_after_sec.map(|sec| (sec.max(0.0) * sample_rate as f32) as usize);
+        let mut downmix = Downmix::new(sample_rate, channels, keep_stereo);
+
+        loop {
+            if stop_after.is_some_and(|stop| downmix.frames >= stop) {
+                break;
+            }
+            let packet = match probe_result.format.next_packet() {
+                Ok(p) => p,
+                Err(symphonia::core::errors::Error::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break;
+                }
+                Err(e) => return Err(decode_error(path, e)),
+            };
+
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(d) => d,
+                Err(_) => continue,
+            };
+
+            let spec = *decoded.spec();
+            let duration = decoded.frames();
+            let mut sample_buffer = SampleBuffer::<f32>::new(
+                symphonia::core::units::Duration::from(duration as u64),
+                spec,
+            );
+            sample_buffer.copy_interleaved_ref(decoded);
+            if on_chunk(downmix.push(sample_buffer.samples())).is_break() {
+                break;
+            }
+        }
+
+        Ok(downmix.finish(tags))
+    }
+}
+
+/// Any format ffmpeg reads: `ffprobe` (next to `ffmpeg`) for the stream and tags, and `ffmpeg` piping 16-bit PCM.
+#[cfg(feature = "ffmpeg")]
+#[derive(Clone, Debug)]
+pub struct Ffmpeg {
+    pub ffmpeg: PathBuf,
+}
+
+/// What `Ffmpeg` asks ffprobe for; `parse_ffprobe` reads the answer.
+#[cfg(feature = "ffmpeg")]
+const FFPROBE_ENTRIES: &str = "stream=sample_rate,channels:format=duration:format_tags=title,artist,album";
+
+#[cfg(feature = "ffmpeg")]
+impl AudioDecoder for Ffmpeg {
+    fn probe(&self, path: &Path) -> Result<TrackInfo, SpectrumError> {
+        if !path.exists() {
+            return Err(SpectrumError::InputNotFound(path.to_path_buf()));
+        }
+        let ffprobe = crate::output::ffprobe_path(&self.ffmpeg);
+        let output = Command::new(&ffprobe)
+            .args(["-v", "error", "-select_streams", "a:0", "-show_entries", FFPROBE_ENTRIES])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .arg(path)
+            .output()
+            .map_err(|e| spawn_error(&ffprobe, path, e))?;
+        parse_ffprobe(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| decode_error(path, format!("ffprobe found no audio: {}", stderr_line(&output.stderr))))
+    }
+
+    fn stream(
+        &self,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        on_chunk: &mut dyn FnMut(AudioChunk<'_>) -> ControlFlow<()>,
+    ) -> Result<StreamedAudio, SpectrumError> {
+        let info = self.probe(path)?;
+        let channels = info.channels.unwrap_or(2);
+        let mut child = Command::new(&self.ffmpeg)
+            .args(["-v", "error", "-i"])
+            .arg(path)
+            .args(["-vn", "-f", "s16le", "-acodec", "pcm_s16le", "-ac", &channels.to_string(), "-"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| spawn_error(&self.ffmpeg, path, e))?;
+        let stdout = child.stdout.take().expect("ffmpeg stdout is piped");
+        // Read stderr alongside stdout: a chatty ffmpeg would otherwise fill the pipe and stall before EOF.
+        let mut stderr = child.stderr.take().expect("ffmpeg stderr is piped");
+        let stderr_reader = std::thread::spawn(move || {
+            let mut log = Vec::new();
+            let _ = stderr.read_to_end(&mut log);
+            log
+        });
+        let pcm = PcmInput { sample_rate: info.sample_rate, channels };
+        let mut stopped = false;
+        let streamed = pcm.read(stdout, path, keep_stereo, stop_after_sec, info.tags, &mut |chunk| {
+            let flow = on_chunk(chunk);
+            stopped |= flow.is_break();
+            flow
+        });
+        // Stopping early leaves ffmpeg writing into a closed pipe.
+        let stopped = stopped || stop_after_sec.is_some();
+        if stopped {
+            let _ = child.kill();
+        }
+        let status = child.wait().map_err(|e| decode_error(path, e))?;
+        let log = stderr_reader.join().unwrap_or_default();
+        if !status.success() && !stopped {
+            return Err(decode_error(path, format!("ffmpeg failed: {}", stderr_line(&log))));
+        }
+        streamed
+    }
+}
+
+/// Headerless interleaved signed 16-bit little-endian PCM (`--decoder raw`, `--raw-format`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RawPcm {
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+impl RawPcm {
+    /// The open file, read as `PcmInput`, and the frames (samples per channel) it holds.
+    fn input(&self, path: &Path) -> Result<(std::fs::File, PcmInput, usize), SpectrumError> {
+        let file = std::fs::File::open(path).map_err(|e| open_error(path, e))?;
+        let bytes = file.metadata().map_err(|e| decode_error(path, e))?.len();
+        let frames = bytes as usize / (2 * self.channels.max(1));
+        Ok((file, PcmInput { sample_rate: self.sample_rate, channels: self.channels }, frames))
+    }
+}
+
+impl AudioDecoder for RawPcm {
+    fn probe(&self, path: &Path) -> Result<TrackInfo, SpectrumError> {
+        let (_, _, frames) = self.input(path)?;
+        Ok(TrackInfo {
+            duration_sec: Some(frames as f32 / self.sample_rate as f32),
+            sample_rate: self.sample_rate,
+            codec: Some("PCM signed 16-bit little-endian"),
+            channels: Some(self.channels),
+            tags: Tags::default(),
+            #[cfg(feature = "symphonia")]
+            all_tags: Vec::new(),
+            #[cfg(feature = "symphonia")]
+            pictures: Vec::new(),
+        })
+    }
+
+    fn stream(
+        &self,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        on_chunk: &mut dyn FnMut(AudioChunk<'_>) -> ControlFlow<()>,
+    ) -> Result<StreamedAudio, SpectrumError> {
+        let (file, input, _) = self.input(path)?;
+        input.read(file, path, keep_stereo, stop_after_sec, Tags::default(), on_chunk)
+    }
+}
+
+/// A stream of interleaved signed 16-bit little-endian PCM, as `RawPcm` and `Ffmpeg` read it.
+struct PcmInput {
+    sample_rate: u32,
+    channels: usize,
+}
+
+impl PcmInput {
+    fn read(
+        &self,
+        mut reader: impl Read,
+        path: &Path,
+        keep_stereo: bool,
+        stop_after_sec: Option<f32>,
+        tags: Tags,
+        on_chunk: &mut dyn FnMut(AudioChunk<'_>) -> ControlFlow<()>,
+    ) -> Result<StreamedAudio, SpectrumError> {
+        if self.sample_rate == 0 || self.channels == 0 {
+            return Err(decode_error(path, "sample rate and channel count must be at least 1"));
+        }
+        let stop_after = stop_after_sec.map(|sec| (sec.max(0.0) * self.sample_rate as f32) as usize);
+        let mut downmix = Downmix::new(self.sample_rate, self.channels, keep_stereo);
+        let frame_bytes = 2 * self.channels;
+        let mut buf = vec![0u8; frame_bytes * 4096];
+        let mut interleaved = Vec::with_capacity(self.channels * 4096);
+        let mut filled = 0;
+        while stop_after.is_none_or(|stop| downmix.frames < stop) {
+            let read = reader.read(&mut buf[filled..]).map_err(|e| decode_error(path, e))?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+            let whole = filled - filled % frame_bytes;
+            interleaved.clear();
+            let pcm = buf[..whole].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0);
+            interleaved.extend(pcm);
+            if on_chunk(downmix.push(&interleaved)).is_break() {
+                break;
+            }
+            buf.copy_within(whole..filled, 0);
+            filled -= whole;
+        }
+        Ok(downmix.finish(tags))
+    }
+}
+
+/// Interleaved chunks turned into mono (and, when kept, the first two channels), one chunk at a time: the buffers
+/// hold the latest chunk only and are reused for the next.
+struct Downmix {
+    sample_rate: u32,
+    channels: usize,
+    keep_stereo: bool,
+    samples: Vec<f32>,
+    stereo: (Vec<f32>, Vec<f32>),
+    /// Frames (mono samples) pushed so far.
+    frames: usize,
+}
+
+impl Downmix {
+    fn new(sample_rate: u32, channels: usize, keep_stereo: bool) -> Self {
+        let (samples, stereo) = (Vec::new(), (Vec::new(), Vec::new()));
+        Self { sample_rate, channels, keep_stereo: keep_stereo && channels >= 2, samples, stereo, frames: 0 }
+    }
+
+    /// The chunk of `interleaved` (whole frames).
+    fn push(&mut self, interleaved: &[f32]) -> AudioChunk<'_> {
+        self.samples.clear();
+        if self.channels == 1 {
+            self.samples.extend_from_slice(interleaved);
+        } else {
+            for ch in interleaved.chunks(self.channels) {
+                let sum: f32 = ch.iter().sum();
+                self.samples.push(sum / self.channels as f32);
+            }
+            if self.keep_stereo {
+                self.stereo.0.clear();
+                self.stereo.1.clear();
+                for ch in interleaved.chunks(self.channels) {
+                    self.stereo.0.push(ch[0]);
+                    self.stereo.1.push(ch[1]);
+                }
+            }
+        }
+        self.frames += self.samples.len();
+        AudioChunk {
+            sample_rate: self.sample_rate,
+            samples: &self.samples,
+            stereo: self.keep_stereo.then_some((&self.stereo.0[..], &self.stereo.1[..])),
+        }
+    }
+
+    fn finish(self, tags: Tags) -> StreamedAudio {
+        StreamedAudio { sample_rate: self.sample_rate, samples: self.frames, stereo: self.keep_stereo, tags }
+    }
+}
+
+/// Sample rate, channels, duration and tags from ffprobe's `key=value` lines (see `FFPROBE_ENTRIES`).
+#[cfg(feature = "ffmpeg")]
+fn parse_ffprobe(output: &str) -> Option<TrackInfo> {
+    let (mut sample_rate, mut channels, mut duration_sec, mut tags) = (None, None, None, Tags::default());
+    for (key, value) in output.lines().filter_map(|line| line.split_once('=')) {
+        let text = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        match key.to_ascii_lowercase().as_str() {
+            "sample_rate" => sample_rate = value.parse().ok().filter(|&rate| rate > 0),
+            "channels" => channels = value.parse().ok().filter(|&count| count > 0),
+            "duration" => duration_sec = value.parse().ok().filter(|d: &f32| d.is_finite() && *d > 0.0),
+            "tag:title" => tags.title = text,
+            "tag:artist" => tags.artist = text,
+            "tag:album" => tags.album = text,
+            _ => {}
+        }
+    }
+    Some(TrackInfo {
+        duration_sec,
+        sample_rate: sample_rate?,
+        codec: None,
+        channels,
+        tags,
+        #[cfg(feature = "symphonia")]
+        all_tags: Vec::new(),
+        #[cfg(feature = "symphonia")]
+        pictures: Vec::new(),
+    })
+}
+
+/// `FfmpegNotFound` when `program` isn't there, a `DecodeError` for anything else keeping it from starting.
+#[cfg(feature = "ffmpeg")]
+fn spawn_error(program: &Path, path: &Path, e: std::io::Error) -> SpectrumError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => SpectrumError::FfmpegNotFound(program.to_path_buf()),
+        _ => decode_error(path, format!("failed to start {:?}: {}", program, e)),
+    }
+}
+
+/// The last line ffmpeg or ffprobe wrote to stderr, the one saying what went wrong.
+#[cfg(feature = "ffmpeg")]
+fn stderr_line(log: &[u8]) -> String {
+    String::from_utf8_lossy(log).lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("").trim().to_string()
+}
+
+/// `InputNotFound` for a missing input, a `DecodeError` for any other failure to open it.
+fn open_error(path: &Path, e: std::io::Error) -> SpectrumError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => SpectrumError::InputNotFound(path.to_path_buf()),
+        _ => decode_error(path, e),
+    }
+}
+
+/// A `DecodeError` for the input at `path`.
+fn decode_error(path: &std::path::Path, message: impl ToString) -> SpectrumError {
+    SpectrumError::DecodeError { path: path.to_path_buf(), message: message.to_string() }
+}
+
+/// Open an MP3 file and read its headers and tags.
+#[cfg(feature = "symphonia")]
+fn probe_file(path: &std::path::Path) -> Result<ProbeResult, SpectrumError> {
+    let src = std::fs::File::open(path).map_err(|e| open_error(path, e))?;
     let mss = MediaSourceStream::new(Box::new(src), Default::default());
 
     let hint = symphonia::core::probe::Hint::new();
@@ -27,70 +520,169 @@ pub fn decode_mp3(path: &std::path::Path) -> Result<DecodedAudio, Box<dyn std::e
     let metadata_opts = MetadataOptions::default();
     let probe = get_probe();
 
-    let mut probe_result = probe
+    probe
         .format(&hint, mss, &format_opts, &metadata_opts)
-        .map_err(|e| format!("format probe error: {}", e))?;
+        .map_err(|e| decode_error(path, format!("format probe error: {}", e)))
+}
+
+/// Tags from the container probe, falling back to the format's own metadata.
+#[cfg(feature = "symphonia")]
+fn read_tags(probe_result: &mut ProbeResult) -> Tags {
+    let probed_metadata = probe_result.metadata.get();
+    let probed = probed_metadata.as_ref().and_then(|m| m.current());
+    let cover_art = first_visual(probed).or_else(|| first_visual(probe_result.format.metadata().current()));
+    let title = tag_value(probed, StandardTagKey::TrackTitle)
+        .or_else(|| tag_value(probe_result.format.metadata().current(), StandardTagKey::TrackTitle));
+    let artist = tag_value(probed, StandardTagKey::Artist)
+        .or_else(|| tag_value(probe_result.format.metadata().current(), StandardTagKey::Artist));
+    let album = tag_value(probed, StandardTagKey::Album)
+        .or_else(|| tag_value(probe_result.format.metadata().current(), StandardTagKey::Album));
+    Tags { cover_art, title, artist, album }
+}
 
+/// Read the length, sample rate and tags of an MP3 file from its headers, without decoding it.
+#[cfg(feature = "symphonia")]
+pub fn probe_track(path: &std::path::Path) -> Result<TrackInfo, SpectrumError> {
+    let mut probe_result = probe_file(path)?;
+    let tags = read_tags(&mut probe_result);
     let track = probe_result
         .format
         .tracks()
         .iter()
         .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-        .ok_or("no audio track found")?;
-
-    let track_id = track.id;
-    let codec_params = track.codec_params.clone();
-    let mut decoder = get_codecs()
-        .make(&codec_params, &DecoderOptions::default())
-        .map_err(|e| format!("decoder creation error: {}", e))?;
-
-    let mut all_samples: Vec<f32> = Vec::new();
-    let sample_rate = codec_params
-        .sample_rate
-        .ok_or("missing sample rate")? as u32;
-    let channels = codec_params.channels.ok_or("missing channel count")?.count() as usize;
-
-    loop {
-        let packet = match probe_result.format.next_packet() {
-            Ok(p) => p,
-            Err(symphonia::core::errors::Error::IoError(e))
-                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
-            {
-                break;
-            }
-            Err(e) => return Err(e.into()),
-        };
+        .ok_or_else(|| decode_error(path, "no audio track found"))?;
+    let sample_rate = track.codec_params.sample_rate.ok_or_else(|| decode_error(path, "missing sample rate"))?;
+    let duration_sec = track.codec_params.n_frames.map(|n| n as f32 / sample_rate as f32);
+    let codec = get_codecs().get_codec(track.codec_params.codec).map(|descriptor| descriptor.long_name);
+    let channels = track.codec_params.channels.map(|channels| channels.count());
+    let (mut all_tags, mut pictures) = (Vec::new(), Vec::new());
+    let probed_metadata = probe_result.metadata.get();
+    let probed = probed_metadata.as_ref().and_then(|m| m.current());
+    for revision in [probed, probe_result.format.metadata().current()].into_iter().flatten() {
+        all_tags.extend(revision.tags().iter().map(|tag| TagEntry {
+            std_key: tag.std_key,
+            key: tag.key.clone(),
+            value: tag.value.to_string(),
+        }));
+        pictures.extend(revision.visuals().iter().map(|visual| Picture {
+            media_type: visual.media_type.clone(),
+            usage: visual.usage,
+            data: visual.data.to_vec(),
+        }));
+    }
+    Ok(TrackInfo { duration_sec, sample_rate, codec, channels, tags, all_tags, pictures })
+}
 
-        if packet.track_id() != track_id {
-            continue;
-        }
+/// Decode an MP3 file and return mono PCM.
+/// For stereo, left and right are averaged to mono. With `keep_stereo`, the first two channels are also kept as-is.
+/// `on_chunk` is called with the sample rate and the new mono samples after each decoded packet,
+/// so analysis can run while decoding instead of in a second pass. With `stop_after_sec`, decoding ends once that
+/// much audio has been decoded. `Symphonia::stream` does the same without keeping the samples.
+#[cfg(feature = "symphonia")]
+pub fn decode_mp3(
+    path: &std::path::Path,
+    keep_stereo: bool,
+    stop_after_sec: Option<f32>,
+    mut on_chunk: impl FnMut(u32, &[f32]),
+) -> Result<DecodedAudio, SpectrumError> {
+    Symphonia.decode(path, keep_stereo, stop_after_sec, &mut on_chunk)
+}
 
-        let decoded = match decoder.decode(&packet) {
-            Ok(d) => d,
-            Err(_) => continue,
-        };
+#[cfg(feature = "symphonia")]
+fn first_visual(revision: Option<&MetadataRevision>) -> Option<Vec<u8>> {
+    revision
+        .and_then(|rev| rev.visuals().first())
+        .map(|visual| visual.data.to_vec())
+}
 
-        let spec = *decoded.spec();
-        let duration = decoded.frames();
-        let mut sample_buffer = SampleBuffer::<f32>::new(
-            symphonia::core::units::Duration::from(duration as u64),
-            spec,
-        );
-        sample_buffer.copy_interleaved_ref(decoded);
-
-        let slice = sample_buffer.samples();
-        if channels == 1 {
-            all_samples.extend_from_slice(slice);
-        } else {
-            for ch in slice.chunks(channels) {
-                let sum: f32 = ch.iter().sum();
-                all_samples.push(sum / channels as f32);
-            }
-        }
+/// Value of the first non-empty tag with `key`, trimmed.
+#[cfg(feature = "symphonia")]
+fn tag_value(revision: Option<&MetadataRevision>, key: StandardTagKey) -> Option<String> {
+    revision?
+        .tags()
+        .iter()
+        .filter(|tag| tag.std_key == Some(key))
+        .map(|tag| tag.value.to_string().trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioDecoder, RawPcm};
+    #[cfg(feature = "ffmpeg")]
+    use super::parse_ffprobe;
+
+    #[test]
+    fn raw_pcm_is_downmixed_and_can_stop_early() {
+        let path = std::env::temp_dir().join(format!("asg-raw-test-{}.pcm", std::process::id()));
+        // Four stereo frames: left at half scale, right silent.
+        let bytes: Vec<u8> = (0..4).flat_map(|_| [16384i16, 0]).flat_map(i16::to_le_bytes).collect();
+        std::fs::write(&path, bytes).unwrap();
+        let raw = RawPcm { sample_rate: 4, channels: 2 };
+        assert_eq!(raw.probe(&path).unwrap().duration_sec, Some(1.0));
+        let mut chunks = 0;
+        let decoded = raw.decode(&path, true, None, &mut |_, _| chunks += 1).unwrap();
+        assert_eq!(decoded.samples, vec![0.25; 4]);
+        assert_eq!(decoded.stereo, Some((vec![0.5; 4], vec![0.0; 4])));
+        assert_eq!(chunks, 1);
+        let decoded = raw.decode(&path, false, Some(0.5), &mut |_, _| {}).unwrap();
+        assert!(decoded.samples.len() >= 2 && decoded.stereo.is_none());
+        std::fs::remove_file(&path).unwrap();
     }
 
-    Ok(DecodedAudio {
-        samples: all_samples,
-        sample_rate,
-    })
+    #[test]
+    fn a_stream_hands_over_chunks_until_told_to_stop() {
+        use std::ops::ControlFlow;
+
+        let path = std::env::temp_dir().join(format!("asg-raw-stream-test-{}.pcm", std::process::id()));
+        let bytes: Vec<u8> = (0..5_000).flat_map(|_| [16384i16, 0]).flat_map(i16::to_le_bytes).collect();
+        std::fs::write(&path, bytes).unwrap();
+        let raw = RawPcm { sample_rate: 1_000, channels: 2 };
+        let (mut chunks, mut samples) = (0, 0);
+        let streamed = raw
+            .stream(&path, true, None, &mut |chunk| {
+                assert_eq!(chunk.stereo.map(|(left, _)| left.len()), Some(chunk.samples.len()));
+                (chunks, samples) = (chunks + 1, samples + chunk.samples.len());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert!(chunks > 1);
+        assert_eq!((samples, streamed.samples, streamed.stereo), (5_000, 5_000, true));
+        let stopped = raw.stream(&path, false, None, &mut |_| ControlFlow::Break(())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(stopped.samples < 5_000 && !stopped.stereo);
+    }
+
+    #[cfg(feature = "ffmpeg")]
+    #[test]
+    fn ffprobe_output_gives_the_stream_and_tags() {
+        let info = parse_ffprobe("sample_rate=48000\nchannels=2\nduration=12.5\nTAG:title=Song \nTAG:ARTIST=Band\n")
+            .unwrap();
+        assert_eq!((info.sample_rate, info.channels, info.duration_sec), (48000, Some(2), Some(12.5)));
+        assert_eq!((info.tags.title.as_deref(), info.tags.artist.as_deref()), (Some("Song"), Some("Band")));
+        assert!(parse_ffprobe("duration=N/A\n").is_none());
+    }
+
+    #[cfg(all(unix, feature = "ffmpeg"))]
+    #[test]
+    fn ffmpeg_filling_stderr_before_stdout_still_decodes() {
+        use super::Ffmpeg;
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("asg-chatty-ffmpeg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str, body: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        script("ffprobe", "printf 'sample_rate=4\\nchannels=1\\nduration=1\\n'");
+        // More than a pipe buffer of warnings, then four mono frames at half scale.
+        let pcm = "\\0\\100".repeat(4);
+        let ffmpeg = script("ffmpeg", &format!("head -c 300000 /dev/zero | tr '\\0' w >&2; printf '{}'", pcm));
+        let input = script("in.mp3", "");
+        let decoded = Ffmpeg { ffmpeg }.decode(&input, false, None, &mut |_, _| {});
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(decoded.unwrap().samples, vec![0.5; 4]);
+    }
 }
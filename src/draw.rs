@@ -2,99 +2,1349 @@
 
 use image::{ImageBuffer, Rgba};
 
-/// Draw one frame: background (image or solid color), then bars.
-/// `bar_heights`: height per bar (0.0–1.0, assumed normalized).
-/// Spectrum band is placed with its bottom edge `spectrum_y_from_bottom` pixels above the frame bottom; bars are vertically centered in that band.
-/// When `spectrum_width` is Some(w), the bar strip is w pixels wide and centered horizontally; when None, it spans the full frame width.
-#[allow(clippy::too_many_arguments)]
-pub fn draw_spectrum_frame(
-    width: u32,
-    height: u32,
-    spectrum_height: u32,
-    spectrum_y_from_bottom: u32,
-    spectrum_width: Option<u32>,
-    bar_heights: &[f32],
-    bar_color: [u8; 4],
-    bg_color: [u8; 4],
+use crate::config::{
+    BarAnchor, BarShape, BarStroke, Config, DotMode, HAlign, Orientation, StereoSplit, Style, VAlign, BAND_MARGIN,
+};
+use crate::effects::{apply_glow, apply_reflection, apply_shadow, rotate_layer};
+use crate::envelope::EnvelopePoint;
+#[cfg(feature = "runner")]
+use crate::particles::Emitter;
+use crate::particles::Particle;
+
+/// Per-frame inputs to the renderer. Each style reads the fields it needs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameData<'a> {
+    /// Normalized bar heights (0.0–1.0), one per bar.
+    pub bar_heights: &'a [f32],
+    /// Mono PCM covering this frame's time window (waveform style).
+    pub waveform: &'a [f32],
+    /// Whole-track envelope, one point per strip column (timeline style).
+    pub timeline: &'a [EnvelopePoint],
+    /// Playback position through the track (0.0–1.0, timeline style).
+    pub progress: f32,
+    /// Recent bar columns: one per analysis hop (spectrogram style) or per video frame (dot trails).
+    pub history: Option<&'a BarHistory>,
+    /// Normalized bar heights of the left and right channels (`config.stereo_split`).
+    pub stereo: Option<(&'a [f32], &'a [f32])>,
+    /// Spectral centroid as a position on the bar strip (0.0–1.0), drawn as a marker in `config.centroid_color`.
+    pub centroid: Option<f32>,
+    /// Live beat particles (`config.particles`), drawn over the bars and faded by their remaining life.
+    pub particles: &'a [Particle],
+    /// Left and right PCM covering this frame's time window (vectorscope style).
+    pub stereo_samples: Option<(&'a [f32], &'a [f32])>,
+}
+
+/// Scrolling history of normalized bar columns, oldest first.
+/// Holds the spectrogram's columns (one per pixel of the strip width) or the dot style's trail frames.
+#[derive(Clone, Debug)]
+pub struct BarHistory {
+    columns: std::collections::VecDeque<Vec<f32>>,
+    capacity: usize,
+}
+
+impl BarHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            columns: std::collections::VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// As much history as `config.style` draws: a column per pixel of the spectrogram's strip, or the dot trail's
+    /// frames; none for the other styles.
+    pub fn for_config(config: &Config) -> Self {
+        Self::new(match config.style {
+            Style::Spectrogram => strip_extent(config).1 as usize,
+            Style::Dots if config.dot_trail > 0 => config.dot_trail + 1,
+            _ => 0,
+        })
+    }
+
+    /// Append the newest column, dropping the oldest when full.
+    pub fn push(&mut self, column: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.columns.len() == self.capacity {
+            self.columns.pop_front();
+        }
+        self.columns.push_back(column);
+    }
+}
+
+impl<'a> FrameData<'a> {
+    /// Frame data with only bar heights set.
+    #[cfg(test)]
+    pub fn bars(bar_heights: &'a [f32]) -> Self {
+        Self {
+            bar_heights,
+            ..Self::default()
+        }
+    }
+}
+
+/// Draw the spectrum in `config.style` over the existing contents of `img` (the background and any earlier layers).
+/// Bar styles use `frame.bar_heights` (0.0–1.0, assumed normalized).
+/// The band is placed by `strip_extent` and `band_bottom`; bars are vertically centered in it.
+/// With `config.glow`, `config.shadow` or `config.reflection`, the spectrum is also drawn onto a transparent layer
+/// that is blurred into a halo beneath it, cast as a drop shadow and/or flipped below the band's bottom edge.
+/// For a non-horizontal `config.orientation`, all of that is drawn onto a transparent layer in the horizontal layout,
+/// which is then turned and composited over `img`; the shadow is cast from the turned layer so it keeps its direction.
+/// Below full `config.opacity`, the finished band is drawn onto a transparent layer and blended over `img`.
+pub fn draw_spectrum_band(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, frame: &FrameData) {
+    if config.opacity < 1.0 {
+        let mut layer = ImageBuffer::new(img.width(), img.height());
+        draw_spectrum_band(&mut layer, &Config { opacity: 1.0, ..config.clone() }, frame);
+        for (x, y, p) in layer.enumerate_pixels() {
+            if p.0[3] > 0 {
+                let blended = blend(*img.get_pixel(x, y), p.0, config.opacity);
+                img.put_pixel(x, y, blended);
+            }
+        }
+        return;
+    }
+    let layer = match config.orientation {
+        Orientation::Horizontal => return draw_spectrum(img, config, frame),
+        Orientation::Vertical => {
+            // Lay the band out on a canvas with swapped sides, then turn it a quarter counter-clockwise.
+            let turned = Config {
+                width: config.height,
+                height: config.width,
+                shadow: None,
+                ..config.clone()
+            };
+            let mut layer = ImageBuffer::new(turned.width, turned.height);
+            draw_spectrum(&mut layer, &turned, frame);
+            image::imageops::rotate270(&layer)
+        }
+        Orientation::Rotate(degrees) => {
+            let mut layer = ImageBuffer::new(config.width, config.height);
+            draw_spectrum(&mut layer, &Config { shadow: None, ..config.clone() }, frame);
+            let (x0, strip_width) = strip_extent(config);
+            let center = (
+                x0 as f32 + strip_width as f32 / 2.0,
+                band_bottom(config) as f32 - config.spectrum_height as f32 / 2.0,
+            );
+            rotate_layer(&layer, center, degrees)
+        }
+    };
+    if let Some(shadow) = config.shadow {
+        apply_shadow(img, &layer, (0, 0), shadow);
+    }
+    image::imageops::overlay(img, &layer, 0, 0);
+}
+
+/// Draw the spectrum (with shadow, glow, reflection and the centroid marker) onto `img` in the horizontal layout.
+fn draw_spectrum(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, frame: &FrameData) {
+    if config.glow.is_some() || config.reflection.is_some() || config.shadow.is_some() {
+        let mut layer = ImageBuffer::new(config.width, config.height);
+        draw_style(&mut layer, config, frame);
+        if let Some(shadow) = config.shadow {
+            apply_shadow(img, &layer, (0, 0), shadow);
+        }
+        if let Some(glow) = config.glow {
+            apply_glow(img, &layer, glow);
+        }
+        if let Some(reflection) = config.reflection {
+            apply_reflection(img, &layer, band_bottom(config), reflection);
+        }
+    }
+    draw_style(img, config, frame);
+    for p in frame.particles {
+        fill_circle(img, p.x, p.y, p.size, p.color, p.life.clamp(0.0, 1.0));
+    }
+    if let Some(position) = frame.centroid {
+        draw_position_marker(img, config, position, config.centroid_color);
+    }
+}
+
+/// A frame with only the background: a copy of `bg_image`, or `config.bg_color` when None.
+pub fn draw_background(
+    config: &Config,
     bg_image: Option<&ImageBuffer<Rgba<u8>, Vec<u8>>>,
 ) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
-    let mut img = match bg_image {
+    match bg_image {
         Some(bg) => bg.clone(),
-        None => ImageBuffer::from_fn(width, height, |_, _| Rgba(bg_color)),
+        None => ImageBuffer::from_fn(config.width, config.height, |_, _| Rgba(config.bg_color)),
+    }
+}
+
+/// A frame of just the spectrum band: `bar_heights` drawn in `config.style` over `config.bg_color`. Styles that
+/// read more than the bars (waveform, history, stereo) draw with those empty.
+pub fn render_frame(config: &Config, bar_heights: &[f32]) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut img = draw_background(config, None);
+    draw_spectrum_band(&mut img, config, &FrameData { bar_heights, ..Default::default() });
+    img
+}
+
+/// Draw the spectrum in `config.style` onto `img`.
+fn draw_style(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, frame: &FrameData) {
+    let bar_heights = frame.bar_heights;
+    match config.style {
+        Style::Waveform => draw_waveform(img, config, frame.waveform),
+        Style::Ring => draw_ring(img, config, frame.waveform),
+        Style::Timeline => draw_timeline(img, config, frame.timeline, frame.progress),
+        Style::Vectorscope => {
+            if let Some((left, right)) = frame.stereo_samples {
+                draw_vectorscope(img, config, left, right);
+            }
+        }
+        Style::Spectrogram => {
+            if let Some(history) = frame.history {
+                draw_spectrogram(img, config, history);
+            }
+        }
+        _ if bar_heights.is_empty() => {}
+        Style::Bars => match (config.stereo_split, frame.stereo) {
+            (Some(StereoSplit::LeftRight), Some((left, right))) => draw_side_bars(img, config, left, right),
+            (Some(StereoSplit::TopBottom), Some((left, right))) => draw_split_bars(img, config, left, right, 1.0),
+            _ => draw_bars(img, config, bar_heights),
+        },
+        Style::Mirror => draw_mirror_bars(img, config, bar_heights),
+        Style::Radial => draw_radial_bars(img, config, bar_heights),
+        Style::Line => draw_line(img, config, bar_heights),
+        Style::Area => draw_area(img, config, bar_heights),
+        Style::Dots => draw_dots(img, config, bar_heights, frame.history),
+        Style::Led => draw_led_bars(img, config, bar_heights),
+    }
+}
+
+/// Placement of `total_bars` bars inside the spectrum band.
+struct BarLayout {
+    start_x: u32,
+    bar_width: u32,
+    gap: u32,
+    radius: u32,
+    y_center: u32,
+    usable_height: u32,
+}
+
+impl BarLayout {
+    fn new(config: &Config, total_bars: u32) -> Self {
+        let usable_height = config.spectrum_height.saturating_sub(BAND_MARGIN);
+        let y_center = band_bottom(config).saturating_sub(config.spectrum_height / 2);
+
+        let gap = config.bar_gap;
+        let total_gaps = total_bars.saturating_sub(1).saturating_mul(gap);
+        let (strip_x0, strip_width) = strip_extent(config);
+        let bar_width = if total_bars > 0 && strip_width > total_gaps {
+            (strip_width - total_gaps) / total_bars
+        } else {
+            0
+        };
+        // Split bars extend their halves past the center line by the radius, so pointed shapes get none.
+        let radius = match config.bar_shape {
+            BarShape::Rect => config.bar_radius.unwrap_or((bar_width / 2).clamp(1, 4)),
+            BarShape::Capsule => bar_width / 2,
+            BarShape::Triangle | BarShape::Diamond => 0,
+        };
+        let bars_width = total_bars.saturating_mul(bar_width).saturating_add(total_gaps);
+        let start_x = strip_x0 + strip_width.saturating_sub(bars_width) / 2;
+        Self {
+            start_x,
+            bar_width,
+            gap,
+            radius,
+            y_center,
+            usable_height,
+        }
+    }
+
+    fn bar_x(&self, i: usize) -> u32 {
+        self.start_x.saturating_add((i as u32).saturating_mul(self.bar_width.saturating_add(self.gap)))
+    }
+}
+
+/// Default style: one rounded bar per value, placed in the band according to `config.bar_anchor`.
+fn draw_bars(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    for (i, &h) in bar_heights.iter().enumerate() {
+        let bar_height = bar_length(h, layout.usable_height, config.bar_min_height);
+        if bar_height == 0 {
+            continue;
+        }
+
+        let x0 = layout.bar_x(i);
+        let band_top = layout.y_center.saturating_sub(layout.usable_height / 2);
+        let y_top = match config.bar_anchor {
+            BarAnchor::Center => layout.y_center.saturating_sub(bar_height / 2),
+            BarAnchor::Bottom => (band_top + layout.usable_height).saturating_sub(bar_height),
+            BarAnchor::Top => band_top,
+        };
+
+        let tip = if config.bar_anchor == BarAnchor::Top { Tip::Down } else { Tip::Up };
+        paint_bar(
+            img,
+            config,
+            Shape::bar(config, (x0, y_top, layout.bar_width, bar_height), layout.radius, tip),
+            y_top..y_top + bar_height,
+            bar_color(config, i, bar_heights.len(), h),
+            1.0,
+        );
+    }
+}
+
+/// Burst origins for `--particles`: the free ends of the `count` loudest bars, in the band's horizontal layout.
+/// Only the bars, mirror and LED styles have bar tops; other styles get no emitters.
+#[cfg(feature = "runner")]
+pub fn particle_emitters(config: &Config, bar_heights: &[f32], count: usize) -> Vec<Emitter> {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    let band_top = layout.y_center.saturating_sub(layout.usable_height / 2);
+    let mut loudest: Vec<usize> = (0..bar_heights.len()).filter(|&i| bar_heights[i] > 0.0).collect();
+    loudest.sort_by(|&a, &b| bar_heights[b].total_cmp(&bar_heights[a]));
+    loudest.truncate(count);
+    let mut emitters = Vec::with_capacity(loudest.len());
+    for i in loudest {
+        let h = bar_heights[i].clamp(0.0, 1.0);
+        let y = match (config.style, config.bar_anchor) {
+            (Style::Mirror, _) => {
+                let max_half = (layout.usable_height / 2).saturating_sub(MIRROR_GAP / 2) as f32;
+                layout.y_center.saturating_sub(MIRROR_GAP / 2) as f32 - h * max_half
+            }
+            (Style::Led, _) | (Style::Bars, BarAnchor::Bottom) => {
+                (band_top + layout.usable_height) as f32 - h * layout.usable_height as f32
+            }
+            (Style::Bars, BarAnchor::Center) => layout.y_center as f32 - h * layout.usable_height as f32 / 2.0,
+            (Style::Bars, BarAnchor::Top) => band_top as f32 + h * layout.usable_height as f32,
+            _ => return Vec::new(),
+        };
+        emitters.push(Emitter {
+            x: layout.bar_x(i) as f32 + layout.bar_width as f32 / 2.0,
+            y,
+            color: bar_color(config, i, bar_heights.len(), h),
+            size: (layout.bar_width as f32 / 4.0).clamp(1.5, 4.0),
+        });
+    }
+    emitters
+}
+
+/// Length (pixels) of a bar with value `h` (0.0–1.0) in `max_len` pixels, at least `min_len` (`config.bar_min_height`)
+/// so silent bars keep a visible stub.
+fn bar_length(h: f32, max_len: u32, min_len: u32) -> u32 {
+    ((h.clamp(0.0, 1.0) * max_len as f32) as u32).max(min_len.min(max_len))
+}
+
+/// Color of bar `i` of `total_bars` with value `h` (0.0–1.0). The base color is sampled from `bar_colormap` by
+/// frequency position (lowest bar = 0.0, highest = 1.0), or is `bar_color` when no colormap is set. With
+/// `bar_color_loud`, the base color is the quiet end and fades toward the loud color as `h` rises.
+fn bar_color(config: &Config, i: usize, total_bars: usize, h: f32) -> [u8; 4] {
+    let base = match config.bar_colormap {
+        Some(map) => {
+            let t = if total_bars > 1 { i as f32 / (total_bars - 1) as f32 } else { 0.0 };
+            map.sample(t, &config.colormap_stops)
+        }
+        None => config.bar_color,
     };
+    match config.bar_color_loud {
+        Some(loud) => mix_colors(base, loud, if h.is_finite() { h } else { 0.0 }),
+        None => base,
+    }
+}
+
+/// Segment colors of the LED ramp, and the fractions of the band height where yellow and red begin.
+const LED_GREEN: [u8; 4] = [40, 200, 70, 255];
+const LED_YELLOW: [u8; 4] = [240, 200, 40, 255];
+const LED_RED: [u8; 4] = [230, 40, 40, 255];
+const LED_YELLOW_FROM: f32 = 0.6;
+const LED_RED_FROM: f32 = 0.85;
+
+/// LED style: each bar is a stack of `led_segment_height` segments separated by `led_segment_gap`, rising from the
+/// band's bottom. A bar lights the segments below its height, rounded to the nearest whole segment.
+fn draw_led_bars(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    let pitch = config.led_segment_height + config.led_segment_gap;
+    let segments = (layout.usable_height + config.led_segment_gap) / pitch.max(1);
+    let bottom = layout.y_center.saturating_sub(layout.usable_height / 2) + layout.usable_height;
+    for (i, &h) in bar_heights.iter().enumerate() {
+        // The minimum height lights whole segments, rounded up.
+        let min_lit = config.bar_min_height.div_ceil(pitch.max(1)).min(segments);
+        let lit = ((h.clamp(0.0, 1.0) * segments as f32).round() as u32).max(min_lit);
+        let x0 = layout.bar_x(i);
+        for k in 0..lit {
+            let y0 = bottom.saturating_sub(k * pitch + config.led_segment_height);
+            let color = if config.led_ramp {
+                led_ramp_color((k as f32 + 0.5) / segments as f32)
+            } else {
+                bar_color(config, i, bar_heights.len(), h)
+            };
+            let rect = (x0, y0, layout.bar_width, config.led_segment_height);
+            let segment = Shape::bar(config, rect, layout.radius, Tip::Up);
+            paint_bar(img, config, segment, y0..y0 + config.led_segment_height, color, 1.0);
+        }
+    }
+}
+
+/// Ramp color of a segment whose center is at `t` (0.0 = band bottom, 1.0 = top).
+fn led_ramp_color(t: f32) -> [u8; 4] {
+    if t >= LED_RED_FROM {
+        LED_RED
+    } else if t >= LED_YELLOW_FROM {
+        LED_YELLOW
+    } else {
+        LED_GREEN
+    }
+}
+
+/// Gap (pixels) between the upper and lower halves in the mirror style.
+const MIRROR_GAP: u32 = 2;
+
+/// Mirror style: each bar grows up from the band's center line and is reflected below it.
+/// The halves are split by `MIRROR_GAP` pixels, have square inner ends, and the lower half is drawn at `mirror_opacity`.
+fn draw_mirror_bars(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    draw_split_bars(img, config, bar_heights, bar_heights, config.mirror_opacity);
+}
 
-    if bar_heights.is_empty() {
-        return img;
+/// Bars growing up from the band's center line with `upper` heights and down from it with `lower` heights
+/// (drawn at `lower_opacity`). Used by the mirror style and the top/bottom stereo split.
+fn draw_split_bars(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    config: &Config,
+    upper: &[f32],
+    lower: &[f32],
+    lower_opacity: f32,
+) {
+    let total_bars = upper.len().min(lower.len());
+    let layout = BarLayout::new(config, total_bars as u32);
+    let half_gap = MIRROR_GAP / 2;
+    let max_half = (layout.usable_height / 2).saturating_sub(half_gap);
+    let upper_end = layout.y_center.saturating_sub(half_gap);
+    let lower_start = layout.y_center + half_gap;
+    let r = layout.radius;
+
+    for (i, (&up, &down)) in upper.iter().zip(lower).enumerate() {
+        let x0 = layout.bar_x(i);
+        // Each half keeps half of the minimum height.
+        let min_half = config.bar_min_height.div_ceil(2);
+        let upper_half = bar_length(up, max_half, min_half);
+        let lower_half = bar_length(down, max_half, min_half);
+        // Extend each half past the center by the radius and clip it there, so only the outer end is rounded.
+        if upper_half > 0 {
+            let upper_top = upper_end.saturating_sub(upper_half);
+            paint_bar(
+                img,
+                config,
+                Shape::bar(config, (x0, upper_top, layout.bar_width, upper_half + r), r, Tip::Up),
+                upper_top..upper_end,
+                bar_color(config, i, total_bars, up),
+                1.0,
+            );
+        }
+        if lower_half > 0 {
+            paint_bar(
+                img,
+                config,
+                Shape::bar(
+                    config,
+                    (x0, lower_start.saturating_sub(r), layout.bar_width, lower_half + r),
+                    r,
+                    Tip::Down,
+                ),
+                lower_start..lower_start + lower_half,
+                bar_color(config, i, total_bars, down),
+                lower_opacity,
+            );
+        }
     }
+}
 
-    let usable_height = spectrum_height.saturating_sub(4);
-    let y_center = height
-        .saturating_sub(spectrum_y_from_bottom)
-        .saturating_sub(spectrum_height / 2);
+/// Left/right stereo split: one horizontal bar per band, stacked from the lowest frequency at the bottom of the
+/// band to the highest at the top. The left channel's bars grow left from the strip's center, the right channel's
+/// grow right; the halves are split by `MIRROR_GAP` pixels.
+fn draw_side_bars(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, left: &[f32], right: &[f32]) {
+    let total_bars = left.len().min(right.len());
+    let layout = BarLayout::new(config, total_bars as u32);
+    let gap = config.bar_gap;
+    let total_gaps = (total_bars as u32).saturating_sub(1).saturating_mul(gap);
+    if total_bars == 0 || layout.usable_height <= total_gaps {
+        return;
+    }
+    let thickness = (layout.usable_height - total_gaps) / total_bars as u32;
+    if thickness == 0 {
+        return;
+    }
+    let radius = config.bar_radius.unwrap_or((thickness / 2).clamp(1, 4));
+    let band_bottom = layout.y_center + layout.usable_height / 2;
+    let (strip_x0, strip_width) = strip_extent(config);
+    let center_x = strip_x0 + strip_width / 2;
+    let half_gap = MIRROR_GAP / 2;
+    let max_len = (strip_width / 2).saturating_sub(half_gap);
 
-    let total_bars = bar_heights.len() as u32;
-    let gap = 1u32;
-    let total_gaps = total_bars.saturating_sub(1) * gap;
-    let strip_width = spectrum_width.unwrap_or(width).min(width);
-    let bar_width = if total_bars > 0 && strip_width > total_gaps {
-        (strip_width - total_gaps) / total_bars
+    for (i, (&l, &r)) in left.iter().zip(right).enumerate() {
+        let stacked = (i as u32 + 1).saturating_mul(thickness).saturating_add((i as u32).saturating_mul(gap));
+        let y0 = band_bottom.saturating_sub(stacked);
+        for (h, grows_left) in [(l, true), (r, false)] {
+            let len = bar_length(h, max_len, config.bar_min_height);
+            if len == 0 {
+                continue;
+            }
+            let x0 = if grows_left { center_x.saturating_sub(half_gap + len) } else { center_x + half_gap };
+            let tip = if grows_left { Tip::Left } else { Tip::Right };
+            paint_bar(
+                img,
+                config,
+                Shape::bar(config, (x0, y0, len, thickness), radius, tip),
+                y0..y0 + thickness,
+                bar_color(config, i, total_bars, h),
+                1.0,
+            );
+        }
+    }
+}
+
+/// Horizontal extent (x0, width) of the spectrum strip: `spectrum_width` (or the full frame width) placed by
+/// `spectrum_x` / `spectrum_align`, kept inside the frame.
+pub fn strip_extent(config: &Config) -> (u32, u32) {
+    let strip_width = config.spectrum_width.unwrap_or(config.width).min(config.width);
+    let x0 = match (config.spectrum_align, config.spectrum_x) {
+        (HAlign::Left, x) => x.unwrap_or(0),
+        (HAlign::Center, Some(x)) => x.saturating_sub(strip_width / 2),
+        (HAlign::Center, None) => (config.width - strip_width) / 2,
+        (HAlign::Right, x) => x.unwrap_or(config.width).saturating_sub(strip_width),
+    };
+    (x0.min(config.width - strip_width), strip_width)
+}
+
+/// Bottom edge (exclusive row) of the spectrum band, placed by `spectrum_y` / `spectrum_valign` and kept inside
+/// the frame. The band is `spectrum_height` tall above it.
+pub fn band_bottom(config: &Config) -> u32 {
+    let h = config.spectrum_height;
+    let anchor = config.spectrum_y.unwrap_or(match config.spectrum_valign {
+        VAlign::Top => 0,
+        VAlign::Middle => config.height / 2,
+        VAlign::Bottom => config.height.saturating_sub(config.spectrum_y_from_bottom),
+    });
+    let bottom = match config.spectrum_valign {
+        VAlign::Top => anchor + h,
+        VAlign::Middle => anchor + h / 2,
+        VAlign::Bottom => anchor,
+    };
+    bottom.min(config.height)
+}
+
+/// Waveform style: the frame's PCM drawn as a line across the strip, centered vertically in the band.
+/// Full scale (±1.0) reaches the top/bottom of the usable band height.
+fn draw_waveform(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, samples: &[f32]) {
+    let (x0, strip_width) = strip_extent(config);
+    if samples.is_empty() || strip_width == 0 {
+        return;
+    }
+    let layout = BarLayout::new(config, 1);
+    let half = layout.usable_height as f32 / 2.0;
+    let y_center = layout.y_center as f32;
+    let points: Vec<(f32, f32)> = (0..strip_width)
+        .map(|c| {
+            let ix = (c as usize * samples.len() / strip_width as usize).min(samples.len() - 1);
+            let s = samples[ix];
+            let s = if s.is_finite() { s.clamp(-1.0, 1.0) } else { 0.0 };
+            (x0 as f32 + c as f32 + 0.5, y_center - s * half)
+        })
+        .collect();
+    draw_polyline(img, &points, config.stroke_width, config.bar_color);
+}
+
+/// Opacity of one plotted sample in the vectorscope; where samples pile up they build to full color like phosphor.
+const VECTORSCOPE_DOT_OPACITY: f32 = 0.3;
+
+/// Vectorscope style: each left/right sample pair of the frame is one point in a square centered in the band, with
+/// side (L − R) across and mid (L + R) upward. Mono content is a vertical line and wide stereo spreads sideways;
+/// full-scale mono reaches the top edge.
+fn draw_vectorscope(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, left: &[f32], right: &[f32]) {
+    let (x0, strip_width) = strip_extent(config);
+    let layout = BarLayout::new(config, 1);
+    let half = strip_width.min(layout.usable_height) as f32 / 2.0;
+    if half < 1.0 {
+        return;
+    }
+    let (cx, cy) = (x0 as f32 + strip_width as f32 / 2.0, layout.y_center as f32);
+    let (width, height) = img.dimensions();
+    let clean = |s: f32| if s.is_finite() { s.clamp(-1.0, 1.0) } else { 0.0 };
+    for (&l, &r) in left.iter().zip(right) {
+        let (l, r) = (clean(l), clean(r));
+        let x = (cx + (l - r) / 2.0 * half).floor();
+        let y = (cy - (l + r) / 2.0 * half).floor();
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            continue;
+        }
+        let (x, y) = (x as u32, y as u32);
+        let blended = blend(*img.get_pixel(x, y), config.bar_color, VECTORSCOPE_DOT_OPACITY);
+        img.put_pixel(x, y, blended);
+    }
+}
+
+/// Ring style: the frame's samples go once around the circle clockwise from `radial_rotation`, each point's radius
+/// offset from the resting radius by the sample value times half of `spectrum_height`. The last point joins the first.
+fn draw_ring(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, samples: &[f32]) {
+    if samples.is_empty() {
+        return;
+    }
+    let (cx, cy, r0) = radial_geometry(config);
+    let excursion = config.spectrum_height.saturating_sub(4) as f32 / 2.0;
+    let rotation = config.radial_rotation.to_radians();
+    // About one point per pixel of circumference, or one per sample if there are fewer.
+    let n = ((std::f32::consts::TAU * r0).ceil() as usize).clamp(3, samples.len().max(3));
+    let mut points: Vec<(f32, f32)> = (0..n)
+        .map(|k| {
+            let ix = (k * samples.len() / n).min(samples.len() - 1);
+            let s = samples[ix];
+            let s = if s.is_finite() { s.clamp(-1.0, 1.0) } else { 0.0 };
+            let r = (r0 + s * excursion).max(0.0);
+            let theta = rotation + std::f32::consts::TAU * k as f32 / n as f32;
+            (cx + r * theta.sin(), cy - r * theta.cos())
+        })
+        .collect();
+    points.push(points[0]);
+    draw_polyline(img, &points, config.stroke_width, config.bar_color);
+}
+
+/// Number of points sampled per bar-to-bar span of the Catmull-Rom curve.
+const CURVE_SUBDIVISIONS: usize = 8;
+
+/// Opacity of the fill under the curve when `line_fill` is set.
+const LINE_FILL_OPACITY: f32 = 0.35;
+
+/// Points of a Catmull-Rom curve through the bar tops (x at each bar's center, y rising from the band baseline).
+fn curve_points(config: &Config, bar_heights: &[f32]) -> Vec<(f32, f32)> {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    let baseline = curve_baseline(&layout);
+    let knots: Vec<(f32, f32)> = bar_heights
+        .iter()
+        .enumerate()
+        .map(|(i, &h)| {
+            let x = layout.bar_x(i) as f32 + layout.bar_width as f32 / 2.0;
+            let h = if h.is_finite() { h.clamp(0.0, 1.0) } else { 0.0 };
+            (x, baseline - h * layout.usable_height as f32)
+        })
+        .collect();
+    if knots.len() < 2 {
+        return knots;
+    }
+    let mut points = Vec::with_capacity((knots.len() - 1) * CURVE_SUBDIVISIONS + 1);
+    for i in 0..knots.len() - 1 {
+        let p0 = knots[i.saturating_sub(1)];
+        let p1 = knots[i];
+        let p2 = knots[i + 1];
+        let p3 = knots[(i + 2).min(knots.len() - 1)];
+        for step in 0..CURVE_SUBDIVISIONS {
+            let t = step as f32 / CURVE_SUBDIVISIONS as f32;
+            points.push(catmull_rom(p0, p1, p2, p3, t));
+        }
+    }
+    points.push(knots[knots.len() - 1]);
+    // Overshoot between steep neighbors must not dip below the baseline or above the band.
+    let top = baseline - layout.usable_height as f32;
+    for p in points.iter_mut() {
+        p.1 = p.1.clamp(top, baseline);
+    }
+    points
+}
+
+/// Bottom edge (y) of the usable band height, where curve-style values of 0.0 sit.
+fn curve_baseline(layout: &BarLayout) -> f32 {
+    layout.y_center as f32 + layout.usable_height as f32 / 2.0
+}
+
+/// Uniform Catmull-Rom interpolation between `p1` and `p2` at `t` (0.0–1.0).
+fn catmull_rom(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), t: f32) -> (f32, f32) {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let f = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * (2.0 * b + (-a + c) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (-a + 3.0 * b - 3.0 * c + d) * t3)
+    };
+    (f(p0.0, p1.0, p2.0, p3.0), f(p0.1, p1.1, p2.1, p3.1))
+}
+
+/// Line style: a smooth curve through the bar values, `stroke_width` wide, optionally filled down to the baseline.
+fn draw_line(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    let points = curve_points(config, bar_heights);
+    if config.line_fill {
+        let layout = BarLayout::new(config, bar_heights.len() as u32);
+        fill_under_curve(img, &points, curve_baseline(&layout), config.bar_color, LINE_FILL_OPACITY);
+    }
+    draw_polyline(img, &points, config.stroke_width, config.bar_color);
+}
+
+/// Fill each pixel column between the curve through `points` (sorted by x) and `baseline`, blending at `opacity`.
+fn fill_under_curve(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    points: &[(f32, f32)],
+    baseline: f32,
+    color: [u8; 4],
+    opacity: f32,
+) {
+    let height = img.height();
+    let y_end = (baseline.round().max(0.0) as u32).min(height);
+    for (x, y_curve) in curve_columns(points, img.width()) {
+        for y in (y_curve.round().max(0.0) as u32)..y_end {
+            let blended = blend(*img.get_pixel(x, y), color, opacity);
+            img.put_pixel(x, y, blended);
+        }
+    }
+}
+
+/// Linearly interpolate the curve through `points` (sorted by x) at every pixel column it covers,
+/// returning (x, y) pairs for columns inside `0..width`.
+fn curve_columns(points: &[(f32, f32)], width: u32) -> Vec<(u32, f32)> {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return Vec::new();
+    };
+    let x_start = first.0.floor().max(0.0) as u32;
+    let x_end = (last.0.ceil().max(0.0) as u32).min(width);
+    let mut seg = 0;
+    (x_start..x_end)
+        .map(|x| {
+            let fx = x as f32 + 0.5;
+            while seg + 2 < points.len() && points[seg + 1].0 < fx {
+                seg += 1;
+            }
+            let (a, b) = if points.len() == 1 {
+                (points[0], points[0])
+            } else {
+                (points[seg], points[seg + 1])
+            };
+            let t = if b.0 > a.0 { ((fx - a.0) / (b.0 - a.0)).clamp(0.0, 1.0) } else { 0.0 };
+            (x, a.1 + (b.1 - a.1) * t)
+        })
+        .collect()
+}
+
+/// Area style: the region under the smooth spectrum contour filled solid, or as a vertical gradient from
+/// `bar_color` (top of the band) to `area_gradient` (baseline). With `area_mirror`, the area is reflected
+/// around the band's center line instead of rising from the baseline.
+fn draw_area(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    let baseline = curve_baseline(&layout);
+    let usable = layout.usable_height as f32;
+    let band_top = baseline - usable;
+    let y_center = layout.y_center as f32;
+    let height = img.height();
+    let points = curve_points(config, bar_heights);
+
+    for (x, y_curve) in curve_columns(&points, img.width()) {
+        let (top, bottom) = if config.area_mirror {
+            let half = (baseline - y_curve) / 2.0;
+            (y_center - half, y_center + half)
+        } else {
+            (y_curve, baseline)
+        };
+        let y_start = top.round().max(0.0) as u32;
+        let y_end = (bottom.round().max(0.0) as u32).min(height);
+        for y in y_start..y_end {
+            let color = match config.area_gradient {
+                Some(end) if usable > 0.0 => {
+                    // With mirroring, the gradient runs from the center line outward.
+                    let t = if config.area_mirror {
+                        1.0 - (y as f32 + 0.5 - y_center).abs() / (usable / 2.0)
+                    } else {
+                        (y as f32 + 0.5 - band_top) / usable
+                    };
+                    mix_colors(config.bar_color, end, t)
+                }
+                _ => config.bar_color,
+            };
+            let blended = blend(*img.get_pixel(x, y), color, 1.0);
+            img.put_pixel(x, y, blended);
+        }
+    }
+}
+
+/// Linear interpolation between colors `a` (t = 0.0) and `b` (t = 1.0), alpha included.
+pub fn mix_colors(a: [u8; 4], b: [u8; 4], t: f32) -> [u8; 4] {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+    [mix(a[0], b[0]), mix(a[1], b[1]), mix(a[2], b[2]), mix(a[3], b[3])]
+}
+
+/// Dot style: one circle per bar. In `DotMode::Size` the radius tracks the value with the dot on the center
+/// line; in `DotMode::Position` the dot rises from the baseline. With a trail `history` (oldest first, ending
+/// with the current frame), earlier frames are drawn first, fading out with age.
+fn draw_dots(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    config: &Config,
+    bar_heights: &[f32],
+    history: Option<&BarHistory>,
+) {
+    let layout = BarLayout::new(config, bar_heights.len() as u32);
+    let trail: Vec<&[f32]> = match history {
+        Some(h) if !h.columns.is_empty() => h.columns.iter().map(|c| c.as_slice()).collect(),
+        _ => vec![bar_heights],
+    };
+    let steps = trail.len();
+    for (age_index, heights) in trail.into_iter().enumerate() {
+        let opacity = (age_index + 1) as f32 / steps as f32;
+        for (i, &h) in heights.iter().enumerate().take(bar_heights.len()) {
+            let (cx, cy, r) = dot_geometry(config, &layout, i, h);
+            if r > 0.0 {
+                fill_circle(img, cx, cy, r, bar_color(config, i, bar_heights.len(), h), opacity);
+            }
+        }
+    }
+}
+
+/// Center and radius of the dot for bar `i` with value `h` (0.0–1.0).
+fn dot_geometry(config: &Config, layout: &BarLayout, i: usize, h: f32) -> (f32, f32, f32) {
+    let h = if h.is_finite() { h.clamp(0.0, 1.0) } else { 0.0 };
+    let cx = layout.bar_x(i) as f32 + layout.bar_width as f32 / 2.0;
+    let max_r = (layout.bar_width as f32 / 2.0).min(layout.usable_height as f32 / 2.0);
+    match config.dot_mode {
+        DotMode::Size => (cx, layout.y_center as f32, h * max_r),
+        DotMode::Position => {
+            // Keep the whole dot inside the band.
+            let baseline = curve_baseline(layout) - max_r;
+            let travel = (layout.usable_height as f32 - 2.0 * max_r).max(0.0);
+            (cx, baseline - h * travel, max_r)
+        }
+    }
+}
+
+/// Fill the pixels whose centers lie within `r` of (`cx`, `cy`), blending at `opacity`.
+pub fn fill_circle(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    cx: f32,
+    cy: f32,
+    r: f32,
+    color: [u8; 4],
+    opacity: f32,
+) {
+    let (width, height) = img.dimensions();
+    let x_start = (cx - r).floor().max(0.0) as u32;
+    let x_end = ((cx + r).ceil().max(0.0) as u32).min(width);
+    let y_start = (cy - r).floor().max(0.0) as u32;
+    let y_end = ((cy + r).ceil().max(0.0) as u32).min(height);
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            if dx * dx + dy * dy <= r * r {
+                let blended = blend(*img.get_pixel(x, y), color, opacity);
+                img.put_pixel(x, y, blended);
+            }
+        }
+    }
+}
+
+/// Timeline style ("audiogram"): the whole track's min/max envelope across the strip, with the played part
+/// in `played_color` and a playhead at `progress`.
+fn draw_timeline(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    config: &Config,
+    envelope: &[EnvelopePoint],
+    progress: f32,
+) {
+    let (x0, strip_width) = strip_extent(config);
+    if envelope.is_empty() || strip_width == 0 {
+        return;
+    }
+    let layout = BarLayout::new(config, 1);
+    let half = layout.usable_height as f32 / 2.0;
+    let y_center = layout.y_center as f32;
+    let (_, height) = img.dimensions();
+    let progress = progress.clamp(0.0, 1.0);
+    let played_columns = (progress * strip_width as f32).round() as u32;
+
+    for c in 0..strip_width {
+        let p = envelope[(c as usize * envelope.len() / strip_width as usize).min(envelope.len() - 1)];
+        let top = (y_center - p.max.clamp(-1.0, 1.0) * half).floor().max(0.0) as u32;
+        let bottom = (y_center - p.min.clamp(-1.0, 1.0) * half).ceil().max(0.0) as u32;
+        let bottom = bottom.max(top + 1).min(height);
+        let color = if c < played_columns {
+            config.played_color
+        } else {
+            config.bar_color
+        };
+        for y in top..bottom {
+            let blended = blend(*img.get_pixel(x0 + c, y), color, 1.0);
+            img.put_pixel(x0 + c, y, blended);
+        }
+    }
+
+    draw_position_marker(img, config, progress, config.played_color);
+}
+
+/// Spectrogram style: a heat map of the spectrum history filling the band. The newest column is at the right
+/// edge and older columns scroll left; low frequencies are at the bottom.
+fn draw_spectrogram(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, history: &BarHistory) {
+    let (x0, strip_width) = strip_extent(config);
+    let y1 = band_bottom(config);
+    let y0 = y1.saturating_sub(config.spectrum_height);
+    let band_height = y1 - y0;
+    if band_height == 0 {
+        return;
+    }
+    let right = x0 + strip_width;
+    for (age, column) in history.columns.iter().rev().enumerate() {
+        let Some(x) = right.checked_sub(age as u32 + 1) else {
+            break;
+        };
+        if x < x0 || column.is_empty() {
+            break;
+        }
+        for y in y0..y1 {
+            let from_bottom = (y1 - 1 - y) as usize;
+            let bar = (from_bottom * column.len() / band_height as usize).min(column.len() - 1);
+            img.put_pixel(x, y, Rgba(heat_color(column[bar])));
+        }
+    }
+}
+
+/// Black → red → yellow → white heat colormap for `v` in 0.0–1.0.
+fn heat_color(v: f32) -> [u8; 4] {
+    let v = if v.is_finite() { v.clamp(0.0, 1.0) } else { 0.0 };
+    let channel = |start: f32| ((v * 3.0 - start).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0), 255]
+}
+
+/// Draw connected line segments through `points` with round joins, `thickness` pixels wide.
+pub fn draw_polyline(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    points: &[(f32, f32)],
+    thickness: f32,
+    color: [u8; 4],
+) {
+    let (width, height) = img.dimensions();
+    let half = (thickness / 2.0).max(0.5);
+    let segments: Vec<((f32, f32), (f32, f32))> = if points.len() == 1 {
+        vec![(points[0], points[0])]
     } else {
-        0
+        points.windows(2).map(|w| (w[0], w[1])).collect()
     };
-    let radius = (bar_width / 2).clamp(1, 4);
-    let start_x = (width.saturating_sub(total_bars * bar_width + total_gaps)) / 2;
+    for (a, b) in segments {
+        let min_x = (a.0.min(b.0) - half).floor().max(0.0) as u32;
+        let max_x = (a.0.max(b.0) + half).ceil().max(0.0) as u32;
+        let min_y = (a.1.min(b.1) - half).floor().max(0.0) as u32;
+        let max_y = (a.1.max(b.1) + half).ceil().max(0.0) as u32;
+        for y in min_y..=max_y.min(height.saturating_sub(1)) {
+            for x in min_x..=max_x.min(width.saturating_sub(1)) {
+                let p = (x as f32 + 0.5, y as f32 + 0.5);
+                if distance_to_segment(p, a, b) <= half {
+                    let blended = blend(*img.get_pixel(x, y), color, 1.0);
+                    img.put_pixel(x, y, blended);
+                }
+            }
+        }
+    }
+}
+
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let len_sq = abx * abx + aby * aby;
+    let t = if len_sq > 0.0 {
+        (((p.0 - a.0) * abx + (p.1 - a.1) * aby) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (qx, qy) = (a.0 + abx * t, a.1 + aby * t);
+    ((p.0 - qx).powi(2) + (p.1 - qy).powi(2)).sqrt()
+}
+
+/// Center (x, y) and inner radius of the circle used by the radial style, in pixels.
+pub fn radial_geometry(config: &Config) -> (f32, f32, f32) {
+    let (cx, cy) = config
+        .radial_center
+        .unwrap_or((config.width / 2, config.height / 2));
+    let r0 = config
+        .radial_inner_radius
+        .unwrap_or(config.width.min(config.height) / 6);
+    (cx as f32, cy as f32, r0 as f32)
+}
+
+/// Radial style: bars point outward from the inner circle, evenly spaced clockwise starting at `radial_rotation`.
+/// Bar length scales with `spectrum_height`; bar thickness fills 60% of each bar's arc on the inner circle.
+fn draw_radial_bars(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, bar_heights: &[f32]) {
+    let (cx, cy, r0) = radial_geometry(config);
+    let n = bar_heights.len() as f32;
+    let thickness = (std::f32::consts::TAU * r0.max(1.0) / n * 0.6).max(1.0);
+    let max_len = config.spectrum_height.saturating_sub(4) as f32;
+    let rotation = config.radial_rotation.to_radians();
 
     for (i, &h) in bar_heights.iter().enumerate() {
-        let bar_height_f = h.clamp(0.0, 1.0) * usable_height as f32;
-        let bar_height = bar_height_f as u32;
-        if bar_height == 0 {
+        let len = (h.clamp(0.0, 1.0) * max_len).max((config.bar_min_height as f32).min(max_len));
+        if len < 1.0 {
             continue;
         }
+        let theta = rotation + std::f32::consts::TAU * i as f32 / n;
+        // 0 rad points up; angles grow clockwise in image coordinates.
+        let dir = (theta.sin(), -theta.cos());
+        let color = bar_color(config, i, bar_heights.len(), h);
+        fill_oriented_bar(img, (cx, cy), dir, r0, r0 + len, thickness, color);
+    }
+}
 
-        let x0 = start_x + i as u32 * (bar_width + gap);
-        let y_top = y_center.saturating_sub(bar_height / 2);
+/// Fill a rectangle running along unit vector `dir` from `start` to `end` (distances from `center`), `thickness` pixels wide.
+fn fill_oriented_bar(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    center: (f32, f32),
+    dir: (f32, f32),
+    start: f32,
+    end: f32,
+    thickness: f32,
+    color: [u8; 4],
+) {
+    let (width, height) = img.dimensions();
+    let (cx, cy) = center;
+    let (dx, dy) = dir;
+    let half = thickness / 2.0;
+    // Normal to `dir`.
+    let (nx, ny) = (-dy, dx);
+    let corners = [
+        (cx + dx * start + nx * half, cy + dy * start + ny * half),
+        (cx + dx * start - nx * half, cy + dy * start - ny * half),
+        (cx + dx * end + nx * half, cy + dy * end + ny * half),
+        (cx + dx * end - nx * half, cy + dy * end - ny * half),
+    ];
+    let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+    let max_x = corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as u32;
+    let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min).floor().max(0.0) as u32;
+    let max_y = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max).ceil().max(0.0) as u32;
 
-        draw_rounded_rect(
-            &mut img,
-            x0,
-            y_top,
-            bar_width,
-            bar_height,
+    for y in min_y..=max_y.min(height.saturating_sub(1)) {
+        for x in min_x..=max_x.min(width.saturating_sub(1)) {
+            let px = x as f32 + 0.5 - cx;
+            let py = y as f32 + 0.5 - cy;
+            let along = px * dx + py * dy;
+            let across = px * nx + py * ny;
+            if along >= start && along <= end && across.abs() <= half {
+                let blended = blend(*img.get_pixel(x, y), color, 1.0);
+                img.put_pixel(x, y, blended);
+            }
+        }
+    }
+}
+
+/// Paste `art` (already square) centered on the radial circle, masked to a circle of the art's diameter.
+#[cfg(feature = "runner")]
+pub fn draw_center_image(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    config: &Config,
+    art: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) {
+    let (width, height) = img.dimensions();
+    let (cx, cy, _) = radial_geometry(config);
+    let d = art.width().min(art.height());
+    let r = d as f32 / 2.0;
+    let x0 = cx - r;
+    let y0 = cy - r;
+    for ay in 0..d {
+        for ax in 0..d {
+            let fx = ax as f32 + 0.5 - r;
+            let fy = ay as f32 + 0.5 - r;
+            if fx * fx + fy * fy > r * r {
+                continue;
+            }
+            let x = x0 + ax as f32;
+            let y = y0 + ay as f32;
+            if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+                continue;
+            }
+            let p = art.get_pixel(ax, ay).0;
+            let opacity = p[3] as f32 / 255.0;
+            let blended = blend(*img.get_pixel(x as u32, y as u32), [p[0], p[1], p[2], 255], opacity);
+            img.put_pixel(x as u32, y as u32, blended);
+        }
+    }
+}
+
+/// Draw a vertical marker line through the spectrum band at `position` (0.0 = left edge of the bar strip, 1.0 = right edge).
+/// Used for the spectral centroid, which shares the bars' log-frequency axis.
+pub fn draw_position_marker(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, config: &Config, position: f32, color: [u8; 4]) {
+    let (width, height) = img.dimensions();
+    let (strip_x0, strip_width) = strip_extent(config);
+    if strip_width == 0 || width == 0 {
+        return;
+    }
+    let x_center = strip_x0 + (position.clamp(0.0, 1.0) * (strip_width - 1) as f32).round() as u32;
+    let y1 = band_bottom(config).min(height);
+    let y0 = y1.saturating_sub(config.spectrum_height);
+    let half = MARKER_WIDTH / 2;
+    for x in x_center.saturating_sub(half)..=(x_center + half).min(width - 1) {
+        for y in y0..y1 {
+            let blended = blend(*img.get_pixel(x, y), color, 1.0);
+            img.put_pixel(x, y, blended);
+        }
+    }
+}
+
+/// Width of the marker drawn by `draw_position_marker` (pixels; odd so it centers on the position).
+const MARKER_WIDTH: u32 = 3;
+
+/// Translucent face drawn behind level meters.
+#[cfg(feature = "runner")]
+const METER_FACE: [u8; 4] = [0, 0, 0, 140];
+
+/// Color of the overload zone of level meters.
+#[cfg(feature = "runner")]
+const METER_RED: [u8; 4] = [230, 40, 40, 255];
+
+/// Half of the needle meter's sweep (degrees either side of straight up).
+#[cfg(feature = "runner")]
+const NEEDLE_SWEEP_DEG: f32 = 45.0;
+
+/// Vertical level meter in `(x0, y0, w, h)`: a face with a `color` bar filling from the bottom up to `position`
+/// (0.0–1.0); the part above `red_from` (0.0–1.0 on the same scale) is drawn red.
+#[cfg(feature = "runner")]
+pub fn draw_vertical_meter(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    (x0, y0, w, h): (u32, u32, u32, u32),
+    position: f32,
+    red_from: f32,
+    color: [u8; 4],
+) {
+    let pad = (w / 6).max(1);
+    fill_shape(img, &Shape::rounded_rect((x0, y0, w, h), pad), y0..y0 + h, METER_FACE, 1.0);
+    let (inner_w, inner_h) = (w.saturating_sub(pad * 2), h.saturating_sub(pad * 2));
+    let inner_bottom = y0 + pad + inner_h;
+    let lit_top = inner_bottom - (position.clamp(0.0, 1.0) * inner_h as f32).round() as u32;
+    let red_row = inner_bottom - (red_from.clamp(0.0, 1.0) * inner_h as f32).round() as u32;
+    let (ix, iy) = (x0 + pad, y0 + pad);
+    let inner = Shape::rounded_rect((ix, iy, inner_w, inner_h), 0);
+    fill_shape(img, &inner, lit_top.max(red_row)..inner_bottom, color, 1.0);
+    fill_shape(img, &inner, lit_top..red_row, METER_RED, 1.0);
+}
+
+/// Needle meter in `(x0, y0, w, h)`: a face with a `color` arc scale (red from `red_from`, 0.0–1.0) and a needle
+/// pivoting at the bottom center, swept from -`NEEDLE_SWEEP_DEG` (position 0.0) to +`NEEDLE_SWEEP_DEG` (1.0).
+#[cfg(feature = "runner")]
+pub fn draw_needle_meter(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    (x0, y0, w, h): (u32, u32, u32, u32),
+    position: f32,
+    red_from: f32,
+    color: [u8; 4],
+) {
+    let pad = (h / 10).max(1);
+    fill_shape(img, &Shape::rounded_rect((x0, y0, w, h), pad), y0..y0 + h, METER_FACE, 1.0);
+    let pivot = (x0 as f32 + w as f32 / 2.0, (y0 + h - pad) as f32);
+    let sweep = NEEDLE_SWEEP_DEG.to_radians();
+    let radius = (h.saturating_sub(pad * 2) as f32).min((w as f32 / 2.0 - pad as f32) / sweep.sin());
+    let stroke = (h as f32 / 60.0).max(1.5);
+    // 0 rad points up; angles grow clockwise in image coordinates.
+    let at = |t: f32, r: f32| {
+        let theta = (t.clamp(0.0, 1.0) * 2.0 - 1.0) * sweep;
+        (pivot.0 + r * theta.sin(), pivot.1 - r * theta.cos())
+    };
+    let arc = |from: f32, to: f32| -> Vec<(f32, f32)> {
+        let steps = ((to - from) * 64.0).ceil().max(1.0) as usize;
+        (0..=steps).map(|k| at(from + (to - from) * k as f32 / steps as f32, radius)).collect()
+    };
+    draw_polyline(img, &arc(0.0, red_from), stroke, color);
+    draw_polyline(img, &arc(red_from, 1.0), stroke, METER_RED);
+    for tick in 0..=4 {
+        let t = tick as f32 / 4.0;
+        let tick_color = if t > red_from { METER_RED } else { color };
+        draw_polyline(img, &[at(t, radius), at(t, radius * 0.88)], stroke, tick_color);
+    }
+    draw_polyline(img, &[pivot, at(position, radius * 0.95)], stroke, color);
+    fill_circle(img, pivot.0, pivot.1, stroke * 2.0, color, 1.0);
+}
+
+/// Progress bar in `(x0, y0, w, h)`: the `track` color across the whole rectangle, then `color` from the left edge
+/// to `progress` (0.0–1.0) of the width.
+#[cfg(feature = "runner")]
+pub fn draw_progress_bar(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    (x0, y0, w, h): (u32, u32, u32, u32),
+    progress: f32,
+    color: [u8; 4],
+    track: [u8; 4],
+) {
+    let filled = (progress.clamp(0.0, 1.0) * w as f32).round() as u32;
+    fill_rounded_rect(img, (x0, y0, w, h), 0, track);
+    fill_rounded_rect(img, (x0, y0, filled, h), 0, color);
+}
+
+/// Fill the box `(x0, y0, w, h)` with `color`, its corners rounded by `radius`.
+#[cfg(any(feature = "runner", feature = "script"))]
+pub fn fill_rounded_rect(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    rect: (u32, u32, u32, u32),
+    radius: u32,
+    color: [u8; 4],
+) {
+    fill_shape(img, &Shape::rounded_rect(rect, radius), rect.1..rect.1 + rect.3, color, 1.0);
+}
+
+/// Direction a bar grows toward; the point of a triangle faces it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tip {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// An outline inside the box `(x0, y0, w, h)`, rasterized by testing which pixel centers it contains.
+#[derive(Clone, Copy, Debug)]
+struct Shape {
+    kind: BarShape,
+    rect: (u32, u32, u32, u32),
+    /// Corner radius of `BarShape::Rect` and `BarShape::Capsule`, at most half the shorter side.
+    radius: u32,
+    tip: Tip,
+}
+
+impl Shape {
+    /// Rectangle with corners rounded by `r`.
+    #[cfg(any(feature = "runner", feature = "script"))]
+    fn rounded_rect(rect: (u32, u32, u32, u32), r: u32) -> Self {
+        Self {
+            kind: BarShape::Rect,
+            rect,
+            radius: r.min(rect.2 / 2).min(rect.3 / 2),
+            tip: Tip::Up,
+        }
+    }
+
+    /// A bar in `config.bar_shape` growing toward `tip`; `r` is the corner radius when the shape is a rectangle.
+    fn bar(config: &Config, rect: (u32, u32, u32, u32), r: u32, tip: Tip) -> Self {
+        let (_, _, w, h) = rect;
+        let radius = match config.bar_shape {
+            BarShape::Capsule => w.min(h) / 2,
+            _ => r.min(w / 2).min(h / 2),
+        };
+        Self {
+            kind: config.bar_shape,
+            rect,
             radius,
-            bar_color,
-        );
+            tip,
+        }
+    }
+
+    fn contains(&self, px: u32, py: u32) -> bool {
+        let (x0, y0, w, h) = self.rect;
+        match self.kind {
+            BarShape::Rect | BarShape::Capsule => point_in_rounded_rect(px, py, x0, y0, w, h, self.radius),
+            BarShape::Triangle | BarShape::Diamond => {
+                if w == 0 || h == 0 {
+                    return false;
+                }
+                // Pixel center in box coordinates, 0.0–1.0 on each axis.
+                let u = (px as f32 + 0.5 - x0 as f32) / w as f32;
+                let v = (py as f32 + 0.5 - y0 as f32) / h as f32;
+                if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+                    return false;
+                }
+                if self.kind == BarShape::Diamond {
+                    return (u - 0.5).abs() + (v - 0.5).abs() <= 0.5;
+                }
+                // Distance from the point (0.0) toward the base (1.0), and offset from the center line.
+                let (along, across) = match self.tip {
+                    Tip::Up => (v, u - 0.5),
+                    Tip::Down => (1.0 - v, u - 0.5),
+                    Tip::Left => (u, v - 0.5),
+                    Tip::Right => (1.0 - u, v - 0.5),
+                };
+                across.abs() <= along / 2.0
+            }
+        }
+    }
+
+    /// Whether `(px, py)` lies at least `inset` pixels inside the edge, i.e. past a stroke that wide.
+    fn contains_inset(&self, px: u32, py: u32, inset: u32) -> bool {
+        let (x0, y0, w, h) = self.rect;
+        match self.kind {
+            BarShape::Rect | BarShape::Capsule => {
+                w > 2 * inset
+                    && h > 2 * inset
+                    && point_in_rounded_rect(
+                        px,
+                        py,
+                        x0 + inset,
+                        y0 + inset,
+                        w - 2 * inset,
+                        h - 2 * inset,
+                        self.radius.saturating_sub(inset),
+                    )
+            }
+            BarShape::Triangle | BarShape::Diamond => {
+                px >= inset
+                    && py >= inset
+                    && self.contains(px - inset, py)
+                    && self.contains(px + inset, py)
+                    && self.contains(px, py - inset)
+                    && self.contains(px, py + inset)
+            }
+        }
+    }
+}
+
+/// Paint one bar `shape` clipped to `rows`: filled with `color`, outlined with `config.bar_stroke` when set
+/// (outline only with `config.bar_stroke_only`), both mixed in at `opacity`.
+fn paint_bar(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    config: &Config,
+    shape: Shape,
+    rows: std::ops::Range<u32>,
+    color: [u8; 4],
+    opacity: f32,
+) {
+    if config.bar_stroke.is_none() || !config.bar_stroke_only {
+        fill_shape(img, &shape, rows.clone(), color, opacity);
+    }
+    if let Some(stroke) = config.bar_stroke {
+        stroke_shape(img, &shape, rows, stroke, opacity);
     }
-
-    img
 }
 
-/// Draw a rounded rectangle (all four corners rounded).
-fn draw_rounded_rect(
+/// Fill the part of `shape` whose rows fall in `rows`, mixing `color` over the existing pixels at `opacity` (0.0–1.0).
+fn fill_shape(
     img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
-    x0: u32,
-    y0: u32,
-    w: u32,
-    h: u32,
-    r: u32,
+    shape: &Shape,
+    rows: std::ops::Range<u32>,
     color: [u8; 4],
+    opacity: f32,
 ) {
     let (width, height) = img.dimensions();
-    let r = r.min(w / 2).min(h / 2);
-    let x1 = x0 + w;
-    let y1 = y0 + h;
-
-    for y in y0..y1 {
-        for x in x0..x1 {
-            if !point_in_rounded_rect(x, y, x0, y0, w, h, r) {
-                continue;
+    let (x0, y0, w, h) = shape.rect;
+    for y in y0.max(rows.start)..(y0 + h).min(rows.end).min(height) {
+        for x in x0..(x0 + w).min(width) {
+            if shape.contains(x, y) {
+                let blended = blend(*img.get_pixel(x, y), color, opacity);
+                img.put_pixel(x, y, blended);
             }
-            if x < width && y < height {
-                img.put_pixel(x, y, Rgba(color));
+        }
+    }
+}
+
+/// Outline `shape` with `stroke.width` pixels inside its edge, limited to rows in `rows`.
+/// Shapes too small to have an interior are filled entirely with the stroke color.
+fn stroke_shape(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    shape: &Shape,
+    rows: std::ops::Range<u32>,
+    stroke: BarStroke,
+    opacity: f32,
+) {
+    let (width, height) = img.dimensions();
+    let (x0, y0, w, h) = shape.rect;
+    for y in y0.max(rows.start)..(y0 + h).min(rows.end).min(height) {
+        for x in x0..(x0 + w).min(width) {
+            if shape.contains(x, y) && !shape.contains_inset(x, y, stroke.width) {
+                let blended = blend(*img.get_pixel(x, y), stroke.color, opacity);
+                img.put_pixel(x, y, blended);
             }
         }
     }
 }
 
+/// Composite `color` over `dst` (source-over, straight alpha), with the color's own alpha scaled by `opacity` (0.0–1.0).
+fn blend(dst: Rgba<u8>, color: [u8; 4], opacity: f32) -> Rgba<u8> {
+    let src_a = color[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+    if src_a >= 1.0 {
+        return Rgba(color);
+    }
+    let d = dst.0;
+    let dst_a = d[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let mix = |d: u8, c: u8| ((c as f32 * src_a + d as f32 * dst_a * (1.0 - src_a)) / out_a).round() as u8;
+    Rgba([
+        mix(d[0], color[0]),
+        mix(d[1], color[1]),
+        mix(d[2], color[2]),
+        (out_a * 255.0).round() as u8,
+    ])
+}
+
 fn point_in_rounded_rect(px: u32, py: u32, x0: u32, y0: u32, w: u32, h: u32, r: u32) -> bool {
     if r == 0 {
         return px >= x0 && px < x0 + w && py >= y0 && py < y0 + h;
@@ -130,7 +1380,39 @@ fn point_in_rounded_rect(px: u32, py: u32, x0: u32, y0: u32, w: u32, h: u32, r:
 
 #[cfg(test)]
 mod tests {
-    use super::{draw_spectrum_frame, point_in_rounded_rect};
+    use super::{
+        band_bottom, bar_color, blend, catmull_rom, curve_points, distance_to_segment, dot_geometry, draw_background,
+        draw_position_marker, draw_spectrum_band, heat_color, mix_colors, point_in_rounded_rect, radial_geometry,
+        strip_extent, BarHistory, EnvelopePoint, FrameData, ImageBuffer, Rgba, Shape, Tip,
+    };
+    use crate::colormap::Colormap;
+    use crate::config::{
+        BarAnchor, BarShape, BarStroke, Config, DotMode, HAlign, Orientation, StereoSplit, Style, VAlign,
+    };
+    use crate::effects::{Glow, Reflection, Shadow};
+    use crate::particles::Particle;
+
+    /// One frame as main draws it without a theme: the background, then a single band.
+    fn draw_spectrum_frame(
+        config: &Config,
+        frame: &FrameData,
+        bg_image: Option<&ImageBuffer<Rgba<u8>, Vec<u8>>>,
+    ) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let mut img = draw_background(config, bg_image);
+        draw_spectrum_band(&mut img, config, frame);
+        img
+    }
+
+    fn test_config(width: u32, height: u32, spectrum_height: u32) -> Config {
+        Config {
+            width,
+            height,
+            spectrum_height,
+            bar_color: [0, 0, 0, 255],
+            bg_color: [255, 255, 255, 255],
+            ..Config::default()
+        }
+    }
 
     #[test]
     fn point_in_rounded_rect_r0_inside() {
@@ -163,21 +1445,21 @@ mod tests {
 
     #[test]
     fn draw_spectrum_frame_empty_bars_returns_unchanged_size() {
-        let img = draw_spectrum_frame(100, 50, 20, 0, None, &[], [0, 0, 0, 255], [255, 255, 255, 255], None);
+        let img = draw_spectrum_frame(&test_config(100, 50, 20), &FrameData::bars(&[]), None);
         assert_eq!(img.dimensions(), (100, 50));
     }
 
     #[test]
     fn draw_spectrum_frame_dimensions_match() {
         let heights = vec![0.5f32; 8];
-        let img = draw_spectrum_frame(64, 32, 16, 0, None, &heights, [0, 0, 0, 255], [255, 255, 255, 255], None);
+        let img = draw_spectrum_frame(&test_config(64, 32, 16), &FrameData::bars(&heights), None);
         assert_eq!(img.dimensions(), (64, 32));
     }
 
     #[test]
     fn draw_spectrum_frame_all_zeros_no_bar_pixels() {
         let heights = vec![0.0f32; 4];
-        let img = draw_spectrum_frame(40, 20, 10, 0, None, &heights, [0, 0, 0, 255], [255, 255, 255, 255], None);
+        let img = draw_spectrum_frame(&test_config(40, 20, 10), &FrameData::bars(&heights), None);
         assert_eq!(img.dimensions(), (40, 20));
         let bg = [255u8, 255, 255, 255];
         for y in 0..20 {
@@ -187,4 +1469,1055 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn strip_extent_places_band_by_anchor_and_alignment() {
+        let base = Config {
+            spectrum_width: Some(40),
+            ..test_config(200, 100, 20)
+        };
+        assert_eq!(strip_extent(&base), (80, 40));
+        let left = Config { spectrum_align: HAlign::Left, ..base.clone() };
+        assert_eq!(strip_extent(&left), (0, 40));
+        let right_at = Config { spectrum_align: HAlign::Right, spectrum_x: Some(150), ..base.clone() };
+        assert_eq!(strip_extent(&right_at), (110, 40));
+        let center_at = Config { spectrum_x: Some(30), ..base.clone() };
+        assert_eq!(strip_extent(&center_at), (10, 40));
+        // Kept inside the frame.
+        let overflow = Config { spectrum_align: HAlign::Left, spectrum_x: Some(190), ..base };
+        assert_eq!(strip_extent(&overflow), (160, 40));
+    }
+
+    #[test]
+    fn band_bottom_places_band_by_anchor_and_alignment() {
+        let base = Config {
+            spectrum_y_from_bottom: 10,
+            ..test_config(200, 100, 20)
+        };
+        assert_eq!(band_bottom(&base), 90);
+        assert_eq!(band_bottom(&Config { spectrum_valign: VAlign::Top, ..base.clone() }), 20);
+        assert_eq!(band_bottom(&Config { spectrum_valign: VAlign::Middle, ..base.clone() }), 60);
+        let top_at = Config { spectrum_valign: VAlign::Top, spectrum_y: Some(30), ..base.clone() };
+        assert_eq!(band_bottom(&top_at), 50);
+        assert_eq!(band_bottom(&Config { spectrum_y: Some(200), ..base }), 100);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bars_follow_band_placement() {
+        let config = Config {
+            spectrum_width: Some(20),
+            spectrum_align: HAlign::Left,
+            spectrum_x: Some(5),
+            spectrum_valign: VAlign::Top,
+            spectrum_y: Some(2),
+            ..test_config(100, 100, 20)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(img.get_pixel(10, 12).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(50, 12).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(10, 60).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_vertical_runs_band_along_height() {
+        let config = Config {
+            orientation: Orientation::Vertical,
+            ..test_config(40, 100, 20)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // The band's bottom edge becomes the right side: columns 20..40, spanning the full height.
+        assert_eq!(img.get_pixel(30, 10).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(30, 90).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_rotate_turns_band_around_its_center() {
+        let config = Config {
+            orientation: Orientation::Rotate(90.0),
+            spectrum_width: Some(60),
+            ..test_config(100, 100, 10)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // Band centered at (50, 95) turned upright: now a vertical strip through x = 50.
+        assert_eq!(img.get_pixel(50, 80).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(30, 95).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_draws_centroid_marker() {
+        let config = Config {
+            centroid_color: [255, 0, 0, 255],
+            ..test_config(101, 40, 10)
+        };
+        let frame = FrameData {
+            centroid: Some(0.5),
+            ..FrameData::bars(&[])
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_eq!(img.get_pixel(50, 35).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_position_marker_spans_band_at_position() {
+        let config = Config {
+            spectrum_y_from_bottom: 5,
+            ..test_config(101, 40, 10)
+        };
+        let mut img = draw_spectrum_frame(&config, &FrameData::bars(&[]), None);
+        draw_position_marker(&mut img, &config, 0.5, [255, 0, 0, 255]);
+        let red = [255u8, 0, 0, 255];
+        for y in 25..35 {
+            assert_eq!(img.get_pixel(50, y).0, red, "pixel (50, {}) should be marker", y);
+        }
+        assert_ne!(img.get_pixel(50, 24).0, red);
+        assert_ne!(img.get_pixel(50, 35).0, red);
+        assert_ne!(img.get_pixel(20, 30).0, red);
+    }
+
+    #[test]
+    fn draw_position_marker_clamps_to_strip() {
+        let config = Config {
+            spectrum_width: Some(50),
+            ..test_config(100, 20, 10)
+        };
+        let mut img = draw_spectrum_frame(&config, &FrameData::bars(&[]), None);
+        draw_position_marker(&mut img, &config, 2.0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(74, 15).0, [255, 0, 0, 255]);
+        assert_ne!(img.get_pixel(76, 15).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_mirror_splits_at_center() {
+        let config = Config {
+            style: Style::Mirror,
+            mirror_opacity: 1.0,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        let x = 5;
+        // y_center = 20, gap rows 19..21 stay background
+        assert_eq!(img.get_pixel(x, 19).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(x, 20).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(x, 18).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(x, 21).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_stereo_top_bottom_uses_each_channel() {
+        let config = Config {
+            stereo_split: Some(StereoSplit::TopBottom),
+            ..test_config(40, 40, 40)
+        };
+        let (left, right) = ([1.0; 4], [0.0; 4]);
+        let frame = FrameData {
+            stereo: Some((&left, &right)),
+            ..FrameData::bars(&[0.5; 4])
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_eq!(img.get_pixel(5, 15).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 25).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_stereo_left_right_stacks_bands_from_bottom() {
+        let config = Config {
+            stereo_split: Some(StereoSplit::LeftRight),
+            ..test_config(40, 40, 40)
+        };
+        // usable height 36, 4 bars with 1px gaps: 8px thick; lowest band at rows 30..38, highest at 3..11.
+        let (left, right) = ([1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]);
+        let frame = FrameData {
+            stereo: Some((&left, &right)),
+            ..FrameData::bars(&[0.0; 4])
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_eq!(img.get_pixel(5, 34).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(30, 34).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(30, 7).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 7).0, [255, 255, 255, 255]);
+        // Center gap stays background.
+        assert_eq!(img.get_pixel(20, 34).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn huge_gaps_set_directly_draw_nothing_instead_of_overflowing() {
+        let (left, right) = ([1.0; 4], [1.0; 4]);
+        for stereo_split in [None, Some(StereoSplit::LeftRight)] {
+            let config = Config { bar_gap: u32::MAX / 2, stereo_split, ..test_config(40, 40, 40) };
+            let frame = FrameData { stereo: Some((&left, &right)), ..FrameData::bars(&[1.0; 4]) };
+            let img = draw_spectrum_frame(&config, &frame, None);
+            assert!(img.pixels().all(|p| p.0 == [255, 255, 255, 255]));
+        }
+    }
+
+    #[test]
+    fn draw_spectrum_frame_mirror_lower_half_uses_opacity() {
+        let config = Config {
+            style: Style::Mirror,
+            mirror_opacity: 0.5,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(img.get_pixel(5, 15).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 25).0, [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_mirror_is_symmetric_in_extent() {
+        let config = Config {
+            style: Style::Mirror,
+            mirror_opacity: 1.0,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        let painted: Vec<u32> = (0..40).filter(|&y| img.get_pixel(5, y).0 != [255, 255, 255, 255]).collect();
+        let above = painted.iter().filter(|&&y| y < 20).count();
+        let below = painted.iter().filter(|&&y| y >= 20).count();
+        assert!(above > 0);
+        assert_eq!(above, below);
+    }
+
+    #[test]
+    fn blend_mixes_by_opacity() {
+        assert_eq!(blend(Rgba([255, 255, 255, 255]), [0, 0, 0, 255], 1.0).0, [0, 0, 0, 255]);
+        assert_eq!(blend(Rgba([255, 255, 255, 255]), [0, 0, 0, 255], 0.0).0, [255, 255, 255, 255]);
+        assert_eq!(blend(Rgba([200, 100, 0, 255]), [0, 100, 200, 255], 0.5).0, [100, 100, 100, 255]);
+    }
+
+    #[test]
+    fn blend_uses_source_alpha() {
+        // 50% black over opaque white.
+        assert_eq!(blend(Rgba([255, 255, 255, 255]), [0, 0, 0, 128], 1.0).0, [127, 127, 127, 255]);
+        // Source alpha and opacity multiply.
+        assert_eq!(blend(Rgba([255, 255, 255, 255]), [0, 0, 0, 128], 0.5).0, [191, 191, 191, 255]);
+        // Over a transparent pixel the source color is kept and alpha accumulates.
+        assert_eq!(blend(Rgba([0, 0, 0, 0]), [255, 0, 0, 128], 1.0).0, [255, 0, 0, 128]);
+        assert_eq!(blend(Rgba([0, 0, 0, 0]), [255, 0, 0, 0], 1.0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_translucent_bars_show_background() {
+        let config = Config {
+            bar_color: [0, 0, 0, 128],
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(img.get_pixel(4, 20).0, [127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn radial_geometry_defaults_to_frame_center() {
+        let config = test_config(120, 60, 20);
+        assert_eq!(radial_geometry(&config), (60.0, 30.0, 10.0));
+        let config = Config {
+            radial_center: Some((10, 20)),
+            radial_inner_radius: Some(5),
+            ..config
+        };
+        assert_eq!(radial_geometry(&config), (10.0, 20.0, 5.0));
+    }
+
+    #[test]
+    fn draw_spectrum_frame_radial_first_bar_points_up() {
+        let config = Config {
+            style: Style::Radial,
+            radial_inner_radius: Some(10),
+            ..test_config(100, 100, 30)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0, 0.0, 0.0, 0.0]), None);
+        // Bar 0 runs from r=10 to r=36 straight up from (50, 50).
+        assert_eq!(img.get_pixel(50, 30).0, [0, 0, 0, 255]);
+        // Nothing inside the inner circle, nor to the right/below/left.
+        assert_eq!(img.get_pixel(50, 45).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(70, 50).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(50, 70).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(30, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_radial_rotation_moves_first_bar() {
+        let config = Config {
+            style: Style::Radial,
+            radial_inner_radius: Some(10),
+            radial_rotation: 90.0,
+            ..test_config(100, 100, 30)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0, 0.0, 0.0, 0.0]), None);
+        assert_eq!(img.get_pixel(70, 50).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(50, 30).0, [255, 255, 255, 255]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn draw_center_image_masks_to_circle() {
+        use super::draw_center_image;
+
+        let config = Config {
+            style: Style::Radial,
+            ..test_config(40, 40, 10)
+        };
+        let mut img = draw_spectrum_frame(&config, &FrameData::bars(&[]), None);
+        let art = ImageBuffer::from_fn(10, 10, |_, _| Rgba([255, 0, 0, 255]));
+        draw_center_image(&mut img, &config, &art);
+        assert_eq!(img.get_pixel(20, 20).0, [255, 0, 0, 255]);
+        // Corner of the art's square lies outside the circle.
+        assert_eq!(img.get_pixel(15, 15).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn distance_to_segment_cases() {
+        assert_eq!(distance_to_segment((5.0, 3.0), (0.0, 0.0), (10.0, 0.0)), 3.0);
+        assert_eq!(distance_to_segment((-3.0, 4.0), (0.0, 0.0), (10.0, 0.0)), 5.0);
+        assert_eq!(distance_to_segment((1.0, 1.0), (1.0, 1.0), (1.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_waveform_silence_is_center_line() {
+        let config = Config {
+            style: Style::Waveform,
+            ..test_config(50, 40, 40)
+        };
+        let samples = vec![0.0f32; 100];
+        let frame = FrameData {
+            waveform: &samples,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        // y_center = 20: the 2 px stroke covers rows 19 and 20 across the width.
+        for x in [0, 25, 49] {
+            assert_eq!(img.get_pixel(x, 19).0, [0, 0, 0, 255]);
+            assert_eq!(img.get_pixel(x, 20).0, [0, 0, 0, 255]);
+            assert_eq!(img.get_pixel(x, 10).0, [255, 255, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn draw_spectrum_frame_waveform_follows_samples() {
+        let config = Config {
+            style: Style::Waveform,
+            ..test_config(40, 40, 40)
+        };
+        // First half full-scale positive, second half full-scale negative.
+        let mut samples = vec![1.0f32; 20];
+        samples.extend(vec![-1.0f32; 20]);
+        let frame = FrameData {
+            waveform: &samples,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        // usable height 36 => half 18: +1.0 at y=2, -1.0 at y=38.
+        assert_eq!(img.get_pixel(5, 2).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(35, 37).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 37).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_waveform_without_samples_is_background() {
+        let config = Config {
+            style: Style::Waveform,
+            ..test_config(20, 20, 10)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert!(img.pixels().all(|p| p.0 == [255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn draw_spectrum_frame_ring_silence_is_circle() {
+        let config = Config {
+            style: Style::Ring,
+            radial_inner_radius: Some(30),
+            ..test_config(100, 100, 24)
+        };
+        let samples = vec![0.0f32; 400];
+        let frame = FrameData {
+            waveform: &samples,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        for (x, y) in [(50, 20), (80, 50), (50, 80), (20, 50)] {
+            assert_eq!(img.get_pixel(x, y).0, [0, 0, 0, 255], "({}, {})", x, y);
+        }
+        assert_eq!(img.get_pixel(50, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_ring_radius_follows_samples() {
+        let config = Config {
+            style: Style::Ring,
+            radial_inner_radius: Some(30),
+            ..test_config(100, 100, 24)
+        };
+        // Positive half pushes the right side of the ring out, negative half pulls the left side in (excursion 10).
+        let mut samples = vec![1.0f32; 200];
+        samples.extend(vec![-1.0f32; 200]);
+        let frame = FrameData {
+            waveform: &samples,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_eq!(img.get_pixel(90, 50).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(80, 50).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(30, 50).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 50).0, [255, 255, 255, 255]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn draw_vertical_meter_fills_from_bottom_with_red_zone() {
+        use super::draw_vertical_meter;
+
+        let mut img = ImageBuffer::from_pixel(24, 106, Rgba([255, 255, 255, 255]));
+        // pad = 4: inner area rows 4..102.
+        draw_vertical_meter(&mut img, (0, 0, 24, 106), 0.9, 0.8, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(12, 100).0, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(12, 15).0, [230, 40, 40, 255]);
+        // Above the level only the translucent face remains.
+        let face = img.get_pixel(12, 6).0;
+        assert!(face[0] < 255 && face[0] == face[1] && face[1] == face[2], "face = {:?}", face);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn draw_needle_meter_points_needle_by_position() {
+        use super::draw_needle_meter;
+
+        let draw = |position: f32| {
+            let mut img = ImageBuffer::from_pixel(200, 120, Rgba([255, 255, 255, 255]));
+            draw_needle_meter(&mut img, (0, 0, 200, 120), position, 0.8, [0, 0, 255, 255]);
+            img
+        };
+        let (left, right) = (draw(0.0), draw(1.0));
+        // Pivot at (100, 108); a point halfway along the needle lies left or right of center.
+        assert_eq!(left.get_pixel(100, 107).0, [0, 0, 255, 255]);
+        assert_eq!(left.get_pixel(70, 78).0, [0, 0, 255, 255]);
+        assert_ne!(right.get_pixel(70, 78).0, [0, 0, 255, 255]);
+        assert_eq!(right.get_pixel(129, 78).0, [0, 0, 255, 255]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn draw_progress_bar_fills_played_fraction_over_track() {
+        use super::draw_progress_bar;
+
+        let mut img = ImageBuffer::from_pixel(100, 20, Rgba([255, 255, 255, 255]));
+        draw_progress_bar(&mut img, (10, 5, 80, 4), 0.25, [255, 0, 0, 255], [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(10, 5).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(29, 8).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(30, 8).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(89, 5).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(90, 5).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(50, 9).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_band_keeps_existing_pixels_outside_band() {
+        let config = test_config(40, 40, 10);
+        let mut img = ImageBuffer::from_pixel(40, 40, Rgba([0, 0, 255, 255]));
+        draw_spectrum_band(&mut img, &config, &FrameData::bars(&[1.0; 4]));
+        assert_eq!(img.get_pixel(20, 5).0, [0, 0, 255, 255]);
+        assert_eq!(img.get_pixel(5, 35).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_timeline_colors_played_part() {
+        let config = Config {
+            style: Style::Timeline,
+            played_color: [255, 0, 0, 255],
+            ..test_config(100, 40, 40)
+        };
+        let envelope = vec![EnvelopePoint { min: -0.5, max: 0.5 }; 10];
+        let frame = FrameData {
+            timeline: &envelope,
+            progress: 0.5,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        // y_center = 20, half = 18: envelope spans rows 11..29.
+        assert_eq!(img.get_pixel(10, 20).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(90, 20).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(90, 5).0, [255, 255, 255, 255]);
+        // Playhead spans the whole band at x = 50.
+        assert_eq!(img.get_pixel(50, 2).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_timeline_silence_draws_baseline() {
+        let config = Config {
+            style: Style::Timeline,
+            ..test_config(20, 20, 20)
+        };
+        let envelope = vec![EnvelopePoint::default(); 20];
+        let frame = FrameData {
+            timeline: &envelope,
+            progress: 0.0,
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_eq!(img.get_pixel(10, 10).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(10, 12).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn heat_color_endpoints() {
+        assert_eq!(heat_color(0.0), [0, 0, 0, 255]);
+        assert_eq!(heat_color(1.0), [255, 255, 255, 255]);
+        assert_eq!(heat_color(f32::NAN), [0, 0, 0, 255]);
+        let mid = heat_color(0.5);
+        assert_eq!(mid[0], 255);
+        assert!(mid[1] > 0 && mid[1] < 255);
+        assert_eq!(mid[2], 0);
+    }
+
+    #[test]
+    fn spectrogram_history_drops_oldest() {
+        let mut h = BarHistory::new(2);
+        h.push(vec![0.1]);
+        h.push(vec![0.2]);
+        h.push(vec![0.3]);
+        let cols: Vec<f32> = h.columns.iter().map(|c| c[0]).collect();
+        assert_eq!(cols, vec![0.2, 0.3]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_spectrogram_newest_column_at_right() {
+        let config = Config {
+            style: Style::Spectrogram,
+            ..test_config(10, 10, 10)
+        };
+        let mut history = BarHistory::new(10);
+        history.push(vec![1.0, 1.0]);
+        history.push(vec![1.0, 0.0]);
+        let frame = FrameData {
+            history: Some(&history),
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        // Newest column (x = 9): low half hot, high half cold.
+        assert_eq!(img.get_pixel(9, 9).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(9, 0).0, [0, 0, 0, 255]);
+        // Older column (x = 8) hot everywhere.
+        assert_eq!(img.get_pixel(8, 0).0, [255, 255, 255, 255]);
+        // No history yet further left: background.
+        assert_eq!(img.get_pixel(7, 0).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(7, 5).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn catmull_rom_hits_knots() {
+        let p = [(0.0, 0.0), (1.0, 2.0), (2.0, 1.0), (3.0, 3.0)];
+        assert_eq!(catmull_rom(p[0], p[1], p[2], p[3], 0.0), (1.0, 2.0));
+        let end = catmull_rom(p[0], p[1], p[2], p[3], 1.0);
+        assert!((end.0 - 2.0).abs() < 1e-6 && (end.1 - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn curve_points_stay_inside_band() {
+        let config = test_config(100, 50, 40);
+        let points = curve_points(&config, &[0.0, 1.0, 0.0, 1.0, 0.0]);
+        // usable height 36, y_center 30 => baseline 48, top 12.
+        assert!(points.iter().all(|p| p.1 >= 12.0 && p.1 <= 48.0));
+        assert!(points.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn draw_spectrum_frame_line_draws_curve_not_bars() {
+        let config = Config {
+            style: Style::Line,
+            stroke_width: 2.0,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        // Flat curve at baseline 38 - 0.5 * 36 = 20.
+        assert_eq!(img.get_pixel(20, 19).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 30).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_line_fill_blends_below_curve() {
+        let config = Config {
+            style: Style::Line,
+            line_fill: true,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        let below = img.get_pixel(20, 30).0;
+        assert!(below[0] < 255 && below[0] > 0, "expected partial fill, got {:?}", below);
+        assert_eq!(img.get_pixel(20, 10).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn mix_colors_endpoints_and_midpoint() {
+        let a = [0, 0, 0, 255];
+        let b = [200, 100, 50, 255];
+        assert_eq!(mix_colors(a, b, 0.0), a);
+        assert_eq!(mix_colors(a, b, 1.0), b);
+        assert_eq!(mix_colors(a, b, 0.5), [100, 50, 25, 255]);
+        assert_eq!(mix_colors(a, b, 2.0), b);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_area_fills_to_baseline() {
+        let config = Config {
+            style: Style::Area,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        // Contour at y = 20, baseline at 38.
+        assert_eq!(img.get_pixel(20, 25).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 37).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 15).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(20, 39).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_area_gradient_changes_with_height() {
+        let config = Config {
+            style: Style::Area,
+            bar_color: [255, 0, 0, 255],
+            area_gradient: Some([0, 0, 255, 255]),
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        let top = img.get_pixel(20, 3).0;
+        let bottom = img.get_pixel(20, 37).0;
+        assert!(top[0] > 200 && top[2] < 50, "top {:?}", top);
+        assert!(bottom[2] > 200 && bottom[0] < 50, "bottom {:?}", bottom);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_area_mirror_is_centered() {
+        let config = Config {
+            style: Style::Area,
+            area_mirror: true,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        // Height 18 px centered on y = 20: rows 11..29.
+        assert_eq!(img.get_pixel(20, 12).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 27).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 8).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(20, 32).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn dot_geometry_size_mode_scales_radius() {
+        let config = Config {
+            style: Style::Dots,
+            ..test_config(40, 40, 40)
+        };
+        let layout = super::BarLayout::new(&config, 4);
+        let (cx, cy, r_full) = dot_geometry(&config, &layout, 0, 1.0);
+        let (_, _, r_half) = dot_geometry(&config, &layout, 0, 0.5);
+        assert!((cx - 4.5).abs() < 1e-4 && cy == 20.0);
+        assert!((r_full - 4.5).abs() < 1e-4);
+        assert!((r_half - 2.25).abs() < 1e-4);
+        assert_eq!(dot_geometry(&config, &layout, 0, f32::NAN).2, 0.0);
+    }
+
+    #[test]
+    fn dot_geometry_position_mode_rises_with_value() {
+        let config = Config {
+            style: Style::Dots,
+            dot_mode: DotMode::Position,
+            ..test_config(40, 40, 40)
+        };
+        let layout = super::BarLayout::new(&config, 4);
+        let (_, y_low, r) = dot_geometry(&config, &layout, 1, 0.0);
+        let (_, y_high, _) = dot_geometry(&config, &layout, 1, 1.0);
+        assert!(y_high < y_low);
+        // Dot stays inside the band (rows 2..38).
+        assert!(y_high - r >= 2.0 - 1e-4 && y_low + r <= 38.0 + 1e-4);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_dots_draws_circles() {
+        let config = Config {
+            style: Style::Dots,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0, 0.0, 1.0, 0.0]), None);
+        assert_eq!(img.get_pixel(4, 20).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(14, 20).0, [255, 255, 255, 255]);
+        // Outside the circle at the bar's corner.
+        assert_eq!(img.get_pixel(0, 16).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_dot_trail_fades_older_frames() {
+        let config = Config {
+            style: Style::Dots,
+            dot_mode: DotMode::Position,
+            ..test_config(40, 40, 40)
+        };
+        let mut history = BarHistory::new(2);
+        history.push(vec![1.0; 4]);
+        history.push(vec![0.0; 4]);
+        let frame = FrameData {
+            bar_heights: &[0.0; 4],
+            history: Some(&history),
+            ..FrameData::default()
+        };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        let (_, y_old, _) = dot_geometry(&config, &super::BarLayout::new(&config, 4), 0, 1.0);
+        let (_, y_now, _) = dot_geometry(&config, &super::BarLayout::new(&config, 4), 0, 0.0);
+        // Older frame at half opacity, current frame solid.
+        assert_eq!(img.get_pixel(4, y_old as u32).0, [128, 128, 128, 255]);
+        assert_eq!(img.get_pixel(4, y_now as u32).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bar_color_samples_colormap_by_position() {
+        let config = Config {
+            bar_colormap: Some(Colormap::Custom),
+            colormap_stops: vec![[0, 0, 0, 255], [200, 0, 0, 255]],
+            ..test_config(40, 40, 40)
+        };
+        assert_eq!(bar_color(&config, 0, 5, 1.0), [0, 0, 0, 255]);
+        assert_eq!(bar_color(&config, 2, 5, 1.0), [100, 0, 0, 255]);
+        assert_eq!(bar_color(&config, 4, 5, 1.0), [200, 0, 0, 255]);
+        assert_eq!(bar_color(&test_config(40, 40, 40), 3, 5, 1.0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bars_use_colormap() {
+        let config = Config {
+            bar_colormap: Some(Colormap::Rainbow),
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(img.get_pixel(4, 20).0, Colormap::Rainbow.sample(0.0, &[]));
+        assert_eq!(img.get_pixel(34, 20).0, Colormap::Rainbow.sample(1.0, &[]));
+    }
+
+    #[test]
+    fn bar_color_fades_toward_loud_color_with_height() {
+        let config = Config {
+            bar_color: [0, 255, 0, 255],
+            bar_color_loud: Some([255, 0, 0, 255]),
+            ..test_config(40, 40, 40)
+        };
+        assert_eq!(bar_color(&config, 0, 4, 0.0), [0, 255, 0, 255]);
+        assert_eq!(bar_color(&config, 0, 4, 0.5), [128, 128, 0, 255]);
+        assert_eq!(bar_color(&config, 0, 4, 1.0), [255, 0, 0, 255]);
+        assert_eq!(bar_color(&config, 0, 4, f32::NAN), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bars_color_by_height() {
+        let config = Config {
+            bar_color: [0, 255, 0, 255],
+            bar_color_loud: Some([255, 0, 0, 255]),
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0, 0.25, 0.0, 0.0]), None);
+        assert_eq!(img.get_pixel(4, 20).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(14, 20).0, [64, 191, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_glow_tints_background_near_bars() {
+        let config = Config {
+            bar_color: [255, 0, 0, 255],
+            bg_color: [0, 0, 0, 255],
+            glow: Some(Glow { radius: 6, intensity: 2.0 }),
+            ..test_config(40, 40, 20)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // Bars themselves are drawn on top, unchanged.
+        assert_eq!(img.get_pixel(4, 30).0, [255, 0, 0, 255]);
+        // Just above the band the background picks up a red halo.
+        let halo = img.get_pixel(4, 19).0;
+        assert!(halo[0] > 0 && halo[1] == 0, "halo = {:?}", halo);
+        // Far from the band it stays untouched.
+        assert_eq!(img.get_pixel(4, 2).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_shadow_falls_right_of_bars() {
+        let config = Config {
+            bar_color: [255, 0, 0, 255],
+            shadow: Some(Shadow { offset: 3, blur: 0, color: [0, 0, 0, 255] }),
+            ..test_config(40, 40, 20)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0, 0.0]), None);
+        let right_edge = (0..40).filter(|&x| img.get_pixel(x, 30).0 == [255, 0, 0, 255]).max().unwrap();
+        assert_eq!(img.get_pixel(right_edge + 2, 30).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(right_edge + 5, 30).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bar_stroke_outlines_bars() {
+        let config = Config {
+            bar_color: [0, 0, 255, 255],
+            bar_stroke: Some(BarStroke { color: [255, 0, 0, 255], width: 2 }),
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // Bar 0 spans x 0..9, rows 2..38.
+        assert_eq!(img.get_pixel(0, 20).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 20).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 20).0, [0, 0, 255, 255]);
+        assert_eq!(img.get_pixel(4, 2).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bar_stroke_only_leaves_interior() {
+        let config = Config {
+            bar_stroke: Some(BarStroke { color: [255, 0, 0, 255], width: 1 }),
+            bar_stroke_only: true,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(img.get_pixel(0, 20).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 20).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bar_layout_uses_configured_gap_and_radius() {
+        let config = Config {
+            bar_gap: 4,
+            bar_radius: Some(0),
+            ..test_config(40, 40, 40)
+        };
+        let layout = super::BarLayout::new(&config, 4);
+        // (40 - 3 * 4) / 4 = 7 px bars, strip 40 px wide.
+        assert_eq!(layout.bar_width, 7);
+        assert_eq!(layout.bar_x(1) - layout.bar_x(0), 11);
+        assert_eq!(layout.radius, 0);
+        assert_eq!(super::BarLayout::new(&test_config(40, 40, 40), 4).radius, 4);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_square_bars_fill_corners() {
+        let config = Config {
+            bar_radius: Some(0),
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // Top-left corner pixel of bar 0 is filled when not rounded.
+        assert_eq!(img.get_pixel(0, 2).0, [0, 0, 0, 255]);
+        let rounded = draw_spectrum_frame(&test_config(40, 40, 40), &FrameData::bars(&[1.0; 4]), None);
+        assert_eq!(rounded.get_pixel(0, 2).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bars_bottom_anchor_sits_on_baseline() {
+        let config = Config {
+            bar_anchor: BarAnchor::Bottom,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        // Usable rows 2..38; an 18 px bar occupies rows 20..38.
+        assert_eq!(img.get_pixel(4, 36).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 22).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 15).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_bars_top_anchor_hangs_from_top() {
+        let config = Config {
+            bar_anchor: BarAnchor::Top,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5; 4]), None);
+        // An 18 px bar occupies rows 2..20.
+        assert_eq!(img.get_pixel(4, 4).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 18).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 25).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn shape_triangle_points_toward_tip() {
+        let config = Config {
+            bar_shape: BarShape::Triangle,
+            ..Config::default()
+        };
+        let up = Shape::bar(&config, (0, 0, 10, 10), 0, Tip::Up);
+        assert!(up.contains(5, 5) && up.contains(0, 9));
+        assert!(!up.contains(0, 1) && !up.contains(10, 9));
+        let down = Shape::bar(&config, (0, 0, 10, 10), 0, Tip::Down);
+        assert!(down.contains(0, 0) && !down.contains(0, 8));
+        let right = Shape::bar(&config, (0, 0, 10, 10), 0, Tip::Right);
+        assert!(right.contains(0, 0) && !right.contains(8, 0));
+        // A 1 px stroke leaves the middle of the triangle but not its slanted edge.
+        assert!(up.contains_inset(5, 7, 1) && !up.contains_inset(1, 8, 1));
+    }
+
+    #[test]
+    fn shape_diamond_and_capsule() {
+        let diamond = Shape::bar(
+            &Config {
+                bar_shape: BarShape::Diamond,
+                ..Config::default()
+            },
+            (0, 0, 10, 10),
+            0,
+            Tip::Up,
+        );
+        assert!(diamond.contains(5, 5) && diamond.contains(2, 5));
+        assert!(!diamond.contains(1, 1) && !diamond.contains(8, 8));
+        let capsule = Shape::bar(
+            &Config {
+                bar_shape: BarShape::Capsule,
+                ..Config::default()
+            },
+            (0, 0, 8, 30),
+            2,
+            Tip::Up,
+        );
+        assert_eq!(capsule.radius, 4);
+    }
+
+    #[cfg(any(feature = "runner", feature = "script"))]
+    #[test]
+    fn rounded_rects_keep_their_radius_inside_the_box() {
+        assert_eq!(Shape::rounded_rect((0, 0, 8, 30), 10).radius, 4);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_triangle_bars_narrow_to_top() {
+        let config = Config {
+            bar_shape: BarShape::Triangle,
+            bar_anchor: BarAnchor::Bottom,
+            ..test_config(40, 40, 40)
+        };
+        // One 40 px wide bar filling rows 2..38: wide at the bottom, a point at the top.
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0]), None);
+        assert_eq!(img.get_pixel(2, 36).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 4).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(5, 10).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_led_stacks_segments_from_bottom() {
+        let config = Config {
+            style: Style::Led,
+            ..test_config(40, 40, 40)
+        };
+        // Usable rows 2..38 hold four 6 px segments with 2 px gaps: 32..38, 24..30, 16..22, 8..14.
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.5]), None);
+        assert_eq!(img.get_pixel(20, 35).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 31).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(20, 27).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 19).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_vectorscope_plots_mono_vertically_and_side_across() {
+        let config = Config { style: Style::Vectorscope, ..test_config(40, 40, 40) };
+        // Band center (20, 20), square half-size 18.
+        let mono = [0.5f32; 4];
+        let frame = FrameData { stereo_samples: Some((&mono, &mono)), ..FrameData::default() };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        let plotted = img.get_pixel(20, 11).0;
+        assert!(plotted[0] < 255, "plotted = {:?}", plotted);
+        // Repeated samples build up toward the full color.
+        assert!(plotted[0] < 100, "plotted = {:?}", plotted);
+        assert_eq!(img.get_pixel(20, 29).0, [255, 255, 255, 255]);
+
+        let (left, right) = ([1.0f32], [-1.0f32]);
+        let frame = FrameData { stereo_samples: Some((&left, &right)), ..FrameData::default() };
+        let img = draw_spectrum_frame(&config, &frame, None);
+        assert_ne!(img.get_pixel(38, 20).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_band_opacity_blends_over_background() {
+        let config = Config { opacity: 0.5, ..test_config(40, 40, 40) };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0]), None);
+        assert_eq!(img.get_pixel(20, 20).0, [128, 128, 128, 255]);
+        let opaque = draw_spectrum_frame(&test_config(40, 40, 40), &FrameData::bars(&[1.0]), None);
+        assert_eq!(opaque.get_pixel(20, 20).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_min_height_keeps_silent_bars_visible() {
+        let config = Config {
+            bar_anchor: BarAnchor::Bottom,
+            bar_min_height: 4,
+            ..test_config(40, 40, 40)
+        };
+        // Usable rows 2..38: a silent bar is a 4 px stub on the bottom, a loud one is unaffected.
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[0.0, 1.0]), None);
+        assert_eq!(img.get_pixel(10, 36).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(10, 33).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(30, 5).0, [0, 0, 0, 255]);
+
+        let led = Config { style: Style::Led, ..config.clone() };
+        let img = draw_spectrum_frame(&led, &FrameData::bars(&[0.0]), None);
+        assert_eq!(img.get_pixel(20, 35).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(20, 27).0, [255, 255, 255, 255]);
+
+        // Mirror halves get half of the minimum each, around the center row 20.
+        let mirror = Config { style: Style::Mirror, ..config };
+        let img = draw_spectrum_frame(&mirror, &FrameData::bars(&[0.0]), None);
+        assert_eq!(img.get_pixel(20, 18).0, [0, 0, 0, 255]);
+        assert_ne!(img.get_pixel(20, 22).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(20, 16).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(20, 24).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_led_ramp_colors_by_height() {
+        let config = Config {
+            style: Style::Led,
+            led_ramp: true,
+            ..test_config(40, 40, 40)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0]), None);
+        assert_eq!(img.get_pixel(20, 35).0, [40, 200, 70, 255]);
+        assert_eq!(img.get_pixel(20, 19).0, [240, 200, 40, 255]);
+        assert_eq!(img.get_pixel(20, 11).0, [230, 40, 40, 255]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn particle_emitters_sit_on_loudest_bar_tops() {
+        use super::particle_emitters;
+
+        let config = Config {
+            bar_anchor: BarAnchor::Bottom,
+            ..test_config(40, 40, 40)
+        };
+        // Usable rows 2..38: a bar at 0.9 tops out 0.9 * 36 px above row 38.
+        let emitters = particle_emitters(&config, &[0.2, 0.9, 0.5, 0.0], 2);
+        assert_eq!(emitters.len(), 2);
+        assert!((emitters[0].y - 5.6).abs() < 1e-3, "y = {}", emitters[0].y);
+        assert!((emitters[1].y - 20.0).abs() < 1e-3, "y = {}", emitters[1].y);
+        assert!(emitters[0].x < emitters[1].x);
+
+        let silent = particle_emitters(&config, &[0.0; 4], 2);
+        assert!(silent.is_empty());
+        let waveform = Config { style: Style::Waveform, ..config };
+        assert!(particle_emitters(&waveform, &[0.9; 4], 2).is_empty());
+    }
+
+    #[test]
+    fn draw_spectrum_frame_particles_fade_with_life() {
+        let spark = |x, life| Particle { x, y: 5.0, vx: 0.0, vy: 0.0, life, color: [255, 0, 0, 255], size: 2.0 };
+        let particles = [spark(5.0, 1.0), spark(15.0, 0.5)];
+        let frame = FrameData { particles: &particles, ..FrameData::bars(&[]) };
+        let img = draw_spectrum_frame(&test_config(40, 40, 10), &frame, None);
+        assert_eq!(img.get_pixel(5, 5).0, [255, 0, 0, 255]);
+        let faded = img.get_pixel(15, 5).0;
+        assert!(faded[1] > 100 && faded[1] < 155, "faded = {:?}", faded);
+        assert_eq!(img.get_pixel(25, 5).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn draw_spectrum_frame_reflection_below_band() {
+        let config = Config {
+            spectrum_y_from_bottom: 20,
+            bar_anchor: BarAnchor::Bottom,
+            reflection: Some(Reflection { opacity: 0.5, height: 10 }),
+            ..test_config(40, 60, 20)
+        };
+        let img = draw_spectrum_frame(&config, &FrameData::bars(&[1.0; 4]), None);
+        // Bars cover rows 22..38 and the reflection starts at the band's bottom (row 40); row 42 mirrors row 37.
+        assert_eq!(img.get_pixel(4, 30).0, [0, 0, 0, 255]);
+        let reflected = img.get_pixel(4, 42).0;
+        assert!(reflected[0] > 0 && reflected[0] < 255, "reflected = {:?}", reflected);
+        assert_eq!(img.get_pixel(4, 55).0, [255, 255, 255, 255]);
+    }
 }
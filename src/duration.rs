@@ -0,0 +1,92 @@
+//! Times and durations as people write them (`90`, `1:30`, `00:01:30.500`, `1m30s`), shared by every option that
+//! takes one and by chapter lists
+
+/// Parse a time or duration in seconds: plain seconds (`90`, `90.5`), a clock (`1:30`, `00:01:30.500`) or units
+/// (`1m30s`, `1h5m`, `2.5s`, `750ms`).
+pub fn parse_duration(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let seconds = if s.contains(':') {
+        parse_clock(s)
+    } else if s.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        parse_units(s)
+    } else {
+        s.parse().ok().filter(|v: &f32| *v >= 0.0 && v.is_finite())
+    };
+    seconds.ok_or_else(|| format!("invalid time {:?} (expected seconds, mm:ss, h:mm:ss or units like 1m30s)", s))
+}
+
+/// `parse_duration`, for lengths that can't be zero.
+pub fn parse_positive_duration(s: &str) -> Result<f32, String> {
+    let seconds = parse_duration(s)?;
+    if seconds > 0.0 { Ok(seconds) } else { Err(format!("time must be longer than zero, got {:?}", s)) }
+}
+
+/// `mm:ss` or `h:mm:ss`, the seconds optionally with a fraction.
+pub fn parse_clock(s: &str) -> Option<f32> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let (seconds, minutes) = parts.split_last()?;
+    let seconds: f32 = seconds.parse().ok().filter(|s: &f32| *s >= 0.0 && s.is_finite())?;
+    // Hours and minutes too large to count in minutes are rejected rather than wrapped.
+    let minutes = minutes.iter().try_fold(0u32, |acc, part| acc.checked_mul(60)?.checked_add(part.parse().ok()?))?;
+    Some(minutes as f32 * 60.0 + seconds)
+}
+
+/// Numbers with units, largest first and each at most once: `1h2m3.5s`, `1m30s`, `750ms`.
+fn parse_units(s: &str) -> Option<f32> {
+    const UNITS: [(&str, f64); 4] = [("h", 3600.0), ("m", 60.0), ("s", 1.0), ("ms", 0.001)];
+    let (mut rest, mut seconds, mut next_unit) = (s, 0.0, 0);
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let unit_len = rest[number_len..].find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len() - number_len);
+        let (number, unit) = (&rest[..number_len], &rest[number_len..number_len + unit_len]);
+        let index = next_unit + UNITS[next_unit..].iter().position(|&(name, _)| name == unit)?;
+        seconds += number.parse::<f64>().ok()? * UNITS[index].1;
+        (rest, next_unit) = (&rest[number_len + unit_len..], index + 1);
+    }
+    Some(seconds as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_clock, parse_duration, parse_positive_duration};
+
+    #[test]
+    fn seconds_clocks_and_units_agree() {
+        for s in ["90", "1:30", "01:30", "0:01:30", "1m30s", "90s", "1.5m", "90000ms"] {
+            assert_eq!(parse_duration(s).unwrap(), 90.0, "{}", s);
+        }
+        assert_eq!(parse_duration("00:01:30.500").unwrap(), 90.5);
+        assert_eq!(parse_duration("1h2m3.5s").unwrap(), 3723.5);
+        assert_eq!(parse_duration("750ms").unwrap(), 0.75);
+        assert_eq!(parse_duration(" 42.5 ").unwrap(), 42.5);
+        assert_eq!(parse_duration("1:02:03").unwrap(), 3723.0);
+        assert_eq!(parse_duration("1:05").unwrap(), 65.0);
+    }
+
+    #[test]
+    fn malformed_times_are_rejected() {
+        for s in ["", "1:2:3:4", "1:xx", "-5", "1.5:00", "1m30", "30s1m", "1m1m", "5x", "m", "1.2.3s", "inf"] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+        assert!(parse_duration("1:xx").unwrap_err().contains("expected seconds, mm:ss"));
+        assert!(parse_positive_duration("0s").unwrap_err().contains("longer than zero"));
+        assert_eq!(parse_positive_duration("2.5").unwrap(), 2.5);
+    }
+
+    #[test]
+    fn clocks_need_minutes_and_seconds() {
+        assert_eq!(parse_clock("3:25"), Some(205.0));
+        assert_eq!(parse_clock("1:00:00.25"), Some(3600.25));
+        assert_eq!(parse_clock("42"), None);
+    }
+
+    #[test]
+    fn clocks_too_long_to_count_are_rejected() {
+        assert_eq!(parse_clock("100000000:0:0"), None);
+        assert_eq!(parse_clock("4294967295:00"), Some(4294967295.0 * 60.0));
+        assert!(parse_duration("100000000:0:0").is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! Post-processing effects applied to rendered layers (glow, shadow, reflection, rotation) and background images (dim)
+
+use image::{ImageBuffer, Rgba};
+use serde::{Deserialize, Serialize};
+
+/// Soft halo around the spectrum: a Gaussian blur of the spectrum layer composited under it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Glow {
+    /// Blur radius (pixels); the Gaussian's sigma is half of this.
+    pub radius: u32,
+    /// Opacity multiplier for the halo (1.0 = the blurred layer's own alpha; larger values brighten it).
+    pub intensity: f32,
+}
+
+/// Soft drop shadow: the layer's silhouette, offset, blurred and filled with one color, composited under it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shadow {
+    /// Shift of the shadow right and down from the layer (pixels; negative values go left and up).
+    pub offset: i32,
+    /// Blur radius (pixels); the Gaussian's sigma is half of this. 0 gives a hard-edged shadow.
+    pub blur: u32,
+    /// Shadow color as RGBA; its alpha scales the shadow's opacity.
+    pub color: [u8; 4],
+}
+
+/// Flipped, fading copy of the spectrum below its baseline, like a glossy floor.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reflection {
+    /// Opacity of the reflection right at the baseline (0.0–1.0); it fades linearly to 0 over `height`.
+    pub opacity: f32,
+    /// How far the reflection extends below the baseline (pixels).
+    pub height: u32,
+}
+
+/// Composite `layer` (straight alpha) flipped around row `baseline` onto the rows below it, fading out over `reflection.height` rows.
+pub fn apply_reflection(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layer: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    baseline: u32,
+    reflection: Reflection,
+) {
+    let (width, height) = img.dimensions();
+    for dy in 0..reflection.height {
+        let (Some(src_y), dst_y) = (baseline.checked_sub(dy + 1), baseline + dy) else {
+            break;
+        };
+        if dst_y >= height {
+            break;
+        }
+        let fade = reflection.opacity * (1.0 - dy as f32 / reflection.height as f32);
+        for x in 0..width {
+            let p = layer.get_pixel(x, src_y).0;
+            if p[3] == 0 {
+                continue;
+            }
+            let opacity = (fade * p[3] as f32 / 255.0).clamp(0.0, 1.0);
+            let dst = img.get_pixel(x, dst_y).0;
+            img.put_pixel(x, dst_y, over(dst, [p[0], p[1], p[2]], opacity));
+        }
+    }
+}
+
+/// Blur `layer` (straight alpha; transparent where nothing was drawn) and composite it over `img` as a glow.
+pub fn apply_glow(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, layer: &ImageBuffer<Rgba<u8>, Vec<u8>>, glow: Glow) {
+    let Some((x0, y0, x1, y1)) = opaque_bounds(layer) else {
+        return;
+    };
+    let (width, height) = layer.dimensions();
+    let r = glow.radius;
+    // Only the drawn region plus the blur radius can receive any glow.
+    let (x0, y0) = (x0.saturating_sub(r), y0.saturating_sub(r));
+    let (x1, y1) = ((x1 + r + 1).min(width), (y1 + r + 1).min(height));
+    let (w, h) = ((x1 - x0) as usize, (y1 - y0) as usize);
+
+    // Premultiplied RGBA so transparent pixels do not darken the halo.
+    let mut buf = vec![[0.0f32; 4]; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let p = layer.get_pixel(x0 + x as u32, y0 + y as u32).0;
+            let a = p[3] as f32 / 255.0;
+            buf[y * w + x] = [p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a, a];
+        }
+    }
+    let kernel = gaussian_kernel(r);
+    let buf = blur_pass(&buf, w, h, &kernel, true);
+    let buf = blur_pass(&buf, w, h, &kernel, false);
+
+    for y in 0..h {
+        for x in 0..w {
+            let [r, g, b, a] = buf[y * w + x];
+            if a <= 0.0 {
+                continue;
+            }
+            let opacity = (a * glow.intensity).clamp(0.0, 1.0);
+            let color = [
+                (r / a).round().clamp(0.0, 255.0) as u8,
+                (g / a).round().clamp(0.0, 255.0) as u8,
+                (b / a).round().clamp(0.0, 255.0) as u8,
+            ];
+            let (px, py) = (x0 + x as u32, y0 + y as u32);
+            let dst = img.get_pixel(px, py).0;
+            img.put_pixel(px, py, over(dst, color, opacity));
+        }
+    }
+}
+
+/// Composite the shadow of `layer` (straight alpha), placed with its top-left corner at `origin` in `img`, onto `img`.
+/// Draw the layer itself afterwards so the shadow ends up beneath it.
+pub fn apply_shadow(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layer: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    origin: (i64, i64),
+    shadow: Shadow,
+) {
+    let Some((x0, y0, x1, y1)) = opaque_bounds(layer) else {
+        return;
+    };
+    let r = shadow.blur as i64;
+    // The blurred silhouette spreads up to the radius past the drawn region, which may lie outside the layer.
+    let (x0, y0) = (x0 as i64 - r, y0 as i64 - r);
+    let (w, h) = ((x1 as i64 + r + 1 - x0) as usize, (y1 as i64 + r + 1 - y0) as usize);
+    let (layer_w, layer_h) = layer.dimensions();
+
+    let mut buf = vec![[0.0f32; 4]; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let (lx, ly) = (x0 + x as i64, y0 + y as i64);
+            if lx >= 0 && ly >= 0 && lx < layer_w as i64 && ly < layer_h as i64 {
+                buf[y * w + x][3] = layer.get_pixel(lx as u32, ly as u32).0[3] as f32 / 255.0;
+            }
+        }
+    }
+    if r > 0 {
+        let kernel = gaussian_kernel(shadow.blur);
+        buf = blur_pass(&buf, w, h, &kernel, true);
+        buf = blur_pass(&buf, w, h, &kernel, false);
+    }
+
+    let (width, height) = img.dimensions();
+    let color = [shadow.color[0], shadow.color[1], shadow.color[2]];
+    let strength = shadow.color[3] as f32 / 255.0;
+    for y in 0..h {
+        for x in 0..w {
+            let a = buf[y * w + x][3];
+            let px = origin.0 + shadow.offset as i64 + x0 + x as i64;
+            let py = origin.1 + shadow.offset as i64 + y0 + y as i64;
+            if a <= 0.0 || px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                continue;
+            }
+            let (px, py) = (px as u32, py as u32);
+            let dst = img.get_pixel(px, py).0;
+            img.put_pixel(px, py, over(dst, color, (a * strength).clamp(0.0, 1.0)));
+        }
+    }
+}
+
+/// Composite an opaque `color` at `opacity` over `dst` (source-over, straight alpha), so effects also work on
+/// transparent layers.
+fn over(dst: [u8; 4], color: [u8; 3], opacity: f32) -> Rgba<u8> {
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = opacity + dst_a * (1.0 - opacity);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let mix = |d: u8, c: u8| ((c as f32 * opacity + d as f32 * dst_a * (1.0 - opacity)) / out_a).round() as u8;
+    Rgba([
+        mix(dst[0], color[0]),
+        mix(dst[1], color[1]),
+        mix(dst[2], color[2]),
+        (out_a * 255.0).round() as u8,
+    ])
+}
+
+/// Rotate `layer` (straight alpha) by `degrees` clockwise around `center` into a new image of the same size,
+/// sampling bilinearly. Parts rotated in from outside the layer are transparent.
+pub fn rotate_layer(
+    layer: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    center: (f32, f32),
+    degrees: f32,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (w, h) = layer.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    ImageBuffer::from_fn(w, h, |x, y| {
+        // Inverse rotation: find the source point that lands on this pixel's center.
+        let (dx, dy) = (x as f32 + 0.5 - center.0, y as f32 + 0.5 - center.1);
+        let sx = cos * dx + sin * dy + center.0 - 0.5;
+        let sy = -sin * dx + cos * dy + center.1 - 0.5;
+        sample_bilinear(layer, sx, sy)
+    })
+}
+
+/// Bilinear sample of `layer` at pixel coordinates (`x`, `y`), interpolating premultiplied colors.
+fn sample_bilinear(layer: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: f32, y: f32) -> Rgba<u8> {
+    let (w, h) = layer.dimensions();
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut acc = [0.0f32; 4];
+    let taps = [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ];
+    for (ox, oy, weight) in taps {
+        let (px, py) = (x0 as i64 + ox, y0 as i64 + oy);
+        if weight <= 0.0 || px < 0 || py < 0 || px >= w as i64 || py >= h as i64 {
+            continue;
+        }
+        let p = layer.get_pixel(px as u32, py as u32).0;
+        let a = p[3] as f32 / 255.0 * weight;
+        acc[0] += p[0] as f32 * a;
+        acc[1] += p[1] as f32 * a;
+        acc[2] += p[2] as f32 * a;
+        acc[3] += a;
+    }
+    if acc[3] <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |v: f32| (v / acc[3]).round().clamp(0.0, 255.0) as u8;
+    Rgba([channel(acc[0]), channel(acc[1]), channel(acc[2]), (acc[3] * 255.0).round() as u8])
+}
+
+/// Darken `img` toward black by `amount` (0.0 = unchanged, 1.0 = black), keeping alpha.
+#[cfg(feature = "runner")]
+pub fn dim_image(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, amount: f32) {
+    let keep = 1.0 - amount.clamp(0.0, 1.0);
+    for p in img.pixels_mut() {
+        for c in &mut p.0[..3] {
+            *c = (*c as f32 * keep).round() as u8;
+        }
+    }
+}
+
+/// Inclusive bounding box (x0, y0, x1, y1) of the pixels with non-zero alpha, or None if the layer is empty.
+fn opaque_bounds(layer: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, p) in layer.enumerate_pixels() {
+        if p.0[3] == 0 {
+            continue;
+        }
+        bounds = Some(match bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+            None => (x, y, x, y),
+        });
+    }
+    bounds
+}
+
+/// Normalized Gaussian weights for offsets `-radius..=radius` (sigma = radius / 2).
+fn gaussian_kernel(radius: u32) -> Vec<f32> {
+    if radius == 0 {
+        return vec![1.0];
+    }
+    let sigma = radius as f32 / 2.0;
+    let weights: Vec<f32> = (-(radius as i32)..=radius as i32)
+        .map(|d| (-(d * d) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / sum).collect()
+}
+
+/// One separable blur pass over a `w` x `h` buffer, horizontal or vertical. Samples outside the buffer count as transparent.
+fn blur_pass(buf: &[[f32; 4]], w: usize, h: usize, kernel: &[f32], horizontal: bool) -> Vec<[f32; 4]> {
+    let radius = (kernel.len() / 2) as isize;
+    let mut out = vec![[0.0f32; 4]; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let mut acc = [0.0f32; 4];
+            for (k, &weight) in kernel.iter().enumerate() {
+                let offset = k as isize - radius;
+                let (sx, sy) = if horizontal {
+                    (x as isize + offset, y as isize)
+                } else {
+                    (x as isize, y as isize + offset)
+                };
+                if sx < 0 || sy < 0 || sx >= w as isize || sy >= h as isize {
+                    continue;
+                }
+                let p = buf[sy as usize * w + sx as usize];
+                for (a, v) in acc.iter_mut().zip(p) {
+                    *a += v * weight;
+                }
+            }
+            out[y * w + x] = acc;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_glow, apply_reflection, apply_shadow, gaussian_kernel, opaque_bounds, over, rotate_layer, Glow,
+        Reflection, Shadow,
+    };
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn gaussian_kernel_is_normalized_and_symmetric() {
+        let k = gaussian_kernel(4);
+        assert_eq!(k.len(), 9);
+        assert!((k.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(k[0], k[8]);
+        assert!(k[4] > k[3]);
+        assert_eq!(gaussian_kernel(0), vec![1.0]);
+    }
+
+    #[test]
+    fn opaque_bounds_finds_drawn_pixels() {
+        let mut layer = ImageBuffer::new(10, 10);
+        assert_eq!(opaque_bounds(&layer), None);
+        layer.put_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        layer.put_pixel(6, 5, Rgba([255, 0, 0, 255]));
+        assert_eq!(opaque_bounds(&layer), Some((2, 3, 6, 5)));
+    }
+
+    #[test]
+    fn apply_glow_spreads_color_around_drawn_pixels() {
+        let mut img = ImageBuffer::from_pixel(21, 21, Rgba([0, 0, 0, 255]));
+        let mut layer = ImageBuffer::new(21, 21);
+        layer.put_pixel(10, 10, Rgba([255, 0, 0, 255]));
+        apply_glow(&mut img, &layer, Glow { radius: 4, intensity: 4.0 });
+        let near = img.get_pixel(12, 10).0;
+        let far = img.get_pixel(20, 20).0;
+        assert!(near[0] > 0 && near[1] == 0 && near[2] == 0, "near = {:?}", near);
+        assert_eq!(far, [0, 0, 0, 255]);
+        // The halo fades with distance from the drawn pixel.
+        assert!(img.get_pixel(10, 10).0[0] > img.get_pixel(12, 10).0[0]);
+    }
+
+    #[test]
+    fn apply_glow_with_empty_layer_is_noop() {
+        let mut img = ImageBuffer::from_pixel(5, 5, Rgba([1, 2, 3, 255]));
+        let layer = ImageBuffer::new(5, 5);
+        apply_glow(&mut img, &layer, Glow { radius: 2, intensity: 1.0 });
+        assert!(img.pixels().all(|p| p.0 == [1, 2, 3, 255]));
+    }
+
+    #[test]
+    fn apply_shadow_offsets_silhouette_in_shadow_color() {
+        let mut img = ImageBuffer::from_pixel(20, 20, Rgba([255, 255, 255, 255]));
+        let mut layer = ImageBuffer::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                layer.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+        let shadow = Shadow { offset: 3, blur: 0, color: [0, 0, 0, 128] };
+        apply_shadow(&mut img, &layer, (5, 5), shadow);
+        // Hard shadow covers (8..12, 8..12) at half strength; the layer's own spot stays clear above it.
+        assert_eq!(img.get_pixel(10, 10).0, [127, 127, 127, 255]);
+        assert_eq!(img.get_pixel(6, 6).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(12, 12).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn apply_shadow_blur_spreads_past_layer_edges() {
+        let mut img = ImageBuffer::from_pixel(20, 20, Rgba([255, 255, 255, 255]));
+        let layer = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 255, 255]));
+        apply_shadow(&mut img, &layer, (8, 8), Shadow { offset: 0, blur: 4, color: [0, 0, 0, 255] });
+        // The blurred silhouette reaches beyond the 2x2 layer and fades with distance.
+        let near = img.get_pixel(7, 8).0[0];
+        let far = img.get_pixel(5, 8).0[0];
+        assert!(near < far && far < 255, "near = {}, far = {}", near, far);
+        assert_eq!(img.get_pixel(0, 0).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn apply_reflection_mirrors_and_fades_below_baseline() {
+        let mut img = ImageBuffer::from_pixel(4, 20, Rgba([0, 0, 0, 255]));
+        let mut layer = ImageBuffer::new(4, 20);
+        for y in 6..10 {
+            layer.put_pixel(1, y, Rgba([200, 100, 0, 255]));
+        }
+        apply_reflection(&mut img, &layer, 10, Reflection { opacity: 0.5, height: 8 });
+        // Row 9 reflects to row 10 at full reflection opacity.
+        assert_eq!(img.get_pixel(1, 10).0, [100, 50, 0, 255]);
+        // Further rows fade out.
+        assert!(img.get_pixel(1, 12).0[0] < 100 && img.get_pixel(1, 12).0[0] > 0);
+        // Row 5 (transparent in the layer) reflects to row 14: untouched.
+        assert_eq!(img.get_pixel(1, 14).0, [0, 0, 0, 255]);
+        // Above the baseline nothing changes.
+        assert_eq!(img.get_pixel(1, 8).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn apply_reflection_clips_at_frame_bottom() {
+        let mut img = ImageBuffer::from_pixel(2, 4, Rgba([0, 0, 0, 255]));
+        let layer = ImageBuffer::from_pixel(2, 4, Rgba([255, 255, 255, 255]));
+        apply_reflection(&mut img, &layer, 3, Reflection { opacity: 1.0, height: 10 });
+        assert_eq!(img.get_pixel(0, 3).0, [255, 255, 255, 255]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn dim_image_scales_rgb_and_keeps_alpha() {
+        use super::dim_image;
+
+        let mut img = ImageBuffer::from_pixel(2, 2, Rgba([200, 100, 50, 128]));
+        dim_image(&mut img, 0.5);
+        assert_eq!(img.get_pixel(0, 0).0, [100, 50, 25, 128]);
+        dim_image(&mut img, 1.0);
+        assert_eq!(img.get_pixel(1, 1).0, [0, 0, 0, 128]);
+    }
+
+    #[test]
+    fn over_composites_onto_transparent_and_opaque() {
+        assert_eq!(over([0, 0, 0, 0], [200, 100, 0], 0.5).0, [200, 100, 0, 128]);
+        assert_eq!(over([0, 0, 0, 255], [200, 100, 0], 0.5).0, [100, 50, 0, 255]);
+    }
+
+    #[test]
+    fn apply_glow_on_transparent_layer_is_visible() {
+        let mut img = ImageBuffer::new(21, 21);
+        let mut layer = ImageBuffer::new(21, 21);
+        layer.put_pixel(10, 10, Rgba([255, 0, 0, 255]));
+        apply_glow(&mut img, &layer, Glow { radius: 4, intensity: 4.0 });
+        let near = img.get_pixel(12, 10).0;
+        assert_eq!(near[0], 255);
+        assert!(near[3] > 0);
+    }
+
+    #[test]
+    fn rotate_layer_quarter_turn_moves_pixels_clockwise() {
+        let mut layer = ImageBuffer::new(11, 11);
+        // A pixel right of the center ends up below it after a 90° clockwise turn.
+        layer.put_pixel(8, 5, Rgba([0, 255, 0, 255]));
+        let out = rotate_layer(&layer, (5.5, 5.5), 90.0);
+        assert_eq!(out.get_pixel(5, 8).0, [0, 255, 0, 255]);
+        assert_eq!(out.get_pixel(8, 5).0[3], 0);
+        // Zero rotation is the identity.
+        assert_eq!(rotate_layer(&layer, (5.5, 5.5), 0.0), layer);
+    }
+}
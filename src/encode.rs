@@ -0,0 +1,11 @@
+//! Writing the video: the audio as a temp WAV, and frames through a `VideoEncoder` (usually piped into ffmpeg)
+
+pub use crate::output::{
+    AudioCodec, AudioSettings, Codec, Container, EncodeSettings, FrameDir, HwAccel, VideoEncoder, VideoSettings,
+    X264Preset,
+};
+#[cfg(feature = "runner")]
+pub use crate::output::Backend;
+#[cfg(feature = "ffmpeg")]
+pub use crate::output::{Encoder, PngSequence};
+pub use crate::wav::{write_wav, WavStream};
@@ -0,0 +1,288 @@
+//! Waveform envelope (min/max per time slice) for waveform-style visualizations, and the per-frame sample windows
+//! the waveform styles draw
+
+/// Min/max of the PCM within one time slice.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EnvelopePoint {
+    /// Smallest sample in the slice (-1.0 to 0.0 for in-range PCM).
+    pub min: f32,
+    /// Largest sample in the slice (0.0 to 1.0 for in-range PCM).
+    pub max: f32,
+}
+
+impl EnvelopePoint {
+    /// Larger of |min| and |max|.
+    pub fn peak(&self) -> f32 {
+        self.max.max(-self.min)
+    }
+}
+
+/// Compute `num_points` min/max envelope points at `points_per_sec` resolution.
+/// Point `i` covers samples `[i * sample_rate / points_per_sec, (i + 1) * sample_rate / points_per_sec)`.
+/// Points past the end of the input, and non-finite samples, are treated as silence.
+pub fn compute_envelope(
+    samples: &[f32],
+    sample_rate: u32,
+    points_per_sec: f64,
+    num_points: usize,
+) -> Vec<EnvelopePoint> {
+    let mut stream = EnvelopeStream::new(sample_rate, points_per_sec);
+    stream.push(samples);
+    stream.finish(num_points)
+}
+
+/// One envelope point per video frame (`fps` points per second).
+pub fn compute_frame_envelopes(
+    samples: &[f32],
+    sample_rate: u32,
+    fps: u32,
+    total_frames: usize,
+) -> Vec<EnvelopePoint> {
+    compute_envelope(samples, sample_rate, fps as f64, total_frames)
+}
+
+/// `compute_envelope` for samples that arrive in chunks: each point is finished once the stream passes its end.
+pub struct EnvelopeStream {
+    /// Samples per point; not finite for a zero sample rate or resolution, which leaves every point silent.
+    samples_per_point: f64,
+    /// Samples pushed so far, and where the current point ends.
+    position: usize,
+    end: usize,
+    current: EnvelopePoint,
+    points: Vec<EnvelopePoint>,
+}
+
+impl EnvelopeStream {
+    pub fn new(sample_rate: u32, points_per_sec: f64) -> Self {
+        let samples_per_point = sample_rate as f64 / points_per_sec;
+        let samples_per_point = if samples_per_point > 0.0 { samples_per_point } else { f64::INFINITY };
+        let end = if samples_per_point.is_finite() { samples_per_point as usize } else { usize::MAX };
+        Self { samples_per_point, position: 0, end, current: EnvelopePoint::default(), points: Vec::new() }
+    }
+
+    /// Append decoded samples, finishing every point that is now complete.
+    pub fn push(&mut self, samples: &[f32]) {
+        for &s in samples {
+            while self.position >= self.end {
+                self.close_point();
+            }
+            if s.is_finite() {
+                self.current = EnvelopePoint { min: self.current.min.min(s), max: self.current.max.max(s) };
+            }
+            self.position += 1;
+        }
+    }
+
+    fn close_point(&mut self) {
+        self.points.push(std::mem::take(&mut self.current));
+        self.end = ((self.points.len() + 1) as f64 * self.samples_per_point) as usize;
+    }
+
+    /// `num_points` points: the last one over the samples it got, any later ones silent.
+    pub fn finish(mut self, num_points: usize) -> Vec<EnvelopePoint> {
+        if self.points.len() < num_points && self.samples_per_point.is_finite() {
+            self.close_point();
+        }
+        self.points.resize(num_points, EnvelopePoint::default());
+        self.points
+    }
+}
+
+/// `points` taken at `points_per_sec` merged into `num_points` points at `to_points_per_sec`: each is the min/max of
+/// the points its time covers, or of the one it falls in when it is shorter than they are.
+pub fn resample_envelope(
+    points: &[EnvelopePoint],
+    points_per_sec: f64,
+    to_points_per_sec: f64,
+    num_points: usize,
+) -> Vec<EnvelopePoint> {
+    let ratio = points_per_sec / to_points_per_sec;
+    if !(ratio > 0.0 && ratio.is_finite()) {
+        return vec![EnvelopePoint::default(); num_points];
+    }
+    (0..num_points)
+        .map(|i| {
+            let start = ((i as f64 * ratio) as usize).min(points.len());
+            let end = (((i + 1) as f64 * ratio) as usize).max(start + 1).min(points.len());
+            points[start..end].iter().fold(EnvelopePoint::default(), |acc, point| EnvelopePoint {
+                min: acc.min.min(point.min),
+                max: acc.max.max(point.max),
+            })
+        })
+        .collect()
+}
+
+/// Samples covering video frame `frame_index`: `[i * sample_rate / fps, (i + 1) * sample_rate / fps)`,
+/// truncated at the end of the input (empty past the end).
+pub fn frame_window(samples: &[f32], sample_rate: u32, fps: u32, frame_index: usize) -> &[f32] {
+    if fps == 0 {
+        return &[];
+    }
+    let n = samples.len();
+    let start = ((frame_index as u64 * sample_rate as u64 / fps as u64) as usize).min(n);
+    let end = (((frame_index as u64 + 1) * sample_rate as u64 / fps as u64) as usize).min(n);
+    &samples[start..end]
+}
+
+/// `frame_window` for samples that arrive in chunks, taken frame by frame in order from `first_frame` on: only the
+/// samples from the end of the last window taken on are held, and those before `first_frame` are dropped as they come.
+pub struct FrameWindows {
+    sample_rate: u32,
+    fps: u32,
+    samples: Vec<f32>,
+    /// Track position of `samples[0]`.
+    offset: usize,
+    /// First sample of `first_frame`.
+    start: usize,
+}
+
+impl FrameWindows {
+    pub fn new(sample_rate: u32, fps: u32, first_frame: usize) -> Self {
+        let mut windows = Self { sample_rate, fps, samples: Vec::new(), offset: 0, start: 0 };
+        windows.start = windows.bound(first_frame);
+        windows
+    }
+
+    /// First sample of frame `i`.
+    fn bound(&self, i: usize) -> usize {
+        if self.fps == 0 {
+            return 0;
+        }
+        (i as u64 * self.sample_rate as u64 / self.fps as u64) as usize
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+        let unwanted = self.start.saturating_sub(self.offset).min(self.samples.len());
+        self.samples.drain(..unwanted);
+        self.offset += unwanted;
+    }
+
+    /// Whether every sample of frame `frame_index` has been pushed.
+    pub fn covers(&self, frame_index: usize) -> bool {
+        self.offset + self.samples.len() >= self.bound(frame_index + 1)
+    }
+
+    /// The samples of frame `frame_index` pushed so far. The samples before its end are dropped, so later calls take
+    /// later frames.
+    pub fn take(&mut self, frame_index: usize) -> Vec<f32> {
+        let held = self.offset..self.offset + self.samples.len();
+        let at = |position: usize| position.clamp(held.start, held.end) - held.start;
+        let (start, end) = (at(self.bound(frame_index)), at(self.bound(frame_index + 1)));
+        let window = self.samples[start..end].to_vec();
+        self.samples.drain(..end);
+        self.offset += end;
+        window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compute_envelope, compute_frame_envelopes, frame_window, resample_envelope, EnvelopePoint, EnvelopeStream,
+        FrameWindows,
+    };
+
+    #[test]
+    fn compute_envelope_returns_num_points() {
+        let samples = vec![0.1f32; 1000];
+        let out = compute_envelope(&samples, 1000, 10.0, 10);
+        assert_eq!(out.len(), 10);
+    }
+
+    #[test]
+    fn compute_envelope_min_max_per_slice() {
+        let samples = vec![0.5f32, -0.25, 0.1, 0.0, -0.75, 0.3];
+        let out = compute_envelope(&samples, 6, 2.0, 2);
+        assert_eq!(out[0], EnvelopePoint { min: -0.25, max: 0.5 });
+        assert_eq!(out[1], EnvelopePoint { min: -0.75, max: 0.3 });
+    }
+
+    #[test]
+    fn compute_envelope_past_end_is_silent() {
+        let samples = vec![1.0f32; 10];
+        let out = compute_envelope(&samples, 10, 1.0, 3);
+        assert_eq!(out[0].peak(), 1.0);
+        assert_eq!(out[1], EnvelopePoint::default());
+        assert_eq!(out[2], EnvelopePoint::default());
+    }
+
+    #[test]
+    fn compute_envelope_skips_non_finite() {
+        let samples = vec![f32::NAN, 0.5, f32::INFINITY, -0.5];
+        let out = compute_envelope(&samples, 4, 1.0, 1);
+        assert_eq!(out[0], EnvelopePoint { min: -0.5, max: 0.5 });
+    }
+
+    #[test]
+    fn compute_frame_envelopes_one_per_frame() {
+        let samples: Vec<f32> = (0..44100).map(|i| (i as f32 * 0.01).sin()).collect();
+        let out = compute_frame_envelopes(&samples, 44100, 30, 30);
+        assert_eq!(out.len(), 30);
+        assert!(out.iter().all(|p| p.min >= -1.0 && p.max <= 1.0));
+    }
+
+    #[test]
+    fn frame_window_covers_frame_duration() {
+        let samples: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let w = frame_window(&samples, 40, 4, 1);
+        assert_eq!(w, &samples[10..20]);
+    }
+
+    #[test]
+    fn frame_window_truncates_at_end() {
+        let samples = vec![0.0f32; 25];
+        assert_eq!(frame_window(&samples, 40, 4, 2).len(), 5);
+        assert!(frame_window(&samples, 40, 4, 3).is_empty());
+        assert!(frame_window(&samples, 40, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn an_envelope_streamed_in_chunks_matches_the_whole() {
+        let samples: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin() * (i % 7) as f32 / 7.0).collect();
+        for (points_per_sec, num_points) in [(30.0, 40), (3.7, 4), (2000.0, 2100)] {
+            let whole = compute_envelope(&samples, 1000, points_per_sec, num_points);
+            let mut stream = EnvelopeStream::new(1000, points_per_sec);
+            samples.chunks(33).for_each(|chunk| stream.push(chunk));
+            assert_eq!(stream.finish(num_points), whole);
+        }
+        assert_eq!(EnvelopeStream::new(0, 10.0).finish(2), vec![EnvelopePoint::default(); 2]);
+    }
+
+    #[test]
+    fn resampled_points_merge_what_they_cover() {
+        let point = |peak: f32| EnvelopePoint { min: -peak, max: peak };
+        let points = [point(0.1), point(0.5), point(0.2), point(0.3)];
+        assert_eq!(resample_envelope(&points, 4.0, 2.0, 3), [point(0.5), point(0.3), EnvelopePoint::default()]);
+        // Finer than the input: each point repeats the one it falls in.
+        assert_eq!(resample_envelope(&points, 4.0, 8.0, 3), [point(0.1), point(0.1), point(0.5)]);
+    }
+
+    #[test]
+    fn frame_windows_cut_from_chunks_match_frame_window() {
+        let samples: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let mut windows = FrameWindows::new(40, 4, 0);
+        let mut chunks = samples.chunks(7);
+        for frame in 0..12 {
+            while !windows.covers(frame) {
+                let Some(chunk) = chunks.next() else { break };
+                windows.push(chunk);
+            }
+            assert_eq!(windows.take(frame), frame_window(&samples, 40, 4, frame), "frame {}", frame);
+        }
+        assert!(windows.samples.is_empty());
+    }
+
+    #[test]
+    fn frame_windows_drop_what_comes_before_the_first_frame() {
+        let samples: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let mut windows = FrameWindows::new(40, 4, 8);
+        let mut chunks = samples.chunks(7);
+        // Samples 0 to 76 all come before frame 8.
+        chunks.by_ref().take(11).for_each(|chunk| windows.push(chunk));
+        assert!(windows.samples.is_empty());
+        chunks.take(2).for_each(|chunk| windows.push(chunk));
+        assert!(windows.covers(8));
+        assert_eq!(windows.take(8), frame_window(&samples, 40, 4, 8));
+    }
+}
@@ -0,0 +1,145 @@
+//! Kinds of failure, each with its own process exit code so scripts can tell them apart
+
+use std::path::PathBuf;
+
+/// Every way a run can fail, so that a caller of `cli::run_with` can match on what went wrong and the binary exit with
+/// a code per kind.
+#[derive(Debug, thiserror::Error)]
+pub enum SpectrumError {
+    /// The command line can't be parsed, or asks for `--help` or `--version`: clap's error, which prints itself and
+    /// exits the way clap does.
+    #[cfg(feature = "runner")]
+    #[error(transparent)]
+    Usage(#[from] clap::Error),
+    /// Options that don't work together, or a config file or saved preset that can't be used.
+    #[error("{0}")]
+    InvalidConfig(String),
+    /// The input MP3 can't be opened or decoded.
+    #[error("failed to decode {path:?}: {message}")]
+    DecodeError { path: PathBuf, message: String },
+    #[error(
+        "ffmpeg not found at {0:?}. Install ffmpeg and add it to your PATH, or point --ffmpeg-path (or FFMPEG_PATH) \
+         at the binary."
+    )]
+    FfmpegNotFound(PathBuf),
+    /// ffmpeg (or the built-in encoder's hardware probe) rejected the encode.
+    #[error("{0}")]
+    EncodeFailed(String),
+    /// An output file (the temp WAV, a snapshot, a frame) can't be written.
+    #[error("failed to write {path:?}: {message}")]
+    WriteFailed { path: PathBuf, message: String },
+    /// The input file doesn't exist.
+    #[error("input file not found: {0:?}")]
+    InputNotFound(PathBuf),
+    /// Ctrl-C or SIGTERM stopped the render.
+    #[error("interrupted")]
+    Interrupted,
+    /// A file the render draws or follows (a logo, image, font, theme, subtitles, chapters or background video) can't
+    /// be read or decoded.
+    #[error("failed to load {what} {path:?}: {source}")]
+    AssetLoad {
+        /// What the file is for, as the options call it ("logo", "background image").
+        what: &'static str,
+        path: PathBuf,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Drawing a frame panicked (in a frame hook, a custom visualizer or the renderer itself), or the job running the
+    /// render did.
+    #[error("{0}")]
+    RenderFailed(String),
+    /// The wizard's terminal can't be set up, drawn or read.
+    #[error("terminal error: {0}")]
+    Terminal(std::io::Error),
+}
+
+impl SpectrumError {
+    /// Exit code for the failure: clap's for the command line (2, or 0 for `--help`), 2 for other options, 3 and up
+    /// for the rest, and the shell's 130 (128 + SIGINT) for an interruption.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            #[cfg(feature = "runner")]
+            Self::Usage(e) => e.exit_code() as u8,
+            Self::InvalidConfig(_) => 2,
+            Self::DecodeError { .. } => 3,
+            Self::FfmpegNotFound(_) => 4,
+            Self::EncodeFailed(_) => 5,
+            Self::WriteFailed { .. } => 6,
+            Self::InputNotFound(_) => 7,
+            Self::Interrupted => 130,
+            Self::AssetLoad { .. } => 8,
+            Self::RenderFailed(_) => 9,
+            Self::Terminal(_) => 10,
+        }
+    }
+}
+
+/// The exit codes, listed at the end of `--help`.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0    Success (and --help)
+  2    Invalid options, config file or saved preset
+  3    The input can't be opened or decoded
+  4    ffmpeg not found
+  5    ffmpeg (or the --hwaccel encoder) failed
+  6    An output file can't be written
+  7    The input file doesn't exist
+  8    A logo, image, font, theme, subtitles, chapters or background video can't be loaded
+  9    Drawing a frame failed
+  10   The wizard's terminal failed
+  130  Interrupted (Ctrl-C or SIGTERM)";
+
+/// Exit code for a boxed error, as from `analysis::analyze_file`: the `SpectrumError` kind's, or 1 for anything else.
+pub fn exit_code(error: &(dyn std::error::Error + 'static)) -> u8 {
+    error.downcast_ref::<SpectrumError>().map_or(1, SpectrumError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{exit_code, SpectrumError, EXIT_CODES_HELP};
+    use std::path::PathBuf;
+
+    #[test]
+    fn kinds_have_distinct_codes_and_other_errors_exit_with_one() {
+        let decode = SpectrumError::DecodeError { path: PathBuf::from("in.mp3"), message: "no audio track".into() };
+        assert_eq!(decode.to_string(), "failed to decode \"in.mp3\": no audio track");
+        let boxed: Box<dyn std::error::Error + Send + Sync> = decode.into();
+        assert_eq!(exit_code(boxed.as_ref()), 3);
+        let boxed: Box<dyn std::error::Error + Send + Sync> = SpectrumError::InvalidConfig("bad".into()).into();
+        assert_eq!(exit_code(boxed.as_ref()), 2);
+        let boxed: Box<dyn std::error::Error + Send + Sync> = SpectrumError::InputNotFound("in.mp3".into()).into();
+        assert_eq!(exit_code(boxed.as_ref()), 7);
+        let boxed: Box<dyn std::error::Error + Send + Sync> = SpectrumError::Interrupted.into();
+        assert_eq!(exit_code(boxed.as_ref()), 130);
+        let boxed: Box<dyn std::error::Error + Send + Sync> = "no audio".into();
+        assert_eq!(exit_code(boxed.as_ref()), 1);
+    }
+
+    #[test]
+    fn a_failed_asset_names_itself_and_keeps_its_cause() {
+        let cause = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let error = SpectrumError::AssetLoad { what: "logo", path: PathBuf::from("logo.png"), source: cause.into() };
+        assert_eq!(error.to_string(), "failed to load logo \"logo.png\": no such file");
+        let cause = std::error::Error::source(&error).and_then(|e| e.downcast_ref::<std::io::Error>());
+        assert_eq!(cause.map(std::io::Error::kind), Some(std::io::ErrorKind::NotFound));
+        assert_eq!(error.exit_code(), 8);
+    }
+
+    #[test]
+    fn help_lists_every_kind_s_code() {
+        let kinds = [
+            SpectrumError::InvalidConfig(String::new()),
+            SpectrumError::DecodeError { path: PathBuf::new(), message: String::new() },
+            SpectrumError::FfmpegNotFound(PathBuf::new()),
+            SpectrumError::EncodeFailed(String::new()),
+            SpectrumError::WriteFailed { path: PathBuf::new(), message: String::new() },
+            SpectrumError::InputNotFound(PathBuf::new()),
+            SpectrumError::Interrupted,
+            SpectrumError::AssetLoad { what: "logo", path: PathBuf::new(), source: "unreadable".into() },
+            SpectrumError::RenderFailed(String::new()),
+            SpectrumError::Terminal(std::io::Error::other("closed")),
+        ];
+        for kind in kinds {
+            assert!(EXIT_CODES_HELP.contains(&format!("\n  {:<4} ", kind.exit_code())), "{:?}", kind);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Export of the per-frame analysis (bar values, levels, beats) as JSON or CSV
+
+#[cfg(feature = "runner")]
+use std::fmt::Write as _;
+#[cfg(feature = "runner")]
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::beat::BeatDetector;
+use crate::envelope::EnvelopePoint;
+#[cfg(feature = "runner")]
+use crate::error::SpectrumError;
+use crate::level::FrameLevel;
+
+/// Analysis of one video frame.
+#[derive(Debug, Serialize)]
+pub struct FrameAnalysis {
+    /// Start of the frame in seconds.
+    pub time: f32,
+    /// Normalized bar values (0.0–1.0), lowest frequency first.
+    pub bars: Vec<f32>,
+    /// RMS of the frame's samples (linear).
+    pub rms: f32,
+    /// Largest absolute sample in the frame (linear).
+    pub peak: f32,
+    /// Momentary loudness (LUFS).
+    pub lufs: f32,
+    /// Whether a beat was detected on this frame.
+    pub beat: bool,
+}
+
+/// One `FrameAnalysis` per video frame. `bars_at` gives a frame's normalized bars (analysis order), and beats are
+/// detected on their lowest `bass_bars` in frame order; frames past the end of `levels` or `envelopes` are silent.
+pub fn frame_analyses(
+    total_frames: usize,
+    fps: u32,
+    bass_bars: usize,
+    mut bars_at: impl FnMut(usize) -> Vec<f32>,
+    levels: &[FrameLevel],
+    envelopes: &[EnvelopePoint],
+) -> Vec<FrameAnalysis> {
+    let mut beats = BeatDetector::new(fps);
+    (0..total_frames)
+        .map(|index| {
+            let bars = bars_at(index);
+            let level = levels.get(index).copied().unwrap_or_default();
+            FrameAnalysis {
+                time: index as f32 / fps as f32,
+                beat: beats.update(&bars, bass_bars),
+                bars,
+                rms: level.rms,
+                peak: envelopes.get(index).map_or(0.0, EnvelopePoint::peak),
+                lufs: level.lufs,
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "runner")]
+#[derive(Serialize)]
+struct AnalysisFile<'a> {
+    fps: u32,
+    bars: usize,
+    frames: &'a [FrameAnalysis],
+}
+
+/// Write `frames` to `path`: CSV for a `.csv` extension (one row per frame, bars as `bar_0`.. columns), JSON
+/// otherwise.
+#[cfg(feature = "runner")]
+pub fn write_analysis(path: &Path, fps: u32, frames: &[FrameAnalysis]) -> Result<(), SpectrumError> {
+    let failed = |message: String| SpectrumError::WriteFailed { path: path.to_path_buf(), message };
+    let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let content = if is_csv {
+        analysis_csv(frames)
+    } else {
+        let bars = frames.first().map_or(0, |f| f.bars.len());
+        serde_json::to_string(&AnalysisFile { fps, bars, frames }).map_err(|e| failed(e.to_string()))?
+    };
+    std::fs::write(path, content).map_err(|e| failed(e.to_string()))
+}
+
+#[cfg(feature = "runner")]
+fn analysis_csv(frames: &[FrameAnalysis]) -> String {
+    let bars = frames.first().map_or(0, |f| f.bars.len());
+    let mut out = String::from("time,rms,peak,lufs,beat");
+    for i in 0..bars {
+        let _ = write!(out, ",bar_{}", i);
+    }
+    out.push('\n');
+    for f in frames {
+        let _ = write!(out, "{:.4},{:.6},{:.6},{:.2},{}", f.time, f.rms, f.peak, f.lufs, f.beat as u8);
+        for v in &f.bars {
+            let _ = write!(out, ",{:.4}", v);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::frame_analyses;
+    use crate::envelope::EnvelopePoint;
+    use crate::level::FrameLevel;
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn csv_has_one_row_per_frame_and_bar_columns() {
+        use super::{analysis_csv, FrameAnalysis};
+
+        let frames = [
+            FrameAnalysis { time: 0.0, bars: vec![0.5, 1.0], rms: 0.1, peak: 0.2, lufs: -20.0, beat: false },
+            FrameAnalysis { time: 0.5, bars: vec![0.25, 0.0], rms: 0.3, peak: 0.4, lufs: -12.5, beat: true },
+        ];
+        let csv = analysis_csv(&frames);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "time,rms,peak,lufs,beat,bar_0,bar_1");
+        assert_eq!(lines[2], "0.5000,0.300000,0.400000,-12.50,1,0.2500,0.0000");
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn analyses_take_levels_and_peaks_per_frame_and_fill_in_silence() {
+        let levels = [FrameLevel { rms: 0.5, lufs: -6.0 }];
+        let envelopes = [EnvelopePoint { min: -0.75, max: 0.25 }];
+        let frames = frame_analyses(2, 4, 1, |index| vec![index as f32], &levels, &envelopes);
+        assert_eq!((frames[0].time, frames[0].rms, frames[0].peak, frames[0].lufs), (0.0, 0.5, 0.75, -6.0));
+        assert_eq!((frames[1].time, frames[1].rms, frames[1].peak), (0.25, 0.0, 0.0));
+        assert_eq!(frames[1].bars, vec![1.0]);
+    }
+}
@@ -0,0 +1,83 @@
+//! Frame decoration: padding around the rendered content and a solid or gradient border around the whole video
+
+use image::{ImageBuffer, Rgba};
+
+use crate::draw::mix_colors;
+
+/// Border drawn along the edges of the video.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Border {
+    /// Thickness (pixels).
+    pub width: u32,
+    /// Color as RGBA (the top edge, with `end_color`).
+    pub color: [u8; 4],
+    /// Color at the bottom edge for a vertical gradient. When None, the border is solid.
+    pub end_color: Option<[u8; 4]>,
+}
+
+impl Border {
+    /// Border color at row `y` of a frame `height` rows tall.
+    fn color_at(&self, y: u32, height: u32) -> [u8; 4] {
+        match self.end_color {
+            Some(end) => mix_colors(self.color, end, y as f32 / height.saturating_sub(1).max(1) as f32),
+            None => self.color,
+        }
+    }
+}
+
+/// Distance (pixels) from each frame edge to the rendered content: the border plus the padding inside it.
+pub fn content_inset(padding: u32, border: Option<Border>) -> u32 {
+    padding + border.map_or(0, |b| b.width)
+}
+
+/// Place `content` in the middle of a `size` frame: the ring around it is `padding` pixels of `fill` inside the
+/// `border`, so the content is `content_inset` pixels from each edge.
+pub fn frame_content(
+    content: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    size: (u32, u32),
+    padding: u32,
+    border: Option<Border>,
+    fill: [u8; 4],
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = size;
+    let mut img = ImageBuffer::from_fn(width, height, |x, y| match border {
+        Some(b) if x < b.width || y < b.width || x + b.width >= width || y + b.width >= height => {
+            Rgba(b.color_at(y, height))
+        }
+        _ => Rgba(fill),
+    });
+    let inset = content_inset(padding, border) as i64;
+    image::imageops::overlay(&mut img, content, inset, inset);
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_inset, frame_content, Border};
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn frame_content_insets_content_inside_padding_and_border() {
+        let content = ImageBuffer::from_pixel(10, 6, Rgba([0, 0, 255, 255]));
+        let border = Border { width: 2, color: [255, 0, 0, 255], end_color: None };
+        assert_eq!(content_inset(3, Some(border)), 5);
+        let img = frame_content(&content, (20, 16), 3, Some(border), [255, 255, 255, 255]);
+        assert_eq!(img.dimensions(), (20, 16));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(19, 8).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(3, 3).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(5, 5).0, [0, 0, 255, 255]);
+        assert_eq!(img.get_pixel(14, 10).0, [0, 0, 255, 255]);
+        assert_eq!(img.get_pixel(15, 11).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn gradient_border_runs_top_to_bottom() {
+        let content = ImageBuffer::new(1, 1);
+        let border = Border { width: 1, color: [0, 0, 0, 255], end_color: Some([200, 100, 0, 255]) };
+        let img = frame_content(&content, (5, 5), 0, Some(border), [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(2, 0).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(0, 2).0, [100, 50, 0, 255]);
+        assert_eq!(img.get_pixel(2, 4).0, [200, 100, 0, 255]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Stopping a render early, and the files a run removes when it doesn't finish. A run's `CancelToken` is a flag
+//! that the render loop checks between frames, so the run stops like any failed run: dropping the encoder kills
+//! ffmpeg, and dropping the `Cleanup` removes what the run wrote. The binary cancels its run on Ctrl-C (and SIGTERM).
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "cli")]
+use crate::logging::debug;
+use crate::logging::{info, verbose};
+use crate::output::{remove_frames, writes_to_stdout, Container};
+
+/// Stops one run; clones share the flag. A run checks it once its output is under way and between frames, and then
+/// fails with `SpectrumError::Interrupted`.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    /// The run has reached the point where it checks the flag and cleans up.
+    watched: AtomicBool,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// From here on the run stops cleanly when cancelled.
+    pub(crate) fn watch(&self) {
+        self.0.watched.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Cancel `token` on Ctrl-C and SIGTERM, for the binary's one run. Before the run watches the token, and on a second
+/// one, the process exits at once, as it would without a handler.
+#[cfg(feature = "cli")]
+pub fn install(token: CancelToken) {
+    let installed = ctrlc::set_handler(move || {
+        if !token.0.watched.load(Ordering::SeqCst) || token.0.cancelled.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    });
+    if let Err(e) = installed {
+        debug!("Ctrl-C handler not installed: {}", e);
+    }
+}
+
+/// What a run has written, removed when it is dropped: the temporary files unless `keep_temp`, and the output too
+/// unless `complete` was called, so a failed or interrupted run leaves no half-written video or stray frames.
+pub struct Cleanup {
+    keep_temp: bool,
+    temp_files: Vec<PathBuf>,
+    output: Option<(PathBuf, Container)>,
+    /// `--frames-only` directory and the index of the first frame this run writes there.
+    frames: Option<(PathBuf, usize)>,
+    /// `--encoder png` frame directory, only needed while encoding.
+    temp_frames: Option<PathBuf>,
+    /// The run's own temporary directory, removed once the files in it are.
+    temp_dir: Option<PathBuf>,
+    complete: bool,
+}
+
+impl Cleanup {
+    pub fn new(keep_temp: bool) -> Self {
+        let temp_files = Vec::new();
+        Self { keep_temp, temp_files, output: None, frames: None, temp_frames: None, temp_dir: None, complete: false }
+    }
+
+    /// The directory the temporary files go in, which only this run uses.
+    pub fn temp_dir(&mut self, dir: &Path) {
+        self.temp_dir = Some(dir.to_path_buf());
+    }
+
+    /// A file only needed while encoding (the WAV, chapters, cover art).
+    pub fn temp_file(&mut self, path: &Path) {
+        self.temp_files.push(path.to_path_buf());
+    }
+
+    /// Frames written as `frame_NNNNNN.png` into `dir` from index 0 on, only needed while encoding.
+    pub fn temp_frames(&mut self, dir: &Path) {
+        self.temp_frames = Some(dir.to_path_buf());
+    }
+
+    /// The video ffmpeg or the native encoder is writing (nothing to remove for `-`).
+    pub fn output(&mut self, path: &Path, container: Container) {
+        if !writes_to_stdout(path) {
+            self.output = Some((path.to_path_buf(), container));
+        }
+    }
+
+    /// Frames written as `frame_NNNNNN.png` into `dir` from index `first` on.
+    pub fn frames(&mut self, dir: &Path, first: usize) {
+        self.frames = Some((dir.to_path_buf(), first));
+    }
+
+    /// The output is finished: keep it.
+    pub fn complete(&mut self) {
+        self.complete = true;
+    }
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        let remove = |path: &Path| {
+            if std::fs::remove_file(path).is_ok() {
+                verbose!("Removed {:?}", path);
+            }
+        };
+        if self.keep_temp {
+            if let Some(dir) = self.temp_files.first().and_then(|path| path.parent()) {
+                info!("Kept the temporary files in {:?}", dir);
+            }
+        } else {
+            self.temp_files.iter().for_each(|path| remove(path));
+            if let Some(ref dir) = self.temp_frames {
+                verbose!("Removed {} frames from {:?}", remove_frames(dir, 0), dir);
+                let _ = std::fs::remove_dir(dir);
+            }
+            if let Some(ref dir) = self.temp_dir {
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+        if self.complete {
+            return;
+        }
+        if let Some((ref path, container)) = self.output {
+            container.written_files(path).iter().for_each(|path| remove(path));
+        }
+        // Frames are written in order, so the first missing one ends this run's.
+        if let Some((dir, first)) = self.frames.as_ref().filter(|_| !self.keep_temp) {
+            let count = remove_frames(dir, *first);
+            if count > 0 {
+                info!("Removed {} unfinished frames from {:?} (--keep-temp keeps them)", count, dir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cleanup;
+    use crate::output::{frame_path, Container};
+
+    #[test]
+    fn unfinished_runs_remove_their_output_and_frames_but_finished_ones_keep_it() {
+        let dir = std::env::temp_dir().join(format!("asg-cleanup-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (video, wav) = (dir.join("out.mp4"), dir.join("audio.wav"));
+        let frames: Vec<_> = (3..6).map(|i| frame_path(&dir, i)).collect();
+        for path in [&video, &wav].into_iter().chain(&frames) {
+            std::fs::write(path, b"x").unwrap();
+        }
+        let mut cleanup = Cleanup::new(false);
+        cleanup.temp_file(&wav);
+        let run_dir = dir.join("run");
+        std::fs::create_dir_all(&run_dir).unwrap();
+        cleanup.temp_dir(&run_dir);
+        cleanup.output(&video, Container::Mp4);
+        cleanup.frames(&dir, 4);
+        drop(cleanup);
+        assert!(!video.exists() && !wav.exists() && !run_dir.exists());
+        // Only this run's frames (from index 4) go.
+        assert!(frames[0].exists() && !frames[1].exists() && !frames[2].exists());
+
+        std::fs::write(&video, b"x").unwrap();
+        std::fs::write(&wav, b"x").unwrap();
+        let mut cleanup = Cleanup::new(true);
+        cleanup.temp_file(&wav);
+        cleanup.output(&video, Container::Mp4);
+        cleanup.complete();
+        drop(cleanup);
+        assert!(video.exists() && wav.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
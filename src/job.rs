@@ -0,0 +1,168 @@
+//! Renders as async jobs (`async` feature): `render_video` runs a `Renderer` on tokio's blocking pool and returns a
+//! `RenderJob`, which is awaited for the run's summary, streams the progress meanwhile and can be cancelled.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::decode::AudioDecoder;
+use crate::error::SpectrumError;
+use crate::interrupt::CancelToken;
+use crate::progress::{CallbackProgress, Phase, ProgressEvent};
+use crate::renderer::{Output, Renderer, RunOptions, RunSummary};
+
+/// How far a render has come, sent after every frame handed on to the encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderProgress {
+    /// Frames rendered so far.
+    pub frames_rendered: usize,
+    /// Frames the render draws: the whole track, or one for a snapshot.
+    pub total_frames: usize,
+}
+
+/// A render running in the background. Await it for the run's summary; dropping it lets the render finish
+/// unobserved.
+pub struct RenderJob {
+    handle: JoinHandle<Result<RunSummary, SpectrumError>>,
+    progress: Option<ProgressStream>,
+    cancel: CancelToken,
+}
+
+/// `RenderProgress` updates of one job, ending when the render does.
+pub struct ProgressStream {
+    updates: mpsc::UnboundedReceiver<RenderProgress>,
+}
+
+/// Start rendering `input`, read by `decoder`, into `output` as `Renderer::run` does, with the progress going to the
+/// job's stream. Must be called from within a tokio runtime; jobs share no temporary files, log levels or
+/// cancellation.
+pub fn render_video(renderer: Renderer, decoder: Box<dyn AudioDecoder>, input: PathBuf, output: Output) -> RenderJob {
+    let (sender, updates) = mpsc::unbounded_channel();
+    let total_frames = AtomicUsize::new(0);
+    let progress = CallbackProgress(move |event| match event {
+        ProgressEvent::Start { phase: Phase::Rendering, total } => {
+            total_frames.store(total.unwrap_or(0) as usize, Ordering::Relaxed);
+        }
+        ProgressEvent::Update { phase: Phase::Rendering, position } => {
+            let total_frames = total_frames.load(Ordering::Relaxed);
+            let _ = sender.send(RenderProgress { frames_rendered: position as usize, total_frames });
+        }
+        _ => {}
+    });
+    let cancel = CancelToken::new();
+    let options = RunOptions { progress: Some(Arc::new(progress)), cancel: cancel.clone(), ..RunOptions::default() };
+    let handle = tokio::task::spawn_blocking(move || renderer.run(decoder.as_ref(), &input, &output, options));
+    RenderJob { handle, progress: Some(ProgressStream { updates }), cancel }
+}
+
+impl RenderJob {
+    /// Stop the render between frames, removing what it wrote; the job then resolves to
+    /// `SpectrumError::Interrupted`. A run that hasn't started its output yet stops once it does.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// The job's progress updates; `None` once taken.
+    pub fn take_progress(&mut self) -> Option<ProgressStream> {
+        self.progress.take()
+    }
+}
+
+impl Future for RenderJob {
+    type Output = Result<RunSummary, SpectrumError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let failed = |e| SpectrumError::RenderFailed(format!("the render job failed: {}", e));
+        Pin::new(&mut self.handle).poll(cx).map(|joined| joined.unwrap_or_else(|e| Err(failed(e))))
+    }
+}
+
+impl Stream for ProgressStream {
+    type Item = RenderProgress;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<RenderProgress>> {
+        self.updates.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_video;
+    use crate::config::Config;
+    use crate::decode::Symphonia;
+    use crate::error::SpectrumError;
+    use crate::renderer::{FrameOutput, Output, RenderSettings, Renderer, VideoOutput};
+    use std::path::PathBuf;
+    use std::pin::Pin;
+
+    fn renderer() -> Renderer {
+        Renderer::single(Config::default(), RenderSettings::default()).unwrap()
+    }
+
+    #[cfg(feature = "wav")]
+    fn snapshot(time: f32, path: PathBuf) -> Output {
+        Output { frames: FrameOutput::Snapshot { time, path }, ..Output::default() }
+    }
+
+    #[test]
+    fn failed_jobs_end_their_progress_and_resolve_to_the_error() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let input = PathBuf::from("/nonexistent/song.mp3");
+            let output = Output {
+                frames: FrameOutput::Video(VideoOutput::new("/nonexistent/song.mp4")),
+                ..Output::default()
+            };
+            let mut job = render_video(renderer(), Box::new(Symphonia), input, output);
+            let mut progress = job.take_progress().unwrap();
+            assert!(job.take_progress().is_none());
+            let error = (&mut job).await.unwrap_err();
+            assert!(matches!(error, SpectrumError::InputNotFound(_)), "{}", error);
+            let next = std::future::poll_fn(|cx| futures_core::Stream::poll_next(Pin::new(&mut progress), cx)).await;
+            assert_eq!(next, None);
+        });
+    }
+
+    // Decodes a WAV file with symphonia.
+    #[cfg(feature = "wav")]
+    #[test]
+    fn snapshots_past_the_end_fail_as_invalid_config() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let path = std::env::temp_dir().join(format!("asg-job-past-end-{}.wav", std::process::id()));
+            crate::wav::write_wav(&path, &vec![0.0; 44_100], 44_100).unwrap();
+            let output = path.with_extension("png");
+            let job = render_video(renderer(), Box::new(Symphonia), path.clone(), snapshot(5.0, output.clone()));
+            let error = job.await.unwrap_err();
+            std::fs::remove_file(&path).ok();
+            assert!(matches!(error, SpectrumError::InvalidConfig(_)), "{}", error);
+            assert_eq!(error.exit_code(), 2);
+            assert!(!output.exists());
+        });
+    }
+
+    // Decodes a WAV file with symphonia.
+    #[cfg(feature = "wav")]
+    #[test]
+    fn cancelled_jobs_end_with_an_error_and_leave_no_output() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(async {
+            let path = std::env::temp_dir().join(format!("asg-job-cancel-{}.wav", std::process::id()));
+            crate::wav::write_wav(&path, &vec![0.0; 44_100], 44_100).unwrap();
+            let output = path.with_extension("png");
+            let job = render_video(renderer(), Box::new(Symphonia), path.clone(), snapshot(0.0, output.clone()));
+            job.cancel();
+            let error = job.await.unwrap_err();
+            std::fs::remove_file(&path).ok();
+            assert!(matches!(error, SpectrumError::Interrupted), "{}", error);
+            assert!(!output.exists());
+        });
+    }
+}
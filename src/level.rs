@@ -0,0 +1,460 @@
+//! Per-frame level metering (RMS and momentary loudness), and whole-track levels for `analyze`, measured as the
+//! samples stream past
+
+use std::collections::VecDeque;
+
+/// Floor used for dB / LUFS values when the signal is silent.
+pub const LEVEL_FLOOR_DB: f32 = -100.0;
+
+/// Loudness window length for momentary loudness (ITU-R BS.1770, 400 ms).
+const MOMENTARY_WINDOW_SEC: f32 = 0.4;
+
+/// Integrated loudness gating (BS.1770-4): 400 ms blocks every 100 ms, an absolute gate at -70 LUFS and a relative
+/// gate 10 LU below the loudness of the blocks that pass it.
+const GATING_BLOCK_SEC: f32 = 0.4;
+const GATING_STEP_SEC: f32 = 0.1;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+/// Samples at or above this magnitude count as clipped (within 0.01 dB of full scale).
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Level measurements for one video frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameLevel {
+    /// RMS of the samples covered by the frame (linear, 0.0–1.0 for in-range PCM).
+    pub rms: f32,
+    /// Momentary loudness in LUFS: K-weighted mean square over the 400 ms ending at the frame.
+    /// Computed on the mono downmix, so it is an approximation for stereo sources.
+    pub lufs: f32,
+}
+
+impl Default for FrameLevel {
+    fn default() -> Self {
+        Self {
+            rms: 0.0,
+            lufs: LEVEL_FLOOR_DB,
+        }
+    }
+}
+
+/// Compute RMS and momentary loudness for each of `total_frames` video frames.
+/// Frame `i` covers samples `[i * sample_rate / fps, (i + 1) * sample_rate / fps)`; `FrameLevelStream` over the whole
+/// of `samples`.
+pub fn compute_frame_levels(samples: &[f32], sample_rate: u32, fps: u32, total_frames: usize) -> Vec<FrameLevel> {
+    let mut stream = FrameLevelStream::new(sample_rate, fps);
+    stream.push(samples);
+    stream.finish(total_frames)
+}
+
+/// Per-frame levels for samples that arrive in chunks: each frame is measured once the stream passes its end, and
+/// only one loudness window of K-weighted samples is held.
+pub struct FrameLevelStream {
+    sample_rate: u32,
+    fps: u32,
+    filter: KWeighting,
+    /// Running sums (f64) of the K-weighted squares, at the last `momentary_len` samples and the one before them.
+    weighted_sq: VecDeque<f64>,
+    momentary_len: usize,
+    /// Sum of the raw squares since the current frame's start.
+    raw_sq: f64,
+    /// Samples pushed so far.
+    position: usize,
+    /// Where the current frame starts and ends.
+    frame: (usize, usize),
+    levels: Vec<FrameLevel>,
+}
+
+impl FrameLevelStream {
+    pub fn new(sample_rate: u32, fps: u32) -> Self {
+        let momentary_len = (MOMENTARY_WINDOW_SEC * sample_rate as f32).round().max(1.0) as usize;
+        let mut stream = Self {
+            sample_rate,
+            fps,
+            filter: KWeighting::new(sample_rate),
+            weighted_sq: VecDeque::from([0.0]),
+            momentary_len,
+            raw_sq: 0.0,
+            position: 0,
+            frame: (0, 0),
+            levels: Vec::new(),
+        };
+        stream.frame = (0, stream.bound(1));
+        stream
+    }
+
+    /// First sample of frame `i`.
+    fn bound(&self, i: usize) -> usize {
+        if self.fps == 0 {
+            return usize::MAX;
+        }
+        (i as u64 * self.sample_rate as u64 / self.fps as u64) as usize
+    }
+
+    /// Append decoded samples, measuring every frame that is now complete.
+    pub fn push(&mut self, samples: &[f32]) {
+        if self.sample_rate == 0 || self.fps == 0 {
+            return;
+        }
+        for &s in samples {
+            while self.position >= self.frame.1 {
+                self.close_frame();
+            }
+            let w = self.filter.next(s);
+            let (s, w) = (finite(s) as f64, finite(w) as f64);
+            self.raw_sq += s * s;
+            let sum = self.weighted_sq.back().unwrap() + w * w;
+            self.weighted_sq.push_back(sum);
+            if self.weighted_sq.len() > self.momentary_len + 1 {
+                self.weighted_sq.pop_front();
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Measure the current frame up to `self.position` and start the next.
+    fn close_frame(&mut self) {
+        let (start, end) = (self.frame.0, self.position.min(self.frame.1));
+        self.levels.push(if end <= start {
+            FrameLevel::default()
+        } else {
+            let mean_sq = self.raw_sq / (end - start) as f64;
+            // The window is the last `momentary_len` samples, or all of them before that many have arrived.
+            let window = self.weighted_sq.len() - 1;
+            let m_mean_sq = (self.weighted_sq.back().unwrap() - self.weighted_sq.front().unwrap()) / window as f64;
+            FrameLevel { rms: mean_sq.sqrt() as f32, lufs: mean_square_to_lufs(m_mean_sq) }
+        });
+        self.raw_sq = 0.0;
+        let next = self.levels.len();
+        self.frame = (self.bound(next), self.bound(next + 1));
+    }
+
+    /// The levels of `total_frames` frames: the last one measured over the samples it got, any later ones silent.
+    pub fn finish(mut self, total_frames: usize) -> Vec<FrameLevel> {
+        if self.sample_rate == 0 || self.fps == 0 {
+            return vec![FrameLevel::default(); total_frames];
+        }
+        if self.levels.len() < total_frames && self.position > self.frame.0 {
+            self.close_frame();
+        }
+        self.levels.resize(total_frames, FrameLevel::default());
+        self.levels
+    }
+}
+
+/// Level measurements for a whole track.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+pub struct TrackLevels {
+    /// Largest sample magnitude of any channel (linear).
+    pub peak: f32,
+    /// RMS over the samples of all channels (linear).
+    pub rms: f32,
+    /// Gated integrated loudness in LUFS, with every channel weighted 1.0 (as L and R are).
+    pub integrated_lufs: f32,
+    /// Samples of all channels at or above `CLIP_LEVEL`.
+    pub clipped_samples: usize,
+}
+
+/// Measure a track from its `channels` (the left and right PCM, or the one channel of a mono input);
+/// `TrackLevelStream` over the whole of them.
+pub fn compute_track_levels(channels: &[&[f32]], sample_rate: u32) -> TrackLevels {
+    let mut stream = TrackLevelStream::new(channels.len(), sample_rate);
+    stream.push(channels);
+    stream.finish()
+}
+
+/// Whole-track levels for channels that arrive in chunks. Integrated loudness (ITU-R BS.1770-4) is the K-weighted
+/// power of the 400 ms blocks that pass both gates, summed over channels; the stream keeps each block's power and
+/// one block of running sums per channel, not the samples.
+pub struct TrackLevelStream {
+    filters: Vec<KWeighting>,
+    /// Running sums (f64) of each channel's K-weighted squares, at the last `block` samples and the one before them.
+    weighted_sq: Vec<VecDeque<f64>>,
+    block: usize,
+    step: usize,
+    /// Samples of every channel pushed so far.
+    position: usize,
+    blocks: Vec<f64>,
+    count: usize,
+    sum_sq: f64,
+    peak: f32,
+    clipped_samples: usize,
+}
+
+impl TrackLevelStream {
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        Self {
+            filters: (0..channels).map(|_| KWeighting::new(sample_rate)).collect(),
+            weighted_sq: vec![VecDeque::from([0.0]); channels],
+            block: (GATING_BLOCK_SEC * sample_rate as f32).round() as usize,
+            step: (GATING_STEP_SEC * sample_rate as f32).round().max(1.0) as usize,
+            position: 0,
+            blocks: Vec::new(),
+            count: 0,
+            sum_sq: 0.0,
+            peak: 0.0,
+            clipped_samples: 0,
+        }
+    }
+
+    /// Append the next samples of every channel (the same number of each; loudness stops at the shortest).
+    pub fn push(&mut self, channels: &[&[f32]]) {
+        for &s in channels.iter().flat_map(|channel| channel.iter()).filter(|s| s.is_finite()) {
+            self.count += 1;
+            self.sum_sq += s as f64 * s as f64;
+            self.peak = self.peak.max(s.abs());
+            self.clipped_samples += (s.abs() >= CLIP_LEVEL) as usize;
+        }
+        let len = channels.iter().map(|channel| channel.len()).min().unwrap_or(0);
+        if self.block == 0 {
+            return;
+        }
+        for i in 0..len {
+            for ((channel, filter), sums) in channels.iter().zip(&mut self.filters).zip(&mut self.weighted_sq) {
+                let w = finite(filter.next(channel[i])) as f64;
+                sums.push_back(sums.back().unwrap() + w * w);
+                if sums.len() > self.block + 1 {
+                    sums.pop_front();
+                }
+            }
+            self.position += 1;
+            if self.position >= self.block && (self.position - self.block).is_multiple_of(self.step) {
+                let block_sq = |sums: &VecDeque<f64>| sums.back().unwrap() - sums.front().unwrap();
+                let power: f64 = self.weighted_sq.iter().map(block_sq).sum();
+                self.blocks.push(power / self.block as f64);
+            }
+        }
+    }
+
+    pub fn finish(self) -> TrackLevels {
+        TrackLevels {
+            peak: self.peak,
+            rms: if self.count > 0 { (self.sum_sq / self.count as f64).sqrt() as f32 } else { 0.0 },
+            integrated_lufs: gated_loudness(&self.blocks),
+            clipped_samples: self.clipped_samples,
+        }
+    }
+}
+
+/// Integrated loudness from the power of each gating block. `LEVEL_FLOOR_DB` for silence or less than one block of
+/// audio.
+fn gated_loudness(blocks: &[f64]) -> f32 {
+    let lufs = |power: f64| -0.691 + 10.0 * power.log10();
+    let gated_mean = |gate_lufs: f64| {
+        let kept: Vec<f64> = blocks.iter().copied().filter(|&power| lufs(power) > gate_lufs).collect();
+        (!kept.is_empty()).then(|| kept.iter().sum::<f64>() / kept.len() as f64)
+    };
+    let Some(absolute) = gated_mean(ABSOLUTE_GATE_LUFS) else {
+        return LEVEL_FLOOR_DB;
+    };
+    mean_square_to_lufs(gated_mean(lufs(absolute) - RELATIVE_GATE_LU).unwrap_or(absolute))
+}
+
+fn finite(s: f32) -> f32 {
+    if s.is_finite() { s } else { 0.0 }
+}
+
+/// Convert a linear amplitude to dBFS, floored at `LEVEL_FLOOR_DB`.
+#[cfg(feature = "runner")]
+pub fn amplitude_to_db(a: f32) -> f32 {
+    if a > 0.0 && a.is_finite() {
+        (20.0 * a.log10()).max(LEVEL_FLOOR_DB)
+    } else {
+        LEVEL_FLOOR_DB
+    }
+}
+
+fn mean_square_to_lufs(mean_sq: f64) -> f32 {
+    if mean_sq > 0.0 && mean_sq.is_finite() {
+        ((-0.691 + 10.0 * mean_sq.log10()) as f32).max(LEVEL_FLOOR_DB)
+    } else {
+        LEVEL_FLOOR_DB
+    }
+}
+
+/// The BS.1770 K-weighting filter (high-shelf pre-filter + RLB high-pass), one sample at a time.
+/// Coefficients are derived for the given sample rate rather than the 48 kHz table.
+struct KWeighting {
+    shelf: Biquad,
+    highpass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: u32) -> Self {
+        let fs = sample_rate as f64;
+
+        // Stage 1: high shelf (+4 dB above ~1.7 kHz)
+        let f0 = 1_681.974_450_955_533;
+        let gain_db = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+            2.0 * (k * k - 1.0) / a0,
+            (1.0 - k / q + k * k) / a0,
+        );
+
+        // Stage 2: high pass (~38 Hz)
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (std::f64::consts::PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad::new(1.0, -2.0, 1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0);
+
+        Self { shelf, highpass }
+    }
+
+    fn next(&mut self, x: f32) -> f32 {
+        let stage1 = self.shelf.next(x);
+        self.highpass.next(stage1)
+    }
+}
+
+/// Direct form I biquad with normalized coefficients (a0 = 1), and its last two inputs and outputs.
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b0: f64, b1: f64, b2: f64, a1: f64, a2: f64) -> Self {
+        Self { b0, b1, b2, a1, a2, x: [0.0; 2], y: [0.0; 2] }
+    }
+
+    fn next(&mut self, x: f32) -> f32 {
+        let x = if x.is_finite() { x as f64 } else { 0.0 };
+        let y = self.b0 * x + self.b1 * self.x[0] + self.b2 * self.x[1] - self.a1 * self.y[0] - self.a2 * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_frame_levels, compute_track_levels, FrameLevelStream, TrackLevelStream, LEVEL_FLOOR_DB};
+
+    fn sine(freq: f32, amp: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let n = (sample_rate as f32 * seconds) as usize;
+        (0..n)
+            .map(|i| amp * (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn compute_frame_levels_returns_one_per_frame() {
+        let samples = sine(440.0, 0.5, 48000, 1.0);
+        let levels = compute_frame_levels(&samples, 48000, 30, 30);
+        assert_eq!(levels.len(), 30);
+    }
+
+    #[test]
+    fn compute_frame_levels_silence_is_floor() {
+        let samples = vec![0.0f32; 48000];
+        let levels = compute_frame_levels(&samples, 48000, 30, 30);
+        for l in &levels {
+            assert_eq!(l.rms, 0.0);
+            assert_eq!(l.lufs, LEVEL_FLOOR_DB);
+        }
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn compute_frame_levels_full_scale_sine_rms() {
+        use super::amplitude_to_db;
+
+        let samples = sine(1000.0, 1.0, 48000, 1.0);
+        let levels = compute_frame_levels(&samples, 48000, 30, 30);
+        let l = levels[15];
+        assert!((l.rms - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01, "rms = {}", l.rms);
+        let db = amplitude_to_db(l.rms);
+        assert!((db + 3.01).abs() < 0.1, "rms dB = {}", db);
+    }
+
+    #[test]
+    fn compute_frame_levels_1k_sine_loudness_near_reference() {
+        // BS.1770: a 0 dBFS 1 kHz sine in one channel reads about -3.01 LUFS.
+        let samples = sine(997.0, 1.0, 48000, 1.0);
+        let levels = compute_frame_levels(&samples, 48000, 30, 30);
+        let l = levels[29];
+        assert!((l.lufs + 3.01).abs() < 0.3, "lufs = {}", l.lufs);
+    }
+
+    #[test]
+    fn compute_frame_levels_frames_past_end_are_floor() {
+        let samples = sine(440.0, 0.5, 48000, 0.5);
+        let levels = compute_frame_levels(&samples, 48000, 30, 30);
+        assert_eq!(levels[29].rms, 0.0);
+    }
+
+    #[test]
+    fn frame_levels_streamed_in_chunks_match_the_whole() {
+        let mut samples = sine(997.0, 0.8, 48000, 2.0);
+        samples.extend(sine(60.0, 0.2, 48000, 1.5));
+        let whole = compute_frame_levels(&samples, 48000, 30, 110);
+        // Chunks shorter than a frame, and ones longer than the 400 ms loudness window that don't divide the frames
+        // evenly.
+        for chunk_samples in [1_000, 70_000] {
+            let mut stream = FrameLevelStream::new(48000, 30);
+            samples.chunks(chunk_samples).for_each(|chunk| stream.push(chunk));
+            assert_eq!(stream.finish(110), whole);
+        }
+    }
+
+    #[test]
+    fn track_levels_streamed_in_chunks_match_the_whole() {
+        let (left, right) = (sine(997.0, 0.5, 48000, 1.3), sine(60.0, 0.9, 48000, 1.3));
+        let whole = compute_track_levels(&[&left, &right], 48000);
+        let mut stream = TrackLevelStream::new(2, 48000);
+        for (left, right) in left.chunks(3_000).zip(right.chunks(3_000)) {
+            stream.push(&[left, right]);
+        }
+        assert_eq!(stream.finish(), whole);
+    }
+
+    #[test]
+    fn track_loudness_is_gated_and_sums_channels() {
+        let tone = sine(997.0, 0.1, 48000, 4.0);
+        let mono = compute_track_levels(&[&tone], 48000);
+        assert!((mono.integrated_lufs + 23.01).abs() < 0.3, "lufs = {}", mono.integrated_lufs);
+        // Silence after the tone falls below the gates instead of pulling the loudness down (ungated, 4 s of
+        // silence would take 3 dB off); only the blocks straddling the end of the tone count a little.
+        let mut padded = tone.clone();
+        padded.extend(vec![0.0; 48000 * 4]);
+        let gated = compute_track_levels(&[&padded], 48000);
+        assert!((gated.integrated_lufs - mono.integrated_lufs).abs() < 0.3, "lufs = {}", gated.integrated_lufs);
+        // The same signal in both channels is 3 dB louder than in one.
+        let stereo = compute_track_levels(&[&tone, &tone], 48000);
+        assert!((stereo.integrated_lufs - mono.integrated_lufs - 3.01).abs() < 0.1);
+        assert_eq!(compute_track_levels(&[&[0.0; 48000]], 48000).integrated_lufs, LEVEL_FLOOR_DB);
+    }
+
+    #[test]
+    fn track_peak_rms_and_clipping() {
+        let levels = compute_track_levels(&[&[0.5, -1.0, 0.2], &[1.2, 0.0, f32::NAN]], 48000);
+        assert_eq!(levels.peak, 1.2);
+        assert_eq!(levels.clipped_samples, 2);
+        let expected_rms = ((0.25 + 1.0 + 0.04 + 1.44) / 5.0f64).sqrt() as f32;
+        assert!((levels.rms - expected_rms).abs() < 1e-6);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn amplitude_to_db_floors_non_positive() {
+        use super::amplitude_to_db;
+
+        assert_eq!(amplitude_to_db(0.0), LEVEL_FLOOR_DB);
+        assert_eq!(amplitude_to_db(f32::NAN), LEVEL_FLOOR_DB);
+        assert!((amplitude_to_db(1.0)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,120 @@
+//! Audio spectrum videos from MP3 files, as a library: the binary's pipeline, stage by stage.
+//!
+//! - [`decode`] reads an MP3 into mono (and optionally stereo) f32 samples.
+//! - [`analyze`] turns samples into per-frame spectra, levels and beats (`analyze_file` does it for a whole file).
+//! - [`render`] draws a frame from a [`render::Config`] and a frame's bar heights.
+//! - [`encode`] writes the audio as WAV and pipes frames through ffmpeg into a video.
+//!
+//! With the `runner` feature (part of the default `cli`), `render::Renderer` takes a track through every stage with
+//! the command line's layers, overlays and encoding, and `cli::run_with_progress` runs a command line reporting to a
+//! `progress::ProgressSink` of your own; with `async`, `job::render_video` runs a `Renderer` as a tokio job with a
+//! progress stream. The example below needs a codec feature such as `mp3` for `decode_mp3` and `ffmpeg` for `Encoder`.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use audio_spectrum_generator::{analyze, decode, encode, render};
+//!
+//! # #[cfg(not(all(feature = "symphonia", feature = "ffmpeg")))]
+//! # fn main() {}
+//! # #[cfg(all(feature = "symphonia", feature = "ffmpeg"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let audio = decode::decode_mp3(Path::new("song.mp3"), false, None, |_, _| {})?;
+//! let config = render::Config::builder().size(1280, 720)?.bars(64)?.style(render::Style::Mirror).build()?;
+//! let (spectra, max) = analyze::compute_all_spectrums(
+//!     &audio.samples, audio.sample_rate, config.fft_size, config.overlap, config.bars, config.tilt,
+//! );
+//! let norm = if max > 0.0 { max } else { 1.0 };
+//! let frames = audio.samples.len() * config.fps as usize / audio.sample_rate as usize;
+//!
+//! encode::write_wav(Path::new("song.wav"), &audio.samples, audio.sample_rate)?;
+//! let mut encoder = encode::Encoder::start(
+//!     Path::new("ffmpeg"),
+//!     Path::new("song.mp4"),
+//!     encode::Container::Mp4,
+//!     &encode::EncodeSettings::default(),
+//!     config.fps,
+//!     (config.width, config.height),
+//!     Path::new("song.wav"),
+//!     |_| {},
+//! )?;
+//! for frame in 0..frames {
+//!     let index = analyze::spectrum_index(frame, spectra.len(), frames);
+//!     let heights = analyze::bar_heights(&spectra, norm, index, &config);
+//!     encoder.write_frame(&render::render_frame(&config, &heights))?;
+//! }
+//! encoder.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "symphonia")]
+mod analysis;
+pub mod analyze;
+#[cfg(feature = "runner")]
+mod background;
+mod beat;
+#[cfg(feature = "runner")]
+mod chapters;
+#[cfg(feature = "runner")]
+pub mod cli;
+mod colormap;
+mod config;
+#[cfg(any(feature = "runner", feature = "plugins"))]
+mod config_dir;
+#[cfg(feature = "runner")]
+mod config_file;
+pub mod decode;
+mod draw;
+#[cfg(feature = "runner")]
+mod duration;
+pub mod encode;
+pub mod error;
+mod effects;
+mod envelope;
+mod export;
+#[cfg(feature = "runner")]
+mod frame;
+#[cfg(feature = "runner")]
+mod interrupt;
+#[cfg(feature = "async")]
+pub mod job;
+mod level;
+#[cfg(any(feature = "runner", feature = "script"))]
+mod logging;
+#[cfg(feature = "runner")]
+mod memory;
+#[cfg(feature = "runner")]
+mod meter;
+#[cfg(feature = "native-encoder")]
+mod native;
+#[cfg(feature = "runner")]
+mod overlay;
+mod output;
+#[cfg(feature = "runner")]
+mod palette;
+mod particles;
+#[cfg(feature = "runner")]
+mod pipeline;
+#[cfg(feature = "plugins")]
+mod plugin;
+#[cfg(feature = "runner")]
+mod preset;
+pub mod progress;
+pub mod render;
+#[cfg(feature = "runner")]
+mod renderer;
+#[cfg(feature = "runner")]
+mod scene;
+#[cfg(feature = "script")]
+mod script;
+mod spectrum;
+#[cfg(feature = "runner")]
+mod subtitles;
+#[cfg(any(feature = "runner", feature = "script"))]
+mod text;
+#[cfg(feature = "runner")]
+mod theme;
+mod visualizer;
+mod wav;
+#[cfg(feature = "cli")]
+mod wizard;
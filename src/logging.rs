@@ -0,0 +1,282 @@
+//! Status messages on stderr, filtered by `-q`/`-v`/`-vv`, the progress bars' shared look, and the stage timings `-v`
+//! reports and a finished render sums up. With the `cli` feature, messages are printed through the progress bars'
+//! `MultiProgress` so they don't tear a bar that is being drawn. All of it belongs to a `RunLog`, one per run, which
+//! the run's threads enter, so runs in the same process keep their levels and timings apart.
+
+use std::cell::RefCell;
+use std::fmt;
+#[cfg(feature = "runner")]
+use std::sync::Mutex;
+#[cfg(feature = "cli")]
+use std::sync::OnceLock;
+use std::sync::{Arc, LazyLock};
+#[cfg(feature = "runner")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "cli")]
+use indicatif::{MultiProgress, ProgressDrawTarget, ProgressStyle};
+
+/// How much a message matters; only messages at or below the chosen level are shown.
+// Without the command line (`-v`), nothing logs at the two verbose levels.
+#[cfg_attr(not(feature = "runner"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Something the user may not expect (a mono input, missing tags); kept with `-q`.
+    Warn,
+    /// What is being done and what was written, plus the progress bars (the default).
+    Info,
+    /// Details about the input and the stage timings (`-v`).
+    Verbose,
+    /// Internals such as the ffmpeg command line and the worker count (`-vv`).
+    Debug,
+}
+
+#[cfg(feature = "runner")]
+impl Level {
+    /// The level for the `-q` flag and the number of `-v` flags.
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Warn,
+            (false, 0) => Self::Info,
+            (false, 1) => Self::Verbose,
+            (false, _) => Self::Debug,
+        }
+    }
+}
+
+/// One run's level, progress bars and stage timings.
+pub struct RunLog {
+    max: Level,
+    #[cfg(feature = "cli")]
+    progress: OnceLock<MultiProgress>,
+    /// Each finished `Stage` and how long it took, in order.
+    #[cfg(feature = "runner")]
+    timings: Mutex<Vec<(&'static str, Duration)>>,
+}
+
+/// What a thread logs to outside any run: `Level::Info`.
+static OUTSIDE_RUNS: LazyLock<Arc<RunLog>> = LazyLock::new(|| RunLog::new(Level::Info));
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<RunLog>>> = const { RefCell::new(None) };
+}
+
+impl RunLog {
+    fn new(max: Level) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            #[cfg(feature = "cli")]
+            progress: OnceLock::new(),
+            #[cfg(feature = "runner")]
+            timings: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+/// The thread is in a run's log until this is dropped, which returns it to the one it was in before.
+#[cfg(feature = "runner")]
+pub struct Entered {
+    previous: Option<Arc<RunLog>>,
+}
+
+#[cfg(feature = "runner")]
+impl Drop for Entered {
+    fn drop(&mut self) {
+        CURRENT.set(self.previous.take());
+    }
+}
+
+/// Start a run's log, showing messages up to `max`, on this thread. Threads the run spawns `enter` it.
+#[cfg(feature = "runner")]
+pub fn init(max: Level) -> Entered {
+    enter(RunLog::new(max))
+}
+
+/// Log to `log` on this thread until the guard is dropped.
+#[cfg(feature = "runner")]
+pub fn enter(log: Arc<RunLog>) -> Entered {
+    Entered { previous: CURRENT.replace(Some(log)) }
+}
+
+/// The log this thread writes to, for the threads it spawns.
+pub fn current() -> Arc<RunLog> {
+    CURRENT.with_borrow(|log| log.clone()).unwrap_or_else(|| OUTSIDE_RUNS.clone())
+}
+
+/// The level this thread's log shows messages up to, for a run started within it.
+#[cfg(feature = "runner")]
+pub fn level() -> Level {
+    current().max
+}
+
+/// Whether messages at `level` are shown.
+pub fn enabled(level: Level) -> bool {
+    level <= current().max
+}
+
+/// The progress bars' container, hidden below `Level::Info` (`-q`).
+#[cfg(feature = "cli")]
+pub fn progress() -> MultiProgress {
+    let log = current();
+    log.progress
+        .get_or_init(|| {
+            if log.max >= Level::Info {
+                MultiProgress::new()
+            } else {
+                MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+            }
+        })
+        .clone()
+}
+
+/// The look every stage's bar shares: the stage name, elapsed time, the bar in `colors`, the count of `unit`s and
+/// the time left at the current rate, as in `Rendering  [00:01:02] =====>---- 1500/7054 frames, ETA 4m`.
+#[cfg(feature = "cli")]
+pub fn stage_style(stage: &str, unit: &str, colors: &str) -> ProgressStyle {
+    let template = format!("{stage:<10} [{{elapsed_precise}}] {{bar:40.{colors}}} {{pos}}/{{len}} {unit}, ETA {{eta}}");
+    ProgressStyle::default_bar().template(&template).unwrap().progress_chars("=>-")
+}
+
+/// Print a message at `level` if it is enabled. Use the `warning!`, `info!`, `verbose!` and `debug!` macros.
+pub fn write(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let print = || match level {
+        Level::Warn => eprintln!("Warning: {}", args),
+        _ => eprintln!("{}", args),
+    };
+    #[cfg(feature = "cli")]
+    progress().suspend(print);
+    #[cfg(not(feature = "cli"))]
+    print();
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Warn, format_args!($($arg)*)) };
+}
+#[cfg(feature = "runner")]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Info, format_args!($($arg)*)) };
+}
+#[cfg(feature = "runner")]
+macro_rules! verbose {
+    ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Verbose, format_args!($($arg)*)) };
+}
+#[cfg(feature = "runner")]
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Debug, format_args!($($arg)*)) };
+}
+pub(crate) use warning;
+#[cfg(feature = "runner")]
+pub(crate) use {debug, info, verbose};
+
+/// A processing stage; `finish` reports how long it took at `Level::Verbose` and records it for `summarize`.
+#[cfg(feature = "runner")]
+pub struct Stage {
+    name: &'static str,
+    start: Instant,
+}
+
+#[cfg(feature = "runner")]
+impl Stage {
+    pub fn start(name: &'static str) -> Self {
+        Self { name, start: Instant::now() }
+    }
+
+    pub fn finish(self) {
+        let elapsed = self.start.elapsed();
+        verbose!("{} took {}", self.name, format_duration(elapsed));
+        current().timings.lock().unwrap().push((self.name, elapsed));
+    }
+}
+
+/// Print the time each stage finished so far took and its share of `total`, the stage spanning the whole run.
+#[cfg(feature = "runner")]
+pub fn summarize(total: Stage) {
+    let log = current();
+    let timings = log.timings.lock().unwrap();
+    info!("Time per stage:\n{}", stage_table(&timings, total.start.elapsed()).join("\n"));
+}
+
+/// One aligned line per stage (name, time, share of `total`) and a last one for the total.
+#[cfg(feature = "runner")]
+fn stage_table(timings: &[(&str, Duration)], total: Duration) -> Vec<String> {
+    let width = timings.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("Total".len());
+    let share = |duration: &Duration| 100.0 * duration.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON);
+    timings
+        .iter()
+        .map(|(name, duration)| {
+            format!("  {name:<width$}  {:>9}  {:>3.0}%", format_duration(*duration), share(duration))
+        })
+        .chain([format!("  {:<width$}  {:>9}", "Total", format_duration(total))])
+        .collect()
+}
+
+/// A duration in milliseconds under a second, else in seconds with two decimals.
+#[cfg(feature = "runner")]
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{:.2} s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(all(test, feature = "runner"))]
+mod tests {
+    use super::{current, enabled, enter, format_duration, init, stage_table, Level};
+    use std::time::Duration;
+
+    #[test]
+    fn quiet_wins_and_each_v_adds_a_level() {
+        assert_eq!(Level::from_flags(false, 0), Level::Info);
+        assert_eq!(Level::from_flags(false, 1), Level::Verbose);
+        assert_eq!(Level::from_flags(false, 2), Level::Debug);
+        assert_eq!(Level::from_flags(false, 5), Level::Debug);
+        assert_eq!(Level::from_flags(true, 0), Level::Warn);
+        assert!(Level::Warn < Level::Info && Level::Verbose < Level::Debug);
+    }
+
+    #[test]
+    fn each_run_keeps_its_own_level_until_it_ends() {
+        assert!(!enabled(Level::Verbose));
+        let quiet = init(Level::Warn);
+        assert!(!enabled(Level::Info));
+        let log = current();
+        std::thread::spawn(move || {
+            assert!(enabled(Level::Info));
+            let _entered = enter(log);
+            assert!(!enabled(Level::Info));
+        })
+        .join()
+        .unwrap();
+        {
+            let _verbose = init(Level::Verbose);
+            assert!(enabled(Level::Verbose));
+        }
+        assert!(!enabled(Level::Info));
+        drop(quiet);
+        assert!(enabled(Level::Info) && !enabled(Level::Verbose));
+    }
+
+    #[test]
+    fn durations_switch_to_seconds_at_one_second() {
+        assert_eq!(format_duration(Duration::from_millis(450)), "450 ms");
+        assert_eq!(format_duration(Duration::from_millis(1234)), "1.23 s");
+        assert_eq!(format_duration(Duration::from_secs(75)), "75.00 s");
+    }
+
+    #[test]
+    fn stage_table_aligns_times_and_gives_each_stage_its_share() {
+        let timings = [("Decoding and analysis", Duration::from_secs(2)), ("Rendering", Duration::from_secs(15))];
+        assert_eq!(
+            stage_table(&timings, Duration::from_secs(20)),
+            [
+                "  Decoding and analysis     2.00 s   10%",
+                "  Rendering                15.00 s   75%",
+                "  Total                    20.00 s",
+            ]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! `--max-memory`: what a render holds at its peak, and the render workers and frames in flight that keep its working
+//! memory under a budget. The samples stream through the analysis and aren't kept, but the spectra (and a timeline's
+//! envelope) are held for the whole render and grow with the track, so they are reported but not bounded.
+
+/// Held whatever the options: the program, fonts, images, the encoder's pipe, the analysis streams' windows and the
+/// per-frame data.
+const BASELINE_BYTES: u64 = 64 << 20;
+/// Frames each render worker holds: one waiting in the queue, one in the writer's reorder buffer, and the layer
+/// buffers it draws into.
+const FRAMES_PER_WORKER: u64 = 4;
+/// Bytes a `Vec` of bars costs beyond its values.
+const VEC_BYTES: u64 = 24;
+
+/// The sizes a render's buffers follow.
+#[derive(Clone, Copy, Debug)]
+pub struct Footprint {
+    /// Spectrum frames and bars per frame, and how many sets of them (three with `--stereo-split`).
+    pub spectrum_frames: u64,
+    pub bars: u64,
+    pub spectra: u64,
+    /// Points of the envelope a timeline strip is cut from, if one is drawn.
+    pub timeline_points: u64,
+    /// Bytes of one RGBA video frame.
+    pub frame_bytes: u64,
+}
+
+/// How the render is laid out in memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryPlan {
+    pub workers: usize,
+    /// Frames rendered ahead of the encoder, twice the workers.
+    pub in_flight: usize,
+}
+
+impl Footprint {
+    /// The spectra and timeline envelope, held from decoding to the last frame whatever the plan.
+    pub fn track_bytes(&self) -> u64 {
+        self.spectra * self.spectrum_frames * (self.bars * 4 + VEC_BYTES) + self.timeline_points * 8
+    }
+
+    /// What `plan` sizes: the workers' frames.
+    pub fn working_bytes(&self, plan: &MemoryPlan) -> u64 {
+        plan.workers as u64 * FRAMES_PER_WORKER * self.frame_bytes
+    }
+
+    /// The most a render laid out as `plan` holds at once.
+    pub fn peak_bytes(&self, plan: &MemoryPlan) -> u64 {
+        BASELINE_BYTES + self.track_bytes() + self.working_bytes(plan)
+    }
+
+    /// Without a budget: a worker per core.
+    pub fn unbounded(&self, cores: usize) -> MemoryPlan {
+        MemoryPlan { workers: cores, in_flight: cores * 2 }
+    }
+
+    /// The fastest plan whose working memory fits in `budget` bytes: as many workers as fit, up to `cores`. Fails,
+    /// naming a budget that would do, when one worker doesn't fit.
+    pub fn plan(&self, cores: usize, budget: u64) -> Result<MemoryPlan, String> {
+        let worker_bytes = FRAMES_PER_WORKER * self.frame_bytes;
+        if budget < worker_bytes {
+            return Err(format!(
+                "--max-memory {} is too small: a render worker needs {} for its frames; use --max-memory {} or more",
+                format_size(budget),
+                format_size(worker_bytes),
+                format_size(worker_bytes)
+            ));
+        }
+        let workers = ((budget / worker_bytes) as usize).min(cores);
+        Ok(MemoryPlan { workers, in_flight: workers * 2 })
+    }
+}
+
+/// `bytes` in the notation `--max-memory` takes, rounded up: `1.3G`, `640M`, `12K`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [(u64, &str); 3] = [(1 << 30, "G"), (1 << 20, "M"), (1 << 10, "K")];
+    match UNITS.iter().find(|&&(unit, _)| bytes >= unit) {
+        Some(&(unit, suffix)) if bytes >= 10 * unit => format!("{}{}", bytes.div_ceil(unit), suffix),
+        Some(&(unit, suffix)) => format!("{:.1}{}", (bytes as f64 / unit as f64 * 10.0).ceil() / 10.0, suffix),
+        None => bytes.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, Footprint, MemoryPlan};
+
+    fn footprint() -> Footprint {
+        // Four minutes at 44.1 kHz, 64 bars at 1024/0.5, 1920x1080.
+        let spectrum_frames = 240 * 44_100 / 512;
+        Footprint { spectrum_frames, bars: 64, spectra: 1, timeline_points: 0, frame_bytes: 1920 * 1080 * 4 }
+    }
+
+    #[test]
+    fn a_budget_limits_the_workers_and_the_plan_fits_it() {
+        let footprint = footprint();
+        let unbounded = footprint.unbounded(8);
+        assert_eq!(unbounded, MemoryPlan { workers: 8, in_flight: 16 });
+        let plan = footprint.plan(8, 128 << 20).unwrap();
+        assert!(plan.workers < 8 && plan.workers >= 1 && plan.in_flight == plan.workers * 2);
+        assert!(footprint.working_bytes(&plan) <= 128 << 20);
+        assert!(footprint.peak_bytes(&plan) > footprint.track_bytes() + footprint.working_bytes(&plan));
+        // A generous budget changes nothing.
+        assert_eq!(footprint.plan(8, 16 << 30).unwrap(), unbounded);
+    }
+
+    #[test]
+    fn a_budget_below_one_worker_names_one_that_fits() {
+        let error = footprint().plan(8, 16 << 20).unwrap_err();
+        assert!(error.starts_with("--max-memory 16M is too small"), "{}", error);
+        let needed = error.rsplit("--max-memory ").next().unwrap().trim_end_matches(" or more");
+        assert_eq!(needed, "32M");
+    }
+
+    #[test]
+    fn the_track_data_is_not_counted_against_the_budget() {
+        // Ten hours of audio hold far more than the budget, which still sizes the working memory.
+        let long = Footprint { spectrum_frames: 36_000 * 44_100 / 512, ..footprint() };
+        let plan = long.plan(8, 64 << 20).unwrap();
+        assert!(long.track_bytes() > 64 << 20);
+        assert!(long.working_bytes(&plan) <= 64 << 20);
+    }
+
+    #[test]
+    fn sizes_round_up_in_the_largest_unit() {
+        assert_eq!(format_size(2 << 30), "2.0G");
+        assert_eq!(format_size((1 << 30) + 1), "1.1G");
+        assert_eq!(format_size(640 << 20), "640M");
+        assert_eq!(format_size(1000), "1000");
+    }
+}
@@ -0,0 +1,120 @@
+//! Level meter ballistics (VU / PPM) driven by the per-frame RMS level
+
+use crate::level::{amplitude_to_db, LEVEL_FLOOR_DB};
+
+/// How a level meter is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MeterStyle {
+    /// Vertical bar filling from the bottom.
+    Vertical,
+    /// Analog dial with a swinging needle.
+    Needle,
+}
+
+/// How fast a level meter follows the signal, and the scale it shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MeterBallistics {
+    /// Volume unit meter: symmetric ~300 ms rise and fall; scale -20 to +3 VU with 0 VU at -18 dBFS.
+    #[default]
+    Vu,
+    /// Peak programme meter: near-instant rise, slow fall (about 24 dB in 2.8 s); scale -50 to 0 dBFS.
+    Ppm,
+}
+
+/// First-order time constant (seconds) that reaches 99% of a step in 300 ms, like a VU needle.
+const VU_TIME_CONSTANT: f32 = 0.065;
+
+/// PPM fall-back rate (dB per second).
+const PPM_RELEASE_DB_PER_SEC: f32 = 24.0 / 2.8;
+
+impl MeterBallistics {
+    /// Displayed range (bottom, top) in dBFS.
+    pub fn range_db(self) -> (f32, f32) {
+        match self {
+            MeterBallistics::Vu => (-38.0, -15.0),
+            MeterBallistics::Ppm => (-50.0, 0.0),
+        }
+    }
+
+    /// Level (dBFS) above which the scale is drawn red: 0 VU, or -9 dBFS for PPM.
+    pub fn red_zone_db(self) -> f32 {
+        match self {
+            MeterBallistics::Vu => -18.0,
+            MeterBallistics::Ppm => -9.0,
+        }
+    }
+
+    /// Position (0.0–1.0) of `db` on the meter scale.
+    pub fn position(self, db: f32) -> f32 {
+        let (lo, hi) = self.range_db();
+        ((db - lo) / (hi - lo)).clamp(0.0, 1.0)
+    }
+}
+
+/// Smoothed meter reading, updated once per video frame.
+#[derive(Clone, Copy, Debug)]
+pub struct MeterFollower {
+    ballistics: MeterBallistics,
+    level_db: f32,
+}
+
+impl MeterFollower {
+    pub fn new(ballistics: MeterBallistics) -> Self {
+        Self {
+            ballistics,
+            level_db: LEVEL_FLOOR_DB,
+        }
+    }
+
+    /// Advance one frame (1 / `fps` seconds) toward the frame's `rms` and return the meter position (0.0–1.0).
+    pub fn update(&mut self, rms: f32, fps: u32) -> f32 {
+        let dt = 1.0 / fps.max(1) as f32;
+        // Start from the bottom of the scale so the first frames rise instead of jumping from the silence floor.
+        let (lo, _) = self.ballistics.range_db();
+        let current = self.level_db.max(lo);
+        let target = amplitude_to_db(rms).max(lo);
+        self.level_db = match self.ballistics {
+            MeterBallistics::Vu => current + (target - current) * (1.0 - (-dt / VU_TIME_CONSTANT).exp()),
+            MeterBallistics::Ppm if target >= current => target,
+            MeterBallistics::Ppm => (current - PPM_RELEASE_DB_PER_SEC * dt).max(target),
+        };
+        self.ballistics.position(self.level_db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeterBallistics, MeterFollower};
+
+    #[test]
+    fn position_maps_range_to_unit() {
+        let vu = MeterBallistics::Vu;
+        assert_eq!(vu.position(-38.0), 0.0);
+        assert_eq!(vu.position(-15.0), 1.0);
+        assert_eq!(vu.position(-100.0), 0.0);
+        assert!((vu.position(vu.red_zone_db()) - 20.0 / 23.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn vu_rises_gradually_and_settles() {
+        let mut meter = MeterFollower::new(MeterBallistics::Vu);
+        let rms = 10f32.powf(-18.0 / 20.0); // 0 VU
+        let first = meter.update(rms, 30);
+        assert!(first > 0.0 && first < 0.8, "first = {}", first);
+        let mut last = first;
+        for _ in 0..30 {
+            last = meter.update(rms, 30);
+        }
+        assert!((last - MeterBallistics::Vu.position(-18.0)).abs() < 1e-3, "last = {}", last);
+    }
+
+    #[test]
+    fn ppm_jumps_up_and_falls_slowly() {
+        let mut meter = MeterFollower::new(MeterBallistics::Ppm);
+        let peak = meter.update(1.0, 10);
+        assert_eq!(peak, 1.0);
+        // One frame of silence at 10 fps falls by about 0.86 dB of the 50 dB scale.
+        let after = meter.update(0.0, 10);
+        assert!((after - (1.0 - 0.857 / 50.0)).abs() < 1e-3, "after = {}", after);
+    }
+}
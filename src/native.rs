@@ -0,0 +1,219 @@
+//! Built-in H.264/MP4 encoder (`--encoder native`, `native-encoder` feature): OpenH264 encodes the frames and the
+//! mp4 crate muxes them, so a video can be written without an ffmpeg install. The output has no audio track.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::RgbaImage;
+use mp4::{AvcConfig, MediaConfig, Mp4Config, Mp4Sample, Mp4Writer, TrackConfig, TrackType};
+use openh264::encoder::{EncoderConfig, FrameType, RateControlMode};
+use openh264::formats::{RgbaSliceU8, YUVBuffer};
+use openh264::OpenH264API;
+
+use crate::output::{Codec, Container, EncodeSettings, VideoEncoder};
+
+/// Bits per pixel per frame when no `--video-bitrate` is given (about 6 Mbit/s for 1080p30).
+const DEFAULT_BITS_PER_PIXEL: f64 = 0.1;
+/// H.264 NAL unit types of the parameter sets, which go into the `avcC` box rather than the samples.
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+/// Track ID the mp4 writer gives the first (only) track.
+const VIDEO_TRACK: u32 = 1;
+
+/// Check that `settings` only ask for what the native encoder does: H.264 in MP4, at an optional bitrate.
+pub fn validate(container: Container, settings: &EncodeSettings) -> Result<(), &'static str> {
+    let video = &settings.video;
+    if container != Container::Mp4 {
+        return Err("--encoder native only writes .mp4");
+    }
+    if video.alpha {
+        return Err("--encoder native cannot write --transparent video");
+    }
+    if video.codec.is_some_and(|codec| codec != Codec::H264) {
+        return Err("--encoder native only encodes H.264");
+    }
+    if video.hwaccel.is_some() || video.crf.is_some() || video.x264_preset.is_some() {
+        return Err("--hwaccel, --crf and --x264-preset need --encoder ffmpeg");
+    }
+    if settings.audio.codec.is_some() || settings.audio.bitrate.is_some() {
+        return Err("--encoder native writes no audio; --audio-codec and --audio-bitrate need --encoder ffmpeg");
+    }
+    if !settings.extra_args.is_empty() {
+        return Err("--ffmpeg-args needs --encoder ffmpeg");
+    }
+    Ok(())
+}
+
+/// Convert a bitrate in ffmpeg's notation (`8M`, `2500k`, `800000`) to bits per second.
+fn bitrate_bps(s: &str) -> u32 {
+    let (number, scale) = match s.chars().last() {
+        Some('k' | 'K') => (&s[..s.len() - 1], 1e3),
+        Some('m' | 'M') => (&s[..s.len() - 1], 1e6),
+        _ => (s, 1.0),
+    };
+    (number.parse::<f64>().unwrap_or(0.0) * scale) as u32
+}
+
+/// Strip the Annex B start code (`00 00 01` or `00 00 00 01`) OpenH264 puts before each NAL unit.
+fn strip_start_code(nal: &[u8]) -> &[u8] {
+    let zeros = nal.iter().take_while(|&&b| b == 0).count();
+    match nal.get(zeros) {
+        Some(1) if zeros >= 2 => &nal[zeros + 1..],
+        _ => nal,
+    }
+}
+
+/// Encodes frames with OpenH264 and writes them to an MP4 file.
+pub struct NativeEncoder {
+    encoder: openh264::encoder::Encoder,
+    writer: Mp4Writer<BufWriter<File>>,
+    yuv: YUVBuffer,
+    size: (u32, u32),
+    fps: u32,
+    track_added: bool,
+    /// Last encoded frame, held back so frames the rate control skips can lengthen it.
+    pending: Option<Mp4Sample>,
+}
+
+impl NativeEncoder {
+    /// Create `output` for `size` frames at `fps`, at the `--video-bitrate` in `settings` or a default for the size.
+    pub fn create(
+        output: &Path,
+        settings: &EncodeSettings,
+        fps: u32,
+        size: (u32, u32),
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if !size.0.is_multiple_of(2) || !size.1.is_multiple_of(2) {
+            return Err(format!("--encoder native needs an even frame size, got {}x{}", size.0, size.1).into());
+        }
+        let bitrate = match settings.video.bitrate.as_deref() {
+            Some(bitrate) => bitrate_bps(bitrate),
+            None => (size.0 as f64 * size.1 as f64 * fps as f64 * DEFAULT_BITS_PER_PIXEL) as u32,
+        };
+        let config = EncoderConfig::new()
+            .set_bitrate_bps(bitrate)
+            .max_frame_rate(fps as f32)
+            .rate_control_mode(RateControlMode::Bitrate);
+        let encoder = openh264::encoder::Encoder::with_api_config(OpenH264API::from_source(), config)?;
+        let file = File::create(output).map_err(|e| format!("failed to create {:?}: {}", output, e))?;
+        let mp4_config = Mp4Config {
+            major_brand: "isom".parse()?,
+            minor_version: 512,
+            compatible_brands: vec!["isom".parse()?, "iso2".parse()?, "avc1".parse()?, "mp41".parse()?],
+            timescale: 1000,
+        };
+        let writer = Mp4Writer::write_start(BufWriter::new(file), &mp4_config)?;
+        let yuv = YUVBuffer::new(size.0 as usize, size.1 as usize);
+        Ok(Self { encoder, writer, yuv, size, fps, track_added: false, pending: None })
+    }
+
+    /// Encode the next frame (its alpha is ignored).
+    pub fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let dimensions = (self.size.0 as usize, self.size.1 as usize);
+        self.yuv.read_rgb(RgbaSliceU8::new(frame.as_raw(), dimensions));
+        let bitstream = self.encoder.encode(&self.yuv)?;
+        let frame_type = bitstream.frame_type();
+        if frame_type == FrameType::Skip {
+            if let Some(ref mut pending) = self.pending {
+                pending.duration += 1;
+            }
+            return Ok(());
+        }
+        let is_sync = matches!(frame_type, FrameType::IDR | FrameType::I);
+        let (mut sps, mut pps, mut sample) = (None, None, Vec::new());
+        for layer in (0..bitstream.num_layers()).filter_map(|i| bitstream.layer(i)) {
+            for nal in (0..layer.nal_count()).filter_map(|i| layer.nal_unit(i)).map(strip_start_code) {
+                match nal.first().map(|b| b & 0x1f) {
+                    Some(NAL_SPS) => sps = Some(nal.to_vec()),
+                    Some(NAL_PPS) => pps = Some(nal.to_vec()),
+                    Some(_) => {
+                        // MP4 samples hold length-prefixed NAL units.
+                        sample.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                        sample.extend_from_slice(nal);
+                    }
+                    None => {}
+                }
+            }
+        }
+        // The track needs the parameter sets, which OpenH264 emits with the first keyframe.
+        if !self.track_added {
+            let (Some(seq_param_set), Some(pic_param_set)) = (sps, pps) else {
+                return Err("OpenH264 did not start with a keyframe".into());
+            };
+            self.writer.add_track(&TrackConfig {
+                track_type: TrackType::Video,
+                timescale: self.fps,
+                language: "und".to_string(),
+                media_conf: MediaConfig::AvcConfig(AvcConfig {
+                    width: self.size.0 as u16,
+                    height: self.size.1 as u16,
+                    seq_param_set,
+                    pic_param_set,
+                }),
+            })?;
+            self.track_added = true;
+        }
+        let sample = Mp4Sample { start_time: 0, duration: 1, rendering_offset: 0, is_sync, bytes: sample.into() };
+        if let Some(previous) = self.pending.replace(sample) {
+            self.writer.write_sample(VIDEO_TRACK, &previous)?;
+        }
+        Ok(())
+    }
+
+    /// Write the MP4 index and flush the file.
+    pub fn finish(mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(last) = self.pending.take() {
+            self.writer.write_sample(VIDEO_TRACK, &last)?;
+        }
+        self.writer.write_end()?;
+        self.writer.into_writer().flush()?;
+        Ok(())
+    }
+}
+
+impl VideoEncoder for NativeEncoder {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        NativeEncoder::write_frame(self, frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        NativeEncoder::finish(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bitrate_bps, strip_start_code, validate};
+    use crate::output::{Codec, Container, EncodeSettings, VideoSettings};
+
+    fn settings(video: VideoSettings) -> EncodeSettings {
+        EncodeSettings { video, ..Default::default() }
+    }
+
+    #[test]
+    fn bitrate_suffixes_scale() {
+        assert_eq!(bitrate_bps("8M"), 8_000_000);
+        assert_eq!(bitrate_bps("2500k"), 2_500_000);
+        assert_eq!(bitrate_bps("800000"), 800_000);
+    }
+
+    #[test]
+    fn start_codes_are_stripped() {
+        assert_eq!(strip_start_code(&[0, 0, 0, 1, 0x67, 0x42]), &[0x67, 0x42]);
+        assert_eq!(strip_start_code(&[0, 0, 1, 0x68]), &[0x68]);
+        assert_eq!(strip_start_code(&[0x65, 0x88]), &[0x65, 0x88]);
+    }
+
+    #[test]
+    fn only_h264_mp4_without_ffmpeg_options() {
+        assert!(validate(Container::Mp4, &settings(VideoSettings::default())).is_ok());
+        let h264 = VideoSettings { codec: Some(Codec::H264), bitrate: Some("4M".into()), ..Default::default() };
+        assert!(validate(Container::Mp4, &settings(h264)).is_ok());
+        assert!(validate(Container::Webm, &settings(VideoSettings::default())).is_err());
+        let hevc = VideoSettings { codec: Some(Codec::Hevc), ..Default::default() };
+        assert!(validate(Container::Mp4, &settings(hevc)).is_err());
+        let crf = VideoSettings { crf: Some(20), ..Default::default() };
+        assert!(validate(Container::Mp4, &settings(crf)).is_err());
+    }
+}
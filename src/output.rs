@@ -0,0 +1,1245 @@
+//! Output container formats, the ffmpeg encoder settings for each, and the ffmpeg process frames are piped into
+
+#[cfg(feature = "ffmpeg")]
+use std::ffi::OsString;
+#[cfg(feature = "ffmpeg")]
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "ffmpeg")]
+use std::process::{Child, ChildStdin, Command, Stdio};
+#[cfg(feature = "ffmpeg")]
+use std::thread::JoinHandle;
+
+use image::RgbaImage;
+
+use crate::error::SpectrumError;
+
+/// Container of the output video, chosen by the output file's extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    /// H.264 (or `--codec`) video and AAC audio (any extension other than `.mov` and `.webm`).
+    Mp4,
+    /// QuickTime: H.264/AAC like MP4, or ProRes 4444 when the video keeps its alpha channel.
+    Mov,
+    /// VP9 (or AV1) video and Opus audio, for web embeds and platforms that prefer WebM.
+    Webm,
+    /// HLS playlist (`.m3u8`) with H.264/AAC MPEG-TS segments written next to it, for streaming players.
+    Hls,
+}
+
+/// Video codec for MP4/MOV (or AV1 in WebM) output (`--codec`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+pub enum Codec {
+    /// H.264 (libx264): plays everywhere.
+    #[default]
+    H264,
+    /// H.265/HEVC (libx265): about half the size of H.264 at the same quality.
+    Hevc,
+    /// AV1 (libsvtav1): smallest files, slower to encode and not supported by older players.
+    Av1,
+}
+
+impl Codec {
+    fn name(self) -> &'static str {
+        match self {
+            Codec::H264 => "h264",
+            Codec::Hevc => "hevc",
+            Codec::Av1 => "av1",
+        }
+    }
+}
+
+/// Hardware encoder used in place of libx264 / libx265 (`--hwaccel`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+pub enum HwAccel {
+    /// NVIDIA NVENC (h264_nvenc, hevc_nvenc).
+    Nvenc,
+    /// Apple VideoToolbox (h264_videotoolbox, hevc_videotoolbox).
+    Videotoolbox,
+    /// Intel Quick Sync Video (h264_qsv, hevc_qsv).
+    Qsv,
+    /// VA-API on Linux (h264_vaapi, hevc_vaapi), using the first render node.
+    Vaapi,
+}
+
+/// Render node VA-API encodes on.
+#[cfg(feature = "ffmpeg")]
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+impl HwAccel {
+    /// ffmpeg encoder name for `codec` (H.264 or HEVC; see `VideoSettings::validate`).
+    pub fn encoder(self, codec: Codec) -> String {
+        let api = match self {
+            HwAccel::Nvenc => "nvenc",
+            HwAccel::Videotoolbox => "videotoolbox",
+            HwAccel::Qsv => "qsv",
+            HwAccel::Vaapi => "vaapi",
+        };
+        format!("{}_{}", codec.name(), api)
+    }
+
+    /// ffmpeg arguments that go before the inputs (device setup).
+    #[cfg(feature = "ffmpeg")]
+    fn global_args(self) -> Vec<&'static str> {
+        match self {
+            HwAccel::Vaapi => vec!["-vaapi_device", VAAPI_DEVICE],
+            _ => Vec::new(),
+        }
+    }
+
+    /// ffmpeg video codec arguments, including the upload to the device's pixel format.
+    fn video_args(self, codec: Codec) -> Vec<String> {
+        let encoder = self.encoder(codec);
+        let args: Vec<&str> = match self {
+            HwAccel::Vaapi => vec!["-vf", "format=nv12,hwupload", "-c:v", &encoder],
+            HwAccel::Qsv => vec!["-c:v", &encoder, "-pix_fmt", "nv12"],
+            HwAccel::Nvenc | HwAccel::Videotoolbox => vec!["-c:v", &encoder, "-pix_fmt", "yuv420p"],
+        };
+        let mut args: Vec<String> = args.into_iter().map(String::from).collect();
+        if codec == Codec::Hevc {
+            args.extend(HEVC_TAG.map(String::from));
+        }
+        args
+    }
+
+    /// Check that the `ffmpeg` build has the `codec` encoder and the hardware accepts it, by encoding one small test
+    /// frame.
+    #[cfg(feature = "ffmpeg")]
+    pub fn probe(self, ffmpeg: &Path, codec: Codec) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let output = Command::new(ffmpeg)
+            .args(["-v", "error"])
+            .args(self.global_args())
+            .args(["-f", "lavfi", "-i", "color=size=256x256:rate=1", "-frames:v", "1"])
+            .args(self.video_args(codec))
+            .args(["-f", "null", "-"])
+            .output()
+            .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+        if !output.status.success() {
+            let log = String::from_utf8_lossy(&output.stderr);
+            let reason = log.lines().rfind(|line| !line.trim().is_empty()).unwrap_or("unknown error");
+            let encoder = self.encoder(codec);
+            let message = format!("--hwaccel: {} is not available on this system ({})", encoder, reason);
+            return Err(SpectrumError::EncodeFailed(message).into());
+        }
+        Ok(())
+    }
+}
+
+/// Output path (`-o -`) that streams the video to stdout.
+#[cfg(feature = "ffmpeg")]
+const STDOUT: &str = "-";
+
+/// Fragmented MP4/MOV for stdout: ffmpeg can't seek back to write the index, so each keyframe starts a
+/// self-contained fragment and the header goes first.
+const FRAGMENTED_MOVFLAGS: [&str; 2] = ["-movflags", "frag_keyframe+empty_moov"];
+
+/// Container of the video streamed to stdout (`--format`), which has no extension to choose by.
+#[cfg(feature = "runner")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StreamFormat {
+    /// Fragmented MP4 (H.264/AAC).
+    #[default]
+    Mp4,
+    /// Fragmented QuickTime, e.g. for ProRes 4444 with `--transparent`.
+    Mov,
+    /// WebM (VP9/Opus), which streams as it is.
+    Webm,
+}
+
+#[cfg(feature = "runner")]
+impl StreamFormat {
+    pub fn container(self) -> Container {
+        match self {
+            Self::Mp4 => Container::Mp4,
+            Self::Mov => Container::Mov,
+            Self::Webm => Container::Webm,
+        }
+    }
+}
+
+/// The ffprobe that ships next to `ffmpeg`; plain `ffprobe` from PATH when `ffmpeg` is a bare command name.
+#[cfg(feature = "ffmpeg")]
+pub fn ffprobe_path(ffmpeg: &Path) -> PathBuf {
+    match ffmpeg.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(format!("ffprobe{}", std::env::consts::EXE_SUFFIX)),
+        _ => PathBuf::from("ffprobe"),
+    }
+}
+
+/// Whether `output` is `-`, i.e. the encoded video goes to stdout.
+#[cfg(feature = "ffmpeg")]
+pub fn writes_to_stdout(output: &Path) -> bool {
+    output == Path::new(STDOUT)
+}
+
+/// File of frame `index` in a `--frames-only` directory.
+pub fn frame_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("frame_{:06}.png", index))
+}
+
+/// Remove the frames in `dir` from index `first` on, returning how many there were. Frames are written in order, so
+/// the first missing one ends the run.
+#[cfg(feature = "ffmpeg")]
+pub fn remove_frames(dir: &Path, first: usize) -> usize {
+    let mut count = 0;
+    while std::fs::remove_file(frame_path(dir, first + count)).is_ok() {
+        count += 1;
+    }
+    count
+}
+
+/// Tag HEVC as `hvc1` so QuickTime and Apple devices play it (ffmpeg's default `hev1` is not recognized).
+const HEVC_TAG: [&str; 2] = ["-tag:v", "hvc1"];
+
+impl Container {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("webm") => Container::Webm,
+            Some(ext) if ext.eq_ignore_ascii_case("mov") => Container::Mov,
+            Some(ext) if ext.eq_ignore_ascii_case("m3u8") => Container::Hls,
+            _ => Container::Mp4,
+        }
+    }
+
+    /// ffmpeg arguments that make the container writable to a pipe (`-o -`), ending with its muxer.
+    pub fn stream_args(self) -> Vec<&'static str> {
+        match self {
+            Container::Mp4 => [&FRAGMENTED_MOVFLAGS[..], &["-f", "mp4"]].concat(),
+            Container::Mov => [&FRAGMENTED_MOVFLAGS[..], &["-f", "mov"]].concat(),
+            Container::Webm => vec!["-f", "webm"],
+            // HLS writes many files and is never streamed (`StreamFormat` has no HLS).
+            Container::Hls => Vec::new(),
+        }
+    }
+
+    /// Name shown in the encoding progress message.
+    pub fn label(self) -> &'static str {
+        match self {
+            Container::Mp4 => "MP4",
+            Container::Mov => "MOV",
+            Container::Webm => "WebM",
+            Container::Hls => "HLS",
+        }
+    }
+
+    /// Whether the container can carry a video alpha channel (`--transparent`).
+    pub fn supports_alpha(self) -> bool {
+        matches!(self, Container::Mov | Container::Webm)
+    }
+
+    /// Whether the encoder picked for `video` stores color at half the resolution (4:2:0, e.g. yuv420p), which needs
+    /// an even frame width and height.
+    pub fn subsamples_chroma(self, video: &VideoSettings) -> bool {
+        self.encoder_library(video) != EncoderLibrary::ProRes
+    }
+
+    /// Whether the container can carry a cover picture (an `attached_pic` stream).
+    pub fn supports_cover_art(self) -> bool {
+        matches!(self, Container::Mp4 | Container::Mov)
+    }
+
+    /// ffmpeg muxer arguments that go before the output path. For HLS: segments of `settings.hls_segment` seconds
+    /// (a keyframe is forced at each boundary so they come out even) named after the playlist, `out_000.ts`, ...;
+    /// HEVC uses fragmented MP4 segments (`.m4s`), as Apple's players require.
+    pub fn muxer_args(self, output: &Path, settings: &EncodeSettings) -> Vec<String> {
+        if self != Container::Hls {
+            return Vec::new();
+        }
+        let segment = settings.hls_segment.unwrap_or(HLS_DEFAULT_SEGMENT);
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let fmp4 = settings.video.codec == Some(Codec::Hevc);
+        let segment_name = format!("{}_%03d.{}", stem, if fmp4 { "m4s" } else { "ts" });
+        let segment_path = output.with_file_name(segment_name);
+        let mut args: Vec<String> = vec![
+            "-force_key_frames".into(),
+            format!("expr:gte(t,n_forced*{})", segment),
+            "-f".into(),
+            "hls".into(),
+            "-hls_time".into(),
+            segment.to_string(),
+            "-hls_playlist_type".into(),
+            "vod".into(),
+            "-hls_segment_filename".into(),
+            segment_path.to_string_lossy().into_owned(),
+        ];
+        if fmp4 {
+            args.extend(["-hls_segment_type".into(), "fmp4".into()]);
+            args.extend(["-hls_fmp4_init_filename".into(), format!("{}_init.mp4", stem)]);
+        }
+        args
+    }
+
+    /// The files written so far for `output`: the file itself and, for HLS, the segments (and fMP4 init segment)
+    /// named after the playlist next to it.
+    pub fn written_files(self, output: &Path) -> Vec<PathBuf> {
+        let mut files = vec![output.to_path_buf()];
+        if self != Container::Hls {
+            return files;
+        }
+        let prefix = format!("{}_", output.file_stem().unwrap_or_default().to_string_lossy());
+        let dir = output.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let is_segment = |name: &str| {
+            let Some(rest) = name.strip_prefix(&prefix) else { return false };
+            let number = rest.strip_suffix(".ts").or_else(|| rest.strip_suffix(".m4s"));
+            rest == "init.mp4" || number.is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        };
+        let entries = std::fs::read_dir(dir).into_iter().flatten().flatten();
+        files.extend(entries.filter(|entry| is_segment(&entry.file_name().to_string_lossy())).map(|e| e.path()));
+        files
+    }
+
+    /// Video encoder used for this container and `video` settings.
+    fn encoder_library(self, video: &VideoSettings) -> EncoderLibrary {
+        match (self, video.alpha, video.codec, video.hwaccel) {
+            (Container::Mov, true, _, _) => EncoderLibrary::ProRes,
+            (Container::Webm, _, Some(Codec::Av1), _) => EncoderLibrary::SvtAv1,
+            (Container::Webm, _, _, _) => EncoderLibrary::Vp9,
+            (_, _, codec, Some(hwaccel)) => EncoderLibrary::Hardware(hwaccel, codec.unwrap_or_default()),
+            (_, _, Some(Codec::Hevc), None) => EncoderLibrary::X265,
+            (_, _, Some(Codec::Av1), None) => EncoderLibrary::SvtAv1,
+            _ => EncoderLibrary::X264,
+        }
+    }
+
+    /// ffmpeg codec arguments for the video stream, for the encoder picked by `video` (see `VideoSettings`) with its
+    /// quality settings applied.
+    pub fn video_args(self, video: &VideoSettings) -> Vec<String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |items: &[&str]| args.extend(items.iter().map(|item| item.to_string()));
+        match self.encoder_library(video) {
+            EncoderLibrary::ProRes => push(&["-c:v", "prores_ks", "-profile:v", "4444", "-pix_fmt", "yuva444p10le"]),
+            EncoderLibrary::X264 => {
+                push(&["-c:v", "libx264"]);
+                if let Some(preset) = video.x264_preset {
+                    push(&["-preset", preset.name()]);
+                }
+                if let Some(crf) = video.crf {
+                    push(&["-crf", &crf.to_string()]);
+                }
+                if let Some(ref bitrate) = video.bitrate {
+                    push(&["-b:v", bitrate]);
+                }
+                push(&["-pix_fmt", "yuv420p"]);
+            }
+            EncoderLibrary::X265 => {
+                push(&["-c:v", "libx265"]);
+                if let Some(crf) = video.crf {
+                    push(&["-crf", &crf.to_string()]);
+                }
+                if let Some(ref bitrate) = video.bitrate {
+                    push(&["-b:v", bitrate]);
+                }
+                push(&["-pix_fmt", "yuv420p"]);
+                push(&HEVC_TAG);
+            }
+            EncoderLibrary::Hardware(hwaccel, codec) => {
+                let hw_args = hwaccel.video_args(codec);
+                push(&hw_args.iter().map(String::as_str).collect::<Vec<_>>());
+                if let Some(ref bitrate) = video.bitrate {
+                    push(&["-b:v", bitrate]);
+                }
+            }
+            // Constant quality (-b:v 0 with -crf) unless a bitrate is given, at a speed setting that keeps encoding
+            // time close to x264's.
+            EncoderLibrary::Vp9 => {
+                push(&["-c:v", "libvpx-vp9"]);
+                match video.bitrate {
+                    Some(ref bitrate) => push(&["-b:v", bitrate]),
+                    None => push(&["-crf", &video.crf.unwrap_or(VP9_DEFAULT_CRF).to_string(), "-b:v", "0"]),
+                }
+                push(&["-deadline", "good", "-cpu-used", "4", "-row-mt", "1"]);
+                push(&["-pix_fmt", if video.alpha { "yuva420p" } else { "yuv420p" }]);
+            }
+            // SVT-AV1's preset 8 keeps encoding time in the same range as x264's medium.
+            EncoderLibrary::SvtAv1 => {
+                push(&["-c:v", "libsvtav1", "-preset", "8"]);
+                match video.bitrate {
+                    Some(ref bitrate) => push(&["-b:v", bitrate]),
+                    None => push(&["-crf", &video.crf.unwrap_or(AV1_DEFAULT_CRF).to_string()]),
+                }
+                push(&["-pix_fmt", "yuv420p"]);
+            }
+        }
+        args
+    }
+
+    /// ffmpeg codec arguments for the audio stream: `audio`'s codec, or the container's default (Opus for WebM,
+    /// AAC otherwise).
+    pub fn audio_args(self, audio: &AudioSettings) -> Vec<String> {
+        let codec = audio.codec.unwrap_or(match self {
+            Container::Webm => AudioCodec::Opus,
+            Container::Mp4 | Container::Mov | Container::Hls => AudioCodec::Aac,
+        });
+        let mut args = vec!["-c:a".to_string(), codec.encoder().to_string()];
+        let bitrate = match codec {
+            AudioCodec::Flac => None,
+            AudioCodec::Opus => Some(audio.bitrate.as_deref().unwrap_or(OPUS_DEFAULT_BITRATE)),
+            AudioCodec::Aac | AudioCodec::Mp3 => audio.bitrate.as_deref(),
+        };
+        if let Some(bitrate) = bitrate {
+            args.extend(["-b:a".to_string(), bitrate.to_string()]);
+        }
+        args
+    }
+}
+
+/// Opus bitrate unless `--audio-bitrate` is given; AAC and MP3 keep ffmpeg's own default (128k).
+const OPUS_DEFAULT_BITRATE: &str = "128k";
+
+/// Audio codec of the output (`--audio-codec`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+    Mp3,
+    /// Lossless; ignores `--audio-bitrate`.
+    Flac,
+}
+
+impl AudioCodec {
+    fn encoder(self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+            AudioCodec::Mp3 => "libmp3lame",
+            AudioCodec::Flac => "flac",
+        }
+    }
+}
+
+/// How the audio stream is encoded, from the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioSettings {
+    /// Codec instead of the container's default (AAC for MP4/MOV, Opus for WebM).
+    pub codec: Option<AudioCodec>,
+    /// Target audio bitrate in ffmpeg's notation (e.g. `192k`).
+    pub bitrate: Option<String>,
+}
+
+impl AudioSettings {
+    /// Reject codecs `container` can't carry, and a bitrate for lossless FLAC.
+    pub fn validate(&self, container: Container) -> Result<(), &'static str> {
+        match (container, self.codec) {
+            (Container::Webm, Some(AudioCodec::Aac | AudioCodec::Mp3 | AudioCodec::Flac)) => {
+                return Err("--audio-codec: WebM carries opus audio only");
+            }
+            (Container::Mov | Container::Hls, Some(AudioCodec::Opus | AudioCodec::Flac)) => {
+                return Err("--audio-codec: use aac or mp3 for a .mov or .m3u8 output");
+            }
+            _ => {}
+        }
+        if self.codec == Some(AudioCodec::Flac) && self.bitrate.is_some() {
+            return Err("--audio-bitrate does not apply to lossless flac");
+        }
+        Ok(())
+    }
+}
+
+/// Everything the encode command is built from besides its inputs and output path.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EncodeSettings {
+    pub video: VideoSettings,
+    pub audio: AudioSettings,
+    /// `--ffmpeg-args`, placed after the codec arguments so they can override them.
+    pub extra_args: Vec<String>,
+    /// HLS segment length in seconds (`--hls-segment`).
+    pub hls_segment: Option<f32>,
+    /// FFMETADATA file with the chapters to embed (`--chapters`, see `chapters::ffmetadata`).
+    pub chapters: Option<PathBuf>,
+    /// Container tags (`title`, `artist`, `album`) written with `-metadata`.
+    pub metadata: Vec<(String, String)>,
+    /// Picture attached to the output as its cover art (MP4/MOV; see `Container::supports_cover_art`).
+    pub cover_art: Option<PathBuf>,
+}
+
+/// HLS segment length when `--hls-segment` is not given, Apple's recommendation.
+const HLS_DEFAULT_SEGMENT: f32 = 6.0;
+
+/// Default VP9 constant quality; x264 and x265 keep ffmpeg's own defaults (23 and 28).
+const VP9_DEFAULT_CRF: u32 = 32;
+/// Default SVT-AV1 constant quality, roughly matching x264's default at a fraction of the size.
+const AV1_DEFAULT_CRF: u32 = 35;
+
+/// Video encoder a `Container` and `VideoSettings` combination ends up using.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EncoderLibrary {
+    X264,
+    X265,
+    SvtAv1,
+    Hardware(HwAccel, Codec),
+    Vp9,
+    ProRes,
+}
+
+/// libx264 speed/compression preset (`--x264-preset`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "runner", derive(clap::ValueEnum))]
+pub enum X264Preset {
+    Ultrafast,
+    Superfast,
+    Veryfast,
+    Faster,
+    Fast,
+    Medium,
+    Slow,
+    Slower,
+    Veryslow,
+}
+
+impl X264Preset {
+    fn name(self) -> &'static str {
+        match self {
+            X264Preset::Ultrafast => "ultrafast",
+            X264Preset::Superfast => "superfast",
+            X264Preset::Veryfast => "veryfast",
+            X264Preset::Faster => "faster",
+            X264Preset::Fast => "fast",
+            X264Preset::Medium => "medium",
+            X264Preset::Slow => "slow",
+            X264Preset::Slower => "slower",
+            X264Preset::Veryslow => "veryslow",
+        }
+    }
+}
+
+/// How the video stream is encoded, from the command line. Unset quality options keep the encoder's defaults.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoSettings {
+    /// Keep the frames' alpha channel (`--transparent`).
+    pub alpha: bool,
+    /// Codec instead of the container's default (H.264 for MP4/MOV, VP9 for WebM).
+    pub codec: Option<Codec>,
+    pub hwaccel: Option<HwAccel>,
+    /// Constant rate factor for libx264/libx265 (0-51) or VP9/AV1 (0-63).
+    pub crf: Option<u32>,
+    /// Target video bitrate in ffmpeg's notation (e.g. `8M`, `2500k`).
+    pub bitrate: Option<String>,
+    pub x264_preset: Option<X264Preset>,
+}
+
+impl VideoSettings {
+    /// Reject options the encoder chosen for `container` has no use for.
+    pub fn validate(&self, container: Container) -> Result<(), &'static str> {
+        if self.alpha && self.codec.is_some() {
+            return Err("--codec does not apply to --transparent output (ProRes 4444 or VP9 with alpha)");
+        }
+        match (container, self.codec) {
+            (Container::Webm, Some(Codec::H264 | Codec::Hevc)) => {
+                return Err("--codec h264 and hevc need an .mp4 or .mov output (WebM carries VP9 or AV1)");
+            }
+            (Container::Mov | Container::Hls, Some(Codec::Av1)) => {
+                return Err("--codec av1 needs an .mp4 or .webm output");
+            }
+            _ => {}
+        }
+        let encoder = container.encoder_library(self);
+        match (self.hwaccel, encoder) {
+            (Some(_), EncoderLibrary::Hardware(_, Codec::Av1)) => {
+                return Err("--hwaccel supports --codec h264 and hevc");
+            }
+            (Some(_), EncoderLibrary::Hardware(..)) | (None, _) => {}
+            (Some(_), _) => {
+                return Err("--hwaccel only applies to H.264/HEVC output (.mp4, or .mov without --transparent)");
+            }
+        }
+        match (self.crf, encoder) {
+            (Some(crf), EncoderLibrary::X264 | EncoderLibrary::X265) if crf > 51 => {
+                return Err("--crf must be 0-51 for H.264 and HEVC");
+            }
+            (Some(crf), EncoderLibrary::Vp9 | EncoderLibrary::SvtAv1) if crf > 63 => {
+                return Err("--crf must be 0-63 for VP9 and AV1");
+            }
+            (Some(_), EncoderLibrary::Hardware(..) | EncoderLibrary::ProRes) => {
+                return Err("--crf applies to software encoders only (use --video-bitrate with --hwaccel)");
+            }
+            _ => {}
+        }
+        if self.bitrate.is_some() && encoder == EncoderLibrary::ProRes {
+            return Err("--video-bitrate does not apply to ProRes (--transparent .mov)");
+        }
+        if self.x264_preset.is_some() && encoder != EncoderLibrary::X264 {
+            return Err("--x264-preset only applies to libx264 (H.264 without --hwaccel or --transparent)");
+        }
+        Ok(())
+    }
+}
+
+/// Split `--ffmpeg-args` into arguments at whitespace, as a shell would: single or double quotes keep spaces
+/// inside one argument, and a backslash escapes the next character outside single quotes.
+#[cfg(feature = "runner")]
+pub fn split_args(s: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.get_or_insert_default().push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or("--ffmpeg-args ends with a lone backslash")?;
+                current.get_or_insert_default().push(escaped);
+            }
+            (Some(_), c) => current.get_or_insert_default().push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_default().push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("--ffmpeg-args has an unterminated quote".to_string());
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// ffmpeg arguments that read raw RGBA frames of `size` at `fps` from stdin.
+#[cfg(feature = "ffmpeg")]
+fn raw_input_args(fps: u32, size: (u32, u32)) -> Vec<String> {
+    ["-f", "rawvideo", "-pix_fmt", "rgba", "-video_size", &format!("{}x{}", size.0, size.1)]
+        .into_iter()
+        .chain(["-framerate", &fps.to_string(), "-i", "-"])
+        .map(String::from)
+        .collect()
+}
+
+/// ffmpeg arguments that read the PNGs `frame_path` names in `dir`, from `frame_000000.png` on, at `fps`.
+#[cfg(feature = "ffmpeg")]
+fn png_input_args(fps: u32, dir: &Path) -> Vec<OsString> {
+    let pattern = dir.join("frame_%06d.png");
+    ["-framerate".into(), fps.to_string().into(), "-start_number".into(), "0".into(), "-i".into(), pattern.into()]
+        .into()
+}
+
+/// Inputs after the frames (0) and the audio (1) — the chapters file and the cover art — and the output arguments
+/// that use them, plus the `-metadata` tags.
+#[cfg(feature = "ffmpeg")]
+fn side_input_args(settings: &EncodeSettings) -> (Vec<&Path>, Vec<String>) {
+    let mut inputs = Vec::new();
+    let mut args = Vec::new();
+    if let Some(ref chapters) = settings.chapters {
+        args.extend(["-map_chapters".to_string(), (2 + inputs.len()).to_string()]);
+        inputs.push(chapters.as_path());
+    }
+    if let Some(ref cover_art) = settings.cover_art {
+        // A third input means streams are no longer picked automatically. The picture is copied as is, as a
+        // second video stream flagged as the attached picture.
+        args.extend(["-map", "0:v", "-map", "1:a", "-map"].map(String::from));
+        args.push((2 + inputs.len()).to_string());
+        args.extend(["-c:v:1", "copy", "-disposition:v:1", "attached_pic"].map(String::from));
+        inputs.push(cover_art.as_path());
+    }
+    for (key, value) in &settings.metadata {
+        args.extend(["-metadata".to_string(), format!("{}={}", key, value)]);
+    }
+    (inputs, args)
+}
+
+/// Read ffmpeg's `-progress` output (blocks of `key=value` lines, each ending with `progress=continue` or
+/// `progress=end`) and report the encoded frame count at the end of every block.
+#[cfg(feature = "ffmpeg")]
+fn read_progress(output: impl BufRead, mut on_frame: impl FnMut(u64)) {
+    let mut frame = 0;
+    for line in output.lines().map_while(Result::ok) {
+        match line.trim().split_once('=') {
+            Some(("frame", value)) => frame = value.trim().parse().unwrap_or(frame),
+            Some(("progress", _)) => on_frame(frame),
+            _ => {}
+        }
+    }
+}
+
+/// The ffmpeg arguments that encode raw RGBA frames from stdin, with the `audio` file as the soundtrack, into
+/// `output`.
+#[cfg(feature = "ffmpeg")]
+pub fn ffmpeg_args(
+    output: &Path,
+    container: Container,
+    settings: &EncodeSettings,
+    fps: u32,
+    size: (u32, u32),
+    audio: &Path,
+) -> Vec<OsString> {
+    let frames = raw_input_args(fps, size).into_iter().map(OsString::from).collect();
+    encode_args(output, container, settings, frames, audio)
+}
+
+/// The ffmpeg arguments that encode the PNG frames in `dir` (see `PngSequence`), with the `audio` file as the
+/// soundtrack, into `output`.
+#[cfg(feature = "ffmpeg")]
+pub fn png_sequence_args(
+    output: &Path,
+    container: Container,
+    settings: &EncodeSettings,
+    fps: u32,
+    dir: &Path,
+    audio: &Path,
+) -> Vec<OsString> {
+    encode_args(output, container, settings, png_input_args(fps, dir), audio)
+}
+
+/// The ffmpeg arguments that encode the frames read by the `frames` input arguments and the `audio` file.
+#[cfg(feature = "ffmpeg")]
+fn encode_args(
+    output: &Path,
+    container: Container,
+    settings: &EncodeSettings,
+    frames: Vec<OsString>,
+    audio: &Path,
+) -> Vec<OsString> {
+    let to_stdout = writes_to_stdout(output);
+    let (side_inputs, side_args) = side_input_args(settings);
+    let mut args: Vec<OsString> = ["-y", "-v", "error", "-nostats"].map(OsString::from).to_vec();
+    if !to_stdout {
+        args.extend(["-progress", "pipe:1"].map(OsString::from));
+    }
+    args.extend(settings.video.hwaccel.map(HwAccel::global_args).unwrap_or_default().into_iter().map(OsString::from));
+    args.extend(frames);
+    args.extend(["-i".into(), audio.into()]);
+    for path in side_inputs {
+        args.extend(["-i".into(), path.into()]);
+    }
+    args.extend(side_args.into_iter().map(OsString::from));
+    args.extend(container.video_args(&settings.video).into_iter().map(OsString::from));
+    args.extend(container.audio_args(&settings.audio).into_iter().map(OsString::from));
+    args.extend(container.muxer_args(output, settings).into_iter().map(OsString::from));
+    args.extend(settings.extra_args.iter().map(OsString::from));
+    if to_stdout {
+        args.extend(container.stream_args().into_iter().map(OsString::from));
+    }
+    args.extend(["-shortest".into(), output.into()]);
+    args
+}
+
+/// `program` and `args` as one shell command line, quoting the arguments that need it.
+#[cfg(feature = "runner")]
+pub fn command_line(program: &Path, args: &[OsString]) -> String {
+    std::iter::once(program.as_os_str())
+        .chain(args.iter().map(OsString::as_os_str))
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=,+%@".contains(c);
+            if !arg.is_empty() && arg.chars().all(plain) {
+                arg.into_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// An ffmpeg subprocess encoding the output video. Rendered frames are written to its stdin as raw RGBA, so no
+/// intermediate image files are needed.
+#[cfg(feature = "ffmpeg")]
+pub struct Encoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stderr: Option<JoinHandle<String>>,
+    progress: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl Encoder {
+    /// Start the `ffmpeg` binary encoding `size` frames at `fps` together with the audio in `audio` into `output`,
+    /// with the streams encoded as `settings` asks (see `Container::video_args` and `Container::audio_args`).
+    /// `on_progress` is called from a reader thread with the number of frames ffmpeg has encoded so far, except when
+    /// `output` is `-`: then ffmpeg's stdout carries the video (fragmented MP4) and there is no progress.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        ffmpeg: &Path,
+        output: &Path,
+        container: Container,
+        settings: &EncodeSettings,
+        fps: u32,
+        size: (u32, u32),
+        audio: &Path,
+        on_progress: impl FnMut(u64) + Send + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let args = ffmpeg_args(output, container, settings, fps, size, audio);
+        let mut encoder = Self::spawn(ffmpeg, args, writes_to_stdout(output), Stdio::piped(), on_progress)?;
+        encoder.stdin = Some(encoder.child.stdin.take().ok_or("failed to take ffmpeg stdin")?);
+        Ok(encoder)
+    }
+
+    /// Start `ffmpeg` with `args`, reading `-progress` from its stdout unless the video goes there (`to_stdout`),
+    /// and draining its stderr. The frame input is left for the caller to set up.
+    fn spawn(
+        ffmpeg: &Path,
+        args: Vec<OsString>,
+        to_stdout: bool,
+        stdin: Stdio,
+        on_progress: impl FnMut(u64) + Send + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut child = Command::new(ffmpeg)
+            .args(args)
+            .stdin(stdin)
+            .stdout(if to_stdout { Stdio::inherit() } else { Stdio::piped() })
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start ffmpeg: {}", e))?;
+        let mut stderr = child.stderr.take().ok_or("failed to take ffmpeg stderr")?;
+        let progress = child
+            .stdout
+            .take()
+            .map(|stdout| std::thread::spawn(move || read_progress(BufReader::new(stdout), on_progress)));
+        // Drained on a thread so a chatty ffmpeg never blocks on a full stderr pipe while we write frames.
+        let stderr = std::thread::spawn(move || {
+            let mut log = String::new();
+            let _ = stderr.read_to_string(&mut log);
+            log
+        });
+        Ok(Self { child, stdin: None, stderr: Some(stderr), progress })
+    }
+
+    /// Send the next frame. Fails with ffmpeg's error output if ffmpeg has exited.
+    pub fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let stdin = self.stdin.as_mut().ok_or("ffmpeg input is already closed")?;
+        if stdin.write_all(frame.as_raw()).is_err() {
+            self.finish()?;
+            return Err(SpectrumError::EncodeFailed("ffmpeg stopped reading frames".into()).into());
+        }
+        Ok(())
+    }
+
+    /// Close the frame input and wait for ffmpeg to finish writing the output.
+    pub fn finish(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        drop(self.stdin.take());
+        let status = self.child.wait()?;
+        let log = self.stderr.take().and_then(|handle| handle.join().ok()).unwrap_or_default();
+        if let Some(progress) = self.progress.take() {
+            let _ = progress.join();
+        }
+        if !status.success() {
+            return Err(SpectrumError::EncodeFailed(format!("ffmpeg failed: {}", log.trim_end())).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        // Rendering failed before `finish`: don't leave ffmpeg waiting for more frames.
+        if self.stdin.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Which encoder writes the output video (`--encoder`).
+#[cfg(feature = "runner")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// Pipe the frames into ffmpeg: every container, codec and audio option.
+    #[default]
+    Ffmpeg,
+    /// Save the frames as PNGs in the temp dir, then encode them with ffmpeg: same options as `ffmpeg`, but slower
+    /// and it needs the disk space (keep the frames with --keep-temp).
+    Png,
+    /// Built-in OpenH264 encoder: H.264 MP4 without audio, no ffmpeg needed (`native-encoder` feature).
+    Native,
+}
+
+#[cfg(feature = "runner")]
+impl Backend {
+    /// Whether this backend runs ffmpeg to encode the output.
+    pub fn uses_ffmpeg(self) -> bool {
+        matches!(self, Backend::Ffmpeg | Backend::Png)
+    }
+}
+
+/// Where rendered frames go, one at a time and in order: an ffmpeg pipe (`Encoder`), a PNG sequence ffmpeg encodes
+/// at the end (`PngSequence`), the built-in encoder (`native::NativeEncoder`), or just PNG files (`FrameDir`).
+pub trait VideoEncoder: Send {
+    /// Take the next frame.
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Finish writing the output after the last frame.
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+#[cfg(feature = "ffmpeg")]
+impl VideoEncoder for Encoder {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Encoder::write_frame(self, frame)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Encoder::finish(&mut self)
+    }
+}
+
+/// Frames saved as `frame_NNNNNN.png` files in a directory (`--frames-only`), numbered from `first`.
+pub struct FrameDir {
+    dir: PathBuf,
+    next: usize,
+}
+
+impl FrameDir {
+    pub fn new(dir: &Path, first: usize) -> Self {
+        Self { dir: dir.to_path_buf(), next: first }
+    }
+}
+
+impl VideoEncoder for FrameDir {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = frame_path(&self.dir, self.next);
+        frame.save(&path).map_err(|e| SpectrumError::WriteFailed { message: e.to_string(), path })?;
+        self.next += 1;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// Frames saved as PNGs in a directory and encoded by ffmpeg once the last one is written (`--encoder png`).
+/// Slower than the pipe and it needs the disk space, but ffmpeg only runs after rendering, reading plain files.
+#[cfg(feature = "ffmpeg")]
+pub struct PngSequence {
+    frames: FrameDir,
+    ffmpeg: PathBuf,
+    args: Vec<OsString>,
+    to_stdout: bool,
+    on_progress: Box<dyn FnMut(u64) + Send>,
+}
+
+#[cfg(feature = "ffmpeg")]
+impl PngSequence {
+    /// Save frames into `dir` (any frames left there by an earlier run are removed), to be encoded as
+    /// `Encoder::start` would encode them. `on_progress` follows ffmpeg's encode once `finish` starts it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        ffmpeg: &Path,
+        output: &Path,
+        container: Container,
+        settings: &EncodeSettings,
+        fps: u32,
+        dir: &Path,
+        audio: &Path,
+        on_progress: impl FnMut(u64) + Send + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        std::fs::create_dir_all(dir)?;
+        remove_frames(dir, 0);
+        Ok(Self {
+            frames: FrameDir::new(dir, 0),
+            ffmpeg: ffmpeg.to_path_buf(),
+            args: png_sequence_args(output, container, settings, fps, dir, audio),
+            to_stdout: writes_to_stdout(output),
+            on_progress: Box::new(on_progress),
+        })
+    }
+}
+
+#[cfg(feature = "ffmpeg")]
+impl VideoEncoder for PngSequence {
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.frames.write_frame(frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Self { ffmpeg, args, to_stdout, on_progress, .. } = *self;
+        Encoder::spawn(&ffmpeg, args, to_stdout, Stdio::null(), on_progress)?.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        frame_path, AudioCodec, AudioSettings, Codec, Container, EncodeSettings, FrameDir, HwAccel, VideoEncoder,
+        VideoSettings, X264Preset,
+    };
+    #[cfg(feature = "ffmpeg")]
+    use super::{ffprobe_path, read_progress};
+    use image::RgbaImage;
+    use std::path::Path;
+
+    fn has_pair(args: &[String], pair: [&str; 2]) -> bool {
+        args.windows(2).any(|w| w[0] == pair[0] && w[1] == pair[1])
+    }
+
+    fn alpha() -> VideoSettings {
+        VideoSettings { alpha: true, ..Default::default() }
+    }
+
+    fn hwaccel(hwaccel: HwAccel) -> VideoSettings {
+        VideoSettings { hwaccel: Some(hwaccel), ..Default::default() }
+    }
+
+    #[test]
+    fn container_follows_extension() {
+        assert_eq!(Container::from_path(Path::new("out.webm")), Container::Webm);
+        assert_eq!(Container::from_path(Path::new("OUT.WebM")), Container::Webm);
+        assert_eq!(Container::from_path(Path::new("out.mov")), Container::Mov);
+        assert_eq!(Container::from_path(Path::new("out.mp4")), Container::Mp4);
+        assert_eq!(Container::from_path(Path::new("out")), Container::Mp4);
+        assert_eq!(Container::from_path(Path::new("live/stream.m3u8")), Container::Hls);
+    }
+
+    #[test]
+    fn hls_segments_sit_next_to_the_playlist() {
+        let settings = EncodeSettings { hls_segment: Some(4.0), ..Default::default() };
+        let args = Container::Hls.muxer_args(Path::new("live/stream.m3u8"), &settings);
+        assert!(has_pair(&args, ["-f", "hls"]) && has_pair(&args, ["-hls_time", "4"]));
+        assert!(has_pair(&args, ["-hls_segment_filename", "live/stream_%03d.ts"]));
+        assert!(has_pair(&args, ["-force_key_frames", "expr:gte(t,n_forced*4)"]));
+        let video = VideoSettings { codec: Some(Codec::Hevc), ..Default::default() };
+        let hevc = EncodeSettings { video, ..settings };
+        let args = Container::Hls.muxer_args(Path::new("stream.m3u8"), &hevc);
+        assert!(has_pair(&args, ["-hls_segment_type", "fmp4"]) && has_pair(&args, ["-hls_time", "4"]));
+        assert!(has_pair(&args, ["-hls_segment_filename", "stream_%03d.m4s"]));
+        assert!(Container::Mp4.muxer_args(Path::new("out.mp4"), &EncodeSettings::default()).is_empty());
+        assert!(has_pair(&Container::Hls.video_args(&VideoSettings::default()), ["-c:v", "libx264"]));
+        assert!(!Container::Hls.supports_alpha());
+    }
+
+    #[test]
+    fn webm_uses_vp9_and_opus() {
+        let args = Container::Webm.video_args(&VideoSettings::default());
+        assert!(has_pair(&args, ["-c:v", "libvpx-vp9"]));
+        assert!(has_pair(&Container::Webm.audio_args(&AudioSettings::default()), ["-c:a", "libopus"]));
+        assert!(has_pair(&Container::Mp4.video_args(&VideoSettings::default()), ["-c:v", "libx264"]));
+    }
+
+    #[test]
+    fn alpha_picks_prores_4444_or_vp9_with_alpha() {
+        assert!(!Container::Mp4.supports_alpha());
+        let mov = Container::Mov.video_args(&alpha());
+        assert!(has_pair(&mov, ["-c:v", "prores_ks"]) && has_pair(&mov, ["-profile:v", "4444"]));
+        assert!(has_pair(&Container::Webm.video_args(&alpha()), ["-pix_fmt", "yuva420p"]));
+        assert!(has_pair(&Container::Mov.video_args(&VideoSettings::default()), ["-c:v", "libx264"]));
+        assert!(!Container::Mov.subsamples_chroma(&alpha()) && Container::Webm.subsamples_chroma(&alpha()));
+    }
+
+    #[test]
+    fn hwaccel_replaces_libx264_only() {
+        let nvenc = Container::Mp4.video_args(&hwaccel(HwAccel::Nvenc));
+        assert!(has_pair(&nvenc, ["-c:v", "h264_nvenc"]));
+        let vaapi = Container::Mov.video_args(&hwaccel(HwAccel::Vaapi));
+        assert!(has_pair(&vaapi, ["-vf", "format=nv12,hwupload"]) && has_pair(&vaapi, ["-c:v", "h264_vaapi"]));
+        assert!(hwaccel(HwAccel::Qsv).validate(Container::Webm).is_err());
+        assert!(VideoSettings { alpha: true, ..hwaccel(HwAccel::Qsv) }.validate(Container::Mov).is_err());
+        assert!(hwaccel(HwAccel::Qsv).validate(Container::Mov).is_ok());
+    }
+
+    #[test]
+    fn codec_picks_encoder_with_defaults() {
+        let codec = |codec| VideoSettings { codec: Some(codec), ..Default::default() };
+        let hevc = Container::Mp4.video_args(&codec(Codec::Hevc));
+        assert!(has_pair(&hevc, ["-c:v", "libx265"]) && has_pair(&hevc, ["-tag:v", "hvc1"]));
+        let av1 = Container::Mp4.video_args(&codec(Codec::Av1));
+        assert!(has_pair(&av1, ["-c:v", "libsvtav1"]) && has_pair(&av1, ["-crf", "35"]));
+        let webm_av1 = Container::Webm.video_args(&codec(Codec::Av1));
+        assert!(has_pair(&webm_av1, ["-c:v", "libsvtav1"]));
+        let nvenc = Container::Mov.video_args(&VideoSettings { codec: Some(Codec::Hevc), ..hwaccel(HwAccel::Nvenc) });
+        assert!(has_pair(&nvenc, ["-c:v", "hevc_nvenc"]));
+    }
+
+    #[test]
+    fn codec_must_fit_container() {
+        let codec = |codec| VideoSettings { codec: Some(codec), ..Default::default() };
+        assert!(codec(Codec::Hevc).validate(Container::Mov).is_ok());
+        assert!(codec(Codec::Av1).validate(Container::Webm).is_ok());
+        assert!(codec(Codec::Hevc).validate(Container::Webm).is_err());
+        assert!(codec(Codec::Av1).validate(Container::Mov).is_err());
+        assert!(codec(Codec::Av1).validate(Container::Hls).is_err());
+        assert!(VideoSettings { alpha: true, ..codec(Codec::Hevc) }.validate(Container::Mov).is_err());
+        assert!(VideoSettings { codec: Some(Codec::Av1), ..hwaccel(HwAccel::Qsv) }.validate(Container::Mp4).is_err());
+        assert!(VideoSettings { crf: Some(60), ..codec(Codec::Av1) }.validate(Container::Mp4).is_ok());
+        let preset = VideoSettings { x264_preset: Some(X264Preset::Slow), ..codec(Codec::Hevc) };
+        assert!(preset.validate(Container::Mp4).is_err());
+    }
+
+    #[test]
+    fn audio_codec_and_bitrate() {
+        let audio = |codec, bitrate: Option<&str>| AudioSettings { codec, bitrate: bitrate.map(String::from) };
+        assert_eq!(Container::Mp4.audio_args(&audio(None, None)), ["-c:a", "aac"]);
+        assert_eq!(Container::Webm.audio_args(&audio(None, None)), ["-c:a", "libopus", "-b:a", "128k"]);
+        let mp3 = Container::Mp4.audio_args(&audio(Some(AudioCodec::Mp3), Some("320k")));
+        assert_eq!(mp3, ["-c:a", "libmp3lame", "-b:a", "320k"]);
+        assert_eq!(Container::Mp4.audio_args(&audio(Some(AudioCodec::Flac), None)), ["-c:a", "flac"]);
+        assert!(audio(Some(AudioCodec::Aac), None).validate(Container::Webm).is_err());
+        assert!(audio(Some(AudioCodec::Flac), None).validate(Container::Mov).is_err());
+        assert!(audio(Some(AudioCodec::Opus), None).validate(Container::Hls).is_err());
+        assert!(audio(Some(AudioCodec::Flac), Some("320k")).validate(Container::Mp4).is_err());
+        assert!(audio(Some(AudioCodec::Opus), Some("96k")).validate(Container::Mp4).is_ok());
+    }
+
+    #[test]
+    fn quality_settings_reach_the_encoder() {
+        let x264 = VideoSettings { crf: Some(18), x264_preset: Some(X264Preset::Slow), ..Default::default() };
+        let args = Container::Mp4.video_args(&x264);
+        assert!(has_pair(&args, ["-crf", "18"]) && has_pair(&args, ["-preset", "slow"]));
+        let vp9 = Container::Webm.video_args(&VideoSettings { bitrate: Some("4M".into()), ..Default::default() });
+        assert!(has_pair(&vp9, ["-b:v", "4M"]) && !vp9.iter().any(|arg| arg == "-crf"));
+        assert!(has_pair(&Container::Webm.video_args(&VideoSettings::default()), ["-crf", "32"]));
+    }
+
+    #[test]
+    fn quality_settings_are_checked_against_the_encoder() {
+        let crf = |crf| VideoSettings { crf: Some(crf), ..Default::default() };
+        assert!(crf(51).validate(Container::Mp4).is_ok());
+        assert!(crf(52).validate(Container::Mp4).is_err());
+        assert!(crf(60).validate(Container::Webm).is_ok());
+        assert!(VideoSettings { crf: Some(20), ..hwaccel(HwAccel::Nvenc) }.validate(Container::Mp4).is_err());
+        let preset = VideoSettings { x264_preset: Some(X264Preset::Fast), ..Default::default() };
+        assert!(preset.validate(Container::Webm).is_err());
+        let bitrate = VideoSettings { bitrate: Some("8M".into()), ..alpha() };
+        assert!(bitrate.validate(Container::Mov).is_err());
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn split_args_follows_shell_quoting() {
+        use super::split_args;
+
+        assert_eq!(split_args("  -movflags +faststart ").unwrap(), ["-movflags", "+faststart"]);
+        assert_eq!(
+            split_args(r#"-metadata "title=My Song" -vf 'eq=contrast=1.1' a\ b """#).unwrap(),
+            ["-metadata", "title=My Song", "-vf", "eq=contrast=1.1", "a b", ""]
+        );
+        assert!(split_args("-metadata \"title").is_err());
+        assert!(split_args("").unwrap().is_empty());
+    }
+
+    #[cfg(feature = "ffmpeg")]
+    #[test]
+    fn raw_input_reads_rgba_frames_from_stdin() {
+        use super::raw_input_args;
+
+        let args = raw_input_args(30, (1280, 720));
+        assert!(has_pair(&args, ["-pix_fmt", "rgba"]));
+        assert!(has_pair(&args, ["-video_size", "1280x720"]));
+        assert!(has_pair(&args, ["-framerate", "30"]));
+        assert!(has_pair(&args, ["-i", "-"]));
+    }
+
+    #[cfg(feature = "ffmpeg")]
+    #[test]
+    fn only_a_bare_dash_means_stdout() {
+        use super::writes_to_stdout;
+
+        assert!(writes_to_stdout(Path::new("-")));
+        assert!(!writes_to_stdout(Path::new("./-")));
+        assert!(!writes_to_stdout(Path::new("out.mp4")));
+    }
+
+    #[test]
+    #[cfg(feature = "ffmpeg")]
+    fn progress_reports_frame_at_each_block_end() {
+        let output = "frame=12\nfps=24.0\nout_time=00:00:00.400000\nprogress=continue\n\
+                      frame=30\nspeed=1.2x\nprogress=end\n";
+        let mut frames = Vec::new();
+        read_progress(output.as_bytes(), |frame| frames.push(frame));
+        assert_eq!(frames, [12, 30]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn command_line_quotes_only_what_needs_it() {
+        use super::command_line;
+
+        let args = ["-metadata", "title=It's here", "-vf", "scale=1280:-2", ""].map(Into::into);
+        assert_eq!(
+            command_line(Path::new("/opt/ffmpeg"), &args),
+            "/opt/ffmpeg -metadata 'title=It'\\''s here' -vf scale=1280:-2 ''"
+        );
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn stdout_output_streams_without_progress() {
+        use super::{command_line, ffmpeg_args};
+
+        let settings = EncodeSettings::default();
+        let args = ffmpeg_args(Path::new("-"), Container::Mp4, &settings, 30, (640, 360), Path::new("a.wav"));
+        let args = command_line(Path::new("ffmpeg"), &args);
+        assert!(!args.contains("-progress"));
+        assert!(args.ends_with("-movflags frag_keyframe+empty_moov -f mp4 -shortest -"), "{}", args);
+        let args = ffmpeg_args(Path::new("a.mp4"), Container::Mp4, &settings, 30, (640, 360), Path::new("a.wav"));
+        assert_eq!(args[4..6], ["-progress", "pipe:1"]);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn stdout_formats_pick_their_streaming_muxer() {
+        use super::{command_line, ffmpeg_args, StreamFormat};
+
+        let settings = EncodeSettings::default();
+        let stream = |format: StreamFormat| {
+            let args = ffmpeg_args(Path::new("-"), format.container(), &settings, 30, (640, 360), Path::new("a.wav"));
+            command_line(Path::new("ffmpeg"), &args)
+        };
+        let webm = stream(StreamFormat::Webm);
+        assert!(webm.contains("-c:v libvpx-vp9") && webm.ends_with("-f webm -shortest -"), "{}", webm);
+        assert!(!webm.contains("movflags"), "{}", webm);
+        let mov = stream(StreamFormat::Mov);
+        assert!(mov.ends_with("-movflags frag_keyframe+empty_moov -f mov -shortest -"), "{}", mov);
+    }
+
+    #[test]
+    #[cfg(feature = "ffmpeg")]
+    fn ffprobe_sits_next_to_ffmpeg() {
+        let exe = std::env::consts::EXE_SUFFIX;
+        let custom = ffprobe_path(Path::new("/opt/ffmpeg/bin/ffmpeg"));
+        assert_eq!(custom, Path::new("/opt/ffmpeg/bin").join(format!("ffprobe{}", exe)));
+        assert_eq!(ffprobe_path(Path::new("ffmpeg")), Path::new("ffprobe"));
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn png_sequences_read_the_frame_files_in_place_of_stdin() {
+        use super::{command_line, png_sequence_args};
+
+        let settings = EncodeSettings::default();
+        let (dir, audio) = (Path::new("/tmp/frames"), Path::new("a.wav"));
+        let args = png_sequence_args(Path::new("a.mp4"), Container::Mp4, &settings, 30, dir, audio);
+        let args = command_line(Path::new("ffmpeg"), &args);
+        assert!(args.contains(" -framerate 30 -start_number 0 -i /tmp/frames/frame_%06d.png -i a.wav "), "{}", args);
+        assert!(!args.contains("rawvideo"));
+    }
+
+    #[test]
+    fn frame_dirs_number_frames_from_the_first_index() {
+        let dir = std::env::temp_dir().join(format!("asg-frame-dir-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut frames: Box<dyn VideoEncoder> = Box::new(FrameDir::new(&dir, 7));
+        for _ in 0..2 {
+            frames.write_frame(&RgbaImage::new(2, 2)).unwrap();
+        }
+        frames.finish().unwrap();
+        assert!(frame_path(&dir, 7).exists() && frame_path(&dir, 8).exists());
+        #[cfg(feature = "ffmpeg")]
+        assert_eq!(super::remove_frames(&dir, 7), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "ffmpeg")]
+    #[test]
+    fn side_inputs_follow_video_and_audio() {
+        use super::side_input_args;
+
+        let settings = EncodeSettings {
+            chapters: Some("chapters.txt".into()),
+            cover_art: Some("cover.jpg".into()),
+            metadata: vec![("title".into(), "Song = 1".into())],
+            ..Default::default()
+        };
+        let (inputs, args) = side_input_args(&settings);
+        assert_eq!(inputs, [Path::new("chapters.txt"), Path::new("cover.jpg")]);
+        let args = args.join(" ");
+        assert!(args.starts_with("-map_chapters 2 -map 0:v -map 1:a -map 3 -c:v:1 copy"));
+        assert!(args.contains("-disposition:v:1 attached_pic"));
+        assert!(args.ends_with("-metadata title=Song = 1"));
+        assert_eq!(side_input_args(&EncodeSettings::default()), (Vec::<&Path>::new(), Vec::new()));
+    }
+}
@@ -0,0 +1,193 @@
+//! Placement and compositing of overlays (text, logo) on rendered frames, and the time counter's text
+
+use image::imageops::FilterType;
+use image::{ImageBuffer, Rgba};
+
+use crate::effects::{apply_shadow, Shadow};
+
+/// Where an overlay is anchored in the frame, inset by the overlay margin.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlayPos {
+    #[default]
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+impl OverlayPos {
+    /// Horizontal anchor as a fraction of the free space (0.0 = left, 0.5 = center, 1.0 = right).
+    pub fn horizontal(self) -> f32 {
+        match self {
+            OverlayPos::TopLeft | OverlayPos::CenterLeft | OverlayPos::BottomLeft => 0.0,
+            OverlayPos::TopCenter | OverlayPos::Center | OverlayPos::BottomCenter => 0.5,
+            OverlayPos::TopRight | OverlayPos::CenterRight | OverlayPos::BottomRight => 1.0,
+        }
+    }
+
+    /// Vertical anchor as a fraction of the free space (0.0 = top, 0.5 = center, 1.0 = bottom).
+    pub fn vertical(self) -> f32 {
+        match self {
+            OverlayPos::TopLeft | OverlayPos::TopCenter | OverlayPos::TopRight => 0.0,
+            OverlayPos::CenterLeft | OverlayPos::Center | OverlayPos::CenterRight => 0.5,
+            OverlayPos::BottomLeft | OverlayPos::BottomCenter | OverlayPos::BottomRight => 1.0,
+        }
+    }
+}
+
+/// Which playback time the time counter shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimeDisplay {
+    /// Time since the start (e.g. 1:05).
+    Elapsed,
+    /// Time until the end, with a leading minus (e.g. -2:30).
+    Remaining,
+    /// Both, separated by a slash (e.g. 1:05 / -2:30).
+    Both,
+}
+
+impl TimeDisplay {
+    /// Counter text at `elapsed_sec` into a track of `total_sec`. Elapsed rounds down and remaining rounds up,
+    /// as media players do.
+    pub fn label(self, elapsed_sec: f32, total_sec: f32) -> String {
+        let elapsed = elapsed_sec.clamp(0.0, total_sec.max(0.0)).floor() as u64;
+        let remaining = (total_sec - elapsed_sec).max(0.0).ceil() as u64;
+        match self {
+            TimeDisplay::Elapsed => format_mm_ss(elapsed),
+            TimeDisplay::Remaining => format!("-{}", format_mm_ss(remaining)),
+            TimeDisplay::Both => format!("{} / -{}", format_mm_ss(elapsed), format_mm_ss(remaining)),
+        }
+    }
+}
+
+/// `m:ss` with zero-padded seconds; minutes are not wrapped into hours.
+fn format_mm_ss(seconds: u64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Top-left corner of a `size` overlay anchored at `pos` in a `frame` with `margin` pixels from the edges.
+/// Centered axes ignore the margin. Overlays larger than the frame may get negative coordinates.
+pub fn overlay_origin(pos: OverlayPos, frame: (u32, u32), size: (u32, u32), margin: u32) -> (i64, i64) {
+    let place = |frac: f32, frame: u32, size: u32| {
+        let free = frame as i64 - size as i64;
+        if frac == 0.0 {
+            margin as i64
+        } else if frac == 1.0 {
+            free - margin as i64
+        } else {
+            (free as f32 * frac).round() as i64
+        }
+    };
+    (
+        place(pos.horizontal(), frame.0, size.0),
+        place(pos.vertical(), frame.1, size.1),
+    )
+}
+
+/// Alpha-composite `layer` onto `img` at `pos`, over its drop shadow when `shadow` is set.
+pub fn composite_overlay(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layer: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    pos: OverlayPos,
+    margin: u32,
+    shadow: Option<Shadow>,
+) {
+    let (x, y) = overlay_origin(pos, img.dimensions(), layer.dimensions(), margin);
+    if let Some(shadow) = shadow {
+        apply_shadow(img, layer, (x, y), shadow);
+    }
+    image::imageops::overlay(img, layer, x, y);
+}
+
+/// Resize `logo` to `scale` × `frame_width` wide (keeping its aspect ratio) and multiply its alpha by `opacity`.
+pub fn prepare_logo(
+    logo: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    frame_width: u32,
+    scale: f32,
+    opacity: f32,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (w, h) = logo.dimensions();
+    let target_w = ((frame_width as f32 * scale).round() as u32).max(1);
+    let target_h = ((h as f32 * target_w as f32 / w.max(1) as f32).round() as u32).max(1);
+    let mut out = if (target_w, target_h) == (w, h) {
+        logo.clone()
+    } else {
+        image::imageops::resize(logo, target_w, target_h, FilterType::Lanczos3)
+    };
+    for p in out.pixels_mut() {
+        p.0[3] = (p.0[3] as f32 * opacity).round() as u8;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{composite_overlay, format_mm_ss, overlay_origin, prepare_logo, OverlayPos, TimeDisplay};
+    use crate::effects::Shadow;
+    use image::{ImageBuffer, Rgba};
+
+    #[test]
+    fn overlay_origin_corners_respect_margin() {
+        assert_eq!(overlay_origin(OverlayPos::TopLeft, (100, 50), (20, 10), 5), (5, 5));
+        assert_eq!(overlay_origin(OverlayPos::TopRight, (100, 50), (20, 10), 5), (75, 5));
+        assert_eq!(overlay_origin(OverlayPos::BottomLeft, (100, 50), (20, 10), 5), (5, 35));
+        assert_eq!(overlay_origin(OverlayPos::BottomRight, (100, 50), (20, 10), 5), (75, 35));
+    }
+
+    #[test]
+    fn overlay_origin_centers_ignore_margin() {
+        assert_eq!(overlay_origin(OverlayPos::Center, (100, 50), (20, 10), 5), (40, 20));
+        assert_eq!(overlay_origin(OverlayPos::BottomCenter, (100, 50), (20, 10), 5), (40, 35));
+    }
+
+    #[test]
+    fn composite_overlay_blends_by_alpha() {
+        let mut img = ImageBuffer::from_pixel(10, 10, Rgba([0, 0, 0, 255]));
+        let mut layer = ImageBuffer::from_pixel(2, 2, Rgba([255, 255, 255, 0]));
+        layer.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        composite_overlay(&mut img, &layer, OverlayPos::TopLeft, 1, None);
+        assert_eq!(img.get_pixel(1, 1).0, [255, 255, 255, 255]);
+        assert_eq!(img.get_pixel(2, 1).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn composite_overlay_draws_shadow_beneath_layer() {
+        let mut img = ImageBuffer::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+        let layer = ImageBuffer::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let shadow = Shadow { offset: 1, blur: 0, color: [0, 0, 0, 255] };
+        composite_overlay(&mut img, &layer, OverlayPos::TopLeft, 1, Some(shadow));
+        assert_eq!(img.get_pixel(2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(3, 3).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(4, 4).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn format_mm_ss_pads_seconds() {
+        assert_eq!(format_mm_ss(0), "0:00");
+        assert_eq!(format_mm_ss(65), "1:05");
+        assert_eq!(format_mm_ss(3725), "62:05");
+    }
+
+    #[test]
+    fn time_display_labels() {
+        assert_eq!(TimeDisplay::Elapsed.label(65.4, 215.0), "1:05");
+        assert_eq!(TimeDisplay::Remaining.label(65.4, 215.0), "-2:30");
+        assert_eq!(TimeDisplay::Both.label(65.4, 215.0), "1:05 / -2:30");
+        // Past the end (last frame rounding) stays at the total.
+        assert_eq!(TimeDisplay::Both.label(216.0, 215.0), "3:35 / -0:00");
+    }
+
+    #[test]
+    fn prepare_logo_scales_to_frame_width_and_fades() {
+        let logo = ImageBuffer::from_pixel(200, 100, Rgba([255, 0, 0, 200]));
+        let out = prepare_logo(&logo, 1000, 0.1, 0.5);
+        assert_eq!(out.dimensions(), (100, 50));
+        assert_eq!(out.get_pixel(50, 25).0, [255, 0, 0, 100]);
+    }
+}
@@ -0,0 +1,83 @@
+//! Built-in color palette presets
+
+/// Named palettes that set the bar, background and gradient colors in one flag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Palette {
+    /// Hot pink bars fading to cyan on deep purple.
+    Synthwave,
+    /// White bars on black.
+    Mono,
+    /// Soft blue bars turning pink when loud, on cream.
+    Pastel,
+    /// Neon green bars turning red when loud, on near-black.
+    Neon,
+}
+
+/// Colors a palette provides. Individual color flags take precedence over these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteColors {
+    /// Base bar color (`Config::bar_color`).
+    pub bar: [u8; 4],
+    /// Color of full-height bars (`Config::bar_color_loud`).
+    pub bar_loud: Option<[u8; 4]>,
+    /// Background color (`Config::bg_color`).
+    pub bg: [u8; 4],
+    /// Gradient end color (`Config::area_gradient`).
+    pub gradient: Option<[u8; 4]>,
+}
+
+impl Palette {
+    pub fn colors(self) -> PaletteColors {
+        match self {
+            Palette::Synthwave => PaletteColors {
+                bar: [255, 42, 109, 255],
+                bar_loud: None,
+                bg: [26, 16, 51, 255],
+                gradient: Some([5, 217, 232, 255]),
+            },
+            Palette::Mono => PaletteColors {
+                bar: [255, 255, 255, 255],
+                bar_loud: None,
+                bg: [0, 0, 0, 255],
+                gradient: Some([85, 85, 85, 255]),
+            },
+            Palette::Pastel => PaletteColors {
+                bar: [126, 166, 224, 255],
+                bar_loud: Some([255, 173, 173, 255]),
+                bg: [253, 252, 220, 255],
+                gradient: Some([189, 178, 255, 255]),
+            },
+            Palette::Neon => PaletteColors {
+                bar: [57, 255, 20, 255],
+                bar_loud: Some([255, 7, 58, 255]),
+                bg: [10, 10, 10, 255],
+                gradient: Some([0, 229, 255, 255]),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+
+    const ALL: [Palette; 4] = [Palette::Synthwave, Palette::Mono, Palette::Pastel, Palette::Neon];
+
+    #[test]
+    fn every_palette_separates_bars_from_background() {
+        for palette in ALL {
+            let c = palette.colors();
+            let luma = |p: [u8; 4]| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+            assert!((luma(c.bar) - luma(c.bg)).abs() > 60.0, "{:?}", palette);
+        }
+    }
+
+    #[test]
+    fn palettes_are_opaque() {
+        for palette in ALL {
+            let c = palette.colors();
+            assert_eq!(c.bar[3], 255);
+            assert_eq!(c.bg[3], 255);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Short-lived particles burst from the tops of the loudest bars on each beat
+
+/// Number of loudest bars that emit particles on a beat.
+#[cfg(feature = "runner")]
+pub const BURST_BARS: usize = 6;
+
+/// Particles spawned per emitting bar.
+#[cfg(feature = "runner")]
+const PARTICLES_PER_BAR: usize = 6;
+
+/// Time a particle takes to fade out completely (seconds).
+#[cfg(feature = "runner")]
+const LIFETIME_SEC: f32 = 0.8;
+
+/// Launch speed range, in spectrum heights per second.
+#[cfg(feature = "runner")]
+const SPEED_RANGE: (f32, f32) = (1.0, 2.5);
+
+/// Downward acceleration, in spectrum heights per second squared.
+#[cfg(feature = "runner")]
+const GRAVITY: f32 = 3.0;
+
+/// Widest launch angle away from straight up (degrees).
+#[cfg(feature = "runner")]
+const SPREAD_DEG: f32 = 35.0;
+
+/// Where a burst starts: the top of one bar.
+#[cfg(feature = "runner")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub x: f32,
+    pub y: f32,
+    pub color: [u8; 4],
+    /// Radius of the particles it spawns (pixels).
+    pub size: f32,
+}
+
+/// One spark, positioned in the band's horizontal layout (pixels).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    /// Remaining life, from 1.0 at spawn down to 0.0; used as opacity.
+    pub life: f32,
+    pub color: [u8; 4],
+    pub size: f32,
+}
+
+/// The particle seed of spectrum band `index` for `--seed`: spread out by a large odd multiplier, so each seed gives
+/// every band its own sequence. Seed 0 (the default) gives `index + 1`.
+#[cfg(feature = "runner")]
+pub fn band_seed(seed: u64, index: usize) -> u64 {
+    seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (index as u64 + 1)
+}
+
+/// Live particles of one spectrum band, carried from frame to frame.
+#[cfg(feature = "runner")]
+#[derive(Clone, Debug)]
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    /// Length unit for speeds and gravity (the band height, pixels).
+    scale: f32,
+    rng: u64,
+}
+
+#[cfg(feature = "runner")]
+impl ParticleSystem {
+    pub fn new(spectrum_height: u32, seed: u64) -> Self {
+        Self {
+            particles: Vec::new(),
+            scale: spectrum_height.max(1) as f32,
+            // xorshift must not start at zero.
+            rng: seed | 1,
+        }
+    }
+
+    /// Spawn a burst of particles from each emitter, flying upward in a fan.
+    pub fn burst(&mut self, emitters: &[Emitter]) {
+        for e in emitters {
+            for _ in 0..PARTICLES_PER_BAR {
+                let angle = (self.next_unit() * 2.0 - 1.0) * SPREAD_DEG.to_radians();
+                let speed = (SPEED_RANGE.0 + (SPEED_RANGE.1 - SPEED_RANGE.0) * self.next_unit()) * self.scale;
+                self.particles.push(Particle {
+                    x: e.x,
+                    y: e.y,
+                    vx: speed * angle.sin(),
+                    vy: -speed * angle.cos(),
+                    life: 1.0,
+                    color: e.color,
+                    size: e.size,
+                });
+            }
+        }
+    }
+
+    /// Advance one frame (1 / `fps` seconds): move, apply gravity, fade, and drop dead particles.
+    pub fn step(&mut self, fps: u32) {
+        let dt = 1.0 / fps.max(1) as f32;
+        let gravity = GRAVITY * self.scale;
+        for p in &mut self.particles {
+            p.x += p.vx * dt;
+            p.y += p.vy * dt;
+            p.vy += gravity * dt;
+            p.life -= dt / LIFETIME_SEC;
+        }
+        self.particles.retain(|p| p.life > 0.0);
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Next pseudo-random value in 0.0..1.0 (xorshift64), so renders are reproducible.
+    fn next_unit(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(all(test, feature = "runner"))]
+mod tests {
+    use super::{band_seed, Emitter, ParticleSystem, LIFETIME_SEC, PARTICLES_PER_BAR};
+
+    const EMITTER: Emitter = Emitter { x: 50.0, y: 100.0, color: [255, 0, 0, 255], size: 3.0 };
+
+    #[test]
+    fn burst_spawns_upward_particles_at_emitters() {
+        let mut system = ParticleSystem::new(100, 7);
+        system.burst(&[EMITTER, Emitter { x: 80.0, ..EMITTER }]);
+        assert_eq!(system.particles().len(), 2 * PARTICLES_PER_BAR);
+        system.step(30);
+        for p in system.particles() {
+            assert!(p.y < 100.0, "{:?}", p);
+            assert!(p.life < 1.0 && p.life > 0.9);
+        }
+    }
+
+    #[test]
+    fn particles_expire_after_lifetime() {
+        let mut system = ParticleSystem::new(100, 7);
+        system.burst(&[EMITTER]);
+        let frames = (LIFETIME_SEC * 30.0).ceil() as usize;
+        for _ in 0..frames - 1 {
+            system.step(30);
+        }
+        assert!(!system.particles().is_empty());
+        system.step(30);
+        assert!(system.particles().is_empty());
+    }
+
+    #[test]
+    fn same_seed_gives_same_burst() {
+        let mut a = ParticleSystem::new(100, 42);
+        let mut b = ParticleSystem::new(100, 42);
+        a.burst(&[EMITTER]);
+        b.burst(&[EMITTER]);
+        assert_eq!(a.particles(), b.particles());
+    }
+
+    #[test]
+    fn seeds_give_each_band_its_own_repeatable_burst() {
+        assert_eq!((band_seed(0, 0), band_seed(0, 2)), (1, 3));
+        let burst = |seed| {
+            let mut system = ParticleSystem::new(100, seed);
+            system.burst(&[EMITTER]);
+            system.particles().to_vec()
+        };
+        assert_eq!(burst(band_seed(7, 0)), burst(band_seed(7, 0)));
+        assert_ne!(burst(band_seed(7, 0)), burst(band_seed(8, 0)));
+        assert_ne!(burst(band_seed(7, 0)), burst(band_seed(7, 1)));
+    }
+}
@@ -0,0 +1,305 @@
+//! The render pipeline: one thread advances the state that builds up frame by frame, render workers draw frames in
+//! parallel, and a writer puts them back in order for the encoder
+
+use std::collections::BTreeMap;
+use std::ops::{ControlFlow, Range};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+
+use image::RgbaImage;
+
+use crate::background::{BassFollower, VideoFrames};
+use crate::beat::BeatDetector;
+use crate::colormap::rotate_hue;
+use crate::config::{Config, Style};
+use crate::decode::AudioDecoder;
+use crate::draw::{particle_emitters, BarHistory};
+use crate::envelope::FrameWindows;
+use crate::error::SpectrumError;
+use crate::interrupt::CancelToken;
+use crate::logging::{self, debug};
+use crate::memory::MemoryPlan;
+use crate::meter::MeterFollower;
+use crate::output::VideoEncoder;
+use crate::particles::{band_seed, ParticleSystem, BURST_BARS};
+use crate::progress::{Phase, ProgressSink};
+use crate::renderer::{encode_failed, write_failed, RenderSettings, Renderer};
+use crate::scene::{FrameState, Scene, Track};
+
+/// Chunks the second read of the audio decodes ahead of the frames.
+const AUDIO_CHUNKS_AHEAD: usize = 16;
+
+/// A band's state that builds up frame by frame, advanced in order before each frame is handed to a render worker.
+struct BandTrail {
+    history: BarHistory,
+    next_spectrogram_index: usize,
+    /// Live beat particles (`config.particles`).
+    particles: Option<ParticleSystem>,
+}
+
+/// Owned mono samples, and left and right when asked for.
+type OwnedChunk = (Vec<f32>, Option<(Vec<f32>, Vec<f32>)>);
+
+/// The second read of the audio, for the styles and visualizers that draw the samples: chunks from the thread
+/// decoding the input again, cut into each frame's window as the frames come up.
+struct AudioFeed {
+    chunks: mpsc::Receiver<OwnedChunk>,
+    mono: FrameWindows,
+    stereo: Option<(FrameWindows, FrameWindows)>,
+}
+
+impl AudioFeed {
+    /// Frame `frame_index`'s samples, waiting on the reader until it has decoded them or the audio ends.
+    fn frame(&mut self, frame_index: usize) -> OwnedChunk {
+        while !self.mono.covers(frame_index) {
+            let Ok((samples, stereo)) = self.chunks.recv() else { break };
+            self.mono.push(&samples);
+            if let (Some((left, right)), Some((l, r))) = (&mut self.stereo, stereo) {
+                left.push(&l);
+                right.push(&r);
+            }
+        }
+        let stereo = self.stereo.as_mut().map(|(left, right)| (left.take(frame_index), right.take(frame_index)));
+        (self.mono.take(frame_index), stereo)
+    }
+}
+
+/// Where the audio is read from a second time, for the frames that draw the samples.
+pub struct AudioSource<'a> {
+    pub decoder: &'a dyn AudioDecoder,
+    pub input: &'a Path,
+    /// Where the first read stopped (a preview's frame plus its lookahead).
+    pub stop_after_sec: Option<f32>,
+}
+
+/// Where the writer puts the frames, in order: an encoder, or a snapshot's PNG.
+pub enum FrameSink<'a> {
+    Encoder(Box<dyn VideoEncoder>),
+    Snapshot(&'a Path),
+}
+
+/// The state that builds up frame by frame (bass, beats, meter, each band's history and particles, the background
+/// video), advanced in order on the thread handing frames to the render workers.
+pub struct Sequence<'a> {
+    config: &'a Config,
+    settings: &'a RenderSettings,
+    track: &'a Track,
+    scene: &'a Scene<'a>,
+    trails: Vec<BandTrail>,
+    bass: BassFollower,
+    beats: BeatDetector,
+    meter: MeterFollower,
+    bg_video: Option<VideoFrames>,
+}
+
+impl<'a> Sequence<'a> {
+    /// The state before the first frame of `scene`, with `bg_video` (if any) at that frame.
+    pub fn new(renderer: &'a Renderer, track: &'a Track, scene: &'a Scene<'a>, bg_video: Option<VideoFrames>) -> Self {
+        let (config, settings) = (renderer.config(), renderer.settings());
+        let trails = scene
+            .bands
+            .iter()
+            .enumerate()
+            .map(|(index, band)| {
+                let band = band.visualizer.config();
+                let history = BarHistory::for_config(band);
+                let seed = band_seed(settings.seed, index);
+                let particles = band.particles.then(|| ParticleSystem::new(band.spectrum_height, seed));
+                BandTrail { history, next_spectrogram_index: 0, particles }
+            })
+            .collect();
+        Self {
+            config,
+            settings,
+            track,
+            scene,
+            trails,
+            bass: BassFollower::default(),
+            beats: BeatDetector::new(config.fps),
+            meter: MeterFollower::new(settings.meter_ballistics),
+            bg_video,
+        }
+    }
+
+    /// Advance to frame `frame_index`, whose samples are `waveform` and `stereo_samples`, and return its state.
+    fn advance(&mut self, frame_index: usize, (waveform, stereo_samples): OwnedChunk) -> FrameState {
+        let (config, settings, track) = (self.config, self.settings, self.track);
+        let spectrum_index = track.spectrum_index(frame_index);
+        // Bass and beats follow the analysis order (lowest bars first), whatever the drawn layout.
+        let spectrum_heights = track.bar_heights(spectrum_index, config);
+        let bar_heights = self.scene.arrange(&spectrum_heights);
+        let beat = self.beats.update(&spectrum_heights, settings.bass_bars);
+        let hue_degrees = settings.color_cycle.map(|period| 360.0 * frame_index as f32 / config.fps as f32 / period);
+        for (band, trail) in self.scene.bands.iter().zip(&mut self.trails) {
+            if let Some(ref mut particles) = trail.particles {
+                particles.step(config.fps);
+                if beat {
+                    let mut emitters = particle_emitters(band.visualizer.config(), &bar_heights, BURST_BARS);
+                    if let Some(degrees) = hue_degrees {
+                        emitters.iter_mut().for_each(|e| e.color = rotate_hue(e.color, degrees));
+                    }
+                    particles.burst(&emitters);
+                }
+            }
+            if band.visualizer.config().style == Style::Spectrogram {
+                // One column per analysis hop, including any hops skipped between video frames.
+                while trail.next_spectrogram_index <= spectrum_index {
+                    let heights = track.bar_heights(trail.next_spectrogram_index, config);
+                    trail.history.push(self.scene.arrange(&heights));
+                    trail.next_spectrogram_index += 1;
+                }
+            } else if band.visualizer.config().style == Style::Dots {
+                trail.history.push(bar_heights.clone());
+            }
+        }
+        let meter_position = match settings.meter {
+            Some(_) => self.meter.update(track.levels.get(frame_index).map_or(0.0, |l| l.rms), config.fps),
+            None => 0.0,
+        };
+        FrameState {
+            index: frame_index,
+            spectrum_index,
+            bar_heights,
+            hue_degrees,
+            trails: self
+                .trails
+                .iter()
+                .map(|trail| {
+                    let particles = trail.particles.as_ref().map_or(&[][..], ParticleSystem::particles);
+                    (trail.history.clone(), particles.to_vec())
+                })
+                .collect(),
+            video_bg: self.bg_video.as_mut().and_then(|v| v.next_frame()),
+            bass_level: self.bass.update(&spectrum_heights, settings.bass_bars),
+            meter_position,
+            beat,
+            waveform,
+            stereo_samples,
+        }
+    }
+}
+
+/// Draw `frames` and hand them to `sink` in order, `memory.workers` at a time with at most `memory.in_flight`
+/// frames held. Tickets cap the frames in flight, which bounds memory. The styles and visualizers that draw the
+/// samples get them from a second read of `audio`, a few chunks ahead of the frames. Returns the sink for the
+/// encode to be finished, or `SpectrumError::Interrupted` once `cancel` is.
+pub fn render_frames<'a>(
+    mut sequence: Sequence,
+    frames: Range<usize>,
+    audio: AudioSource,
+    mut sink: FrameSink<'a>,
+    memory: MemoryPlan,
+    progress: &dyn ProgressSink,
+    cancel: &CancelToken,
+) -> Result<FrameSink<'a>, SpectrumError> {
+    let MemoryPlan { workers, in_flight } = memory;
+    debug!("Render workers: {}, frames in flight: {}", workers, in_flight);
+    let scene = sequence.scene;
+    let (needs_samples, needs_stereo) = scene.needs_samples();
+    let (sample_rate, stereo) = (sequence.track.sample_rate, sequence.track.stereo);
+    let fps = sequence.config.fps;
+    let (job_tx, job_rx) = mpsc::sync_channel::<FrameState>(in_flight);
+    let job_rx = Mutex::new(job_rx);
+    // A frame whose drawing panicked comes back as the panic's message, so the writer stops instead of waiting on it.
+    let (done_tx, done_rx) = mpsc::sync_channel::<(usize, Result<RgbaImage, String>)>(in_flight);
+    let (ticket_tx, ticket_rx) = mpsc::sync_channel::<()>(in_flight);
+    for _ in 0..in_flight {
+        ticket_tx.send(()).expect("ticket channel has room for every ticket");
+    }
+    let first_frame = frames.start;
+    let log = logging::current();
+    let (sink, read) = std::thread::scope(|scope| {
+        let (reader, mut feed) = if needs_samples {
+            let (chunk_tx, chunk_rx) = mpsc::sync_channel::<OwnedChunk>(AUDIO_CHUNKS_AHEAD);
+            let (AudioSource { decoder, input, stop_after_sec }, log) = (audio, log.clone());
+            let reader = scope.spawn(move || {
+                let _log = logging::enter(log);
+                decoder.stream(input, needs_stereo, stop_after_sec, &mut |chunk| {
+                    let stereo = chunk.stereo.map(|(left, right)| (left.to_vec(), right.to_vec()));
+                    match chunk_tx.send((chunk.samples.to_vec(), stereo)) {
+                        Ok(()) => ControlFlow::Continue(()),
+                        // The render ended before the audio did.
+                        Err(_) => ControlFlow::Break(()),
+                    }
+                })
+            });
+            let windows = || FrameWindows::new(sample_rate, fps, first_frame);
+            let stereo = (needs_stereo && stereo).then(|| (windows(), windows()));
+            (Some(reader), Some(AudioFeed { chunks: chunk_rx, mono: windows(), stereo }))
+        } else {
+            (None, None)
+        };
+        for _ in 0..workers {
+            let (job_rx, done_tx, log) = (&job_rx, done_tx.clone(), log.clone());
+            scope.spawn(move || {
+                let _log = logging::enter(log);
+                let mut subtitle_block = None;
+                loop {
+                    let job = job_rx.lock().expect("render worker panicked").recv();
+                    let Ok(state) = job else { break };
+                    let drawn = panic::catch_unwind(AssertUnwindSafe(|| scene.draw(&state, &mut subtitle_block)));
+                    let img = drawn.map_err(|payload| panic_message(payload.as_ref()));
+                    if done_tx.send((state.index, img)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(done_tx);
+
+        let writer = scope.spawn(move || -> Result<_, SpectrumError> {
+            let _log = logging::enter(log);
+            let mut pending = BTreeMap::new();
+            let mut next = first_frame;
+            for (index, img) in done_rx {
+                let img = img.map_err(|message| {
+                    SpectrumError::RenderFailed(format!("drawing frame {} failed: {}", index, message))
+                })?;
+                pending.insert(index, img);
+                while let Some(img) = pending.remove(&next) {
+                    match sink {
+                        FrameSink::Encoder(ref mut encoder) => encoder.write_frame(&img).map_err(encode_failed)?,
+                        FrameSink::Snapshot(path) => img.save(path).map_err(|e| write_failed(path, e))?,
+                    }
+                    next += 1;
+                    progress.update(Phase::Rendering, (next - first_frame) as u64);
+                    let _ = ticket_tx.send(());
+                }
+            }
+            Ok(sink)
+        });
+
+        for frame_index in frames {
+            // No ticket: the writer stopped on an error, reported below.
+            if cancel.is_cancelled() || ticket_rx.recv().is_err() {
+                break;
+            }
+            let samples = feed.as_mut().map_or((Vec::new(), None), |feed| feed.frame(frame_index));
+            if job_tx.send(sequence.advance(frame_index, samples)).is_err() {
+                break;
+            }
+        }
+        drop(job_tx);
+        // Dropping the feed stops a reader still decoding ahead.
+        drop(feed);
+        let sink = writer.join().expect("frame writer panicked");
+        (sink, reader.map(|reader| reader.join().expect("audio reader panicked")))
+    });
+    // ffmpeg also gets a terminal's Ctrl-C, so its failure to take more frames is part of the interruption.
+    if cancel.is_cancelled() {
+        return Err(SpectrumError::Interrupted);
+    }
+    let sink = sink?;
+    read.transpose()?;
+    Ok(sink)
+}
+
+/// What a caught panic said: its `panic!` message, or a placeholder for a payload that isn't a string.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "panicked".to_string(),
+    }
+}
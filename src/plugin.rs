@@ -0,0 +1,294 @@
+//! `--plugin`: visualizers from shared libraries in the plugins directory, through a small C interface
+//!
+//! A plugin exports these functions (C calling convention, unmangled names):
+//!
+//! - `uint32_t asg_plugin_abi_version(void)`: `PLUGIN_ABI_VERSION`, checked before anything else is called.
+//! - `const char *asg_plugin_name(void)`: the name `--plugin` selects it by, NUL-terminated and static.
+//! - `void *asg_plugin_create(uint32_t width, uint32_t height, uint32_t bars)`: the plugin's state for frames of
+//!   that size and bar count (may be null).
+//! - `void asg_plugin_draw(void *state, uint8_t *rgba, uint32_t width, uint32_t height, const PluginFrame *frame)`:
+//!   draw over the frame, `width * height` straight-alpha RGBA pixels row by row. Called from several threads at
+//!   once and out of order, so the state is read-only here.
+//! - `void asg_plugin_destroy(void *state)`: free the state.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use libloading::Library;
+
+use crate::draw::FrameData;
+use crate::error::SpectrumError;
+use crate::export::FrameAnalysis;
+use crate::config_dir::user_config_dir;
+use crate::visualizer::Visualizer;
+
+/// Version of the plugin interface; bumped whenever a function or `PluginFrame` changes.
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+/// One frame's `FrameAnalysis` as plugins receive it, laid out for C.
+#[repr(C)]
+#[derive(Debug)]
+pub struct PluginFrame {
+    /// Start of the frame in seconds.
+    pub time: f32,
+    /// `bar_count` normalized bar values (0.0–1.0), lowest frequency first; valid during the draw call only.
+    pub bars: *const f32,
+    pub bar_count: usize,
+    pub rms: f32,
+    pub peak: f32,
+    pub lufs: f32,
+    pub beat: bool,
+    /// `sample_count` mono samples (-1.0 to 1.0) the frame covers; valid during the draw call only.
+    pub samples: *const f32,
+    pub sample_count: usize,
+}
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type CreateFn = unsafe extern "C" fn(u32, u32, u32) -> *mut c_void;
+type DrawFn = unsafe extern "C" fn(*mut c_void, *mut u8, u32, u32, *const PluginFrame);
+type DestroyFn = unsafe extern "C" fn(*mut c_void);
+
+/// A loaded plugin library, checked against `PLUGIN_ABI_VERSION`.
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+    create: CreateFn,
+    draw: DrawFn,
+    destroy: DestroyFn,
+    /// Keeps the functions above loaded.
+    _library: Library,
+}
+
+impl Plugin {
+    /// Load the shared library at `path`. Loading runs the library's own initialization code, so only trusted
+    /// libraries belong in a plugins directory.
+    pub fn load(path: &Path) -> Result<Self, SpectrumError> {
+        let failed = |message: String| SpectrumError::InvalidConfig(format!("plugin {:?}: {}", path, message));
+        // SAFETY: see above; the plugin is code the user installed.
+        let library = unsafe { Library::new(path) }.map_err(|e| failed(loader_message(&e)))?;
+        // SAFETY: each symbol is looked up with its type in the interface, and the version is checked before any
+        // other function is called.
+        unsafe {
+            let version = symbol::<AbiVersionFn>(&library, "asg_plugin_abi_version").map_err(failed)?();
+            if version != PLUGIN_ABI_VERSION {
+                let message = format!("interface version {} (this build loads {})", version, PLUGIN_ABI_VERSION);
+                return Err(failed(message));
+            }
+            let name = symbol::<NameFn>(&library, "asg_plugin_name").map_err(failed)?();
+            if name.is_null() {
+                return Err(failed("asg_plugin_name returned null".into()));
+            }
+            Ok(Self {
+                name: CStr::from_ptr(name).to_string_lossy().into_owned(),
+                path: path.to_path_buf(),
+                create: symbol(&library, "asg_plugin_create").map_err(failed)?,
+                draw: symbol(&library, "asg_plugin_draw").map_err(failed)?,
+                destroy: symbol(&library, "asg_plugin_destroy").map_err(failed)?,
+                _library: library,
+            })
+        }
+    }
+
+    /// The name `--plugin` selects the plugin by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// The exported function `name` of `library`.
+///
+/// # Safety
+/// `T` must be the function's type in the plugin interface.
+unsafe fn symbol<T: Copy>(library: &Library, name: &str) -> Result<T, String> {
+    // SAFETY: passed on to the caller.
+    let function = unsafe { library.get::<T>(name) };
+    function.map(|function| *function).map_err(|e| format!("missing {}: {}", name, loader_message(&e)))
+}
+
+/// A loader error with the system's reason, which libloading keeps as the source.
+fn loader_message(error: &libloading::Error) -> String {
+    match std::error::Error::source(error) {
+        Some(reason) => format!("{}: {}", error, reason),
+        None => error.to_string(),
+    }
+}
+
+/// A plugin with its state for one frame size, as a `Visualizer`; render workers share one instance.
+pub struct PluginVisualizer {
+    plugin: Plugin,
+    state: *mut c_void,
+}
+
+// SAFETY: the interface makes `asg_plugin_draw` callable from any thread, concurrently, with the same state.
+unsafe impl Send for PluginVisualizer {}
+unsafe impl Sync for PluginVisualizer {}
+
+impl PluginVisualizer {
+    /// Create the plugin's state for frames of `size` with `bars` bars.
+    pub fn new(plugin: Plugin, size: (u32, u32), bars: usize) -> Self {
+        // SAFETY: `create` is the plugin's `asg_plugin_create`.
+        let state = unsafe { (plugin.create)(size.0, size.1, bars as u32) };
+        Self { plugin, state }
+    }
+
+}
+
+/// Lets the plugin draw `analysis`, with `data.waveform` as the samples, onto `frame`.
+impl Visualizer for PluginVisualizer {
+    fn draw(&self, frame: &mut RgbaImage, analysis: &FrameAnalysis, data: &FrameData) {
+        let (width, height) = frame.dimensions();
+        let input = PluginFrame {
+            time: analysis.time,
+            bars: analysis.bars.as_ptr(),
+            bar_count: analysis.bars.len(),
+            rms: analysis.rms,
+            peak: analysis.peak,
+            lufs: analysis.lufs,
+            beat: analysis.beat,
+            samples: data.waveform.as_ptr(),
+            sample_count: data.waveform.len(),
+        };
+        // SAFETY: `frame` holds `width * height` RGBA pixels, and `input` and the bars and samples it points to
+        // outlive the call.
+        unsafe { (self.plugin.draw)(self.state, frame.as_mut_ptr(), width, height, &input) }
+    }
+}
+
+impl Drop for PluginVisualizer {
+    fn drop(&mut self) {
+        // SAFETY: `state` came from this plugin's `asg_plugin_create` and is destroyed once.
+        unsafe { (self.plugin.destroy)(self.state) }
+    }
+}
+
+/// The default plugins directory: `audio-spectrum-generator/plugins` in the user config directory.
+pub fn default_plugin_dir() -> Result<PathBuf, String> {
+    user_config_dir("plugins", "plugins")
+}
+
+/// Every shared library in `dir` (`.so`, `.dylib` or `.dll`, whichever the platform uses), loaded, in file name
+/// order. A missing directory holds no plugins.
+pub fn discover_plugins(dir: &Path) -> Vec<Result<Plugin, SpectrumError>> {
+    library_files(dir).iter().map(|path| Plugin::load(path)).collect()
+}
+
+/// The shared libraries in `dir`, in file name order, without loading them.
+fn library_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The plugin called `name` in `dir`: the library named after it (`name.so` or `libname.so`, with the platform's
+/// extension), which must report the same name. No other library is loaded.
+pub fn find_plugin(dir: &Path, name: &str) -> Result<Plugin, SpectrumError> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(SpectrumError::InvalidConfig(format!("{:?} is not a plugin name", name)));
+    }
+    let extension = std::env::consts::DLL_EXTENSION;
+    let files = [format!("{}.{}", name, extension), format!("lib{}.{}", name, extension)];
+    let Some(path) = files.iter().map(|file| dir.join(file)).find(|path| path.is_file()) else {
+        let found: Vec<String> = library_files(dir)
+            .iter()
+            .filter_map(|path| path.file_name().map(|file| file.to_string_lossy().into_owned()))
+            .collect();
+        let found = if found.is_empty() { "none".to_string() } else { found.join(", ") };
+        let message = format!("no plugin named {:?} in {:?} (found: {})", name, dir, found);
+        return Err(SpectrumError::InvalidConfig(message));
+    };
+    let plugin = Plugin::load(&path)?;
+    if plugin.name != name {
+        let message = format!("plugin {:?} is called {:?}, not {:?}", path, plugin.name, name);
+        return Err(SpectrumError::InvalidConfig(message));
+    }
+    Ok(plugin)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use image::{Rgba, RgbaImage};
+
+    use super::{discover_plugins, find_plugin, Plugin, PluginVisualizer};
+    use crate::draw::FrameData;
+    use crate::export::FrameAnalysis;
+    use crate::visualizer::Visualizer;
+
+    #[test]
+    fn only_shared_libraries_are_loaded_and_broken_ones_are_reported() {
+        let dir = std::env::temp_dir().join(format!("asg-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a plugin").unwrap();
+        let broken = dir.join(format!("broken.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::write(&broken, "not a library either").unwrap();
+
+        let plugins = discover_plugins(&dir);
+        assert_eq!(plugins.len(), 1);
+        assert!(plugins[0].as_ref().is_err_and(|e| e.to_string().contains("broken")));
+        assert!(Plugin::load(&broken).is_err());
+        let missing = find_plugin(&dir, "flash").err().unwrap().to_string();
+        let listed = format!("found: broken.{}", std::env::consts::DLL_EXTENSION);
+        assert!(missing.contains("no plugin named \"flash\"") && missing.contains(&listed), "{}", missing);
+        assert!(find_plugin(&dir, "broken").is_err_and(|e| e.to_string().contains("broken")));
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(discover_plugins(&dir).is_empty());
+    }
+
+    /// Builds `examples/outline_plugin.rs` with cargo and returns the library.
+    fn build_example_plugin() -> PathBuf {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--quiet", "--example", "outline", "--features", "plugins", "--manifest-path"])
+            .arg(PathBuf::from(manifest).join("Cargo.toml"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "building the example plugin failed");
+        let target = std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from);
+        let target = target.unwrap_or_else(|| PathBuf::from(manifest).join("target"));
+        let file = format!("{}outline{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX);
+        target.join("debug").join("examples").join(file)
+    }
+
+    #[test]
+    fn the_example_plugin_is_found_by_file_name_and_draws() {
+        let library = build_example_plugin();
+        let dir = std::env::temp_dir().join(format!("asg-plugins-example-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let extension = std::env::consts::DLL_EXTENSION;
+        std::fs::copy(&library, dir.join(format!("outline.{}", extension))).unwrap();
+        std::fs::copy(&library, dir.join(format!("libmislabeled.{}", extension))).unwrap();
+        // Never loaded: only the library named after the plugin is.
+        std::fs::write(dir.join(format!("broken.{}", extension)), "not a library").unwrap();
+
+        let plugin = find_plugin(&dir, "outline").unwrap();
+        assert_eq!(plugin.name(), "outline");
+        let mislabeled = find_plugin(&dir, "mislabeled").err().unwrap().to_string();
+        assert!(mislabeled.contains("is called \"outline\""), "{}", mislabeled);
+        assert!(find_plugin(&dir, "../outline").is_err());
+
+        let visualizer = PluginVisualizer::new(plugin, (40, 20), 4);
+        let mut frame = RgbaImage::new(40, 20);
+        let analysis = FrameAnalysis { time: 0.0, bars: vec![1.0; 4], rms: 0.0, peak: 0.0, lufs: -100.0, beat: true };
+        visualizer.draw(&mut frame, &analysis, &FrameData { waveform: &[0.0; 8], ..Default::default() });
+        // On a beat the outline is white; the left edge of the first bar runs to the bottom.
+        assert_eq!(*frame.get_pixel(0, 19), Rgba([255; 4]));
+        assert_eq!(*frame.get_pixel(5, 19), Rgba([0; 4]));
+        drop(visualizer);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,173 @@
+//! Platform presets (--preset): frame size, frame rate, spectrum layout and safe margins in one flag. `--preset`
+//! also recalls option sets saved with `preset save NAME`.
+
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+use crate::config::Style;
+use crate::config_dir::user_config_dir;
+
+/// Target platform for the output video.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+    /// 1920x1080 landscape at 30 fps.
+    Youtube,
+    /// YouTube Shorts: 1080x1920 portrait, band raised above the caption and channel UI.
+    Shorts,
+    /// TikTok: 1080x1920 portrait, with wider margins for the side buttons and caption area.
+    Tiktok,
+    /// Instagram feed post: 1080x1080 square.
+    InstagramSquare,
+    /// Square audiogram: whole-track waveform with a playhead and the track title.
+    PodcastAudiogram,
+}
+
+/// Settings a preset supplies. Flags given on the command line take precedence over each of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresetSettings {
+    pub resolution: (u32, u32),
+    pub fps: u32,
+    pub style: Style,
+    pub bars: usize,
+    pub spectrum_height: u32,
+    pub spectrum_width: Option<u32>,
+    pub spectrum_y_from_bottom: u32,
+    /// Distance that keeps overlays clear of the platform's own UI (pixels).
+    pub overlay_margin: u32,
+    pub show_title: bool,
+}
+
+/// A `--preset` value: a platform preset, or the name of a preset saved with `preset save`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresetChoice {
+    Platform(Preset),
+    Saved(String),
+}
+
+/// Parse `--preset`: platform preset names first, anything else names a saved preset.
+pub fn parse_preset(s: &str) -> Result<PresetChoice, String> {
+    if let Ok(preset) = Preset::from_str(s, true) {
+        return Ok(PresetChoice::Platform(preset));
+    }
+    parse_saved_name(s).map(PresetChoice::Saved)
+}
+
+/// Parse a saved preset name: letters, digits, `-` and `_`, and not a platform preset's name (which would hide it).
+pub fn parse_saved_name(s: &str) -> Result<String, String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("invalid preset name {:?}: use letters, digits, '-' and '_'", s));
+    }
+    if Preset::from_str(s, true).is_ok() {
+        return Err(format!("{:?} is a platform preset", s));
+    }
+    Ok(s.to_string())
+}
+
+/// Directory holding saved presets: `audio-spectrum-generator/presets` in the user config directory.
+pub fn saved_preset_dir() -> Result<PathBuf, String> {
+    user_config_dir("presets", "saved presets")
+}
+
+/// File of the saved preset `name` (a TOML file of options, as read by `--config`).
+pub fn saved_preset_path(name: &str) -> Result<PathBuf, String> {
+    Ok(saved_preset_dir()?.join(format!("{}.toml", name)))
+}
+
+impl Preset {
+    pub fn settings(self) -> PresetSettings {
+        match self {
+            Preset::Youtube => PresetSettings {
+                resolution: (1920, 1080),
+                fps: 30,
+                style: Style::Bars,
+                bars: 128,
+                spectrum_height: 200,
+                spectrum_width: None,
+                spectrum_y_from_bottom: 0,
+                overlay_margin: 96,
+                show_title: false,
+            },
+            Preset::Shorts => PresetSettings {
+                resolution: (1080, 1920),
+                fps: 30,
+                style: Style::Bars,
+                bars: 64,
+                spectrum_height: 320,
+                spectrum_width: Some(840),
+                spectrum_y_from_bottom: 420,
+                overlay_margin: 120,
+                show_title: false,
+            },
+            Preset::Tiktok => PresetSettings {
+                resolution: (1080, 1920),
+                fps: 30,
+                style: Style::Bars,
+                bars: 64,
+                spectrum_height: 320,
+                spectrum_width: Some(800),
+                spectrum_y_from_bottom: 480,
+                overlay_margin: 140,
+                show_title: false,
+            },
+            Preset::InstagramSquare => PresetSettings {
+                resolution: (1080, 1080),
+                fps: 30,
+                style: Style::Bars,
+                bars: 64,
+                spectrum_height: 240,
+                spectrum_width: None,
+                spectrum_y_from_bottom: 0,
+                overlay_margin: 54,
+                show_title: false,
+            },
+            Preset::PodcastAudiogram => PresetSettings {
+                resolution: (1080, 1080),
+                fps: 24,
+                style: Style::Timeline,
+                bars: 64,
+                spectrum_height: 240,
+                spectrum_width: Some(936),
+                spectrum_y_from_bottom: 160,
+                overlay_margin: 72,
+                show_title: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_preset, parse_saved_name, Preset, PresetChoice};
+    use clap::ValueEnum;
+
+    #[test]
+    fn preset_names_are_platform_first_then_saved() {
+        assert_eq!(parse_preset("shorts"), Ok(PresetChoice::Platform(Preset::Shorts)));
+        assert_eq!(parse_preset("instagram-square"), Ok(PresetChoice::Platform(Preset::InstagramSquare)));
+        assert_eq!(parse_preset("my_podcast-2"), Ok(PresetChoice::Saved("my_podcast-2".into())));
+        assert!(parse_preset("../music").is_err());
+        assert!(parse_saved_name("").is_err());
+        assert!(parse_saved_name("TikTok").is_err());
+    }
+
+    #[test]
+    fn presets_keep_band_and_margins_inside_frame() {
+        for preset in Preset::value_variants() {
+            let s = preset.settings();
+            let (w, h) = s.resolution;
+            assert!(s.spectrum_width.is_none_or(|sw| sw + 2 * s.overlay_margin <= w), "{:?}", preset);
+            assert!(s.spectrum_height + s.spectrum_y_from_bottom <= h, "{:?}", preset);
+            assert!(s.overlay_margin * 2 < w.min(h), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn portrait_presets_raise_band_above_platform_ui() {
+        for preset in [Preset::Shorts, Preset::Tiktok] {
+            let s = preset.settings();
+            assert_eq!(s.resolution, (1080, 1920));
+            assert!(s.spectrum_y_from_bottom >= 1920 / 5, "{:?}", preset);
+        }
+    }
+}
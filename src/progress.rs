@@ -0,0 +1,251 @@
+//! How far a render's long phases have come, reported to a `ProgressSink`: the command line's terminal bars, JSON
+//! lines, a callback, or nothing. `cli::run_with_progress` takes any sink; the binary picks one with `--progress`.
+//! Only the terminal bars need the `cli` feature (indicatif); the trait, the events and the other sinks are always
+//! built.
+
+use std::io::Write;
+#[cfg(feature = "runner")]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+#[cfg(feature = "cli")]
+use indicatif::ProgressBar;
+use serde::Serialize;
+
+#[cfg(feature = "cli")]
+use crate::logging;
+
+/// A phase of a render that reports progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    /// Decoding the input, counted in seconds of audio.
+    Decoding,
+    /// Drawing frames, counted in frames handed on in order.
+    Rendering,
+    /// ffmpeg encoding the frames it was given, counted in frames.
+    Encoding,
+}
+
+/// One call to a `ProgressSink`, as a value; what `JsonProgress` writes and `CallbackProgress` passes on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum ProgressEvent {
+    Start { phase: Phase, total: Option<u64> },
+    Update { phase: Phase, position: u64 },
+    Finish { phase: Phase },
+}
+
+impl Phase {
+    /// The phase's name in JSON.
+    fn json_name(self) -> &'static str {
+        match self {
+            Self::Decoding => "decoding",
+            Self::Rendering => "rendering",
+            Self::Encoding => "encoding",
+        }
+    }
+}
+
+impl ProgressEvent {
+    /// The event as the one-line JSON object its `Serialize` form gives, without needing a JSON library.
+    fn json(self) -> String {
+        match self {
+            Self::Start { phase, total } => {
+                let total = total.map_or_else(|| "null".to_string(), |total| total.to_string());
+                format!(r#"{{"event":"start","phase":"{}","total":{}}}"#, phase.json_name(), total)
+            }
+            Self::Update { phase, position } => {
+                format!(r#"{{"event":"update","phase":"{}","position":{}}}"#, phase.json_name(), position)
+            }
+            Self::Finish { phase } => format!(r#"{{"event":"finish","phase":"{}"}}"#, phase.json_name()),
+        }
+    }
+}
+
+/// Receives a render's progress. Phases overlap (ffmpeg encodes while frames are drawn) and report from their own
+/// threads, so sinks are shared.
+pub trait ProgressSink: Send + Sync {
+    /// `phase` starts with `total` units to go, if known.
+    fn start(&self, phase: Phase, total: Option<u64>);
+    /// `phase` has done `position` units so far.
+    fn update(&self, phase: Phase, position: u64);
+    /// `phase` is done; not called when the render fails.
+    fn finish(&self, phase: Phase);
+}
+
+/// The command line's bars (`--progress bars`, the default), hidden with `-q`. A phase without a total gets no bar,
+/// and the decoding bar is cleared once done.
+#[cfg(feature = "cli")]
+#[derive(Default)]
+pub struct TerminalProgress {
+    bars: Mutex<Vec<(Phase, ProgressBar)>>,
+}
+
+#[cfg(feature = "cli")]
+impl TerminalProgress {
+    fn bar(&self, phase: Phase) -> Option<ProgressBar> {
+        let bars = self.bars.lock().unwrap();
+        bars.iter().find(|(started, _)| *started == phase).map(|(_, bar)| bar.clone())
+    }
+}
+
+#[cfg(feature = "cli")]
+impl ProgressSink for TerminalProgress {
+    fn start(&self, phase: Phase, total: Option<u64>) {
+        let bar = match total {
+            // Rendering starts after ffmpeg's encoding but goes above it.
+            Some(total) if phase == Phase::Rendering => logging::progress().insert(0, ProgressBar::new(total)),
+            Some(total) => logging::progress().add(ProgressBar::new(total)),
+            None => ProgressBar::hidden(),
+        };
+        let (name, unit, colors) = match phase {
+            Phase::Decoding => ("Decoding", "s of audio", "yellow/black"),
+            Phase::Rendering => ("Rendering", "frames", "cyan/blue"),
+            Phase::Encoding => ("Encoding", "frames", "green/black"),
+        };
+        bar.set_style(logging::stage_style(name, unit, colors));
+        self.bars.lock().unwrap().push((phase, bar));
+    }
+
+    fn update(&self, phase: Phase, position: u64) {
+        if let Some(bar) = self.bar(phase) {
+            bar.set_position(position);
+        }
+    }
+
+    fn finish(&self, phase: Phase) {
+        match self.bar(phase) {
+            Some(bar) if phase == Phase::Decoding => bar.finish_and_clear(),
+            Some(bar) => bar.finish(),
+            None => {}
+        }
+    }
+}
+
+/// Every event as one JSON object per line (`--progress json` writes them to stderr), such as
+/// `{"event":"update","phase":"rendering","position":120}`.
+pub struct JsonProgress<W> {
+    out: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonProgress<W> {
+    pub fn new(out: W) -> Self {
+        Self { out: Mutex::new(out) }
+    }
+
+    fn emit(&self, event: ProgressEvent) {
+        let mut out = self.out.lock().unwrap();
+        // Progress is only a report: a closed pipe doesn't stop the render.
+        let _ = writeln!(out, "{}", event.json());
+        let _ = out.flush();
+    }
+}
+
+impl<W: Write + Send> ProgressSink for JsonProgress<W> {
+    fn start(&self, phase: Phase, total: Option<u64>) {
+        self.emit(ProgressEvent::Start { phase, total });
+    }
+
+    fn update(&self, phase: Phase, position: u64) {
+        self.emit(ProgressEvent::Update { phase, position });
+    }
+
+    fn finish(&self, phase: Phase) {
+        self.emit(ProgressEvent::Finish { phase });
+    }
+}
+
+/// Passes every event to a function, from whichever thread reports it.
+pub struct CallbackProgress<F>(pub F);
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressSink for CallbackProgress<F> {
+    fn start(&self, phase: Phase, total: Option<u64>) {
+        (self.0)(ProgressEvent::Start { phase, total });
+    }
+
+    fn update(&self, phase: Phase, position: u64) {
+        (self.0)(ProgressEvent::Update { phase, position });
+    }
+
+    fn finish(&self, phase: Phase) {
+        (self.0)(ProgressEvent::Finish { phase });
+    }
+}
+
+/// Which sink the command line reports to (`--progress`).
+#[cfg(feature = "runner")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressFormat {
+    /// Terminal progress bars.
+    #[default]
+    Bars,
+    /// JSON lines on stderr, for a program running the render.
+    Json,
+    /// Nothing.
+    None,
+}
+
+#[cfg(feature = "runner")]
+impl ProgressFormat {
+    pub fn sink(self) -> Arc<dyn ProgressSink> {
+        match self {
+            #[cfg(feature = "cli")]
+            Self::Bars => Arc::new(TerminalProgress::default()),
+            // Without the `cli` feature there is no terminal to draw bars on.
+            #[cfg(not(feature = "cli"))]
+            Self::Bars => Arc::new(SilentProgress),
+            Self::Json => Arc::new(JsonProgress::new(std::io::stderr())),
+            Self::None => Arc::new(SilentProgress),
+        }
+    }
+}
+
+/// Reports nothing (`--progress none`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SilentProgress;
+
+impl ProgressSink for SilentProgress {
+    fn start(&self, _: Phase, _: Option<u64>) {}
+
+    fn update(&self, _: Phase, _: u64) {}
+
+    fn finish(&self, _: Phase) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonProgress, Phase, ProgressEvent, ProgressSink};
+
+    #[test]
+    fn json_progress_writes_one_tagged_object_per_line() {
+        let sink = JsonProgress::new(Vec::new());
+        sink.start(Phase::Decoding, None);
+        sink.update(Phase::Rendering, 12);
+        sink.finish(Phase::Encoding);
+        let out = String::from_utf8(sink.out.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"start","phase":"decoding","total":null}"#,
+                r#"{"event":"update","phase":"rendering","position":12}"#,
+                r#"{"event":"finish","phase":"encoding"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines_match_the_events_serialized_form() {
+        for phase in [Phase::Decoding, Phase::Rendering, Phase::Encoding] {
+            for event in [
+                ProgressEvent::Start { phase, total: None },
+                ProgressEvent::Start { phase, total: Some(7054) },
+                ProgressEvent::Update { phase, position: 0 },
+                ProgressEvent::Finish { phase },
+            ] {
+                assert_eq!(event.json(), serde_json::to_string(&event).unwrap());
+            }
+        }
+    }
+}
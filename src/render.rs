@@ -0,0 +1,31 @@
+//! Drawing frames: the `Config` every style reads and the spectrum band drawn from it, and with the `runner`
+//! feature the `Renderer` taking a whole track through decoding, analysis, drawing and encoding
+
+pub use crate::colormap::Colormap;
+pub use crate::config::*;
+pub use crate::draw::{draw_background, draw_spectrum_band, render_frame, BarHistory, FrameData};
+pub use crate::effects::{Glow, Reflection, Shadow};
+#[cfg(feature = "runner")]
+pub use crate::background::{BgMotion, BgPulse, BgVideoFit};
+#[cfg(feature = "runner")]
+pub use crate::frame::Border;
+#[cfg(feature = "runner")]
+pub use crate::interrupt::CancelToken;
+#[cfg(feature = "runner")]
+pub use crate::meter::{MeterBallistics, MeterStyle};
+#[cfg(feature = "runner")]
+pub use crate::overlay::{OverlayPos, TimeDisplay};
+#[cfg(feature = "runner")]
+pub use crate::renderer::{
+    EncodePlan, FrameContext, FrameHook, FrameOutput, Output, RenderPlan, RenderSettings, Renderer, RunOptions,
+    RunSummary, TrackStats, VideoOutput,
+};
+#[cfg(feature = "runner")]
+pub use crate::theme::{Background, Layer, Theme};
+pub use crate::visualizer::{StyleVisualizer, Visualizer};
+#[cfg(feature = "script")]
+pub use crate::script::ScriptVisualizer;
+#[cfg(feature = "plugins")]
+pub use crate::plugin::{
+    default_plugin_dir, discover_plugins, find_plugin, Plugin, PluginFrame, PluginVisualizer, PLUGIN_ABI_VERSION,
+};
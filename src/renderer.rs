@@ -0,0 +1,1178 @@
+//! The whole render as a library type: a `Renderer`, built from a `Config`, its layers and the `RenderSettings` around
+//! them, decodes and analyzes a track, prepares its `Scene` and runs the frame pipeline into an `Output`
+
+use std::ops::{ControlFlow, Range};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::background::{BgMotion, BgPulse, BgVideoFit, VideoFrames};
+use crate::chapters::{ffmetadata, load_chapters};
+use crate::colormap::Colormap;
+use crate::config::{Config, Orientation, Style, Symmetry};
+use crate::decode::{AudioChunk, AudioDecoder, Tags, TrackInfo};
+use crate::error::SpectrumError::{self, InvalidConfig};
+use crate::envelope::EnvelopeStream;
+use crate::export::{frame_analyses, write_analysis};
+use crate::frame::{content_inset, Border};
+use crate::interrupt::{CancelToken, Cleanup};
+use crate::level::{amplitude_to_db, FrameLevelStream, TrackLevelStream, TrackLevels, LEVEL_FLOOR_DB};
+use crate::logging::{self, debug, info, verbose, warning, Stage};
+use crate::memory::{Footprint, MemoryPlan};
+use crate::meter::{MeterBallistics, MeterStyle};
+#[cfg(feature = "native-encoder")]
+use crate::native;
+use crate::output::{
+    command_line, ffmpeg_args, png_sequence_args, writes_to_stdout, Backend, Container, EncodeSettings, Encoder,
+    FrameDir, HwAccel, PngSequence, VideoEncoder,
+};
+use crate::overlay::{OverlayPos, TimeDisplay};
+use crate::pipeline::{render_frames, AudioSource, FrameSink, Sequence};
+use crate::progress::{Phase, ProgressSink, SilentProgress};
+use crate::scene::{Images, Scene, Track};
+use crate::spectrum::{hop_size, BandEnergyStream, SpectrumAnalysis, SpectrumStream, FREQUENCY_BANDS};
+use crate::subtitles::Cue;
+use crate::theme::Layer;
+use crate::visualizer::Visualizer;
+use crate::wav::WavStream;
+
+/// Audio decoded past a preview's frame, covering the analysis window around it.
+const PREVIEW_LOOKAHEAD_SEC: f32 = 1.0;
+/// Points per second of the envelope a timeline strip is merged from: a strip's own points per second depend on the
+/// track's length, known only once decoding ends.
+pub const TIMELINE_POINTS_PER_SEC: f64 = 1000.0;
+/// Pixels one render worker draws per second, for the `Renderer::plan` estimate (release build): typical styles and
+/// effects, and with the per-frame background resampling of `bg_motion`.
+const RENDER_PIXELS_PER_SEC: f64 = 300e6;
+const MOTION_RENDER_PIXELS_PER_SEC: f64 = 20e6;
+
+/// Numbers the runs in this process, so each gets a temporary directory of its own.
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// What a `Renderer` draws around its layers and how it runs, named after the command-line options they come from
+/// (`bg_image` is `--bg-image`; see `--help`). The default is the command line's.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings {
+    /// Background image, resized to the frame and drawn instead of `Config::bg_color`, then blurred and dimmed
+    /// toward black (0.0–1.0); `bg_blur` and `bg_dim` apply to `bg_video` frames too.
+    pub bg_image: Option<PathBuf>,
+    pub bg_blur: Option<f32>,
+    pub bg_dim: f32,
+    /// Slow camera motion over the background image across the video.
+    pub bg_motion: Option<BgMotion>,
+    /// Background video, decoded with ffmpeg and matched to the track's length by `bg_video_fit`.
+    pub bg_video: Option<PathBuf>,
+    pub bg_video_fit: BgVideoFit,
+    /// The background's reaction to bass, at `bg_pulse_amount` strength at full bass.
+    pub bg_pulse: Option<BgPulse>,
+    pub bg_pulse_amount: f32,
+    /// Lowest bars averaged as the bass level, for `bg_pulse` and the beats.
+    pub bass_bars: usize,
+    /// Bars mirrored around the center.
+    pub symmetric: Option<Symmetry>,
+    /// Seconds for the bar colors to turn once around the hue circle.
+    pub color_cycle: Option<f32>,
+    /// Seed for the particles.
+    pub seed: u64,
+    /// Image in the circle of radial and ring bands, or with `radial_art` the input's cover art.
+    pub radial_image: Option<PathBuf>,
+    pub radial_art: bool,
+    /// Level meter, its response, place and size (pixels), drawn instead of the spectrum layers with `meter_only`.
+    pub meter: Option<MeterStyle>,
+    pub meter_ballistics: MeterBallistics,
+    pub meter_pos: OverlayPos,
+    pub meter_size: u32,
+    pub meter_only: bool,
+    /// Draw the title and artist tags; `title` and `artist` replace them and imply it.
+    pub show_title: bool,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub title_pos: OverlayPos,
+    pub show_time: Option<TimeDisplay>,
+    pub time_pos: OverlayPos,
+    /// Subtitles or lyrics (.srt or .lrc).
+    pub subtitles: Option<PathBuf>,
+    pub subtitle_pos: OverlayPos,
+    /// Chapters (.cue or timestamp list) embedded into a video, and drawn with `show_chapter`.
+    pub chapters: Option<PathBuf>,
+    pub show_chapter: bool,
+    pub chapter_pos: OverlayPos,
+    /// Font for the text (a system font when unset), its size in pixels and color (the bar color when unset).
+    pub font: Option<PathBuf>,
+    pub font_size: f32,
+    pub text_color: Option<[u8; 4]>,
+    /// Logo image, its width as a fraction of the frame's and its opacity.
+    pub logo: Option<PathBuf>,
+    pub logo_pos: OverlayPos,
+    pub logo_scale: f32,
+    pub logo_opacity: f32,
+    /// Distance between the overlays and the content's edges (pixels).
+    pub overlay_margin: u32,
+    /// Space around the content in `Config::bg_color` (pixels), and the border around that.
+    pub padding: u32,
+    pub border: Option<Border>,
+    /// ffmpeg binary, for the video and the background video.
+    pub ffmpeg_path: PathBuf,
+    /// Keep the temporary files.
+    pub keep_temp: bool,
+    /// Bound on the render's working memory (bytes), met by drawing fewer frames at once.
+    pub max_memory: Option<u64>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            bg_image: None,
+            bg_blur: None,
+            bg_dim: 0.0,
+            bg_motion: None,
+            bg_video: None,
+            bg_video_fit: BgVideoFit::Loop,
+            bg_pulse: None,
+            bg_pulse_amount: 0.1,
+            bass_bars: 4,
+            symmetric: None,
+            color_cycle: None,
+            seed: 0,
+            radial_image: None,
+            radial_art: false,
+            meter: None,
+            meter_ballistics: MeterBallistics::Vu,
+            meter_pos: OverlayPos::CenterRight,
+            meter_size: 240,
+            meter_only: false,
+            show_title: false,
+            title: None,
+            artist: None,
+            title_pos: OverlayPos::TopLeft,
+            show_time: None,
+            time_pos: OverlayPos::BottomRight,
+            subtitles: None,
+            subtitle_pos: OverlayPos::BottomCenter,
+            chapters: None,
+            show_chapter: false,
+            chapter_pos: OverlayPos::TopRight,
+            font: None,
+            font_size: 48.0,
+            text_color: None,
+            logo: None,
+            logo_pos: OverlayPos::TopRight,
+            logo_scale: 0.1,
+            logo_opacity: 0.8,
+            overlay_margin: 40,
+            padding: 0,
+            border: None,
+            ffmpeg_path: PathBuf::from("ffmpeg"),
+            keep_temp: false,
+            max_memory: None,
+        }
+    }
+}
+
+/// What a run writes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Output {
+    pub frames: FrameOutput,
+    /// Per-frame analysis: CSV for a .csv path, JSON otherwise.
+    pub export_spectrum: Option<PathBuf>,
+    /// Measure the whole track's levels and frequency bands for `RunSummary::track_stats`.
+    pub track_stats: bool,
+}
+
+/// Where the frames go.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FrameOutput {
+    /// Nowhere: the run only analyzes the track.
+    #[default]
+    None,
+    Video(VideoOutput),
+    /// Every frame as a numbered PNG in the directory.
+    Frames(PathBuf),
+    /// The frame at `time` (seconds) as a PNG.
+    Snapshot { time: f32, path: PathBuf },
+    /// The frame at `time`, the middle of the track by default, as a PNG, decoding only up to it.
+    Preview { time: Option<f32>, path: PathBuf },
+}
+
+/// A video file, or stdout for `-`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VideoOutput {
+    pub path: PathBuf,
+    pub container: Container,
+    pub encoder: Backend,
+    /// The codecs and their options; the chapters, tags and cover art are filled in by the run.
+    pub settings: EncodeSettings,
+    /// Tag the video with the title, artist and album and attach the cover art.
+    pub metadata: bool,
+}
+
+impl VideoOutput {
+    /// A video at `path` in the container its extension names, piped into ffmpeg with the default settings.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Self {
+            container: Container::from_path(&path),
+            path,
+            encoder: Backend::Ffmpeg,
+            settings: EncodeSettings::default(),
+            metadata: true,
+        }
+    }
+
+    /// Check that the container, encoder and settings go together for frames of `frame_size`.
+    pub fn check(&self, frame_size: (u32, u32)) -> Result<(), SpectrumError> {
+        let settings = &self.settings;
+        if self.encoder == Backend::Native && writes_to_stdout(&self.path) {
+            let message = "--encoder native cannot write to stdout (-o -); it needs a seekable file";
+            return Err(InvalidConfig(message.into()));
+        }
+        if settings.hls_segment.is_some() && self.container != Container::Hls {
+            return Err(InvalidConfig("--hls-segment requires a .m3u8 output".into()));
+        }
+        if settings.video.alpha && !self.container.supports_alpha() {
+            let message = "--transparent requires a .mov (ProRes 4444) or .webm (VP9) output";
+            return Err(InvalidConfig(message.into()));
+        }
+        match self.encoder {
+            Backend::Ffmpeg | Backend::Png => {
+                settings.video.validate(self.container).map_err(|e| InvalidConfig(e.into()))?;
+                settings.audio.validate(self.container).map_err(|e| InvalidConfig(e.into()))?;
+            }
+            #[cfg(feature = "native-encoder")]
+            Backend::Native => native::validate(self.container, settings).map_err(|e| InvalidConfig(e.into()))?,
+            #[cfg(not(feature = "native-encoder"))]
+            Backend::Native => {
+                let message = "--encoder native needs a build with the native-encoder feature \
+                               (cargo build --release --features native-encoder)";
+                return Err(InvalidConfig(message.into()));
+            }
+        }
+        let (width, height) = frame_size;
+        let subsampled = self.encoder == Backend::Native || self.container.subsamples_chroma(&settings.video);
+        if subsampled && (!width.is_multiple_of(2) || !height.is_multiple_of(2)) {
+            let message = format!(
+                "{}x{} has an odd side, which yuv420p video (color at half size) can't encode: use --resolution {}x{}",
+                width,
+                height,
+                width & !1,
+                height & !1
+            );
+            return Err(InvalidConfig(message));
+        }
+        Ok(())
+    }
+}
+
+/// What `Renderer::run` (and `cli::run_with`) take besides the render itself. The default adds nothing to the
+/// frames, reports progress nowhere (as `--progress` says for `run_with`) and is never cancelled.
+#[derive(Default)]
+pub struct RunOptions<'a> {
+    /// Called on every rendered frame, as in `cli::run_with_hook`.
+    pub on_frame: Option<FrameHook<'a>>,
+    /// Reports the progress; for `run_with`, in place of what `--progress` picks.
+    pub progress: Option<Arc<dyn ProgressSink>>,
+    /// Stops the run between frames; it then fails with `SpectrumError::Interrupted`.
+    pub cancel: CancelToken,
+    /// Draws in place of the spectrum layers' styles, like `--script` and `--plugin` (which it rules out for
+    /// `run_with`), after the theme layers and before the meter. Called with the frame's samples in
+    /// `FrameData::waveform`.
+    pub visualizer: Option<&'a dyn Visualizer>,
+}
+
+/// What a `run_with_hook` callback knows about the frame it draws on.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameContext<'a> {
+    /// Video frame index, counted from the start of the track.
+    pub index: usize,
+    /// The frame's time in the track (seconds).
+    pub time: f32,
+    /// Frames in the whole track.
+    pub total_frames: usize,
+    pub fps: u32,
+    /// Bar heights (0.0–1.0) in drawing order.
+    pub bar_heights: &'a [f32],
+    /// Whether a beat falls on this frame.
+    pub beat: bool,
+    /// RMS level of the frame's audio (0.0–1.0).
+    pub rms: f32,
+}
+
+/// Callback drawing on each rendered frame (see `run_with_hook`).
+pub type FrameHook<'a> = &'a (dyn Fn(&mut image::RgbaImage, &FrameContext) + Sync);
+
+/// What a finished run reports.
+#[derive(Clone, Debug, Default)]
+pub struct RunSummary {
+    /// The whole track's levels and frequency bands, with `Output::track_stats`.
+    pub track_stats: Option<TrackStats>,
+}
+
+/// The whole track's levels and where its energy lies, as `analyze` prints them.
+#[derive(Clone, Debug)]
+pub struct TrackStats {
+    pub sample_rate: u32,
+    /// Mono samples decoded, and whether they came with left and right.
+    pub samples: usize,
+    pub stereo: bool,
+    pub levels: TrackLevels,
+    /// Share of the energy in each of `analyze::FREQUENCY_BANDS`.
+    pub band_shares: [f32; FREQUENCY_BANDS.len()],
+}
+
+/// What `Renderer::run` would do, worked out from the input's headers without rendering or writing anything.
+#[derive(Clone, Debug)]
+pub struct RenderPlan {
+    pub duration_sec: f32,
+    pub sample_rate: u32,
+    /// Frames in the whole track, and those the run draws (one for a snapshot or preview).
+    pub total_frames: usize,
+    pub rendered_frames: usize,
+    pub frame_size: (u32, u32),
+    /// Render workers, and a rough render time with that many (seconds).
+    pub workers: usize,
+    pub render_sec: f64,
+    /// Memory at the peak, and of it what the track's spectra hold and what the frames in flight take (bytes).
+    pub peak_bytes: u64,
+    pub track_bytes: u64,
+    pub working_bytes: u64,
+    /// What an ffmpeg encode would run and write.
+    pub encode: Option<EncodePlan>,
+}
+
+/// The part of a `RenderPlan` an ffmpeg encode adds.
+#[derive(Clone, Debug)]
+pub struct EncodePlan {
+    /// Chapters embedded into the video.
+    pub chapters: Option<usize>,
+    /// The run's temporary directory, and the bytes written there besides the PNG frames `temp_frames` holds.
+    pub temp_dir: PathBuf,
+    pub temp_bytes: u64,
+    pub temp_frames: Option<PathBuf>,
+    /// The ffmpeg command line, and whether the ffmpeg it runs is there.
+    pub ffmpeg_command: String,
+    pub ffmpeg_found: bool,
+}
+
+/// Renders tracks into videos, frames or snapshots: the spectrum `Config` laid out in the content area (the frame
+/// less `RenderSettings::padding` and `border` on every side), the layers drawn over the background in order, and
+/// the overlays and options around them.
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    config: Config,
+    layers: Vec<Layer>,
+    settings: RenderSettings,
+}
+
+/// What a run works with besides the renderer.
+struct RunContext<'a> {
+    decoder: &'a dyn AudioDecoder,
+    input: &'a Path,
+    output: &'a Output,
+    progress: &'a Arc<dyn ProgressSink>,
+    cancel: &'a CancelToken,
+}
+
+/// How far a run decodes: the whole track, or up to a preview's frame.
+struct Reach {
+    /// The header's track length, which sizes the decoding bar.
+    header_sec: Option<f32>,
+    /// A preview's frame time (the middle of the track unless given), when the header tells it.
+    preview: Option<Option<f32>>,
+}
+
+impl Reach {
+    /// Where decoding stops: a second past the preview's frame.
+    fn stop_after_sec(&self) -> Option<f32> {
+        self.preview.flatten().map(|time| time + PREVIEW_LOOKAHEAD_SEC)
+    }
+
+    /// The track's length as the header gives it, for a preview that decodes only part of it.
+    fn track_sec(&self) -> Option<f32> {
+        self.header_sec.filter(|_| self.preview.is_some())
+    }
+}
+
+/// The files a run writes to its own temporary directory.
+struct TempFiles {
+    dir: PathBuf,
+    wav: PathBuf,
+    chapters: PathBuf,
+    /// The cover art, with its picture's extension.
+    cover: PathBuf,
+    frames: PathBuf,
+}
+
+impl TempFiles {
+    fn new() -> Self {
+        let run_id = format!("{}-{}", std::process::id(), NEXT_RUN.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join("audio-spectrum-generator").join(run_id);
+        Self {
+            wav: dir.join("audio.wav"),
+            chapters: dir.join("chapters.txt"),
+            cover: dir.join("cover"),
+            frames: dir.join("frames"),
+            dir,
+        }
+    }
+}
+
+/// What the one pass over the decoded audio measures, fed chunk by chunk so that no sample outlives its chunk.
+struct TrackAnalysis {
+    spectrum: SpectrumStream,
+    /// Left and right spectra for `Config::stereo_split`, when the input has both.
+    stereo_spectra: Option<(SpectrumStream, SpectrumStream)>,
+    levels: FrameLevelStream,
+    envelopes: EnvelopeStream,
+    /// The fine envelope timeline strips are merged from, when one is drawn.
+    timeline: Option<EnvelopeStream>,
+    /// Whole-track levels and band energy for `Output::track_stats`.
+    stats: Option<(TrackLevelStream, BandEnergyStream)>,
+    /// The WAV ffmpeg takes the audio from.
+    wav: Option<WavStream>,
+}
+
+impl TrackAnalysis {
+    fn push(&mut self, chunk: &AudioChunk) -> Result<(), SpectrumError> {
+        self.spectrum.push(chunk.samples);
+        if let (Some((left, right)), Some((l, r))) = (&mut self.stereo_spectra, chunk.stereo) {
+            left.push(l);
+            right.push(r);
+        }
+        self.levels.push(chunk.samples);
+        self.envelopes.push(chunk.samples);
+        if let Some(ref mut timeline) = self.timeline {
+            timeline.push(chunk.samples);
+        }
+        if let Some((ref mut levels, ref mut bands)) = self.stats {
+            match chunk.stereo {
+                Some((left, right)) => levels.push(&[left, right]),
+                None => levels.push(&[chunk.samples]),
+            }
+            bands.push(chunk.samples);
+        }
+        match self.wav {
+            Some(ref mut wav) => wav.push(chunk.samples),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Renderer {
+    /// A renderer drawing `layers` (spectrum layers are `config`s of their own, as `Theme::layers` resolves them)
+    /// over `config`'s background, with `settings` around them. Checks each spectrum layer and the style options
+    /// that go together.
+    pub fn new(config: Config, layers: Vec<Layer>, settings: RenderSettings) -> Result<Self, SpectrumError> {
+        check_styles(&config, &settings)?;
+        for layer in &layers {
+            if let Layer::Spectrum(band) = layer {
+                band.validate().map_err(InvalidConfig)?;
+            }
+        }
+        Ok(Self { config, layers, settings })
+    }
+
+    /// A renderer drawing `config`'s spectrum band alone.
+    pub fn single(config: Config, settings: RenderSettings) -> Result<Self, SpectrumError> {
+        let layers = vec![Layer::Spectrum(Box::new(config.clone()))];
+        Self::new(config, layers, settings)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Size of the frames written: the content area plus the padding and border around it.
+    pub fn frame_size(&self) -> (u32, u32) {
+        content_frame_size(&self.config, &self.settings)
+    }
+
+    /// Check that `video` can take this renderer's frames: `VideoOutput::check`, plus the chapters and transparency
+    /// the settings ask for.
+    pub fn check_video(&self, video: &VideoOutput) -> Result<(), SpectrumError> {
+        if self.settings.chapters.is_some() && video.container == Container::Hls {
+            return Err(InvalidConfig("--chapters cannot be embedded in HLS output".into()));
+        }
+        if self.settings.chapters.is_some() && video.encoder == Backend::Native {
+            return Err(InvalidConfig("--chapters needs --encoder ffmpeg to be embedded".into()));
+        }
+        if video.settings.video.alpha && (self.settings.bg_image.is_some() || self.settings.bg_video.is_some()) {
+            return Err(InvalidConfig("--transparent cannot be combined with a background image or video".into()));
+        }
+        video.check(self.frame_size())
+    }
+
+    /// Work out what rendering `input` into `output` would take from the input's headers (decoding it only when
+    /// they don't give its length), without rendering or writing anything.
+    pub fn plan(
+        &self,
+        decoder: &dyn AudioDecoder,
+        input: &Path,
+        output: &Output,
+    ) -> Result<RenderPlan, SpectrumError> {
+        self.check(input, output)?;
+        Images::load(&self.config, &self.settings)?;
+        let info = decoder.probe(input)?;
+        let duration_sec = match info.duration_sec {
+            Some(sec) => sec,
+            // The headers don't say: decode to count the samples.
+            None => {
+                let streamed = decoder.stream(input, false, None, &mut |_| ControlFlow::Continue(()))?;
+                streamed.samples as f32 / streamed.sample_rate as f32
+            }
+        };
+        let total_frames = (duration_sec * self.config.fps as f32).ceil().max(1.0) as usize;
+        let rendered_frames = match output.frames {
+            FrameOutput::Snapshot { .. } | FrameOutput::Preview { .. } => 1,
+            _ => total_frames,
+        };
+        let samples = (duration_sec as f64 * info.sample_rate as f64) as u64;
+        let footprint = self.footprint(samples, info.sample_rate);
+        let memory = self.plan_memory(&footprint)?;
+        let (width, height) = self.frame_size();
+        let pixels = rendered_frames as f64 * width as f64 * height as f64;
+        let rate = if self.settings.bg_motion.is_some() { MOTION_RENDER_PIXELS_PER_SEC } else { RENDER_PIXELS_PER_SEC };
+        let encode = match output.frames {
+            FrameOutput::Video(ref video) if video.encoder.uses_ffmpeg() => {
+                Some(self.plan_encode(video, &info, duration_sec, samples)?)
+            }
+            _ => None,
+        };
+        Ok(RenderPlan {
+            duration_sec,
+            sample_rate: info.sample_rate,
+            total_frames,
+            rendered_frames,
+            frame_size: (width, height),
+            workers: memory.workers,
+            render_sec: pixels / (rate * memory.workers as f64),
+            peak_bytes: footprint.peak_bytes(&memory),
+            track_bytes: footprint.track_bytes(),
+            working_bytes: footprint.working_bytes(&memory),
+            encode,
+        })
+    }
+
+    /// Render `input`, decoded by `decoder`, into `output`. Each run has its own temporary directory and stage
+    /// timings, logged at the level of the log it runs in, so runs can share a process; cancelling it through
+    /// `options` removes what it wrote.
+    pub fn run(
+        &self,
+        decoder: &dyn AudioDecoder,
+        input: &Path,
+        output: &Output,
+        options: RunOptions,
+    ) -> Result<RunSummary, SpectrumError> {
+        let RunOptions { on_frame, progress, cancel, visualizer } = options;
+        let on_frame = on_frame.unwrap_or(&|_, _| {});
+        let progress = progress.unwrap_or_else(|| Arc::new(SilentProgress));
+        let _log = logging::init(logging::level());
+        let total = Stage::start("Everything");
+        self.check(input, output)?;
+        self.check_environment(output)?;
+        let images = Images::load(&self.config, &self.settings)?;
+        let run = RunContext { decoder, input, output, progress: &progress, cancel: &cancel };
+
+        // From here on, cancelling (Ctrl-C) stops the decoding, or the render between frames, and `cleanup` removes
+        // what was written.
+        cancel.watch();
+        if cancel.is_cancelled() {
+            return Err(SpectrumError::Interrupted);
+        }
+        let mut cleanup = Cleanup::new(self.settings.keep_temp);
+        let temp = TempFiles::new();
+        // ffmpeg takes the audio from a WAV, written as the input is decoded.
+        let wav = match output.frames {
+            FrameOutput::Video(ref video) if video.encoder.uses_ffmpeg() => {
+                std::fs::create_dir_all(&temp.dir).map_err(|e| write_failed(&temp.dir, e))?;
+                cleanup.temp_dir(&temp.dir);
+                cleanup.temp_file(&temp.wav);
+                verbose!("Writing WAV: {:?}", temp.wav);
+                Some(temp.wav.as_path())
+            }
+            _ => None,
+        };
+        let header = decoder.probe(input)?;
+        let reach = Reach {
+            header_sec: header.duration_sec,
+            preview: match output.frames {
+                FrameOutput::Preview { time, .. } => Some(time.or(header.duration_sec.map(|sec| sec / 2.0))),
+                _ => None,
+            },
+        };
+        // Planned from the header's length when it gives one, so that a `max_memory` too small fails before decoding.
+        let header_samples = |sec: f32| (sec as f64 * header.sample_rate as f64) as u64;
+        let early_plan = reach.stop_after_sec().or(reach.header_sec).map(|sec| {
+            self.plan_memory(&self.footprint(header_samples(sec), header.sample_rate))
+        });
+        let early_plan = early_plan.transpose()?;
+        let (track, track_stats) = self.analyze(&run, &reach, wav)?;
+        let summary = RunSummary { track_stats };
+        let memory = match early_plan {
+            Some(plan) => plan,
+            None => self.plan_memory(&self.footprint(track.samples as u64, track.sample_rate))?,
+        };
+        if let Some(ref path) = output.export_spectrum {
+            self.write_export(path, &track)?;
+        }
+        if output.frames == FrameOutput::None {
+            total.finish();
+            return Ok(summary);
+        }
+
+        let stage = Stage::start("Preparing layers");
+        let scene = Scene::prepare(self, &track, images, visualizer, on_frame)?;
+        if let FrameOutput::Frames(ref dir) = output.frames {
+            std::fs::create_dir_all(dir).map_err(|e| write_failed(dir, e))?;
+        }
+        stage.finish();
+        let frames = self.frame_range(output, &reach, &track)?;
+
+        // Frames are piped straight into ffmpeg; as PNG frames or a snapshot, no audio is needed.
+        let mut reports_encoding = false;
+        let sink = match output.frames {
+            FrameOutput::Video(_) => {
+                let (encoder, reports) = self.start_encoder(&run, &track, scene.chapters(), &temp, &mut cleanup)?;
+                reports_encoding = reports;
+                FrameSink::Encoder(encoder)
+            }
+            FrameOutput::Frames(ref dir) => {
+                cleanup.frames(dir, frames.start);
+                FrameSink::Encoder(Box::new(FrameDir::new(dir, frames.start)))
+            }
+            FrameOutput::Snapshot { ref path, .. } | FrameOutput::Preview { ref path, .. } => FrameSink::Snapshot(path),
+            FrameOutput::None => unreachable!("a run without frames ends after the analysis"),
+        };
+        progress.start(Phase::Rendering, Some(frames.len() as u64));
+        let bg_video = self.open_bg_video(&track, frames.start)?;
+
+        let stage = Stage::start("Rendering");
+        let sequence = Sequence::new(self, &track, &scene, bg_video);
+        let audio = AudioSource { decoder, input, stop_after_sec: reach.stop_after_sec() };
+        let sink = render_frames(sequence, frames, audio, sink, memory, progress.as_ref(), &cancel)?;
+        progress.finish(Phase::Rendering);
+        stage.finish();
+
+        let (FrameOutput::Video(video), FrameSink::Encoder(encoder)) = (&output.frames, sink) else {
+            // PNG frames and snapshots are all saved already.
+            match output.frames {
+                FrameOutput::Frames(ref dir) => info!("Done: {} frames in {:?}", track.total_frames, dir),
+                FrameOutput::Snapshot { ref path, .. } | FrameOutput::Preview { ref path, .. } => {
+                    info!("Done: {:?}", path)
+                }
+                _ => {}
+            }
+            cleanup.complete();
+            logging::summarize(total);
+            return Ok(summary);
+        };
+        let stage = Stage::start("Finishing the encode");
+        let encoded = encoder.finish();
+        if cancel.is_cancelled() {
+            return Err(SpectrumError::Interrupted);
+        }
+        encoded.map_err(encode_failed)?;
+        cleanup.complete();
+        if reports_encoding {
+            progress.finish(Phase::Encoding);
+        }
+        stage.finish();
+        info!("Done: {:?}", video.path);
+        logging::summarize(total);
+        Ok(summary)
+    }
+
+    /// Check what needs no environment: the input is there and the video can take the frames.
+    fn check(&self, input: &Path, output: &Output) -> Result<(), SpectrumError> {
+        // Checked first, so a missing input isn't reported as a bad option.
+        if !input.exists() {
+            return Err(SpectrumError::InputNotFound(input.to_path_buf()));
+        }
+        match output.frames {
+            FrameOutput::Video(ref video) => self.check_video(video),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that ffmpeg is there when the output or background video needs it, and that it has the hardware
+    /// encoder asked for.
+    fn check_environment(&self, output: &Output) -> Result<(), SpectrumError> {
+        let ffmpeg = &self.settings.ffmpeg_path;
+        let video = match output.frames {
+            FrameOutput::Video(ref video) if video.encoder.uses_ffmpeg() => Some(video),
+            _ => None,
+        };
+        if (video.is_some() || self.settings.bg_video.is_some()) && !ffmpeg_found(ffmpeg) {
+            return Err(SpectrumError::FfmpegNotFound(ffmpeg.clone()));
+        }
+        if let Some(video) = video
+            && let Some(hwaccel) = video.settings.video.hwaccel
+        {
+            hwaccel.probe(ffmpeg, video.settings.video.codec.unwrap_or_default()).map_err(encode_failed)?;
+        }
+        Ok(())
+    }
+
+    /// Whether any spectrum layer draws in `style`.
+    fn draws(&self, style: Style) -> bool {
+        self.layers.iter().any(|layer| matches!(layer, Layer::Spectrum(band) if band.style == style))
+    }
+
+    /// What a render of `samples` decoded samples at `sample_rate` holds.
+    fn footprint(&self, samples: u64, sample_rate: u32) -> Footprint {
+        let (width, height) = self.frame_size();
+        Footprint {
+            spectrum_frames: samples / hop_size(self.config.fft_size, self.config.overlap) as u64 + 1,
+            bars: self.config.bars as u64,
+            spectra: if self.config.stereo_split.is_some() { 3 } else { 1 },
+            timeline_points: match self.draws(Style::Timeline) {
+                true => (samples as f64 * TIMELINE_POINTS_PER_SEC / sample_rate.max(1) as f64) as u64,
+                false => 0,
+            },
+            frame_bytes: width as u64 * height as u64 * 4,
+        }
+    }
+
+    /// The render workers and frames in flight that fit `footprint` into `max_memory`.
+    fn plan_memory(&self, footprint: &Footprint) -> Result<MemoryPlan, SpectrumError> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        match self.settings.max_memory {
+            Some(budget) => footprint.plan(cores, budget).map_err(InvalidConfig),
+            None => Ok(footprint.unbounded(cores)),
+        }
+    }
+
+    /// Decode the input once, taking every measurement and writing `wav` as the chunks come, so no sample is kept.
+    fn analyze(
+        &self,
+        run: &RunContext,
+        reach: &Reach,
+        wav: Option<&Path>,
+    ) -> Result<(Track, Option<TrackStats>), SpectrumError> {
+        let config = &self.config;
+        let needs_vectorscope = self.draws(Style::Vectorscope);
+        let needs_timeline = self.draws(Style::Timeline);
+        let report_stats = run.output.track_stats;
+        let keep_stereo = config.stereo_split.is_some() || needs_vectorscope || report_stats;
+        info!("Decoding MP3 and computing spectrum: {:?}", run.input);
+        let stage = Stage::start("Decoding and analysis");
+        let new_analysis = |sample_rate: u32, stereo: bool| -> Result<TrackAnalysis, SpectrumError> {
+            let new_spectrum = || {
+                SpectrumStream::new(sample_rate, config.fft_size, config.overlap, config.bars, config.tilt)
+            };
+            let spectrum = new_spectrum();
+            let stats = || {
+                let channels = if stereo { 2 } else { 1 };
+                (TrackLevelStream::new(channels, sample_rate), BandEnergyStream::new(sample_rate, config.fft_size))
+            };
+            Ok(TrackAnalysis {
+                spectrum: if config.centroid_marker { spectrum.with_centroids() } else { spectrum },
+                stereo_spectra: (config.stereo_split.is_some() && stereo).then(|| (new_spectrum(), new_spectrum())),
+                levels: FrameLevelStream::new(sample_rate, config.fps),
+                envelopes: EnvelopeStream::new(sample_rate, config.fps as f64),
+                timeline: needs_timeline.then(|| EnvelopeStream::new(sample_rate, TIMELINE_POINTS_PER_SEC)),
+                stats: report_stats.then(stats),
+                wav: wav.map(|path| WavStream::create(path, sample_rate)).transpose()?,
+            })
+        };
+        // The header's track length sizes the decoding bar; a preview's runs to where its decoding stops.
+        let stop_after_sec = reach.stop_after_sec();
+        let (progress, cancel) = (run.progress, run.cancel);
+        progress.start(Phase::Decoding, stop_after_sec.or(reach.header_sec).map(|sec| sec.ceil() as u64));
+        let (mut decoded_samples, mut decoded_sec) = (0, 0);
+        let mut analysis: Option<TrackAnalysis> = None;
+        let mut failed = None;
+        let streamed = run.decoder.stream(run.input, keep_stereo, stop_after_sec, &mut |chunk| {
+            decoded_samples += chunk.samples.len();
+            // Whole seconds only, so a sink hears once per second rather than per chunk.
+            let sec = (decoded_samples / chunk.sample_rate as usize) as u64;
+            if sec != decoded_sec {
+                decoded_sec = sec;
+                progress.update(Phase::Decoding, sec);
+            }
+            if cancel.is_cancelled() {
+                return ControlFlow::Break(());
+            }
+            let pushed = match analysis {
+                Some(ref mut analysis) => analysis.push(&chunk),
+                None => new_analysis(chunk.sample_rate, chunk.stereo.is_some())
+                    .and_then(|new| analysis.insert(new).push(&chunk)),
+            };
+            match pushed {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(e) => {
+                    failed = Some(e);
+                    ControlFlow::Break(())
+                }
+            }
+        });
+        if cancel.is_cancelled() {
+            return Err(SpectrumError::Interrupted);
+        }
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        let streamed = streamed?;
+        progress.finish(Phase::Decoding);
+        verbose!("Decoded {} samples at {} Hz", streamed.samples, streamed.sample_rate);
+        let analysis = match analysis {
+            Some(analysis) => analysis,
+            None => new_analysis(streamed.sample_rate, streamed.stereo)?,
+        };
+        let TrackAnalysis { spectrum, stereo_spectra, levels, envelopes, timeline, stats, wav } = analysis;
+        if let Some(wav) = wav {
+            wav.finish()?;
+        }
+        let SpectrumAnalysis { frames: spectra, global_max, centroids } = spectrum.finish();
+        // Per-channel spectra for --stereo-split, normalized by their shared maximum so the channels stay comparable.
+        let stereo_spectra = match (config.stereo_split, stereo_spectra) {
+            (Some(_), Some((left, right))) => {
+                let (left, right) = (left.finish(), right.finish());
+                let max = left.global_max.max(right.global_max);
+                Some((left.frames, right.frames, if max > 0.0 { max } else { 1.0 }))
+            }
+            (Some(_), None) => {
+                warning!("Input is mono; both stereo halves show the same signal");
+                None
+            }
+            (None, _) => None,
+        };
+        if needs_vectorscope && !streamed.stereo {
+            warning!("Input is mono; the vectorscope shows a vertical line");
+        }
+        let decoded_sec = streamed.samples as f32 / streamed.sample_rate as f32;
+        let duration_sec = reach.track_sec().unwrap_or(decoded_sec).max(decoded_sec);
+        let total_frames = (duration_sec * config.fps as f32).ceil().max(1.0) as usize;
+        verbose!("Spectrum frames: {}, total video frames: {}", spectra.len(), total_frames);
+        let levels = levels.finish(total_frames);
+        let envelopes = envelopes.finish(total_frames);
+        let timeline_points = (streamed.samples as f64 * TIMELINE_POINTS_PER_SEC / streamed.sample_rate as f64).ceil();
+        let peak_sample = envelopes.iter().map(|p| p.peak()).fold(0.0f32, f32::max);
+        let peak_rms = levels.iter().map(|l| l.rms).fold(0.0f32, f32::max);
+        let peak_lufs = levels.iter().map(|l| l.lufs).fold(LEVEL_FLOOR_DB, f32::max);
+        verbose!(
+            "Peak sample: {:.1} dBFS, peak frame RMS: {:.1} dBFS, peak momentary loudness: {:.1} LUFS",
+            amplitude_to_db(peak_sample),
+            amplitude_to_db(peak_rms),
+            peak_lufs
+        );
+        let stats = stats.map(|(track_levels, band_energy)| TrackStats {
+            sample_rate: streamed.sample_rate,
+            samples: streamed.samples,
+            stereo: streamed.stereo,
+            levels: track_levels.finish(),
+            band_shares: band_energy.shares(),
+        });
+        stage.finish();
+        let track = Track {
+            sample_rate: streamed.sample_rate,
+            samples: streamed.samples,
+            stereo: streamed.stereo,
+            tags: streamed.tags,
+            duration_sec,
+            total_frames,
+            // Video frames the decoded audio covers: all of them, except for a preview.
+            decoded_frames: (decoded_sec * config.fps as f32).ceil().max(1.0) as usize,
+            spectra,
+            norm: if global_max > 0.0 { global_max } else { 1.0 },
+            centroids,
+            stereo_spectra,
+            levels,
+            envelopes,
+            timeline: timeline.map(|stream| stream.finish(timeline_points as usize)),
+        };
+        Ok((track, stats))
+    }
+
+    /// Write the per-frame analysis of `track` to `path`.
+    fn write_export(&self, path: &Path, track: &Track) -> Result<(), SpectrumError> {
+        let analysis = frame_analyses(
+            track.total_frames,
+            self.config.fps,
+            self.settings.bass_bars,
+            |frame_index| track.bar_heights(track.spectrum_index(frame_index), &self.config),
+            &track.levels,
+            &track.envelopes,
+        );
+        write_analysis(path, self.config.fps, &analysis)?;
+        info!("Wrote analysis: {:?}", path);
+        Ok(())
+    }
+
+    /// The frames `output` takes: all of them, or the one a snapshot or preview shows.
+    fn frame_range(&self, output: &Output, reach: &Reach, track: &Track) -> Result<Range<usize>, SpectrumError> {
+        let time = match output.frames {
+            FrameOutput::Snapshot { time, .. } => time,
+            FrameOutput::Preview { .. } => reach.preview.flatten().unwrap_or(track.duration_sec / 2.0),
+            _ => return Ok(0..track.total_frames),
+        };
+        let index = (time * self.config.fps as f32).floor() as usize;
+        if index >= track.total_frames {
+            let message = format!("snapshot time is past the end of the track ({:.1}s)", track.duration_sec);
+            return Err(InvalidConfig(message));
+        }
+        Ok(index..index + 1)
+    }
+
+    /// The background video, at frame `first`.
+    fn open_bg_video(&self, track: &Track, first: usize) -> Result<Option<VideoFrames>, SpectrumError> {
+        let Some(ref path) = self.settings.bg_video else { return Ok(None) };
+        verbose!("Using background video: {:?}", path);
+        let mut video = VideoFrames::open(
+            &self.settings.ffmpeg_path,
+            path,
+            (self.config.width, self.config.height),
+            self.config.fps,
+            self.settings.bg_video_fit,
+            track.duration_sec,
+            self.settings.bg_blur,
+        )?;
+        // A snapshot starts the background video at its own time.
+        for _ in 0..first {
+            video.next_frame();
+        }
+        Ok(Some(video))
+    }
+
+    /// `video`'s settings with the chapters, tags and cover art the encode embeds, and the cover art's file in
+    /// `temp` with its bytes.
+    fn encode_settings<'t>(
+        &self,
+        video: &VideoOutput,
+        tags: &'t Tags,
+        has_chapters: bool,
+        temp: &TempFiles,
+    ) -> (EncodeSettings, Option<(PathBuf, &'t [u8])>) {
+        let mut encode = video.settings.clone();
+        if has_chapters {
+            encode.chapters = Some(temp.chapters.clone());
+        }
+        let mut cover = None;
+        if video.metadata {
+            let (title, artist) = title_and_artist(&self.settings, tags);
+            encode.metadata = output_metadata(title, artist, tags.album.as_deref().unwrap_or(""));
+            let hwaccel = video.settings.video.hwaccel;
+            if let Some(ref picture) = tags.cover_art
+                && let Some(extension) = cover_art_extension(video.container, &video.path, hwaccel, picture)
+            {
+                let path = temp.cover.with_extension(extension);
+                encode.cover_art = Some(path.clone());
+                cover = Some((path, &picture[..]));
+            }
+        }
+        (encode, cover)
+    }
+
+    /// The `RenderPlan` part of an ffmpeg encode of a `duration_sec` long input into `video`.
+    fn plan_encode(
+        &self,
+        video: &VideoOutput,
+        info: &TrackInfo,
+        duration_sec: f32,
+        samples: u64,
+    ) -> Result<EncodePlan, SpectrumError> {
+        let temp = TempFiles::new();
+        // The 16-bit mono WAV, plus the chapters and cover art the encode would also write to the temp dir.
+        let mut temp_bytes = 44 + samples * 2;
+        let chapters = match self.settings.chapters {
+            Some(ref path) => {
+                let chapters = load_chapters(path, duration_sec)?;
+                temp_bytes += ffmetadata(&chapters).len() as u64;
+                Some(chapters.len())
+            }
+            None => None,
+        };
+        let (encode, cover) = self.encode_settings(video, &info.tags, chapters.is_some(), &temp);
+        temp_bytes += cover.map_or(0, |(_, picture)| picture.len() as u64);
+        let (output, container, fps) = (&video.path, video.container, self.config.fps);
+        let (args, temp_frames) = match video.encoder {
+            Backend::Png => {
+                let args = png_sequence_args(output, container, &encode, fps, &temp.frames, &temp.wav);
+                (args, Some(temp.frames))
+            }
+            _ => (ffmpeg_args(output, container, &encode, fps, self.frame_size(), &temp.wav), None),
+        };
+        let ffmpeg = &self.settings.ffmpeg_path;
+        Ok(EncodePlan {
+            chapters,
+            temp_dir: temp.dir,
+            temp_bytes,
+            temp_frames,
+            ffmpeg_command: command_line(ffmpeg, &args),
+            ffmpeg_found: ffmpeg_found(ffmpeg),
+        })
+    }
+
+    /// Start encoding the run's video, writing the chapters and cover art to `temp` first, and say whether its
+    /// progress is reported. ffmpeg buffers frames, so its own progress (`-progress`) is reported as a phase of its
+    /// own.
+    fn start_encoder(
+        &self,
+        run: &RunContext,
+        track: &Track,
+        chapters: &[Cue],
+        temp: &TempFiles,
+        cleanup: &mut Cleanup,
+    ) -> Result<(Box<dyn VideoEncoder>, bool), SpectrumError> {
+        let FrameOutput::Video(ref video) = run.output.frames else { unreachable!("only a video is encoded") };
+        let (output, container, fps, frame_size) = (&video.path, video.container, self.config.fps, self.frame_size());
+        let (encode, cover) = self.encode_settings(video, &track.tags, !chapters.is_empty(), temp);
+        if video.encoder == Backend::Native {
+            #[cfg(feature = "native-encoder")]
+            {
+                info!("Encoding H.264 (no audio) with the built-in encoder: {:?}", output);
+                let encoder = native::NativeEncoder::create(output, &encode, fps, frame_size).map_err(encode_failed)?;
+                cleanup.output(output, container);
+                return Ok((Box::new(encoder), false));
+            }
+            #[cfg(not(feature = "native-encoder"))]
+            unreachable!("VideoOutput::check turns the native encoder down");
+        }
+        if !chapters.is_empty() {
+            cleanup.temp_file(&temp.chapters);
+            std::fs::write(&temp.chapters, ffmetadata(chapters)).map_err(|e| write_failed(&temp.chapters, e))?;
+        }
+        if let Some((ref path, picture)) = cover {
+            cleanup.temp_file(path);
+            std::fs::write(path, picture).map_err(|e| write_failed(path, e))?;
+        }
+        // HLS writes a playlist plus many segments, usually into a directory of their own.
+        if let Some(dir) = output.parent().filter(|_| container == Container::Hls) {
+            std::fs::create_dir_all(dir).map_err(|e| write_failed(dir, e))?;
+        }
+        info!("Encoding {} with ffmpeg: {:?}", container.label(), output);
+        // ffmpeg's stdout carries the video itself for `-o -`, so there is no progress to report.
+        let reports_encoding = !writes_to_stdout(output);
+        if reports_encoding {
+            run.progress.start(Phase::Encoding, Some(track.total_frames as u64));
+        }
+        let on_progress = {
+            let progress = run.progress.clone();
+            move |frame| progress.update(Phase::Encoding, frame)
+        };
+        let (ffmpeg, audio, frames_dir) = (&self.settings.ffmpeg_path, &temp.wav, &temp.frames);
+        if logging::enabled(logging::Level::Debug) {
+            let ffmpeg_args = match video.encoder {
+                Backend::Png => png_sequence_args(output, container, &encode, fps, frames_dir, audio),
+                _ => ffmpeg_args(output, container, &encode, fps, frame_size, audio),
+            };
+            debug!("ffmpeg command: {}", command_line(ffmpeg, &ffmpeg_args));
+        }
+        let encoder: Result<Box<dyn VideoEncoder>, _> = match video.encoder {
+            Backend::Png => {
+                verbose!("Writing frames: {:?}", frames_dir);
+                cleanup.temp_frames(frames_dir);
+                PngSequence::create(ffmpeg, output, container, &encode, fps, frames_dir, audio, on_progress)
+                    .map(|encoder| Box::new(encoder) as _)
+            }
+            _ => Encoder::start(ffmpeg, output, container, &encode, fps, frame_size, audio, on_progress)
+                .map(|encoder| Box::new(encoder) as _),
+        };
+        let encoder = encoder.map_err(encode_failed)?;
+        cleanup.output(output, container);
+        Ok((encoder, reports_encoding))
+    }
+}
+
+/// Check the style options that go together: what a style or option needs of the others.
+fn check_styles(config: &Config, settings: &RenderSettings) -> Result<(), SpectrumError> {
+    if config.bar_stroke_only && config.bar_stroke.is_none() {
+        return Err(InvalidConfig("--bar-stroke-only requires --bar-stroke".into()));
+    }
+    if config.stereo_split.is_some() && config.style != Style::Bars {
+        return Err(InvalidConfig("--stereo-split requires --style bars".into()));
+    }
+    if settings.symmetric.is_some() && config.centroid_marker {
+        return Err(InvalidConfig("--centroid-marker cannot be combined with --symmetric".into()));
+    }
+    if config.particles && !matches!(config.style, Style::Bars | Style::Mirror | Style::Led) {
+        return Err(InvalidConfig("--particles requires --style bars, mirror or led".into()));
+    }
+    if config.orientation != Orientation::Horizontal && matches!(config.style, Style::Radial | Style::Ring) {
+        return Err(InvalidConfig("--orientation does not apply to --style radial or ring".into()));
+    }
+    if config.bar_colormap == Some(Colormap::Custom) && config.colormap_stops.is_empty() {
+        return Err(InvalidConfig("--bar-colormap custom requires --colormap-stops".into()));
+    }
+    Ok(())
+}
+
+/// Size of the frames around `config`'s content area: the content plus the padding and border on every side.
+pub fn content_frame_size(config: &Config, settings: &RenderSettings) -> (u32, u32) {
+    let inset = content_inset(settings.padding, settings.border);
+    (config.width + inset * 2, config.height + inset * 2)
+}
+
+/// Whether the ffmpeg at `path` runs.
+fn ffmpeg_found(path: &Path) -> bool {
+    std::process::Command::new(path).arg("-version").output().is_ok()
+}
+
+/// The title and artist drawn and tagged: `settings`' own, or the input's `tags`.
+pub fn title_and_artist<'a>(settings: &'a RenderSettings, tags: &'a Tags) -> (&'a str, &'a str) {
+    let title = settings.title.as_ref().or(tags.title.as_ref()).map_or("", String::as_str);
+    let artist = settings.artist.as_ref().or(tags.artist.as_ref()).map_or("", String::as_str);
+    (title, artist)
+}
+
+/// A `WriteFailed` error for the file at `path`.
+pub fn write_failed(path: &Path, error: impl std::fmt::Display) -> SpectrumError {
+    SpectrumError::WriteFailed { path: path.to_path_buf(), message: error.to_string() }
+}
+
+/// An encoder's error as a `SpectrumError`: its own when it is one, `EncodeFailed` otherwise.
+pub fn encode_failed(error: Box<dyn std::error::Error + Send + Sync>) -> SpectrumError {
+    match error.downcast::<SpectrumError>() {
+        Ok(error) => *error,
+        Err(error) => SpectrumError::EncodeFailed(error.to_string()),
+    }
+}
+
+/// Open and decode the image at `path`, failing with an `AssetLoad` error for `what` (e.g. "logo").
+pub fn load_image(what: &'static str, path: &Path) -> Result<image::DynamicImage, SpectrumError> {
+    let failed = |source: Box<dyn std::error::Error + Send + Sync>| SpectrumError::AssetLoad {
+        what,
+        path: path.to_path_buf(),
+        source,
+    };
+    image::ImageReader::open(path).map_err(|e| failed(e.into()))?.decode().map_err(|e| failed(e.into()))
+}
+
+/// `-metadata` tags for the output: title, artist and album, without the empty ones.
+fn output_metadata(title: &str, artist: &str, album: &str) -> Vec<(String, String)> {
+    [("title", title), ("artist", artist), ("album", album)]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// File extension to attach `picture` to `output` as its cover art with, or `None` when it can't be.
+fn cover_art_extension(
+    container: Container,
+    output: &Path,
+    hwaccel: Option<HwAccel>,
+    picture: &[u8],
+) -> Option<&'static str> {
+    // The picture is stream-copied, which VAAPI's -vf (applied to every video stream) would break.
+    let attach = container.supports_cover_art() && !writes_to_stdout(output) && hwaccel != Some(HwAccel::Vaapi);
+    picture_extension(picture).filter(|_| attach)
+}
+
+/// File extension for embedded cover art bytes: ffmpeg can attach PNG and JPEG pictures to MP4/MOV.
+fn picture_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("jpg")
+    } else {
+        None
+    }
+}
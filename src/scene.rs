@@ -0,0 +1,592 @@
+//! One frame's picture: the analyzed `Track`, the layers and overlays a `Scene` prepares from it once per run, and
+//! `Scene::draw`, which the render workers call for every frame
+
+use ab_glyph::FontVec;
+use image::imageops::FilterType;
+use image::RgbaImage;
+
+use crate::background::{motion_source_size, pulse_background, render_background};
+use crate::chapters::load_chapters;
+use crate::colormap::{rotate_hue, Colormap};
+use crate::config::{Config, Style};
+use crate::decode::Tags;
+use crate::draw::{
+    draw_background, draw_center_image, draw_needle_meter, draw_progress_bar, draw_vertical_meter, radial_geometry,
+    strip_extent, BarHistory, FrameData,
+};
+use crate::effects::dim_image;
+use crate::envelope::{resample_envelope, EnvelopePoint};
+use crate::error::SpectrumError;
+use crate::export::FrameAnalysis;
+use crate::frame::{content_inset, frame_content};
+use crate::level::FrameLevel;
+use crate::logging::{verbose, warning};
+use crate::meter::MeterStyle;
+use crate::overlay::{composite_overlay, overlay_origin, prepare_logo, TimeDisplay};
+use crate::particles::Particle;
+use crate::renderer::{
+    content_frame_size, load_image, title_and_artist, FrameContext, FrameHook, RenderSettings, Renderer,
+    TIMELINE_POINTS_PER_SEC,
+};
+use crate::spectrum::{bar_heights, frequency_to_position, mirror_bars, spectrum_index};
+use crate::subtitles::{active_cue, load_subtitles, Cue};
+use crate::text::{load_font, render_text_block, wrap_text, TextLine};
+use crate::theme::{fill_tags, fill_time, is_timed, Layer};
+use crate::visualizer::{StyleVisualizer, Visualizer};
+
+/// Size of the artist line relative to `RenderSettings::font_size`.
+const ARTIST_SIZE_RATIO: f32 = 0.7;
+
+/// Left and right spectrum frames, and the maximum normalizing both.
+pub type StereoSpectra = (Vec<Vec<f32>>, Vec<Vec<f32>>, f32);
+
+/// What the one pass over the audio measured, finished for the whole track.
+pub struct Track {
+    pub sample_rate: u32,
+    /// Mono samples decoded, and whether they came with left and right.
+    pub samples: usize,
+    pub stereo: bool,
+    pub tags: Tags,
+    /// The track's length (a preview's comes from the header, as it decodes only up to its frame).
+    pub duration_sec: f32,
+    /// Video frames in the whole track, and those the decoded audio covers: all of them, except for a preview.
+    pub total_frames: usize,
+    pub decoded_frames: usize,
+    /// Spectrum frames, their normalization and each one's centroid (`Config::centroid_marker`).
+    pub spectra: Vec<Vec<f32>>,
+    pub norm: f32,
+    pub centroids: Vec<f32>,
+    /// Left and right spectra for `Config::stereo_split`, normalized by their shared maximum.
+    pub stereo_spectra: Option<StereoSpectra>,
+    pub levels: Vec<FrameLevel>,
+    pub envelopes: Vec<EnvelopePoint>,
+    /// The fine envelope timeline strips are merged from, when one is drawn.
+    pub timeline: Option<Vec<EnvelopePoint>>,
+}
+
+impl Track {
+    /// The spectrum frame shown on video frame `frame_index`.
+    pub fn spectrum_index(&self, frame_index: usize) -> usize {
+        spectrum_index(frame_index, self.spectra.len(), self.decoded_frames)
+    }
+
+    /// Bar heights of spectrum frame `index` in analysis order (lowest bars first).
+    pub fn bar_heights(&self, index: usize, config: &Config) -> Vec<f32> {
+        bar_heights(&self.spectra, self.norm, index, config)
+    }
+}
+
+/// A spectrum layer and the state its style carries across frames.
+pub struct Band {
+    pub visualizer: StyleVisualizer,
+    center_image: Option<RgbaImage>,
+    timeline: Vec<EnvelopePoint>,
+}
+
+/// Everything one frame needs from the frames before it, so frames can be drawn in parallel.
+pub struct FrameState {
+    pub index: usize,
+    pub spectrum_index: usize,
+    /// Bars in drawing order.
+    pub bar_heights: Vec<f32>,
+    pub hue_degrees: Option<f32>,
+    /// Each band's history and live particles as of this frame.
+    pub trails: Vec<(BarHistory, Vec<Particle>)>,
+    /// This frame of the background video, before dimming.
+    pub video_bg: Option<RgbaImage>,
+    pub bass_level: f32,
+    pub meter_position: f32,
+    pub beat: bool,
+    /// The frame's samples, when a style or visualizer draws them, and left and right for a vectorscope.
+    pub waveform: Vec<f32>,
+    pub stereo_samples: Option<(Vec<f32>, Vec<f32>)>,
+}
+
+/// The background image and logo, loaded before decoding so that a bad file fails the run early.
+pub struct Images {
+    background: Option<RgbaImage>,
+    logo: Option<RgbaImage>,
+}
+
+impl Images {
+    /// Load `settings`' background image, fitted to `config`'s frame (with room for `bg_motion`), blurred and
+    /// dimmed, and its logo.
+    pub fn load(config: &Config, settings: &RenderSettings) -> Result<Self, SpectrumError> {
+        let background = match settings.bg_image {
+            Some(ref path) => {
+                let rgba = load_image("background image", path)?.to_rgba8();
+                let (w, h) = rgba.dimensions();
+                // With a motion, keep headroom around the frame for the per-frame zoom/pan crop.
+                let (bg_w, bg_h) = match settings.bg_motion {
+                    Some(_) => motion_source_size(config.width, config.height),
+                    None => (config.width, config.height),
+                };
+                let mut bg = if w == bg_w && h == bg_h {
+                    rgba
+                } else {
+                    image::imageops::resize(&rgba, bg_w, bg_h, FilterType::Triangle)
+                };
+                if let Some(sigma) = settings.bg_blur {
+                    bg = image::imageops::blur(&bg, sigma);
+                }
+                if settings.bg_dim > 0.0 {
+                    dim_image(&mut bg, settings.bg_dim);
+                }
+                verbose!("Using background image: {:?}", path);
+                Some(bg)
+            }
+            None => None,
+        };
+        let logo = match settings.logo {
+            Some(ref path) => Some(load_logo(path, config.width, settings.logo_scale, settings.logo_opacity)?),
+            None => None,
+        };
+        Ok(Self { background, logo })
+    }
+}
+
+/// Load a logo image and prepare it for a `frame_width` wide frame.
+fn load_logo(path: &std::path::Path, frame_width: u32, scale: f32, opacity: f32) -> Result<RgbaImage, SpectrumError> {
+    let img = load_image("logo", path)?;
+    verbose!("Using logo: {:?}", path);
+    Ok(prepare_logo(&img.to_rgba8(), frame_width, scale, opacity))
+}
+
+/// What every frame is drawn from: the track, the layers with their images and the overlays, prepared once.
+pub struct Scene<'a> {
+    config: &'a Config,
+    layers: &'a [Layer],
+    settings: &'a RenderSettings,
+    track: &'a Track,
+    pub bands: Vec<Band>,
+    /// Theme logos and text without time placeholders, rendered once; timed text is rendered per frame.
+    layer_images: Vec<Option<RgbaImage>>,
+    images: Images,
+    font: Option<FontVec>,
+    text_color: [u8; 4],
+    title_block: Option<RgbaImage>,
+    /// Chapter titles are few, so each is rendered once up front.
+    chapter_blocks: Vec<RgbaImage>,
+    cues: Vec<Cue>,
+    chapters: Vec<Cue>,
+    visualizer: Option<&'a dyn Visualizer>,
+    on_frame: FrameHook<'a>,
+}
+
+impl<'a> Scene<'a> {
+    /// Prepare the layers and overlays for `track`, drawing `visualizer` in place of the spectrum layers' styles and
+    /// calling `on_frame` on each frame before it is framed.
+    pub fn prepare(
+        renderer: &'a Renderer,
+        track: &'a Track,
+        images: Images,
+        visualizer: Option<&'a dyn Visualizer>,
+        on_frame: FrameHook<'a>,
+    ) -> Result<Self, SpectrumError> {
+        let (config, layers, settings) = (renderer.config(), renderer.layers(), renderer.settings());
+        let (tag_title, tag_artist) = title_and_artist(settings, &track.tags);
+        let mut title_lines = Vec::new();
+        if settings.show_title || settings.title.is_some() || settings.artist.is_some() {
+            if !tag_title.is_empty() {
+                title_lines.push(TextLine { text: tag_title, size: settings.font_size });
+            }
+            if !tag_artist.is_empty() {
+                title_lines.push(TextLine { text: tag_artist, size: settings.font_size * ARTIST_SIZE_RATIO });
+            }
+            if title_lines.is_empty() {
+                warning!("No title/artist tags found");
+            }
+        }
+        let cues = match settings.subtitles {
+            Some(ref path) => {
+                let cues = load_subtitles(path, track.duration_sec)?;
+                verbose!("Loaded {} subtitle cues from {:?}", cues.len(), path);
+                cues
+            }
+            None => Vec::new(),
+        };
+        let chapters = match settings.chapters {
+            Some(ref path) => {
+                let chapters = load_chapters(path, track.duration_sec)?;
+                verbose!("Loaded {} chapters from {:?}", chapters.len(), path);
+                chapters
+            }
+            None => Vec::new(),
+        };
+        let has_text_layers = layers.iter().any(|layer| matches!(layer, Layer::Text { .. }));
+        let needs_font =
+            !title_lines.is_empty() || settings.show_time.is_some() || !cues.is_empty() || settings.show_chapter;
+        let font = if needs_font || has_text_layers { Some(load_font(settings.font.as_deref())?) } else { None };
+        let text_color = settings.text_color.unwrap_or(config.bar_color);
+        let title_block = match font {
+            Some(ref font) if !title_lines.is_empty() => {
+                Some(render_text_block(font, &title_lines, text_color, settings.title_pos.horizontal()))
+            }
+            _ => None,
+        };
+        let chapter_blocks = match font {
+            Some(ref font) if settings.show_chapter => chapters
+                .iter()
+                .map(|chapter| {
+                    let lines = [TextLine { text: &chapter.text, size: settings.font_size }];
+                    render_text_block(font, &lines, text_color, settings.chapter_pos.horizontal())
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut layer_images = Vec::with_capacity(layers.len());
+        for layer in layers {
+            layer_images.push(match (layer, &font) {
+                (Layer::Logo { path, scale, opacity, .. }, _) => Some(load_logo(
+                    path,
+                    config.width,
+                    scale.unwrap_or(settings.logo_scale),
+                    opacity.unwrap_or(settings.logo_opacity),
+                )?),
+                (Layer::Text { text, pos, size, color }, Some(font)) if !is_timed(text) => {
+                    let text = fill_tags(text, tag_title, tag_artist);
+                    let lines = [TextLine { text: &text, size: size.unwrap_or(settings.font_size) }];
+                    Some(render_text_block(font, &lines, color.unwrap_or(text_color), pos.horizontal()))
+                }
+                _ => None,
+            });
+        }
+        Ok(Self {
+            config,
+            layers,
+            settings,
+            track,
+            bands: prepare_bands(layers, settings, track)?,
+            layer_images,
+            images,
+            font,
+            text_color,
+            title_block,
+            chapter_blocks,
+            cues,
+            chapters,
+            visualizer,
+            on_frame,
+        })
+    }
+
+    /// The chapters, for the encode to embed.
+    pub fn chapters(&self) -> &[Cue] {
+        &self.chapters
+    }
+
+    /// Whether the frames need their samples: for the waveform and ring styles, a vectorscope (left and right too)
+    /// or a custom visualizer.
+    pub fn needs_samples(&self) -> (bool, bool) {
+        let needs_vectorscope = self.bands.iter().any(|band| band.visualizer.config().style == Style::Vectorscope);
+        let needs_waveform = self.visualizer.is_some()
+            || self
+                .bands
+                .iter()
+                .any(|band| matches!(band.visualizer.config().style, Style::Waveform | Style::Ring));
+        (needs_waveform || needs_vectorscope, needs_vectorscope)
+    }
+
+    /// Bars in drawing order: analysis order, or mirrored with `RenderSettings::symmetric`.
+    pub fn arrange(&self, heights: &[f32]) -> Vec<f32> {
+        match self.settings.symmetric {
+            Some(symmetry) => mirror_bars(heights, symmetry),
+            None => heights.to_vec(),
+        }
+    }
+
+    /// Draw one frame from its state. Only reads shared data, so render workers call it in parallel; each worker
+    /// keeps its own cache of the current subtitle cue's rendered text in `subtitle_block`.
+    pub fn draw(&self, state: &FrameState, subtitle_block: &mut Option<(usize, RgbaImage)>) -> RgbaImage {
+        let (config, settings, track) = (self.config, self.settings, self.track);
+        let (needs_waveform, needs_vectorscope) = self.needs_samples();
+        let frame_index = state.index;
+        let time = frame_index as f32 / config.fps as f32;
+        let spectrum_index = state.spectrum_index;
+        let bar_heights = &state.bar_heights;
+        let stereo_heights = track.stereo_spectra.as_ref().map(|(left, right, norm)| {
+            let heights = |frames| self.arrange(&crate::spectrum::bar_heights(frames, *norm, spectrum_index, config));
+            (heights(left), heights(right))
+        });
+        let frame = FrameData {
+            bar_heights,
+            waveform: if needs_waveform { &state.waveform } else { &[] },
+            timeline: &[],
+            progress: time / track.duration_sec.max(f32::EPSILON),
+            history: None,
+            stereo: match stereo_heights {
+                Some((ref left, ref right)) => Some((left, right)),
+                None if config.stereo_split.is_some() => Some((bar_heights, bar_heights)),
+                None => None,
+            },
+            centroid: track
+                .centroids
+                .get(spectrum_index)
+                .filter(|&&c| c > 0.0)
+                .map(|&c| frequency_to_position(c, track.sample_rate, config.fft_size)),
+            particles: &[],
+            stereo_samples: needs_vectorscope.then(|| match state.stereo_samples {
+                Some((ref left, ref right)) => (&left[..], &right[..]),
+                None => (&state.waveform[..], &state.waveform[..]),
+            }),
+        };
+        let mut img = self.draw_background(state);
+        let level = track.levels.get(frame_index).copied().unwrap_or_default();
+        let analysis = FrameAnalysis {
+            time,
+            bars: track.bar_heights(spectrum_index, config),
+            rms: level.rms,
+            peak: track.envelopes.get(frame_index).map_or(0.0, EnvelopePoint::peak),
+            lufs: level.lufs,
+            beat: state.beat,
+        };
+        let (margin, shadow) = (settings.overlay_margin, config.shadow);
+        let mut next_band = self.bands.iter().zip(&state.trails);
+        for (layer, layer_image) in self.layers.iter().zip(&self.layer_images) {
+            match layer {
+                Layer::Spectrum(_) => {
+                    let (band, (history, particles)) = next_band.next().expect("one band per spectrum layer");
+                    if !settings.meter_only && self.visualizer.is_none() {
+                        let frame = FrameData { timeline: &band.timeline, history: Some(history), particles, ..frame };
+                        let cycled = state.hue_degrees.map(|degrees| {
+                            StyleVisualizer::new(rotate_colors(band.visualizer.config(), degrees))
+                        });
+                        cycled.as_ref().unwrap_or(&band.visualizer).draw(&mut img, &analysis, &frame);
+                    }
+                    if let Some(ref art) = band.center_image {
+                        draw_center_image(&mut img, band.visualizer.config(), art);
+                    }
+                }
+                Layer::Text { text, pos, size, color } => match (layer_image, &self.font) {
+                    (Some(block), _) => composite_overlay(&mut img, block, *pos, margin, shadow),
+                    (None, Some(font)) => {
+                        let (title, artist) = title_and_artist(settings, &track.tags);
+                        let text = fill_time(
+                            &fill_tags(text, title, artist),
+                            &TimeDisplay::Elapsed.label(time, track.duration_sec),
+                            &TimeDisplay::Remaining.label(time, track.duration_sec),
+                        );
+                        let lines = [TextLine { text: &text, size: size.unwrap_or(settings.font_size) }];
+                        let block =
+                            render_text_block(font, &lines, color.unwrap_or(self.text_color), pos.horizontal());
+                        composite_overlay(&mut img, &block, *pos, margin, shadow);
+                    }
+                    (None, None) => {}
+                },
+                Layer::Logo { pos, .. } => {
+                    if let Some(logo) = layer_image {
+                        composite_overlay(&mut img, logo, *pos, margin, shadow);
+                    }
+                }
+                Layer::Progress { rect, color, track } => {
+                    draw_progress_bar(&mut img, *rect, frame.progress, *color, *track);
+                }
+            }
+        }
+        if let Some(visualizer) = self.visualizer {
+            visualizer.draw(&mut img, &analysis, &frame);
+        }
+        self.draw_overlays(&mut img, state, subtitle_block);
+        (self.on_frame)(
+            &mut img,
+            &FrameContext {
+                index: frame_index,
+                time,
+                total_frames: track.total_frames,
+                fps: config.fps,
+                bar_heights,
+                beat: state.beat,
+                rms: level.rms,
+            },
+        );
+        // Everything is laid out in the content area inside the padding and border, then framed here.
+        if content_inset(settings.padding, settings.border) > 0 {
+            let frame_size = content_frame_size(config, settings);
+            img = frame_content(&img, frame_size, settings.padding, settings.border, config.bg_color);
+        }
+        img
+    }
+
+    /// The frame's background: the video frame, the moving image, the image or the color, pulsed with the bass.
+    fn draw_background(&self, state: &FrameState) -> RgbaImage {
+        let (config, settings) = (self.config, self.settings);
+        let video_bg = state.video_bg.clone().map(|mut frame| {
+            if settings.bg_dim > 0.0 {
+                dim_image(&mut frame, settings.bg_dim);
+            }
+            frame
+        });
+        let moving_bg = match (settings.bg_motion, &self.images.background) {
+            (Some(motion), Some(source)) => Some(render_background(
+                source,
+                motion,
+                config.width,
+                config.height,
+                state.index as f32 / self.track.total_frames.saturating_sub(1).max(1) as f32,
+            )),
+            _ => None,
+        };
+        let bg = video_bg.as_ref().or(moving_bg.as_ref()).or(self.images.background.as_ref());
+        let pulsed_bg = settings.bg_pulse.map(|pulse| match bg {
+            Some(bg) => pulse_background(bg, pulse, settings.bg_pulse_amount, state.bass_level),
+            None => {
+                let solid = RgbaImage::from_pixel(config.width, config.height, image::Rgba(config.bg_color));
+                pulse_background(&solid, pulse, settings.bg_pulse_amount, state.bass_level)
+            }
+        });
+        draw_background(config, pulsed_bg.as_ref().or(bg))
+    }
+
+    /// The meter, logo, title, subtitles, chapter title and time counter, over the layers.
+    fn draw_overlays(&self, img: &mut RgbaImage, state: &FrameState, subtitle_block: &mut Option<(usize, RgbaImage)>) {
+        let (config, settings) = (self.config, self.settings);
+        let (margin, shadow) = (settings.overlay_margin, config.shadow);
+        let time = state.index as f32 / config.fps as f32;
+        if let Some(style) = settings.meter {
+            let ballistics = settings.meter_ballistics;
+            let red_from = ballistics.position(ballistics.red_zone_db());
+            let size = match style {
+                MeterStyle::Vertical => ((settings.meter_size / 6).max(4), settings.meter_size),
+                MeterStyle::Needle => (settings.meter_size, settings.meter_size * 3 / 5),
+            };
+            let (x, y) = overlay_origin(settings.meter_pos, (config.width, config.height), size, margin);
+            let rect = (x.max(0) as u32, y.max(0) as u32, size.0, size.1);
+            let position = state.meter_position;
+            match style {
+                MeterStyle::Vertical => draw_vertical_meter(img, rect, position, red_from, config.bar_color),
+                MeterStyle::Needle => draw_needle_meter(img, rect, position, red_from, config.bar_color),
+            }
+        }
+        if let Some(ref logo) = self.images.logo {
+            composite_overlay(img, logo, settings.logo_pos, margin, shadow);
+        }
+        if let Some(ref block) = self.title_block {
+            composite_overlay(img, block, settings.title_pos, margin, shadow);
+        }
+        if let Some(font) = &self.font
+            && let Some(cue) = active_cue(&self.cues, time)
+        {
+            if subtitle_block.as_ref().is_none_or(|(shown, _)| *shown != cue) {
+                let max_width = config.width.saturating_sub(margin * 2) as f32;
+                let wrapped = wrap_text(font, settings.font_size, &self.cues[cue].text, max_width);
+                let lines: Vec<TextLine> =
+                    wrapped.iter().map(|text| TextLine { text, size: settings.font_size }).collect();
+                let align = settings.subtitle_pos.horizontal();
+                *subtitle_block = Some((cue, render_text_block(font, &lines, self.text_color, align)));
+            }
+            if let Some((_, ref block)) = *subtitle_block {
+                composite_overlay(img, block, settings.subtitle_pos, margin, shadow);
+            }
+        }
+        let chapter = active_cue(&self.chapters, time);
+        if let Some(block) = chapter.and_then(|index| self.chapter_blocks.get(index)) {
+            composite_overlay(img, block, settings.chapter_pos, margin, shadow);
+        }
+        if let (Some(display), Some(font)) = (settings.show_time, &self.font) {
+            let label = display.label(time, self.track.duration_sec);
+            let lines = [TextLine { text: &label, size: settings.font_size }];
+            let block = render_text_block(font, &lines, self.text_color, settings.time_pos.horizontal());
+            composite_overlay(img, &block, settings.time_pos, margin, shadow);
+        }
+    }
+}
+
+/// The spectrum layers' bands, with their timeline strips and the image in the circle of radial and ring bands.
+fn prepare_bands(layers: &[Layer], settings: &RenderSettings, track: &Track) -> Result<Vec<Band>, SpectrumError> {
+    let band_configs: Vec<&Config> = layers
+        .iter()
+        .filter_map(|layer| match layer {
+            Layer::Spectrum(band) => Some(band.as_ref()),
+            _ => None,
+        })
+        .collect();
+    let has_center = band_configs.iter().any(|band| matches!(band.style, Style::Radial | Style::Ring));
+    let center_source = if !has_center {
+        None
+    } else if let Some(ref path) = settings.radial_image {
+        let img = load_image("radial image", path)?;
+        verbose!("Using radial center image: {:?}", path);
+        Some(img)
+    } else if settings.radial_art {
+        match track.tags.cover_art.as_deref().map(image::load_from_memory) {
+            Some(Ok(img)) => {
+                verbose!("Using embedded album art in the radial center");
+                Some(img)
+            }
+            Some(Err(e)) => {
+                warning!("Ignoring embedded album art: {}", e);
+                None
+            }
+            None => {
+                warning!("No embedded album art found");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let bands = band_configs
+        .into_iter()
+        .map(|band| {
+            let (_, strip_width) = strip_extent(band);
+            let timeline = match track.timeline {
+                Some(ref points) if band.style == Style::Timeline => resample_envelope(
+                    points,
+                    TIMELINE_POINTS_PER_SEC,
+                    strip_width as f64 / track.duration_sec.max(f32::EPSILON) as f64,
+                    strip_width as usize,
+                ),
+                _ => Vec::new(),
+            };
+            let center_image = match center_source {
+                Some(ref img) if matches!(band.style, Style::Radial | Style::Ring) => {
+                    let (_, _, inner_radius) = radial_geometry(band);
+                    let diameter = (inner_radius * 2.0 * 0.9) as u32;
+                    (diameter > 0).then(|| img.resize_to_fill(diameter, diameter, FilterType::Triangle).to_rgba8())
+                }
+                _ => None,
+            };
+            Band { visualizer: StyleVisualizer::new(band.clone()), center_image, timeline }
+        })
+        .collect();
+    Ok(bands)
+}
+
+/// `config` with its bar, loud and played colors and its colormap turned `degrees` around the hue circle
+/// (--color-cycle). A built-in colormap is replaced by its stops so they can be turned too.
+fn rotate_colors(config: &Config, degrees: f32) -> Config {
+    let colormap_stops = match config.bar_colormap {
+        Some(map) => map.to_stops(&config.colormap_stops).into_iter().map(|c| rotate_hue(c, degrees)).collect(),
+        None => config.colormap_stops.clone(),
+    };
+    Config {
+        bar_color: rotate_hue(config.bar_color, degrees),
+        bar_color_loud: config.bar_color_loud.map(|c| rotate_hue(c, degrees)),
+        played_color: rotate_hue(config.played_color, degrees),
+        bar_colormap: config.bar_colormap.map(|_| Colormap::Custom),
+        colormap_stops,
+        ..config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rotate_colors;
+    use crate::colormap::Colormap;
+    use crate::config::Config;
+
+    #[test]
+    fn rotate_colors_turns_bar_colors_and_colormap() {
+        let config = Config {
+            bar_color: [255, 0, 0, 255],
+            bar_colormap: Some(Colormap::Rainbow),
+            ..Config::default()
+        };
+        let turned = rotate_colors(&config, 120.0);
+        assert_eq!(turned.bar_color, [0, 255, 0, 255]);
+        assert_eq!(turned.bar_colormap, Some(Colormap::Custom));
+        assert_eq!(turned.colormap_stops[0], [0, 255, 0, 255]);
+        assert_eq!(turned.bars, config.bars);
+        assert_eq!(rotate_colors(&config, 0.0).colormap_stops, Colormap::Rainbow.to_stops(&[]));
+    }
+}
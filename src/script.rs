@@ -0,0 +1,317 @@
+//! `--script`: a Rhai script that draws each frame from its analysis, in place of the built-in styles
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ab_glyph::FontVec;
+use image::RgbaImage;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use crate::colormap::parse_hex_color;
+use crate::draw::{draw_polyline, fill_circle, fill_rounded_rect, FrameData};
+use crate::error::SpectrumError;
+use crate::export::FrameAnalysis;
+use crate::logging::warning;
+use crate::text::{render_text_block, text_width, TextLine};
+use crate::visualizer::Visualizer;
+
+/// The script function called for every frame, with the frame's analysis as its one argument.
+const ENTRY: &str = "frame";
+
+/// Limits on what one frame's script run may do, so a runaway script fails instead of hanging or exhausting memory.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1024;
+const MAX_ARRAY_SIZE: usize = 65_536;
+const MAX_MAP_SIZE: usize = 4096;
+/// Drawing calls per frame.
+const MAX_COMMANDS: usize = 10_000;
+/// How far past the frame's left edge text may start and still be drawn, in frame widths.
+const TEXT_LEAD_FRAMES: f32 = 1.0;
+
+/// A drawing call, collected while the script runs and drawn once it returns.
+enum Command {
+    Rect { x: f32, y: f32, w: f32, h: f32, radius: f32, color: [u8; 4] },
+    Circle { x: f32, y: f32, r: f32, color: [u8; 4] },
+    Line { from: (f32, f32), to: (f32, f32), width: f32, color: [u8; 4] },
+    Text { x: f32, y: f32, size: f32, text: String, color: [u8; 4] },
+}
+
+thread_local! {
+    /// Drawing calls of the script running on this thread. Render workers run the script in parallel, each on its
+    /// own frame, so every worker collects its own.
+    static COMMANDS: RefCell<Vec<Command>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A user script as a `Visualizer`. The script defines `fn frame(f)`, where `f` holds the frame's `FrameAnalysis`
+/// fields plus `width` and `height`, and draws with `rect`, `circle`, `line` and `text` (colors as hex strings, as
+/// on the command line). Frames may run out of order, so the script keeps no state between them. Each frame's run is
+/// limited in operations, call depth, string, array and map sizes and drawing calls; sizes are clamped to the frame.
+pub struct ScriptVisualizer {
+    engine: Engine,
+    ast: AST,
+    font: Option<FontVec>,
+    /// Set by the first frame the script fails on; later failures aren't reported again.
+    failed: AtomicBool,
+}
+
+impl ScriptVisualizer {
+    /// Compile the script at `path`. `font` draws its `text` calls; without one, `text` fails.
+    pub fn load(path: &Path, font: Option<FontVec>) -> Result<Self, SpectrumError> {
+        let engine = script_engine(font.is_some());
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| SpectrumError::InvalidConfig(format!("--script {:?}: {}", path, e)))?;
+        if !ast.iter_functions().any(|f| f.name == ENTRY && f.params.len() == 1) {
+            let message = format!("--script {:?} must define fn {}(f)", path, ENTRY);
+            return Err(SpectrumError::InvalidConfig(message));
+        }
+        Ok(Self { engine, ast, font, failed: AtomicBool::new(false) })
+    }
+
+    /// Run the script once on a silent frame of `size` with `bars` bars, so mistakes show up before a render.
+    pub fn check(&self, size: (u32, u32), bars: usize) -> Result<(), SpectrumError> {
+        let bars = vec![0.0; bars];
+        let silence = FrameAnalysis { time: 0.0, bars, rms: 0.0, peak: 0.0, lufs: -100.0, beat: false };
+        let mut frame = RgbaImage::new(size.0, size.1);
+        let result = self.try_draw(&mut frame, &silence, &[]);
+        result.map_err(|e| SpectrumError::InvalidConfig(format!("--script: {}", e)))
+    }
+
+    /// Run the script for one frame, with the frame's mono `samples`, and draw what it asked for onto `frame`, up to
+    /// the point where it failed.
+    pub fn try_draw(
+        &self,
+        frame: &mut RgbaImage,
+        analysis: &FrameAnalysis,
+        samples: &[f32],
+    ) -> Result<(), Box<EvalAltResult>> {
+        COMMANDS.with_borrow_mut(Vec::clear);
+        let input = frame_input(frame.dimensions(), analysis, samples);
+        let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, ENTRY, (input,));
+        for command in COMMANDS.take() {
+            self.paint(frame, command);
+        }
+        result.map(drop)
+    }
+
+
+    fn paint(&self, frame: &mut RgbaImage, command: Command) {
+        match command {
+            Command::Rect { x, y, w, h, radius, color } => {
+                if let Some(rect) = clip_rect(frame.dimensions(), x, y, w, h) {
+                    let radius = radius.clamp(0.0, rect.2.min(rect.3) as f32 / 2.0);
+                    fill_rounded_rect(frame, rect, radius.round() as u32, color);
+                }
+            }
+            Command::Circle { x, y, r, color } => fill_circle(frame, x, y, r, color, 1.0),
+            Command::Line { from, to, width, color } => {
+                let (frame_width, frame_height) = frame.dimensions();
+                let width = width.clamp(0.0, (frame_width + frame_height) as f32);
+                draw_polyline(frame, &[from, to], width, color);
+            }
+            Command::Text { x, y, size, text, color } => {
+                let Some(ref font) = self.font else { return };
+                let (frame_width, frame_height) = frame.dimensions();
+                let size = size.clamp(0.0, frame_height as f32);
+                // Only what can reach the frame is rasterized.
+                let lead = x.min(0.0).max(-TEXT_LEAD_FRAMES * frame_width as f32);
+                let text = fitting_prefix(font, size, &text, frame_width as f32 - lead);
+                if size > 0.0 && !text.is_empty() && y < frame_height as f32 {
+                    let block = render_text_block(font, &[TextLine { text, size }], color, 0.0);
+                    image::imageops::overlay(frame, &block, x.round() as i64, y.round() as i64);
+                }
+            }
+        }
+    }
+}
+
+/// `try_draw` with `data.waveform` as the samples, reporting the first failure as a warning.
+impl Visualizer for ScriptVisualizer {
+    fn draw(&self, frame: &mut RgbaImage, analysis: &FrameAnalysis, data: &FrameData) {
+        if let Err(e) = self.try_draw(frame, analysis, data.waveform)
+            && !self.failed.swap(true, Ordering::Relaxed)
+        {
+            warning!("--script failed at {:.2}s (later failures are not reported): {}", analysis.time, e);
+        }
+    }
+}
+
+/// An engine with the drawing functions and the per-run limits. `rect` takes an optional corner radius before the
+/// color.
+fn script_engine(has_font: bool) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .set_max_map_size(MAX_MAP_SIZE);
+    engine.register_fn("rect", |x: Dynamic, y: Dynamic, w: Dynamic, h: Dynamic, color: Dynamic| {
+        let (x, y, w, h) = (number(&x)?, number(&y)?, number(&w)?, number(&h)?);
+        issue(Command::Rect { x, y, w, h, radius: 0.0, color: color_of(&color)? })
+    });
+    engine.register_fn("rect", |x: Dynamic, y: Dynamic, w: Dynamic, h: Dynamic, radius: Dynamic, color: Dynamic| {
+        let (x, y, w, h, radius) = (number(&x)?, number(&y)?, number(&w)?, number(&h)?, number(&radius)?);
+        issue(Command::Rect { x, y, w, h, radius, color: color_of(&color)? })
+    });
+    engine.register_fn("circle", |x: Dynamic, y: Dynamic, r: Dynamic, color: Dynamic| {
+        issue(Command::Circle { x: number(&x)?, y: number(&y)?, r: number(&r)?, color: color_of(&color)? })
+    });
+    engine.register_fn(
+        "line",
+        |x0: Dynamic, y0: Dynamic, x1: Dynamic, y1: Dynamic, width: Dynamic, color: Dynamic| {
+            let (from, to) = ((number(&x0)?, number(&y0)?), (number(&x1)?, number(&y1)?));
+            issue(Command::Line { from, to, width: number(&width)?, color: color_of(&color)? })
+        },
+    );
+    engine.register_fn("text", move |x: Dynamic, y: Dynamic, size: Dynamic, text: Dynamic, color: Dynamic| {
+        if !has_font {
+            return Err("text() needs a font: no system font found, pass one with --font".into());
+        }
+        let (x, y, size) = (number(&x)?, number(&y)?, number(&size)?);
+        issue(Command::Text { x, y, size, text: text.to_string(), color: color_of(&color)? })
+    });
+    engine
+}
+
+fn issue(command: Command) -> Result<(), Box<EvalAltResult>> {
+    COMMANDS.with_borrow_mut(|commands| {
+        if commands.len() >= MAX_COMMANDS {
+            return Err(format!("more than {} drawing calls in one frame", MAX_COMMANDS).into());
+        }
+        commands.push(command);
+        Ok(())
+    })
+}
+
+/// A script number, integer or float; infinities and NaN are rejected.
+fn number(value: &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    let number = match (value.as_float(), value.as_int()) {
+        (Ok(v), _) => v as f32,
+        (_, Ok(v)) => v as f32,
+        _ => return Err(format!("expected a number, got {}", value.type_name()).into()),
+    };
+    if number.is_finite() { Ok(number) } else { Err(format!("expected a finite number, got {}", number).into()) }
+}
+
+/// The longest start of `text` no wider than `max_width` at `size`.
+fn fitting_prefix<'t>(font: &FontVec, size: f32, text: &'t str, max_width: f32) -> &'t str {
+    let ends: Vec<usize> = text.char_indices().map(|(i, c)| i + c.len_utf8()).collect();
+    let fitting = ends.partition_point(|&end| text_width(font, size, &text[..end]) <= max_width);
+    &text[..fitting.checked_sub(1).map_or(0, |last| ends[last])]
+}
+
+/// A script color: a hex string such as "ff6600" or "#ff660080".
+fn color_of(value: &Dynamic) -> Result<[u8; 4], Box<EvalAltResult>> {
+    let text = value.clone().into_immutable_string().map_err(|t| format!("expected a color string, got {}", t))?;
+    parse_hex_color(&text).map_err(Into::into)
+}
+
+/// The script's `f`: the analysis fields under their export names, and the frame size.
+fn frame_input((width, height): (u32, u32), analysis: &FrameAnalysis, samples: &[f32]) -> Map {
+    let bars: Array = analysis.bars.iter().map(|&v| Dynamic::from_float(v as f64)).collect();
+    let samples: Array = samples.iter().map(|&v| Dynamic::from_float(v as f64)).collect();
+    let mut input = Map::new();
+    input.insert("width".into(), Dynamic::from_int(width as i64));
+    input.insert("height".into(), Dynamic::from_int(height as i64));
+    input.insert("time".into(), Dynamic::from_float(analysis.time as f64));
+    input.insert("bars".into(), bars.into());
+    input.insert("rms".into(), Dynamic::from_float(analysis.rms as f64));
+    input.insert("peak".into(), Dynamic::from_float(analysis.peak as f64));
+    input.insert("lufs".into(), Dynamic::from_float(analysis.lufs as f64));
+    input.insert("beat".into(), Dynamic::from_bool(analysis.beat));
+    input.insert("samples".into(), samples.into());
+    input
+}
+
+/// The part of the box at (`x`, `y`) sized `w` × `h` that lies inside a frame of `size`, in whole pixels.
+fn clip_rect(size: (u32, u32), x: f32, y: f32, w: f32, h: f32) -> Option<(u32, u32, u32, u32)> {
+    let x0 = x.round().clamp(0.0, size.0 as f32);
+    let y0 = y.round().clamp(0.0, size.1 as f32);
+    let x1 = (x + w).round().clamp(0.0, size.0 as f32);
+    let y1 = (y + h).round().clamp(0.0, size.1 as f32);
+    (x1 > x0 && y1 > y0).then_some((x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clip_rect, ScriptVisualizer};
+    use crate::error::SpectrumError;
+    use crate::export::FrameAnalysis;
+    use image::RgbaImage;
+
+    fn script(name: &str, source: &str) -> Result<ScriptVisualizer, SpectrumError> {
+        let path = std::env::temp_dir().join(format!("asg-script-{}-{}.rhai", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let script = ScriptVisualizer::load(&path, None);
+        std::fs::remove_file(&path).ok();
+        script
+    }
+
+    #[test]
+    fn the_script_draws_from_the_frame_analysis() {
+        let source = r##"
+            fn frame(f) {
+                let w = f.width / f.bars.len();
+                for i in 0..f.bars.len() {
+                    let h = f.bars[i] * f.height;
+                    rect(i * w, f.height - h, w, h, "ff0000");
+                }
+                if f.beat { circle(0, 0, 2, "#00ff00") }
+                for i in 0..f.samples.len() {
+                    if f.samples[i] > 0.5 { rect(i, 1, 1, 1, "0000ff") }
+                }
+            }
+        "##;
+        let script = script("bars", source).unwrap();
+        let analysis = FrameAnalysis { time: 0.0, bars: vec![0.0, 1.0], rms: 0.0, peak: 0.0, lufs: -100.0, beat: true };
+        let mut frame = RgbaImage::new(4, 4);
+        script.try_draw(&mut frame, &analysis, &[0.0, 1.0]).unwrap();
+        assert_eq!(frame.get_pixel(0, 3).0, [0, 0, 0, 0]);
+        assert_eq!(frame.get_pixel(1, 1).0, [0, 0, 255, 255]);
+        assert_eq!(frame.get_pixel(2, 0).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(0, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn mistakes_are_reported_before_rendering() {
+        let error = |source| script("bad", source).and_then(|s| s.check((8, 8), 4)).unwrap_err().to_string();
+        assert!(error("fn frame(f) {").contains("--script"));
+        assert!(error("fn draw(f) {}").contains("must define fn frame(f)"));
+        assert!(error(r#"fn frame(f) { rect(0, 0, 1, 1, "nope") }"#).contains("hex"));
+        assert!(error(r#"fn frame(f) { text(0, 0, 12, "hi", "ffffff") }"#).contains("needs a font"));
+        let ok = script("ok", r#"fn frame(f) { line(0, 0, f.width, f.bars[3], 2, "fff000") }"#).unwrap();
+        assert!(ok.check((8, 8), 4).is_ok());
+    }
+
+    #[test]
+    fn runaway_scripts_fail_instead_of_hanging_or_exhausting_memory() {
+        let error = |source| script("runaway", source).and_then(|s| s.check((8, 8), 4)).unwrap_err().to_string();
+        assert!(error("fn frame(f) { loop {} }").contains("Too many operations"));
+        assert!(error("fn frame(f) { frame(f) }").contains("Stack overflow"));
+        assert!(error(r#"fn frame(f) { let s = "x"; loop { s += s } }"#).contains("Length of string"));
+        let flood = error(r#"fn frame(f) { loop { circle(0, 0, 1, "ffffff") } }"#);
+        assert!(flood.contains("drawing calls"), "{}", flood);
+        assert!(error(r#"fn frame(f) { rect(0, 0, 1 / 0.0, 1, "ffffff") }"#).contains("finite"));
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn huge_text_is_cut_to_the_frame() {
+        let Ok(font) = crate::text::load_font(None) else { return };
+        let path = std::env::temp_dir().join(format!("asg-script-text-{}.rhai", std::process::id()));
+        std::fs::write(&path, r#"fn frame(f) { text(0, 0, 100000, "hello", "ffffff") }"#).unwrap();
+        let script = ScriptVisualizer::load(&path, Some(font));
+        std::fs::remove_file(&path).ok();
+        script.unwrap().check((64, 32), 4).unwrap();
+    }
+
+    #[test]
+    fn rects_are_clipped_to_the_frame() {
+        assert_eq!(clip_rect((10, 10), -5.0, 2.0, 8.0, 20.0), Some((0, 2, 3, 8)));
+        assert_eq!(clip_rect((10, 10), 12.0, 0.0, 4.0, 4.0), None);
+    }
+}
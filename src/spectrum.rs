@@ -1,32 +1,57 @@
 //! FFT and bin computation (rustfft)
 
 use rustfft::num_complex::Complex;
-use rustfft::FftPlanner;
+use std::sync::Arc;
 
-/// Per-frame spectrum amplitude (one f32 per bar).
-/// Frequency uses a log scale; amplitude uses log(1+x) to expand dynamic range.
-pub fn compute_spectrum_frame(
-    samples: &[f32],
-    sample_rate: u32,
-    frame_index: u32,
-    _fps: u32,
-    fft_size: usize,
-    overlap: f32,
-    bars: usize,
-) -> Vec<f32> {
-    let hop = (fft_size as f32 * (1.0 - overlap)).max(1.0) as usize;
-    let start = (frame_index as usize).saturating_mul(hop);
-    if start + fft_size > samples.len() {
-        return vec![0.0; bars];
+use rustfft::{Fft, FftPlanner};
+
+#[cfg(feature = "runner")]
+use crate::config::Symmetry;
+use crate::config::{Config, SmoothingKernel};
+
+/// Aggregate bins to bars with log frequency scale, apply per-bar gains, then log(1+x) for amplitude
+/// (makes the display more dynamic). `gains.len()` is the number of bars.
+fn bars_from_magnitudes(sample_rate: u32, fft_size: usize, magnitudes: &[f32], gains: &[f32]) -> Vec<f32> {
+    let raw = aggregate_bins_to_bars_log(sample_rate, fft_size, magnitudes, gains.len());
+    raw.into_iter()
+        .zip(gains)
+        .map(|(x, g)| (1.0 + x * g).ln())
+        .collect()
+}
+
+/// Reference frequency for tilt compensation: bars at this frequency are left unchanged.
+const TILT_REF_HZ: f32 = 1000.0;
+
+/// Linear gain per bar for a spectral tilt of `db_per_octave`, evaluated at each bar's center frequency.
+/// Music energy falls off roughly like pink noise (-3 dB/octave), so a positive tilt evens out the display.
+fn tilt_gains(sample_rate: u32, fft_size: usize, bars: usize, db_per_octave: f32) -> Vec<f32> {
+    if db_per_octave == 0.0 {
+        return vec![1.0; bars];
     }
+    (0..bars)
+        .map(|i| {
+            let t = (i as f32 + 0.5) / bars as f32;
+            let f = position_to_frequency(t, sample_rate, fft_size);
+            let octaves = (f / TILT_REF_HZ).log2();
+            10f32.powf(db_per_octave * octaves / 20.0)
+        })
+        .collect()
+}
 
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(fft_size);
+/// Samples between the starts of consecutive analysis frames.
+pub fn hop_size(fft_size: usize, overlap: f32) -> usize {
+    (fft_size as f32 * (1.0 - overlap)).max(1.0) as usize
+}
 
-    let mut buffer: Vec<Complex<f32>> = samples[start..start + fft_size]
+/// Hann-window `frame` (length = FFT size), run the FFT and return the `len / 2 + 1` bin magnitudes.
+fn windowed_magnitudes(fft: &dyn Fft<f32>, frame: &[f32]) -> Vec<f32> {
+    let fft_size = frame.len();
+    let mut buffer: Vec<Complex<f32>> = frame
         .iter()
         .enumerate()
         .map(|(i, &s)| {
+            // A single NaN/Inf sample (corrupt frame) would poison every bin of the FFT.
+            let s = if s.is_finite() { s } else { 0.0 };
             let w = hann_window(i, fft_size);
             Complex::new(s * w, 0.0)
         })
@@ -35,15 +60,10 @@ pub fn compute_spectrum_frame(
     fft.process(&mut buffer);
 
     let half = fft_size / 2 + 1;
-    let magnitudes: Vec<f32> = buffer[..half]
+    buffer[..half]
         .iter()
         .map(|c| c.norm())
-        .collect();
-
-    // Aggregate bins to bars with log frequency scale; log(1+x) for amplitude makes the display more dynamic
-    let raw = aggregate_bins_to_bars_log(sample_rate, fft_size, &magnitudes, bars);
-    raw.into_iter()
-        .map(|x| (1.0 + x).ln())
+        .map(|m| if m.is_finite() { m } else { 0.0 })
         .collect()
 }
 
@@ -64,17 +84,10 @@ fn aggregate_bins_to_bars_log(
         return vec![0.0; bars];
     }
     let sr = sample_rate as f32;
-    let f_min = sr / fft_size as f32;
-    let f_max = sr * 0.5;
-    let log_f_min = (f_min + 1.0).ln();
-    let log_f_max = (f_max + 1.0).ln();
-    let log_span = log_f_max - log_f_min;
-
     let mut result = vec![0.0f32; bars];
     for (bin_ix, &mag) in magnitudes.iter().enumerate().skip(1) {
         let f = bin_ix as f32 * sr / fft_size as f32;
-        let log_f = (f + 1.0).ln();
-        let t = ((log_f - log_f_min) / log_span).clamp(0.0, 1.0);
+        let t = frequency_to_position(f, sample_rate, fft_size);
         let bar_ix = (t * bars as f32).min(bars as f32 - 1.0) as usize;
         if bar_ix < bars && mag > result[bar_ix] {
             result[bar_ix] = mag;
@@ -83,46 +96,350 @@ fn aggregate_bins_to_bars_log(
     result
 }
 
+/// Horizontal position (0.0–1.0) of frequency `f` (Hz) on the log-frequency axis used for bars.
+/// 0.0 is the first FFT bin (`sample_rate / fft_size`), 1.0 is Nyquist.
+pub fn frequency_to_position(f: f32, sample_rate: u32, fft_size: usize) -> f32 {
+    let sr = sample_rate as f32;
+    let f_min = sr / fft_size as f32;
+    let f_max = sr * 0.5;
+    let log_f_min = (f_min + 1.0).ln();
+    let log_f_max = (f_max + 1.0).ln();
+    let log_span = log_f_max - log_f_min;
+    (((f + 1.0).ln() - log_f_min) / log_span).clamp(0.0, 1.0)
+}
+
+/// Inverse of `frequency_to_position`: frequency (Hz) at horizontal position `t` (0.0–1.0).
+pub fn position_to_frequency(t: f32, sample_rate: u32, fft_size: usize) -> f32 {
+    let sr = sample_rate as f32;
+    let log_f_min = (sr / fft_size as f32 + 1.0).ln();
+    let log_f_max = (sr * 0.5 + 1.0).ln();
+    (log_f_min + t.clamp(0.0, 1.0) * (log_f_max - log_f_min)).exp() - 1.0
+}
+
+/// Spectral centroid (Hz): magnitude-weighted mean frequency, DC bin excluded.
+/// Returns 0.0 for silent frames.
+fn spectral_centroid(magnitudes: &[f32], sample_rate: u32, fft_size: usize) -> f32 {
+    let bin_hz = sample_rate as f32 / fft_size as f32;
+    let (weighted, total) = magnitudes
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold((0.0f32, 0.0f32), |(w, t), (bin_ix, &mag)| {
+            (w + bin_ix as f32 * bin_hz * mag, t + mag)
+        });
+    if total > 0.0 { weighted / total } else { 0.0 }
+}
+
+/// Spectrum analysis of a whole input: per-frame bar values plus the global max for normalization.
+pub struct SpectrumAnalysis {
+    /// One entry per analysis frame, each with `bars` values (not normalized).
+    pub frames: Vec<Vec<f32>>,
+    /// Largest bar value across all frames.
+    pub global_max: f32,
+    /// Spectral centroid (Hz) per analysis frame; empty unless enabled with `SpectrumStream::with_centroids`.
+    pub centroids: Vec<f32>,
+}
+
+/// Incremental spectrum computation: feed mono samples as they are decoded and FFT frames are
+/// computed as soon as enough samples are buffered, so decoding and analysis are one pass. The stream
+/// holds only about one FFT window of PCM and reuses the FFT plan, and the decoder keeps none, so nothing holds the
+/// whole track's samples.
+pub struct SpectrumStream {
+    sample_rate: u32,
+    fft_size: usize,
+    hop: usize,
+    gains: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    pending: Vec<f32>,
+    skip: usize,
+    frames: Vec<Vec<f32>>,
+    global_max: f32,
+    centroids: Option<Vec<f32>>,
+}
+
+impl SpectrumStream {
+    /// Frames start every `fft_size * (1 - overlap)` samples (`hop_size`).
+    pub fn new(
+        sample_rate: u32,
+        fft_size: usize,
+        overlap: f32,
+        bars: usize,
+        tilt_db_per_octave: f32,
+    ) -> Self {
+        let hop = hop_size(fft_size, overlap);
+        Self {
+            sample_rate,
+            fft_size,
+            hop,
+            gains: tilt_gains(sample_rate, fft_size, bars, tilt_db_per_octave),
+            fft: FftPlanner::new().plan_fft_forward(fft_size),
+            pending: Vec::with_capacity(fft_size * 2),
+            skip: 0,
+            frames: Vec::new(),
+            global_max: 0.0,
+            centroids: None,
+        }
+    }
+
+    /// Also compute the spectral centroid of every frame.
+    pub fn with_centroids(mut self) -> Self {
+        self.centroids = Some(Vec::new());
+        self
+    }
+
+    /// Append decoded samples, computing every frame that is now complete.
+    pub fn push(&mut self, samples: &[f32]) {
+        // With hop > fft_size, samples between frames are skipped without buffering.
+        let skip = self.skip.min(samples.len());
+        self.skip -= skip;
+        self.pending.extend_from_slice(&samples[skip..]);
+
+        let mut start = 0;
+        while start + self.fft_size <= self.pending.len() {
+            self.analyze(start);
+            start += self.hop;
+        }
+        if start > self.pending.len() {
+            self.skip += start - self.pending.len();
+            start = self.pending.len();
+        }
+        self.pending.drain(..start);
+    }
+
+    fn analyze(&mut self, start: usize) {
+        let frame = &self.pending[start..start + self.fft_size];
+        let magnitudes = windowed_magnitudes(self.fft.as_ref(), frame);
+        let bar_values = bars_from_magnitudes(self.sample_rate, self.fft_size, &magnitudes, &self.gains);
+        let m = bar_values.iter().copied().fold(0.0f32, f32::max);
+        if m > self.global_max {
+            self.global_max = m;
+        }
+        self.frames.push(bar_values);
+        if let Some(centroids) = self.centroids.as_mut() {
+            centroids.push(spectral_centroid(&magnitudes, self.sample_rate, self.fft_size));
+        }
+    }
+
+    /// Finish the stream. Input shorter than one FFT window yields a single all-zero frame.
+    pub fn finish(mut self) -> SpectrumAnalysis {
+        if self.frames.is_empty() {
+            self.frames.push(vec![0.0; self.gains.len()]);
+            if let Some(centroids) = self.centroids.as_mut() {
+                centroids.push(0.0);
+            }
+        }
+        SpectrumAnalysis {
+            frames: self.frames,
+            global_max: self.global_max,
+            centroids: self.centroids.unwrap_or_default(),
+        }
+    }
+}
+
 /// Compute spectrum for all frames and return the global max for normalization.
 /// Returns (frame_spectrums, global_max). Each frame has `bars` f32 values; normalization is done by the caller.
+/// Batch form of `SpectrumStream` for callers that already hold all samples.
 pub fn compute_all_spectrums(
     samples: &[f32],
     sample_rate: u32,
-    fps: u32,
     fft_size: usize,
     overlap: f32,
     bars: usize,
+    tilt_db_per_octave: f32,
 ) -> (Vec<Vec<f32>>, f32) {
-    let hop = (fft_size as f32 * (1.0 - overlap)).max(1.0) as usize;
-    let num_frames = samples.len().saturating_sub(fft_size).saturating_add(hop) / hop;
-    let mut frame_spectrums = Vec::with_capacity(num_frames);
-    let mut global_max = 0.0f32;
-
-    for frame_index in 0..num_frames {
-        let bar_values = compute_spectrum_frame(
-            samples,
-            sample_rate,
-            frame_index as u32,
-            fps,
-            fft_size,
-            overlap,
-            bars,
-        );
-        let m = bar_values.iter().copied().fold(0.0f32, f32::max);
-        if m > global_max {
-            global_max = m;
+    let mut stream = SpectrumStream::new(sample_rate, fft_size, overlap, bars, tilt_db_per_octave);
+    for chunk in samples.chunks(fft_size.max(1)) {
+        stream.push(chunk);
+    }
+    let analysis = stream.finish();
+    (analysis.frames, analysis.global_max)
+}
+
+/// Named frequency bands `analyze` reports on: name, lowest and highest frequency (Hz).
+pub const FREQUENCY_BANDS: [(&str, f32, f32); 7] = [
+    ("sub-bass", 20.0, 60.0),
+    ("bass", 60.0, 250.0),
+    ("low-mid", 250.0, 500.0),
+    ("mid", 500.0, 2000.0),
+    ("upper-mid", 2000.0, 4000.0),
+    ("presence", 4000.0, 6000.0),
+    ("brilliance", 6000.0, 20000.0),
+];
+
+/// Share (0.0–1.0) of the energy of `samples` in each of `FREQUENCY_BANDS`, from the power spectrum summed over
+/// back-to-back `fft_size` windows. Energy outside 20 Hz–20 kHz is left out; all zeros for silence.
+pub fn band_energy_shares(samples: &[f32], sample_rate: u32, fft_size: usize) -> [f32; FREQUENCY_BANDS.len()] {
+    let mut stream = BandEnergyStream::new(sample_rate, fft_size);
+    stream.push(samples);
+    stream.shares()
+}
+
+/// `band_energy_shares` for samples that arrive in chunks, holding less than one window of them.
+pub struct BandEnergyStream {
+    sample_rate: u32,
+    fft_size: usize,
+    fft: Option<Arc<dyn Fft<f32>>>,
+    pending: Vec<f32>,
+    energy: [f64; FREQUENCY_BANDS.len()],
+}
+
+impl BandEnergyStream {
+    pub fn new(sample_rate: u32, fft_size: usize) -> Self {
+        let fft = (fft_size > 0).then(|| FftPlanner::new().plan_fft_forward(fft_size));
+        let (pending, energy) = (Vec::with_capacity(fft_size), [0.0; FREQUENCY_BANDS.len()]);
+        Self { sample_rate, fft_size, fft, pending, energy }
+    }
+
+    /// Append decoded samples, adding the energy of every window that is now complete.
+    pub fn push(&mut self, mut samples: &[f32]) {
+        let Some(ref fft) = self.fft else { return };
+        while !samples.is_empty() {
+            let take = (self.fft_size - self.pending.len()).min(samples.len());
+            self.pending.extend_from_slice(&samples[..take]);
+            samples = &samples[take..];
+            if self.pending.len() < self.fft_size {
+                break;
+            }
+            for (bin, magnitude) in windowed_magnitudes(fft.as_ref(), &self.pending).into_iter().enumerate().skip(1) {
+                let f = bin as f32 * self.sample_rate as f32 / self.fft_size as f32;
+                if let Some(band) = FREQUENCY_BANDS.iter().position(|&(_, low, high)| (low..high).contains(&f)) {
+                    self.energy[band] += magnitude as f64 * magnitude as f64;
+                }
+            }
+            self.pending.clear();
         }
-        frame_spectrums.push(bar_values);
     }
 
-    (frame_spectrums, global_max)
+    /// The shares of the whole windows pushed; a last partial window is left out.
+    pub fn shares(&self) -> [f32; FREQUENCY_BANDS.len()] {
+        let total: f64 = self.energy.iter().sum();
+        self.energy.map(|e| if total > 0.0 { (e / total) as f32 } else { 0.0 })
+    }
+}
+
+/// Normalize raw bar values by `norm` into 0.0–1.0, then apply power-law shaping `x^gamma`.
+/// NaN values map to 0.0.
+/// gamma < 1.0 boosts small values (livelier display); gamma > 1.0 suppresses them (only peaks stand out).
+pub fn normalize_bars(values: &[f32], norm: f32, gamma: f32) -> Vec<f32> {
+    values
+        .iter()
+        .map(|&v| {
+            let x = (v / norm).clamp(0.0, 1.0);
+            if x.is_nan() {
+                return 0.0;
+            }
+            if gamma == 1.0 { x } else { x.powf(gamma) }
+        })
+        .collect()
+}
+
+/// Smooth bar values across neighboring bars with a kernel of the given `radius` (0 = unchanged).
+/// Near the edges the kernel is truncated and renormalized so the ends do not sag.
+pub fn smooth_bars(values: &[f32], radius: usize, kernel: SmoothingKernel) -> Vec<f32> {
+    if radius == 0 || values.len() < 2 {
+        return values.to_vec();
+    }
+    let sigma = radius as f32 / 2.0;
+    let weights: Vec<f32> = (0..=radius)
+        .map(|d| match kernel {
+            SmoothingKernel::Box => 1.0,
+            SmoothingKernel::Gaussian => (-((d * d) as f32) / (2.0 * sigma * sigma)).exp(),
+        })
+        .collect();
+    let n = values.len();
+    (0..n)
+        .map(|i| {
+            let lo = i.saturating_sub(radius);
+            let hi = (i + radius).min(n - 1);
+            let (sum, weight) = (lo..=hi).fold((0.0f32, 0.0f32), |(s, w), j| {
+                let k = weights[i.abs_diff(j)];
+                (s + values[j] * k, w + k)
+            });
+            sum / weight
+        })
+        .collect()
+}
+
+/// Bar heights (0.0–1.0) of analysis frame `index` as they are drawn: normalized by `norm` with `config.amp_gamma`,
+/// then smoothed across bars as `config.bar_smoothing` asks. An index past the end gives `config.bars` zeros.
+pub fn bar_heights(frames: &[Vec<f32>], norm: f32, index: usize, config: &Config) -> Vec<f32> {
+    match frames.get(index) {
+        Some(frame) => smooth_bars(
+            &normalize_bars(frame, norm, config.amp_gamma),
+            config.bar_smoothing,
+            config.bar_smoothing_kernel,
+        ),
+        None => vec![0.0; config.bars],
+    }
+}
+
+/// The analysis frame shown at video frame `frame` when `spectra` analysis frames cover `frames` video frames.
+pub fn spectrum_index(frame: usize, spectra: usize, frames: usize) -> usize {
+    if spectra == 0 { 0 } else { (frame * spectra / frames.max(1)).min(spectra - 1) }
+}
+
+/// Bar values (low to high frequency) laid out twice, mirrored around the center: `2 * values.len()` bars.
+#[cfg(feature = "runner")]
+pub fn mirror_bars(values: &[f32], symmetry: Symmetry) -> Vec<f32> {
+    let (left, right): (Vec<f32>, Vec<f32>) = match symmetry {
+        Symmetry::LowCenter => (values.iter().rev().copied().collect(), values.to_vec()),
+        Symmetry::HighCenter => (values.to_vec(), values.iter().rev().copied().collect()),
+    };
+    [left, right].concat()
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        aggregate_bins_to_bars_log, compute_all_spectrums, compute_spectrum_frame, hann_window,
+        aggregate_bins_to_bars_log, band_energy_shares, bar_heights, bars_from_magnitudes, compute_all_spectrums,
+        frequency_to_position, hann_window, hop_size, normalize_bars, position_to_frequency, smooth_bars,
+        spectral_centroid, spectrum_index, tilt_gains, windowed_magnitudes, BandEnergyStream, SpectrumStream,
     };
+    use rustfft::FftPlanner;
+    use crate::config::{Config, SmoothingKernel};
+
+    /// Reference for `SpectrumStream`: analysis frame `frame_index` computed on its own from all the samples, zeros
+    /// when it runs past the end.
+    fn spectrum_frame(
+        samples: &[f32],
+        sample_rate: u32,
+        frame_index: usize,
+        fft_size: usize,
+        overlap: f32,
+        bars: usize,
+        tilt_db_per_octave: f32,
+    ) -> Vec<f32> {
+        let start = frame_index * hop_size(fft_size, overlap);
+        if start + fft_size > samples.len() {
+            return vec![0.0; bars];
+        }
+        let fft = FftPlanner::new().plan_fft_forward(fft_size);
+        let magnitudes = windowed_magnitudes(fft.as_ref(), &samples[start..start + fft_size]);
+        let gains = tilt_gains(sample_rate, fft_size, bars, tilt_db_per_octave);
+        bars_from_magnitudes(sample_rate, fft_size, &magnitudes, &gains)
+    }
+
+    fn centroids(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+        let mut stream = SpectrumStream::new(sample_rate, 2048, 0.5, 16, 0.0).with_centroids();
+        stream.push(samples);
+        stream.finish().centroids
+    }
+
+    #[test]
+    fn band_energy_goes_to_the_tone_band() {
+        let tone = |freq: f32| -> Vec<f32> {
+            (0..44100).map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / 44100.0).sin()).collect()
+        };
+        let shares = band_energy_shares(&tone(100.0), 44100, 2048);
+        assert!(shares[1] > 0.95, "bass share = {}", shares[1]);
+        let shares = band_energy_shares(&tone(1000.0), 44100, 2048);
+        assert!(shares[3] > 0.95, "mid share = {}", shares[3]);
+        assert!((shares.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert_eq!(band_energy_shares(&[0.0; 4096], 44100, 2048), [0.0; 7]);
+        let mut stream = BandEnergyStream::new(44100, 2048);
+        tone(1000.0).chunks(1000).for_each(|chunk| stream.push(chunk));
+        assert_eq!(stream.shares(), shares);
+    }
 
     #[test]
     fn hann_window_range() {
@@ -163,25 +480,25 @@ mod tests {
     }
 
     #[test]
-    fn compute_spectrum_frame_insufficient_samples_returns_zeros() {
+    fn compute_all_spectrums_insufficient_samples_returns_one_zero_frame() {
         let samples = vec![0.1f32; 100];
-        let out = compute_spectrum_frame(&samples, 44100, 0, 30, 2048, 0.5, 64);
-        assert_eq!(out.len(), 64);
-        assert!(out.iter().all(|&x| x == 0.0));
+        let (frames, _) = compute_all_spectrums(&samples, 44100, 2048, 0.5, 64, 0.0);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), 64);
+        assert!(frames[0].iter().all(|&x| x == 0.0));
     }
 
     #[test]
-    fn compute_spectrum_frame_enough_samples_returns_bars_len() {
+    fn compute_all_spectrums_enough_samples_returns_bars_len() {
         let samples: Vec<f32> = (0..4096).map(|i| 0.001 * (i as f32).sin()).collect();
-        let out = compute_spectrum_frame(&samples, 44100, 0, 30, 2048, 0.5, 32);
-        assert_eq!(out.len(), 32);
+        let (frames, _) = compute_all_spectrums(&samples, 44100, 2048, 0.5, 32, 0.0);
+        assert!(frames.iter().all(|frame| frame.len() == 32));
     }
 
     #[test]
     fn compute_all_spectrums_frame_count_and_global_max() {
         let samples: Vec<f32> = (0..8192).map(|i| 0.01 * (i as f32 * 0.1).sin()).collect();
-        let (frames, global_max) =
-            compute_all_spectrums(&samples, 44100, 30, 2048, 0.5, 16);
+        let (frames, global_max) = compute_all_spectrums(&samples, 44100, 2048, 0.5, 16, 0.0);
         let hop = (2048_f32 * 0.5) as usize;
         let expected_frames = (8192usize.saturating_sub(2048).saturating_add(hop)) / hop;
         assert_eq!(frames.len(), expected_frames);
@@ -193,4 +510,262 @@ mod tests {
             assert!(global_max.is_finite());
         }
     }
+
+    #[test]
+    fn frequency_to_position_endpoints() {
+        assert_eq!(frequency_to_position(44100.0 / 2048.0, 44100, 2048), 0.0);
+        assert!((frequency_to_position(22050.0, 44100, 2048) - 1.0).abs() < 1e-6);
+        assert_eq!(frequency_to_position(0.0, 44100, 2048), 0.0);
+        assert_eq!(frequency_to_position(100000.0, 44100, 2048), 1.0);
+    }
+
+    #[test]
+    fn spectral_centroid_single_bin() {
+        let mut mags = vec![0.0f32; 1025];
+        mags[100] = 1.0;
+        let c = spectral_centroid(&mags, 44100, 2048);
+        assert!((c - 100.0 * 44100.0 / 2048.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spectral_centroid_silence_is_zero() {
+        let mags = vec![0.0f32; 1025];
+        assert_eq!(spectral_centroid(&mags, 44100, 2048), 0.0);
+    }
+
+    #[test]
+    fn spectrum_stream_centroids_higher_for_higher_tone() {
+        let sr = 44100u32;
+        let tone = |freq: f64| -> Vec<f32> {
+            (0..8192)
+                .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / sr as f64).sin() as f32)
+                .collect()
+        };
+        let low = centroids(&tone(300.0), sr);
+        let high = centroids(&tone(6000.0), sr);
+        let (frames, _) = compute_all_spectrums(&tone(300.0), sr, 2048, 0.5, 16, 0.0);
+        assert_eq!(low.len(), frames.len());
+        for (l, h) in low.iter().zip(high.iter()) {
+            assert!(*l > 0.0 && l < h, "centroid low {} should be below high {}", l, h);
+        }
+    }
+
+    #[test]
+    fn spectrum_stream_centroids_silence_is_zero() {
+        let samples = vec![0.0f32; 4096];
+        let centroids = centroids(&samples, 44100);
+        assert!(!centroids.is_empty());
+        assert!(centroids.iter().all(|&c| c == 0.0));
+    }
+
+    #[test]
+    fn normalize_bars_gamma_one_is_linear() {
+        let out = normalize_bars(&[0.0, 1.0, 2.0, 4.0], 2.0, 1.0);
+        assert_eq!(out, vec![0.0, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn normalize_bars_gamma_below_one_boosts_small_values() {
+        let out = normalize_bars(&[0.25], 1.0, 0.5);
+        assert!((out[0] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn normalize_bars_gamma_above_one_suppresses_small_values() {
+        let out = normalize_bars(&[0.5, 1.0], 1.0, 2.0);
+        assert!((out[0] - 0.25).abs() < 1e-6);
+        assert_eq!(out[1], 1.0);
+    }
+
+    #[test]
+    fn position_to_frequency_inverts_frequency_to_position() {
+        for f in [50.0f32, 440.0, 1000.0, 8000.0] {
+            let t = frequency_to_position(f, 44100, 2048);
+            let back = position_to_frequency(t, 44100, 2048);
+            assert!((back - f).abs() / f < 1e-3, "{} -> {} -> {}", f, t, back);
+        }
+    }
+
+    #[test]
+    fn tilt_gains_zero_is_unity() {
+        let g = tilt_gains(44100, 2048, 16, 0.0);
+        assert_eq!(g, vec![1.0; 16]);
+    }
+
+    #[test]
+    fn tilt_gains_positive_boosts_high_bars() {
+        let g = tilt_gains(44100, 2048, 32, 3.0);
+        assert!(g[0] < 1.0);
+        assert!(g[31] > 1.0);
+        assert!(g.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn tilt_gains_octave_above_reference() {
+        // Bar whose center is ~2 kHz should get ~+3 dB with 3 dB/octave.
+        let bars = 64;
+        let g = tilt_gains(44100, 2048, bars, 3.0);
+        let ix = (0..bars)
+            .min_by(|&a, &b| {
+                let fa = position_to_frequency((a as f32 + 0.5) / bars as f32, 44100, 2048);
+                let fb = position_to_frequency((b as f32 + 0.5) / bars as f32, 44100, 2048);
+                (fa - 2000.0).abs().partial_cmp(&(fb - 2000.0).abs()).unwrap()
+            })
+            .unwrap();
+        let f = position_to_frequency((ix as f32 + 0.5) / bars as f32, 44100, 2048);
+        let expected_db = 3.0 * (f / 1000.0).log2();
+        let got_db = 20.0 * g[ix].log10();
+        assert!((got_db - expected_db).abs() < 1e-3);
+    }
+
+    #[cfg(feature = "runner")]
+    #[test]
+    fn mirror_bars_puts_chosen_end_in_the_middle() {
+        use super::mirror_bars;
+        use crate::config::Symmetry;
+
+        assert_eq!(mirror_bars(&[1.0, 2.0, 3.0], Symmetry::LowCenter), vec![3.0, 2.0, 1.0, 1.0, 2.0, 3.0]);
+        assert_eq!(mirror_bars(&[1.0, 2.0, 3.0], Symmetry::HighCenter), vec![1.0, 2.0, 3.0, 3.0, 2.0, 1.0]);
+        assert!(mirror_bars(&[], Symmetry::LowCenter).is_empty());
+    }
+
+    #[test]
+    fn bar_heights_are_normalized_and_smoothed_and_frames_map_onto_spectra() {
+        let config =
+            Config { bars: 3, bar_smoothing: 1, bar_smoothing_kernel: SmoothingKernel::Box, ..Default::default() };
+        let frames = vec![vec![0.0, 6.0, 0.0]];
+        assert_eq!(bar_heights(&frames, 2.0, 0, &config), vec![0.5, 1.0 / 3.0, 0.5]);
+        assert_eq!(bar_heights(&frames, 2.0, 1, &config), vec![0.0; 3]);
+        assert_eq!((0..4).map(|frame| spectrum_index(frame, 2, 4)).collect::<Vec<_>>(), vec![0, 0, 1, 1]);
+        assert_eq!(spectrum_index(9, 2, 4), 1);
+        assert_eq!(spectrum_index(3, 0, 4), 0);
+    }
+
+    #[test]
+    fn smooth_bars_radius_zero_is_identity() {
+        let v = vec![0.0, 1.0, 0.0, 0.5];
+        assert_eq!(smooth_bars(&v, 0, SmoothingKernel::Gaussian), v);
+    }
+
+    #[test]
+    fn smooth_bars_box_averages_neighbors() {
+        let v = vec![0.0, 0.0, 3.0, 0.0, 0.0];
+        let out = smooth_bars(&v, 1, SmoothingKernel::Box);
+        assert_eq!(out, vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn smooth_bars_preserves_constant_signal() {
+        let v = vec![0.5f32; 10];
+        for kernel in [SmoothingKernel::Box, SmoothingKernel::Gaussian] {
+            let out = smooth_bars(&v, 3, kernel);
+            assert!(out.iter().all(|&x| (x - 0.5).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn smooth_bars_gaussian_spreads_spike() {
+        let mut v = vec![0.0f32; 9];
+        v[4] = 1.0;
+        let out = smooth_bars(&v, 2, SmoothingKernel::Gaussian);
+        assert!(out[4] < 1.0);
+        assert!(out[3] > 0.0 && out[5] > 0.0);
+        assert!(out[3] < out[4] && out[2] < out[3]);
+        assert_eq!(out[0], 0.0);
+    }
+
+    #[test]
+    fn nan_samples_stay_finite() {
+        let mut samples: Vec<f32> = (0..4096).map(|i| 0.1 * (i as f32 * 0.05).sin()).collect();
+        samples[100] = f32::NAN;
+        samples[200] = f32::INFINITY;
+        samples[300] = f32::NEG_INFINITY;
+        let out = &compute_all_spectrums(&samples, 44100, 2048, 0.5, 32, 0.0).0[0];
+        assert!(out.iter().all(|x| x.is_finite()));
+        assert!(out.iter().any(|&x| x > 0.0), "corrupt samples should not blank the frame");
+    }
+
+    #[test]
+    fn compute_all_spectrums_nan_input_keeps_global_max_finite() {
+        let mut samples: Vec<f32> = (0..8192).map(|i| 0.1 * (i as f32 * 0.05).sin()).collect();
+        for s in samples.iter_mut().skip(1000).take(500) {
+            *s = f32::NAN;
+        }
+        let (frames, global_max) = compute_all_spectrums(&samples, 44100, 2048, 0.5, 16, 0.0);
+        assert!(global_max.is_finite() && global_max > 0.0);
+        for f in &frames {
+            assert!(f.iter().all(|x| x.is_finite()));
+        }
+        let heights = normalize_bars(&frames[0], global_max, 1.0);
+        assert!(heights.iter().all(|&h| (0.0..=1.0).contains(&h)));
+    }
+
+    #[test]
+    fn spectrum_stream_centroids_nan_input_stays_finite() {
+        let mut samples: Vec<f32> = (0..4096).map(|i| 0.1 * (i as f32 * 0.05).sin()).collect();
+        samples[10] = f32::NAN;
+        let centroids = centroids(&samples, 44100);
+        assert!(centroids.iter().all(|c| c.is_finite()));
+    }
+
+    #[test]
+    fn normalize_bars_nan_maps_to_zero() {
+        let out = normalize_bars(&[f32::NAN, 1.0], 1.0, 0.5);
+        assert_eq!(out, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn spectrum_stream_matches_per_frame_computation() {
+        let samples: Vec<f32> = (0..10000).map(|i| 0.2 * (i as f32 * 0.03).sin() + 0.1 * (i as f32 * 0.4).cos()).collect();
+        let mut stream = SpectrumStream::new(44100, 1024, 0.5, 24, 2.0);
+        // Uneven chunk sizes, like decoded packets.
+        let mut rest = &samples[..];
+        for size in [1usize, 700, 1152, 3, 4000].iter().cycle() {
+            if rest.is_empty() {
+                break;
+            }
+            let n = (*size).min(rest.len());
+            stream.push(&rest[..n]);
+            rest = &rest[n..];
+        }
+        let analysis = stream.finish();
+        let hop = 512;
+        let expected_frames = (10000 - 1024 + hop) / hop;
+        assert_eq!(analysis.frames.len(), expected_frames);
+        for (ix, frame) in analysis.frames.iter().enumerate() {
+            let expected = spectrum_frame(&samples, 44100, ix, 1024, 0.5, 24, 2.0);
+            for (a, b) in frame.iter().zip(expected.iter()) {
+                assert!((a - b).abs() < 1e-4, "frame {}: {} vs {}", ix, a, b);
+            }
+        }
+        assert!(analysis.centroids.is_empty());
+    }
+
+    #[test]
+    fn spectrum_stream_hop_larger_than_window_skips_samples() {
+        let samples: Vec<f32> = (0..5000).map(|i| (i as f32 * 0.01).sin()).collect();
+        // overlap -1.0 => hop = 2 * fft_size
+        let mut stream = SpectrumStream::new(8000, 256, -1.0, 8, 0.0);
+        for chunk in samples.chunks(100) {
+            stream.push(chunk);
+        }
+        let analysis = stream.finish();
+        assert_eq!(analysis.frames.len(), (5000 - 256 + 512) / 512);
+        for (ix, frame) in analysis.frames.iter().enumerate() {
+            let expected = spectrum_frame(&samples, 8000, ix, 256, -1.0, 8, 0.0);
+            for (a, b) in frame.iter().zip(expected.iter()) {
+                assert!((a - b).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn spectrum_stream_short_input_yields_one_zero_frame() {
+        let mut stream = SpectrumStream::new(44100, 2048, 0.5, 8, 0.0).with_centroids();
+        stream.push(&[0.5; 100]);
+        let analysis = stream.finish();
+        assert_eq!(analysis.frames, vec![vec![0.0; 8]]);
+        assert_eq!(analysis.centroids, vec![0.0]);
+        assert_eq!(analysis.global_max, 0.0);
+    }
 }
@@ -0,0 +1,202 @@
+//! Timed text cues from SRT subtitles or LRC lyrics
+
+use std::path::Path;
+
+use crate::error::SpectrumError;
+
+/// One piece of text shown from `start` (inclusive) to `end` (exclusive), in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cue {
+    pub start: f32,
+    pub end: f32,
+    pub text: String,
+}
+
+/// Read cues from `path`: LRC when the extension is `.lrc`, SRT otherwise.
+/// LRC lines have no end time, so each one lasts until the next line (the last one until `duration_sec`).
+pub fn load_subtitles(path: &Path, duration_sec: f32) -> Result<Vec<Cue>, SpectrumError> {
+    let failed = |source| SpectrumError::AssetLoad { what: "subtitles", path: path.to_path_buf(), source };
+    let content = std::fs::read_to_string(path).map_err(|e| failed(e.into()))?;
+    let content = content.trim_start_matches('\u{feff}');
+    let is_lrc = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("lrc"));
+    let cues = if is_lrc {
+        parse_lrc(content, duration_sec)
+    } else {
+        parse_srt(content)
+    };
+    cues.map_err(|e| failed(e.into()))
+}
+
+/// Parse SRT: blank-line separated blocks of an index, a `HH:MM:SS,mmm --> HH:MM:SS,mmm` line and text lines.
+/// Formatting tags such as `<i>` are removed.
+pub fn parse_srt(content: &str) -> Result<Vec<Cue>, String> {
+    let mut cues = Vec::new();
+    let mut lines = content.lines().map(str::trim_end).enumerate().peekable();
+    while let Some((n, line)) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // The numeric index line is optional in practice; accept blocks that start with the timing line.
+        let (n, timing) = if line.contains("-->") {
+            (n, line)
+        } else {
+            lines.next().ok_or_else(|| format!("line {}: missing timing line", n + 1))?
+        };
+        let (start, end) = timing
+            .split_once("-->")
+            .ok_or_else(|| format!("line {}: expected START --> END, got {:?}", n + 1, timing))?;
+        // Position hints after the end time (e.g. "X1:..") are ignored.
+        let end = end.split_whitespace().next().unwrap_or("");
+        let start = parse_srt_time(start.trim()).ok_or_else(|| format!("line {}: invalid time {:?}", n + 1, start))?;
+        let end = parse_srt_time(end).ok_or_else(|| format!("line {}: invalid time {:?}", n + 1, end))?;
+        let mut text = Vec::new();
+        while let Some((_, l)) = lines.next_if(|(_, l)| !l.trim().is_empty()) {
+            text.push(strip_tags(l.trim()));
+        }
+        cues.push(Cue { start, end, text: text.join("\n") });
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(cues)
+}
+
+/// Parse LRC: lines of one or more `[mm:ss.xx]` tags followed by the lyric. Metadata tags (`[ar:...]`) are skipped.
+/// Each cue ends where the next one starts; the last ends at `duration_sec`. Empty lyrics only end the previous cue.
+pub fn parse_lrc(content: &str, duration_sec: f32) -> Result<Vec<Cue>, String> {
+    let mut stamped: Vec<(f32, String)> = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+        while let Some(tag_end) = rest.strip_prefix('[').and_then(|r| r.find(']')) {
+            let tag = &rest[1..tag_end + 1];
+            rest = rest[tag_end + 2..].trim_start();
+            match parse_lrc_time(tag) {
+                Some(t) => times.push(t),
+                None if tag.split_once(':').is_some_and(|(k, _)| k.chars().all(|c| c.is_ascii_alphabetic())) => {}
+                None => return Err(format!("line {}: invalid time tag [{}]", n + 1, tag)),
+            }
+        }
+        for t in times {
+            stamped.push((t, rest.to_string()));
+        }
+    }
+    stamped.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut cues = Vec::new();
+    for (i, (start, text)) in stamped.iter().enumerate() {
+        if text.is_empty() {
+            continue;
+        }
+        let end = stamped.get(i + 1).map_or(duration_sec, |(t, _)| *t);
+        cues.push(Cue { start: *start, end: end.max(*start), text: text.clone() });
+    }
+    Ok(cues)
+}
+
+/// Index of the cue showing at `t` seconds. Later cues win when cues overlap.
+pub fn active_cue(cues: &[Cue], t: f32) -> Option<usize> {
+    cues.iter().rposition(|c| c.start <= t && t < c.end)
+}
+
+/// `HH:MM:SS,mmm` (a `.` decimal separator is also accepted).
+fn parse_srt_time(s: &str) -> Option<f32> {
+    let mut parts = s.splitn(3, ':');
+    let h: u32 = parts.next()?.trim().parse().ok()?;
+    let m: u32 = parts.next()?.trim().parse().ok()?;
+    let sec: f32 = parts.next()?.trim().replace(',', ".").parse().ok()?;
+    Some(h as f32 * 3600.0 + m as f32 * 60.0 + sec)
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx` (hundredths).
+fn parse_lrc_time(s: &str) -> Option<f32> {
+    let (m, rest) = s.split_once(':')?;
+    let m: u32 = m.trim().parse().ok()?;
+    let sec: f32 = match rest.split_once(':') {
+        Some((sec, hundredths)) => format!("{}.{}", sec, hundredths).parse().ok()?,
+        None => rest.trim().parse().ok()?,
+    };
+    Some(m as f32 * 60.0 + sec)
+}
+
+/// Remove `<...>` markup (e.g. `<i>`, `<font color=...>`).
+fn strip_tags(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{active_cue, parse_lrc, parse_lrc_time, parse_srt, parse_srt_time, Cue};
+
+    #[test]
+    fn parse_srt_reads_blocks() {
+        let srt = "1\r\n00:00:01,500 --> 00:00:03,000\r\nHello\r\n<i>world</i>\r\n\r\n2\r\n00:01:00,000 --> 00:01:02,250\r\nBye\r\n";
+        let cues = parse_srt(srt).unwrap();
+        assert_eq!(
+            cues,
+            vec![
+                Cue { start: 1.5, end: 3.0, text: "Hello\nworld".to_string() },
+                Cue { start: 60.0, end: 62.25, text: "Bye".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_srt_reports_bad_timing() {
+        let err = parse_srt("1\n00:00:01,000 -> 00:00:02,000\nHi\n").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn srt_times() {
+        assert_eq!(parse_srt_time("01:02:03,500"), Some(3723.5));
+        assert_eq!(parse_srt_time("00:00:00.250"), Some(0.25));
+        assert_eq!(parse_srt_time("1:2"), None);
+    }
+
+    #[test]
+    fn parse_lrc_chains_end_times_and_skips_metadata() {
+        let lrc = "[ar:Someone]\n[ti:Song]\n[00:01.00]First\n[00:04.50][00:10.00]Chorus\n[00:06.00]\n";
+        let cues = parse_lrc(lrc, 12.0).unwrap();
+        assert_eq!(
+            cues,
+            vec![
+                Cue { start: 1.0, end: 4.5, text: "First".to_string() },
+                Cue { start: 4.5, end: 6.0, text: "Chorus".to_string() },
+                Cue { start: 10.0, end: 12.0, text: "Chorus".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_lrc_rejects_malformed_time() {
+        assert!(parse_lrc("[0x:01.00]Oops\n", 5.0).unwrap_err().contains("line 1"));
+    }
+
+    #[test]
+    fn lrc_times() {
+        assert_eq!(parse_lrc_time("01:02.50"), Some(62.5));
+        assert_eq!(parse_lrc_time("01:02:50"), Some(62.5));
+        assert_eq!(parse_lrc_time("01:02"), Some(62.0));
+        assert_eq!(parse_lrc_time("ar:Someone"), None);
+    }
+
+    #[test]
+    fn active_cue_finds_cue_at_time() {
+        let cues = vec![
+            Cue { start: 1.0, end: 2.0, text: "a".to_string() },
+            Cue { start: 3.0, end: 4.0, text: "b".to_string() },
+        ];
+        assert_eq!(active_cue(&cues, 0.5), None);
+        assert_eq!(active_cue(&cues, 1.0), Some(0));
+        assert_eq!(active_cue(&cues, 2.0), None);
+        assert_eq!(active_cue(&cues, 3.5), Some(1));
+    }
+}
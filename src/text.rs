@@ -0,0 +1,213 @@
+//! Text rendering with TrueType/OpenType fonts (ab_glyph)
+
+#[cfg(feature = "runner")]
+use std::path::Path;
+
+#[cfg(feature = "runner")]
+use crate::error::SpectrumError;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{ImageBuffer, Rgba};
+
+/// Fonts tried in order when no font path is given.
+#[cfg(feature = "runner")]
+const FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Load the font at `path`, or the first available system font from `FALLBACK_FONTS` when None.
+/// For font collections (.ttc), the first face is used.
+#[cfg(feature = "runner")]
+pub fn load_font(path: Option<&Path>) -> Result<FontVec, SpectrumError> {
+    let path = match path {
+        Some(p) => p.to_path_buf(),
+        None => FALLBACK_FONTS
+            .iter()
+            .map(Path::new)
+            .find(|p| p.is_file())
+            .ok_or_else(|| SpectrumError::InvalidConfig("no system font found; pass one with --font".into()))?
+            .to_path_buf(),
+    };
+    let failed = |source| SpectrumError::AssetLoad { what: "font", path: path.clone(), source };
+    let data = std::fs::read(&path).map_err(|e| failed(e.into()))?;
+    FontVec::try_from_vec_and_index(data, 0).map_err(|e| failed(format!("not a font ({})", e).into()))
+}
+
+/// One line of text at a pixel size.
+pub struct TextLine<'a> {
+    pub text: &'a str,
+    pub size: f32,
+}
+
+/// Advance width of `text` at pixel `size`, including kerning.
+pub fn text_width(font: &FontVec, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut prev = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(p) = prev {
+            width += scaled.kern(p, id);
+        }
+        width += scaled.h_advance(id);
+        prev = Some(id);
+    }
+    width
+}
+
+/// Split `text` into lines no wider than `max_width` at pixel `size`, breaking at spaces.
+/// Existing line breaks are kept; a single word wider than `max_width` gets a line of its own.
+#[cfg(feature = "runner")]
+pub fn wrap_text(font: &FontVec, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if !line.is_empty() && text_width(font, size, &candidate) > max_width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Render `lines` top to bottom into a transparent image just large enough to hold them.
+/// Lines are aligned within the block by `align` (0.0 = left, 0.5 = centered, 1.0 = right).
+pub fn render_text_block(
+    font: &FontVec,
+    lines: &[TextLine],
+    color: [u8; 4],
+    align: f32,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let widths: Vec<f32> = lines.iter().map(|l| text_width(font, l.size, l.text)).collect();
+    let block_w = widths.iter().cloned().fold(0.0f32, f32::max).ceil() as u32;
+    let block_h = lines
+        .iter()
+        .map(|l| font.as_scaled(PxScale::from(l.size)).height())
+        .sum::<f32>()
+        .ceil() as u32;
+    let mut img = ImageBuffer::new(block_w.max(1), block_h.max(1));
+
+    let mut y = 0.0;
+    for (line, width) in lines.iter().zip(widths) {
+        let scaled = font.as_scaled(PxScale::from(line.size));
+        let x = (block_w as f32 - width) * align;
+        draw_line(&mut img, font, line, (x, y + scaled.ascent()), color);
+        y += scaled.height();
+    }
+    img
+}
+
+/// Rasterize one line with its baseline starting at `origin`, writing coverage into the alpha channel.
+fn draw_line(
+    img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    font: &FontVec,
+    line: &TextLine,
+    origin: (f32, f32),
+    color: [u8; 4],
+) {
+    let scale = PxScale::from(line.size);
+    let scaled = font.as_scaled(scale);
+    let (width, height) = img.dimensions();
+    let mut x = origin.0;
+    let mut prev = None;
+    for c in line.text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(p) = prev {
+            x += scaled.kern(p, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(x, origin.1));
+        x += scaled.h_advance(id);
+        prev = Some(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px as u32 >= width || py as u32 >= height {
+                return;
+            }
+            let alpha = (coverage.clamp(0.0, 1.0) * color[3] as f32).round() as u8;
+            let existing = img.get_pixel(px as u32, py as u32).0[3];
+            if alpha > existing {
+                img.put_pixel(px as u32, py as u32, Rgba([color[0], color[1], color[2], alpha]));
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "runner"))]
+mod tests {
+    use super::{load_font, render_text_block, text_width, wrap_text, SpectrumError, TextLine};
+    use std::path::Path;
+
+    #[test]
+    fn load_font_reports_missing_file() {
+        let err = load_font(Some(Path::new("/nonexistent/font.ttf"))).unwrap_err();
+        assert!(matches!(err, SpectrumError::AssetLoad { what: "font", .. }), "{}", err);
+    }
+
+    #[test]
+    fn load_font_rejects_non_font_data() {
+        let err = load_font(Some(Path::new("Cargo.toml"))).unwrap_err();
+        assert!(err.to_string().contains("not a font"), "{}", err);
+    }
+
+    #[test]
+    fn text_width_grows_with_text_and_size() {
+        // Rendering needs a real font; skip where no system font is installed.
+        let Ok(font) = load_font(None) else {
+            return;
+        };
+        assert_eq!(text_width(&font, 20.0, ""), 0.0);
+        let short = text_width(&font, 20.0, "ab");
+        let long = text_width(&font, 20.0, "abcd");
+        assert!(long > short && short > 0.0);
+        assert!(text_width(&font, 40.0, "ab") > short);
+    }
+
+    #[test]
+    fn render_text_block_stacks_lines_and_aligns_them() {
+        let Ok(font) = load_font(None) else {
+            return;
+        };
+        let lines = [TextLine { text: "Wide title", size: 40.0 }, TextLine { text: "ab", size: 20.0 }];
+        let color = [255, 0, 0, 255];
+        let left = render_text_block(&font, &lines, color, 0.0);
+        let right = render_text_block(&font, &lines, color, 1.0);
+        assert_eq!(left.dimensions(), right.dimensions());
+        assert!(left.width() as f32 >= text_width(&font, 40.0, "Wide title"));
+        assert!(left.pixels().any(|p| p.0 == [255, 0, 0, 255]));
+        // The short second line sits at the left or right edge depending on alignment.
+        let (w, h) = left.dimensions();
+        let inked = |img: &image::ImageBuffer<image::Rgba<u8>, Vec<u8>>, x0: u32, x1: u32| {
+            (x0..x1).any(|x| (h * 3 / 4..h).any(|y| img.get_pixel(x, y).0[3] > 0))
+        };
+        assert!(inked(&left, 0, w / 2) && !inked(&left, w / 2, w));
+        assert!(inked(&right, w / 2, w) && !inked(&right, 0, w / 2));
+    }
+
+    #[test]
+    fn wrap_text_breaks_at_spaces_within_width() {
+        let Ok(font) = load_font(None) else {
+            return;
+        };
+        let text = "one two three four five six\nseven";
+        let max = text_width(&font, 20.0, "one two three");
+        let lines = wrap_text(&font, 20.0, text, max);
+        assert_eq!(lines, vec!["one two three", "four five six", "seven"]);
+        assert_eq!(wrap_text(&font, 20.0, "unbreakable", 1.0), vec!["unbreakable"]);
+    }
+}
@@ -0,0 +1,485 @@
+//! Declarative layout files (TOML or JSON): the frame as an ordered list of layers drawn bottom to top
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::colormap::{parse_hex_color, Colormap};
+use crate::config::{BarShape, Config, HAlign, Orientation, Style, VAlign};
+use crate::error::SpectrumError;
+use crate::overlay::OverlayPos;
+use crate::cli::parse_orientation;
+
+/// Progress bar thickness (pixels) when a progress layer gives no height.
+const DEFAULT_PROGRESS_HEIGHT: u32 = 8;
+
+/// A parsed theme file: `[[layer]]` tables (TOML) or a `"layer"` array (JSON), each with a `type`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    #[serde(default, rename = "layer")]
+    layers: Vec<LayerSpec>,
+}
+
+/// One layer as written in the file. Enum-like values use the same names as the matching command-line flags.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum LayerSpec {
+    Background(BackgroundSpec),
+    Spectrum(SpectrumSpec),
+    Text(TextSpec),
+    Logo(LogoSpec),
+    Progress(ProgressSpec),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct BackgroundSpec {
+    color: Option<String>,
+    image: Option<PathBuf>,
+    dim: Option<f32>,
+    blur: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct SpectrumSpec {
+    style: Option<String>,
+    x: Option<u32>,
+    y: Option<u32>,
+    width: Option<u32>,
+    height: Option<u32>,
+    align: Option<String>,
+    valign: Option<String>,
+    orientation: Option<String>,
+    color: Option<String>,
+    colormap: Option<String>,
+    bar_gap: Option<u32>,
+    bar_shape: Option<String>,
+    bar_radius: Option<u32>,
+    stroke_width: Option<f32>,
+    mirror_opacity: Option<f32>,
+    opacity: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct TextSpec {
+    text: String,
+    pos: Option<String>,
+    size: Option<f32>,
+    color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LogoSpec {
+    path: PathBuf,
+    pos: Option<String>,
+    scale: Option<f32>,
+    opacity: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ProgressSpec {
+    x: Option<u32>,
+    y: Option<u32>,
+    width: Option<u32>,
+    height: Option<u32>,
+    color: Option<String>,
+    track: Option<String>,
+}
+
+/// Frame background from the theme's background layer; unset fields keep the command-line values.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Background {
+    pub color: Option<[u8; 4]>,
+    pub image: Option<PathBuf>,
+    pub dim: Option<f32>,
+    pub blur: Option<f32>,
+}
+
+/// A layer drawn over the background, in file order. Unset sizes and colors fall back to the command-line values.
+/// Serialized like a theme file's layers, tagged with their `type`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Layer {
+    /// A spectrum band: the command-line configuration with the layer's overrides.
+    Spectrum(Box<Config>),
+    /// Text with `{title}`, `{artist}`, `{elapsed}` and `{remaining}` placeholders.
+    Text {
+        text: String,
+        pos: OverlayPos,
+        size: Option<f32>,
+        color: Option<[u8; 4]>,
+    },
+    Logo {
+        path: PathBuf,
+        pos: OverlayPos,
+        scale: Option<f32>,
+        opacity: Option<f32>,
+    },
+    /// Playback progress bar at `(x, y, width, height)`.
+    Progress {
+        rect: (u32, u32, u32, u32),
+        color: [u8; 4],
+        track: [u8; 4],
+    },
+}
+
+impl Theme {
+    /// Read a theme from `path`: JSON when the extension is `.json`, TOML otherwise.
+    /// Relative image paths are resolved against the theme file's directory.
+    pub fn load(path: &Path) -> Result<Self, SpectrumError> {
+        let failed = |source| SpectrumError::AssetLoad { what: "theme", path: path.to_path_buf(), source };
+        let content = std::fs::read_to_string(path).map_err(|e| failed(e.into()))?;
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let theme: Result<Self, String> = if is_json {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        };
+        let mut theme = theme.map_err(|e| failed(e.into()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for layer in &mut theme.layers {
+            match layer {
+                LayerSpec::Background(BackgroundSpec { image: Some(image), .. }) => *image = dir.join(&image),
+                LayerSpec::Logo(spec) => spec.path = dir.join(&spec.path),
+                _ => {}
+            }
+        }
+        Ok(theme)
+    }
+
+    /// The background layer, which must come first when present.
+    pub fn background(&self) -> Result<Option<Background>, String> {
+        let mut background = None;
+        for (i, layer) in self.layers.iter().enumerate() {
+            let LayerSpec::Background(spec) = layer else {
+                continue;
+            };
+            if i > 0 {
+                return Err(format!("layer {}: the background must be the first layer", i + 1));
+            }
+            background = Some(Background {
+                color: spec.color.as_deref().map(parse_hex_color).transpose().map_err(|e| at(i, e))?,
+                image: spec.image.clone(),
+                dim: spec.dim.map(|d| check_unit(d, "dim")).transpose().map_err(|e| at(i, e))?,
+                blur: spec.blur,
+            });
+        }
+        Ok(background)
+    }
+
+    /// The layers after the background, resolved against the command-line configuration `base`.
+    pub fn layers(&self, base: &Config) -> Result<Vec<Layer>, String> {
+        let mut layers = Vec::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            let resolved = match layer {
+                LayerSpec::Background(_) => continue,
+                LayerSpec::Spectrum(spec) => spectrum_layer(spec, base),
+                LayerSpec::Text(spec) => text_layer(spec),
+                LayerSpec::Logo(spec) => logo_layer(spec),
+                LayerSpec::Progress(spec) => progress_layer(spec, base),
+            };
+            layers.push(resolved.map_err(|e| at(i, e))?);
+        }
+        Ok(layers)
+    }
+}
+
+/// Whether `text` needs re-rendering every frame (it shows the playback time).
+pub fn is_timed(text: &str) -> bool {
+    text.contains("{elapsed}") || text.contains("{remaining}")
+}
+
+/// Replace the title and artist placeholders in `text`.
+pub fn fill_tags(text: &str, title: &str, artist: &str) -> String {
+    text.replace("{title}", title).replace("{artist}", artist)
+}
+
+/// Replace the playback time placeholders in `text`.
+pub fn fill_time(text: &str, elapsed: &str, remaining: &str) -> String {
+    text.replace("{elapsed}", elapsed).replace("{remaining}", remaining)
+}
+
+fn spectrum_layer(spec: &SpectrumSpec, base: &Config) -> Result<Layer, String> {
+    let mut config = base.clone();
+    if let Some(ref style) = spec.style {
+        config.style = Style::from_str(style, false)?;
+    }
+    config.spectrum_x = spec.x.or(config.spectrum_x);
+    config.spectrum_y = spec.y.or(config.spectrum_y);
+    config.spectrum_width = spec.width.or(config.spectrum_width);
+    config.spectrum_height = spec.height.unwrap_or(config.spectrum_height);
+    if let Some(ref align) = spec.align {
+        config.spectrum_align = HAlign::from_str(align, false)?;
+    }
+    if let Some(ref valign) = spec.valign {
+        config.spectrum_valign = VAlign::from_str(valign, false)?;
+    }
+    if let Some(ref orientation) = spec.orientation {
+        config.orientation = parse_orientation(orientation)?;
+    }
+    if config.orientation != Orientation::Horizontal && matches!(config.style, Style::Radial | Style::Ring) {
+        return Err("orientation does not apply to style radial or ring".to_string());
+    }
+    if let Some(ref color) = spec.color {
+        config.bar_color = parse_hex_color(color)?;
+        config.bar_colormap = None;
+    }
+    if let Some(ref colormap) = spec.colormap {
+        config.bar_colormap = Some(Colormap::from_str(colormap, false)?);
+    }
+    if config.bar_colormap == Some(Colormap::Custom) && config.colormap_stops.is_empty() {
+        return Err("colormap custom requires --colormap-stops".to_string());
+    }
+    config.bar_gap = spec.bar_gap.unwrap_or(config.bar_gap);
+    if let Some(ref shape) = spec.bar_shape {
+        config.bar_shape = BarShape::from_str(shape, false)?;
+    }
+    config.bar_radius = spec.bar_radius.or(config.bar_radius);
+    config.stroke_width = spec.stroke_width.unwrap_or(config.stroke_width);
+    if let Some(opacity) = spec.mirror_opacity {
+        config.mirror_opacity = check_unit(opacity, "mirror-opacity")?;
+    }
+    if let Some(opacity) = spec.opacity {
+        config.opacity = check_unit(opacity, "opacity")?;
+    }
+    config.validate()?;
+    Ok(Layer::Spectrum(Box::new(config)))
+}
+
+fn text_layer(spec: &TextSpec) -> Result<Layer, String> {
+    Ok(Layer::Text {
+        text: spec.text.clone(),
+        pos: parse_pos(spec.pos.as_deref())?,
+        size: spec.size,
+        color: spec.color.as_deref().map(parse_hex_color).transpose()?,
+    })
+}
+
+fn logo_layer(spec: &LogoSpec) -> Result<Layer, String> {
+    Ok(Layer::Logo {
+        path: spec.path.clone(),
+        pos: parse_pos(spec.pos.as_deref())?,
+        scale: spec.scale,
+        opacity: spec.opacity.map(|o| check_unit(o, "opacity")).transpose()?,
+    })
+}
+
+/// Defaults to a full-width bar along the bottom edge in the bar color, over a transparent track.
+fn progress_layer(spec: &ProgressSpec, base: &Config) -> Result<Layer, String> {
+    let x = spec.x.unwrap_or(0).min(base.width);
+    let height = spec.height.unwrap_or(DEFAULT_PROGRESS_HEIGHT).min(base.height);
+    let y = spec.y.unwrap_or(base.height - height).min(base.height - height);
+    let width = spec.width.unwrap_or(base.width - x).min(base.width - x);
+    Ok(Layer::Progress {
+        rect: (x, y, width, height),
+        color: spec.color.as_deref().map_or(Ok(base.bar_color), parse_hex_color)?,
+        track: spec.track.as_deref().map_or(Ok([0, 0, 0, 0]), parse_hex_color)?,
+    })
+}
+
+fn parse_pos(pos: Option<&str>) -> Result<OverlayPos, String> {
+    pos.map_or(Ok(OverlayPos::default()), |p| OverlayPos::from_str(p, false))
+}
+
+fn check_unit(value: f32, name: &str) -> Result<f32, String> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{} must be between 0.0 and 1.0, got {}", name, value))
+    }
+}
+
+/// Prefix an error with the 1-based layer number.
+fn at(index: usize, e: String) -> String {
+    format!("layer {}: {}", index + 1, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_tags, fill_time, is_timed, Background, Layer, Theme};
+    use crate::config::{Config, HAlign, Orientation, Style};
+    use crate::overlay::OverlayPos;
+    use std::path::PathBuf;
+
+    fn base() -> Config {
+        Config {
+            width: 1000,
+            height: 500,
+            bar_color: [0, 0, 0, 255],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn toml_layers_resolve_in_order() {
+        let theme: Theme = toml::from_str(
+            r##"
+            [[layer]]
+            type = "background"
+            color = "#101018"
+            dim = 0.4
+
+            [[layer]]
+            type = "spectrum"
+            style = "mirror"
+            x = 40
+            width = 300
+            align = "left"
+            orientation = "rotate:-10"
+            color = "#ff6600"
+
+            [[layer]]
+            type = "text"
+            text = "{title}"
+            pos = "top-right"
+
+            [[layer]]
+            type = "progress"
+            height = 4
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            theme.background().unwrap(),
+            Some(Background { color: Some([16, 16, 24, 255]), dim: Some(0.4), ..Background::default() })
+        );
+        let layers = theme.layers(&base()).unwrap();
+        assert_eq!(layers.len(), 3);
+        let Layer::Spectrum(ref band) = layers[0] else {
+            panic!("expected a spectrum layer, got {:?}", layers[0]);
+        };
+        assert_eq!(band.style, Style::Mirror);
+        assert_eq!((band.spectrum_x, band.spectrum_width), (Some(40), Some(300)));
+        assert_eq!(band.spectrum_align, HAlign::Left);
+        assert_eq!(band.orientation, Orientation::Rotate(-10.0));
+        assert_eq!(band.bar_color, [255, 102, 0, 255]);
+        assert_eq!(
+            layers[1],
+            Layer::Text { text: "{title}".to_string(), pos: OverlayPos::TopRight, size: None, color: None }
+        );
+        assert_eq!(
+            layers[2],
+            Layer::Progress { rect: (0, 496, 1000, 4), color: [0, 0, 0, 255], track: [0, 0, 0, 0] }
+        );
+    }
+
+    #[test]
+    fn spectrum_layers_stack_over_shared_analysis() {
+        let theme: Theme = toml::from_str(
+            r##"
+            [[layer]]
+            type = "spectrum"
+            style = "spectrogram"
+            height = 500
+            opacity = 0.35
+
+            [[layer]]
+            type = "spectrum"
+            style = "bars"
+            height = 200
+            y = 100
+
+            [[layer]]
+            type = "spectrum"
+            style = "waveform"
+            height = 60
+            valign = "bottom"
+            "##,
+        )
+        .unwrap();
+        let layers = theme.layers(&base()).unwrap();
+        let bands: Vec<&Config> = layers
+            .iter()
+            .map(|layer| match layer {
+                Layer::Spectrum(band) => band.as_ref(),
+                other => panic!("expected a spectrum layer, got {:?}", other),
+            })
+            .collect();
+        let styles: Vec<Style> = bands.iter().map(|b| b.style).collect();
+        assert_eq!(styles, [Style::Spectrogram, Style::Bars, Style::Waveform]);
+        assert_eq!(bands.iter().map(|b| b.opacity).collect::<Vec<_>>(), [0.35, 1.0, 1.0]);
+        assert_eq!(bands[0].spectrum_height, 500);
+        // Every band analyzes the same bars.
+        assert!(bands.iter().all(|b| b.bars == base().bars && b.fft_size == base().fft_size));
+
+        let theme: Theme = toml::from_str("[[layer]]\ntype = \"spectrum\"\nopacity = 1.5\n").unwrap();
+        assert!(theme.layers(&base()).unwrap_err().contains("opacity must be between"));
+    }
+
+    #[test]
+    fn json_layers_parse() {
+        let theme: Theme =
+            serde_json::from_str(r#"{"layer": [{"type": "logo", "path": "logo.png", "pos": "bottom-left"}]}"#)
+                .unwrap();
+        assert_eq!(theme.background().unwrap(), None);
+        assert_eq!(
+            theme.layers(&base()).unwrap(),
+            vec![Layer::Logo {
+                path: PathBuf::from("logo.png"),
+                pos: OverlayPos::BottomLeft,
+                scale: None,
+                opacity: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_layers_report_their_number() {
+        let theme: Theme =
+            toml::from_str("[[layer]]\ntype = \"text\"\ntext = \"a\"\n[[layer]]\ntype = \"background\"\n").unwrap();
+        assert!(theme.background().unwrap_err().contains("layer 2: the background must be the first layer"));
+
+        let theme: Theme =
+            toml::from_str("[[layer]]\ntype = \"spectrum\"\nstyle = \"ring\"\norientation = \"vertical\"\n").unwrap();
+        assert!(theme.layers(&base()).unwrap_err().starts_with("layer 1: orientation"));
+
+        let theme: Theme = toml::from_str("[[layer]]\ntype = \"spectrum\"\nstyle = \"sparkles\"\n").unwrap();
+        assert!(theme.layers(&base()).unwrap_err().contains("sparkles"));
+    }
+
+    #[test]
+    fn themes_and_resolved_layers_serialize_back() {
+        let source = "[[layer]]\ntype = \"text\"\ntext = \"{title}\"\npos = \"top-right\"\n";
+        let theme: Theme = toml::from_str(source).unwrap();
+        let reparsed: Theme = toml::from_str(&toml::to_string(&theme).unwrap()).unwrap();
+        assert_eq!(reparsed.layers(&base()).unwrap(), theme.layers(&base()).unwrap());
+        let layers = vec![
+            Layer::Spectrum(Box::new(Config { style: Style::Line, ..base() })),
+            Layer::Progress { rect: (0, 496, 1000, 4), color: [0, 0, 0, 255], track: [0, 0, 0, 0] },
+        ];
+        let json = serde_json::to_string(&layers).unwrap();
+        assert!(json.starts_with(r#"[{"type":"spectrum","width":1000"#), "{}", json);
+        assert_eq!(serde_json::from_str::<Vec<Layer>>(&json).unwrap(), layers);
+    }
+
+    #[test]
+    fn unknown_fields_and_types_are_rejected() {
+        assert!(toml::from_str::<Theme>("[[layer]]\ntype = \"spectrum\"\ncolour = \"#fff\"\n").is_err());
+        assert!(toml::from_str::<Theme>("[[layer]]\ntype = \"video\"\n").is_err());
+    }
+
+    #[test]
+    fn load_resolves_image_paths_against_theme_dir() {
+        let dir = std::env::temp_dir().join("audio-spectrum-generator-theme-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("theme.json");
+        std::fs::write(&path, r#"{"layer": [{"type": "background", "image": "bg.png"}]}"#).unwrap();
+        let theme = Theme::load(&path).unwrap();
+        assert_eq!(theme.background().unwrap().unwrap().image, Some(dir.join("bg.png")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(fill_tags("{title} - {artist}", "Song", "Band"), "Song - Band");
+        assert!(is_timed("{elapsed} / {remaining}") && !is_timed("{title}"));
+        assert_eq!(fill_time("{elapsed} / {remaining}", "1:05", "-2:30"), "1:05 / -2:30");
+    }
+}
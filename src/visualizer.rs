@@ -0,0 +1,116 @@
+//! `Visualizer`: anything that draws a frame from its analysis, with the built-in styles as one implementation and
+//! `--script` and `--plugin` as others
+
+use image::RgbaImage;
+
+use crate::config::Config;
+use crate::draw::{draw_spectrum_band, FrameData};
+use crate::export::FrameAnalysis;
+
+/// Draws one frame from its analysis (see `export::frame_analyses`) and `FrameData`: the frame's samples, the bars
+/// as laid out, and the history and particles built up by the frames before it. Render workers share one visualizer
+/// and call it for several frames at once, in any order, so `draw` only reads `self`; whatever carries over from
+/// frame to frame comes in `data`, advanced in order by the caller.
+pub trait Visualizer: Sync {
+    /// Draw onto `frame`, which already holds the background and any layers below.
+    fn draw(&self, frame: &mut RgbaImage, analysis: &FrameAnalysis, data: &FrameData);
+}
+
+/// The built-in styles as a `Visualizer`: draws `config.style` with `draw_spectrum_band`. The bars are
+/// `data.bar_heights`, or the analysis's when `data` has none; the sample styles (waveform, ring, vectorscope) draw
+/// `data.waveform` and `data.stereo_samples`, and the spectrogram and dot trails `data.history`.
+#[derive(Clone, Debug)]
+pub struct StyleVisualizer {
+    config: Config,
+}
+
+impl StyleVisualizer {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl Visualizer for StyleVisualizer {
+    fn draw(&self, frame: &mut RgbaImage, analysis: &FrameAnalysis, data: &FrameData) {
+        let bar_heights = if data.bar_heights.is_empty() { &analysis.bars } else { data.bar_heights };
+        draw_spectrum_band(frame, &self.config, &FrameData { bar_heights, ..*data });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StyleVisualizer, Visualizer};
+    use crate::config::{Config, Style};
+    use crate::draw::{draw_background, BarHistory, FrameData};
+    use crate::export::FrameAnalysis;
+
+    fn analysis(bars: Vec<f32>) -> FrameAnalysis {
+        FrameAnalysis { time: 0.0, bars, rms: 0.0, peak: 0.0, lufs: -100.0, beat: false }
+    }
+
+    /// Columns of `frame` holding anything but the background.
+    fn drawn_columns(frame: &image::RgbaImage, config: &Config) -> usize {
+        (0..frame.width()).filter(|&x| (0..frame.height()).any(|y| frame.get_pixel(x, y).0 != config.bg_color)).count()
+    }
+
+    #[test]
+    fn the_style_visualizer_draws_bars_and_the_history_it_is_given() {
+        let config = Config { width: 64, height: 64, bars: 4, spectrum_height: 32, ..Default::default() };
+        let bars = StyleVisualizer::new(config.clone());
+        let mut frame = draw_background(&config, None);
+        bars.draw(&mut frame, &analysis(vec![1.0; 4]), &FrameData::default());
+        assert!(frame.pixels().any(|p| p.0 == config.bar_color));
+
+        let spectrogram = Config { style: Style::Spectrogram, ..config.clone() };
+        let mut history = BarHistory::for_config(&spectrogram);
+        (0..3).for_each(|_| history.push(vec![0.5; 4]));
+        let mut frame = draw_background(&spectrogram, None);
+        let data = FrameData { history: Some(&history), ..Default::default() };
+        StyleVisualizer::new(spectrogram.clone()).draw(&mut frame, &analysis(vec![0.5; 4]), &data);
+        // One column per column of history.
+        assert_eq!(drawn_columns(&frame, &spectrogram), 3);
+    }
+
+    #[test]
+    fn sample_styles_draw_the_samples_they_are_given() {
+        let config = Config { width: 64, height: 64, bars: 4, spectrum_height: 32, ..Default::default() };
+        let wave: Vec<f32> = (0..256).map(|i| (i as f32 / 8.0).sin()).collect();
+        let silence = vec![0.0; 256];
+        for style in [Style::Waveform, Style::Vectorscope] {
+            let visualizer = StyleVisualizer::new(Config { style, ..config.clone() });
+            let draw = |samples: &[f32]| {
+                let mut frame = draw_background(&config, None);
+                let stereo_samples = Some((samples, samples));
+                let data = FrameData { waveform: samples, stereo_samples, ..Default::default() };
+                visualizer.draw(&mut frame, &analysis(vec![0.0; 4]), &data);
+                frame
+            };
+            assert_ne!(draw(&wave), draw(&silence), "{:?} draws the same with and without a signal", style);
+        }
+    }
+
+    #[test]
+    fn render_workers_share_one_visualizer() {
+        let config = Config { width: 32, height: 32, bars: 4, spectrum_height: 16, ..Default::default() };
+        let visualizer: &dyn Visualizer = &StyleVisualizer::new(config.clone());
+        let frames: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|i| {
+                    let config = &config;
+                    scope.spawn(move || {
+                        let mut frame = draw_background(config, None);
+                        visualizer.draw(&mut frame, &analysis(vec![i as f32 / 4.0; 4]), &FrameData::default());
+                        frame
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+        });
+        assert_eq!(frames[0], draw_background(&config, None));
+        assert_ne!(frames[3], frames[0]);
+    }
+}
@@ -1,29 +1,58 @@
 //! PCM → WAV output (hound)
 
+use crate::error::SpectrumError;
+
 /// Write mono f32 samples (-1.0 to 1.0) to a WAV file.
 pub fn write_wav(
     path: &std::path::Path,
     samples: &[f32],
     sample_rate: u32,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut writer = hound::WavWriter::create(path, spec)?;
-    for &s in samples {
-        let sample_i16 = (s.clamp(-1.0, 1.0) * 32767.0) as i16;
-        writer.write_sample(sample_i16)?;
+) -> Result<(), SpectrumError> {
+    let mut wav = WavStream::create(path, sample_rate)?;
+    wav.push(samples)?;
+    wav.finish()
+}
+
+/// A mono 16-bit WAV file written as the samples arrive, so they needn't be kept until the end.
+pub struct WavStream {
+    path: std::path::PathBuf,
+    writer: hound::WavWriter<std::io::BufWriter<std::fs::File>>,
+}
+
+impl WavStream {
+    pub fn create(path: &std::path::Path, sample_rate: u32) -> Result<Self, SpectrumError> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec).map_err(|e| write_error(path, e))?;
+        Ok(Self { path: path.to_path_buf(), writer })
     }
-    writer.finalize()?;
-    Ok(())
+
+    /// Append mono f32 samples, clamped to -1.0 to 1.0.
+    pub fn push(&mut self, samples: &[f32]) -> Result<(), SpectrumError> {
+        for &s in samples {
+            let sample_i16 = (s.clamp(-1.0, 1.0) * 32767.0) as i16;
+            self.writer.write_sample(sample_i16).map_err(|e| write_error(&self.path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Write the header's lengths and close the file.
+    pub fn finish(self) -> Result<(), SpectrumError> {
+        self.writer.finalize().map_err(|e| write_error(&self.path, e))
+    }
+}
+
+fn write_error(path: &std::path::Path, e: hound::Error) -> SpectrumError {
+    SpectrumError::WriteFailed { path: path.to_path_buf(), message: e.to_string() }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::write_wav;
+    use super::{write_wav, WavStream};
 
     #[test]
     fn write_wav_roundtrip_channels_rate_samples() {
@@ -64,4 +93,23 @@ mod tests {
 
         std::fs::remove_file(&path).ok();
     }
+
+    #[test]
+    fn a_stream_written_in_chunks_holds_every_sample() {
+        let dir = std::env::temp_dir().join("audio-spectrum-generator-test");
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join(format!("stream-{}.wav", std::process::id()));
+
+        let mut wav = WavStream::create(&path, 8000).unwrap();
+        for chunk in [[0.5f32; 3], [-0.5; 3]] {
+            wav.push(&chunk).unwrap();
+        }
+        wav.finish().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        let read_samples: Vec<i16> = reader.into_samples().filter_map(Result::ok).collect();
+        assert_eq!(read_samples, [16383, 16383, 16383, -16383, -16383, -16383]);
+
+        std::fs::remove_file(&path).ok();
+    }
 }
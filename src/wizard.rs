@@ -0,0 +1,534 @@
+//! `wizard`: an interactive walk through the input, style, colors and resolution with a live sketch of the frame
+//! layout, ending with the command line to run or a config file for --config
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style as TextStyle};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::config::Style;
+use crate::error::SpectrumError;
+use crate::logging::info;
+
+/// A command-line value parser, such as the one for `--bar-color`.
+type Parser<T> = fn(&str) -> Result<T, String>;
+
+/// Sizes Up and Down step through on the resolution step.
+const COMMON_RESOLUTIONS: [&str; 6] = ["1920x1080", "1280x720", "3840x2160", "1080x1920", "1080x1080", "720x1280"];
+
+/// Height of the spectrum band the sketch draws (the `--spectrum-height` default, pixels).
+const SPECTRUM_HEIGHT: u32 = 200;
+
+/// The wizard's questions, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Step {
+    Input,
+    Style,
+    BarColor,
+    BgColor,
+    Resolution,
+    Output,
+    Finish,
+    ConfigPath,
+}
+
+impl Step {
+    const ALL: [Step; 8] = [
+        Step::Input,
+        Step::Style,
+        Step::BarColor,
+        Step::BgColor,
+        Step::Resolution,
+        Step::Output,
+        Step::Finish,
+        Step::ConfigPath,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Step::Input => "Input MP3 file",
+            Step::Style => "Visualization style",
+            Step::BarColor => "Bar color (hex RGB or RGBA)",
+            Step::BgColor => "Background color (hex RGB)",
+            Step::Resolution => "Resolution (Up/Down for common sizes)",
+            Step::Output => "Output video file",
+            Step::Finish => "Print the command, or save a config file",
+            Step::ConfigPath => "Config file to write",
+        }
+    }
+
+    fn next(self) -> Step {
+        Step::ALL[(self as usize + 1).min(Step::ALL.len() - 1)]
+    }
+
+    fn previous(self) -> Step {
+        Step::ALL[(self as usize).saturating_sub(1)]
+    }
+}
+
+/// The options the wizard asked for.
+#[derive(Clone, Debug, PartialEq)]
+struct Answers {
+    input: PathBuf,
+    output: PathBuf,
+    style: Style,
+    /// Colors as typed, so the command line and config file show what the user chose.
+    bar_color: String,
+    bg_color: String,
+    resolution: (u32, u32),
+}
+
+/// How a key press leaves the wizard.
+enum Flow {
+    Continue,
+    Cancel,
+    /// The last question is answered; save the options to the config file if one is given.
+    Done(Option<PathBuf>),
+}
+
+struct Wizard {
+    parse_color: Parser<[u8; 4]>,
+    parse_resolution: Parser<(u32, u32)>,
+    step: Step,
+    input: String,
+    style: usize,
+    bar_color: String,
+    bg_color: String,
+    resolution: String,
+    output: String,
+    save_config: bool,
+    config_path: String,
+    error: Option<String>,
+}
+
+/// Run the wizard in the terminal, then print the command line (and write the config file if one was chosen).
+pub fn run(
+    parse_color: Parser<[u8; 4]>,
+    parse_resolution: Parser<(u32, u32)>,
+) -> Result<(), SpectrumError> {
+    let mut wizard = Wizard {
+        parse_color,
+        parse_resolution,
+        step: Step::Input,
+        input: String::new(),
+        style: 0,
+        bar_color: "000000".into(),
+        bg_color: "ffffff".into(),
+        resolution: COMMON_RESOLUTIONS[0].into(),
+        output: String::new(),
+        save_config: false,
+        config_path: "spectrum.toml".into(),
+        error: None,
+    };
+    let mut terminal = ratatui::init();
+    let flow = wizard.event_loop(&mut terminal);
+    ratatui::restore();
+    let config = match flow.map_err(SpectrumError::Terminal)? {
+        Flow::Done(config) => config,
+        _ => {
+            info!("Wizard cancelled");
+            return Ok(());
+        }
+    };
+    let answers = wizard.answers();
+    if let Some(ref path) = config {
+        let failed = |message: String| SpectrumError::WriteFailed { path: path.clone(), message };
+        let content = toml::to_string(&config_table(&answers)).map_err(|e| failed(e.to_string()))?;
+        std::fs::write(path, content).map_err(|e| failed(e.to_string()))?;
+        info!("Saved the options to {:?}", path);
+    }
+    println!("{}", command_line(&answers, config.as_deref()));
+    Ok(())
+}
+
+impl Wizard {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<Flow> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match self.handle_key(key) {
+                    Flow::Continue => {}
+                    flow => return Ok(flow),
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Flow {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Flow::Cancel;
+        }
+        match key.code {
+            KeyCode::Esc if self.step == Step::Input => return Flow::Cancel,
+            KeyCode::Esc => {
+                self.step = self.step.previous();
+                self.error = None;
+            }
+            KeyCode::Enter => match self.check() {
+                Err(e) => self.error = Some(e),
+                Ok(()) => {
+                    self.error = None;
+                    if self.step == Step::Input && self.output.is_empty() {
+                        self.output = Path::new(&self.input).with_extension("mp4").display().to_string();
+                    }
+                    match self.step {
+                        Step::Finish if !self.save_config => return Flow::Done(None),
+                        Step::ConfigPath => return Flow::Done(Some(PathBuf::from(&self.config_path))),
+                        step => self.step = step.next(),
+                    }
+                }
+            },
+            KeyCode::Up | KeyCode::Down => {
+                let down = key.code == KeyCode::Down;
+                match self.step {
+                    Step::Style => self.style = step_index(self.style, Style::value_variants().len(), down),
+                    Step::Finish => self.save_config = !self.save_config,
+                    Step::Resolution => {
+                        let current = COMMON_RESOLUTIONS.iter().position(|r| *r == self.resolution);
+                        let i = current.map_or(0, |i| step_index(i, COMMON_RESOLUTIONS.len(), down));
+                        self.resolution = COMMON_RESOLUTIONS[i].into();
+                    }
+                    _ => {}
+                }
+            }
+            KeyCode::Backspace => {
+                if let Some(field) = self.field() {
+                    field.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(field) = self.field() {
+                    field.push(c);
+                }
+            }
+            _ => {}
+        }
+        Flow::Continue
+    }
+
+    /// The text typed on the current step, when it asks for text.
+    fn field(&mut self) -> Option<&mut String> {
+        match self.step {
+            Step::Input => Some(&mut self.input),
+            Step::BarColor => Some(&mut self.bar_color),
+            Step::BgColor => Some(&mut self.bg_color),
+            Step::Resolution => Some(&mut self.resolution),
+            Step::Output => Some(&mut self.output),
+            Step::ConfigPath => Some(&mut self.config_path),
+            Step::Style | Step::Finish => None,
+        }
+    }
+
+    /// Check the current step's answer before moving on.
+    fn check(&self) -> Result<(), String> {
+        match self.step {
+            Step::Input if !Path::new(&self.input).is_file() => Err(format!("no such file: {:?}", self.input)),
+            Step::BarColor => (self.parse_color)(&self.bar_color).map(drop),
+            Step::BgColor => (self.parse_color)(&self.bg_color).map(drop),
+            Step::Resolution => (self.parse_resolution)(&self.resolution).map(drop),
+            Step::Output | Step::ConfigPath if self.current_text().is_empty() => Err("enter a file name".into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn answers(&self) -> Answers {
+        Answers {
+            input: PathBuf::from(&self.input),
+            output: PathBuf::from(&self.output),
+            style: Style::value_variants()[self.style],
+            bar_color: self.bar_color.clone(),
+            bg_color: self.bg_color.clone(),
+            resolution: (self.parse_resolution)(&self.resolution).unwrap_or((1920, 1080)),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, help] = Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Length(44), Constraint::Fill(1)]).areas(main);
+        let [steps, question] = Layout::vertical([Constraint::Length(10), Constraint::Fill(1)]).areas(left);
+
+        let shown_steps = Step::ALL.iter().filter(|step| **step != Step::ConfigPath || self.save_config);
+        let step_lines: Vec<Line> = shown_steps
+            .map(|&step| {
+                let (mark, text) = match step.cmp(&self.step) {
+                    std::cmp::Ordering::Less => ("✓ ", TextStyle::new().dim()),
+                    std::cmp::Ordering::Equal => ("› ", TextStyle::new().bold()),
+                    std::cmp::Ordering::Greater => ("  ", TextStyle::new()),
+                };
+                Line::styled(format!("{}{}", mark, step.title()), text)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(step_lines).block(Block::bordered().title(" Wizard ")), steps);
+
+        let block = Block::bordered().title(format!(" {} ", self.step.title()));
+        match self.step {
+            Step::Style => {
+                let names = Style::value_variants().iter().map(|style| {
+                    let value = style.to_possible_value().expect("no hidden styles");
+                    value.get_name().to_string()
+                });
+                let mut state = ListState::default().with_selected(Some(self.style));
+                frame.render_stateful_widget(choice_list(names, block), question, &mut state);
+            }
+            Step::Finish => {
+                let mut state = ListState::default().with_selected(Some(usize::from(self.save_config)));
+                let choices = ["Print the command line", "Save a config file for --config"];
+                frame.render_stateful_widget(choice_list(choices, block), question, &mut state);
+            }
+            _ => {
+                let mut lines = vec![Line::raw(format!("{}_", self.current_text()))];
+                if let Some(ref error) = self.error {
+                    lines.push(Line::raw(""));
+                    lines.push(Line::styled(error.clone(), TextStyle::new().red()));
+                }
+                frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }).block(block), question);
+            }
+        }
+
+        self.draw_preview(frame, right);
+        let keys = "Enter: next   Esc: back   Up/Down: choose   Ctrl-C: quit";
+        frame.render_widget(Line::styled(keys, TextStyle::new().dim()), help);
+    }
+
+    fn current_text(&self) -> &str {
+        match self.step {
+            Step::Input => &self.input,
+            Step::BarColor => &self.bar_color,
+            Step::BgColor => &self.bg_color,
+            Step::Resolution => &self.resolution,
+            Step::Output => &self.output,
+            Step::ConfigPath => &self.config_path,
+            Step::Style | Step::Finish => "",
+        }
+    }
+
+    /// Sketch of the frame with the chosen style, size and colors; invalid answers show the defaults until fixed.
+    fn draw_preview(&self, frame: &mut Frame, area: Rect) {
+        let style = Style::value_variants()[self.style];
+        let resolution = (self.parse_resolution)(&self.resolution).unwrap_or((1920, 1080));
+        let bar = (self.parse_color)(&self.bar_color).unwrap_or([0, 0, 0, 255]);
+        let bg = (self.parse_color)(&self.bg_color).unwrap_or([255, 255, 255, 255]);
+        let name = style.to_possible_value().expect("no hidden styles");
+        let (width, height) = resolution;
+        let block = Block::bordered().title(format!(" Preview: {} at {}x{} ", name.get_name(), width, height));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let lines = sketch(style, resolution, inner.width as usize, inner.height as usize);
+        let (cols, rows) = (lines.first().map_or(0, |line| line.chars().count()) as u16, lines.len() as u16);
+        let canvas = Rect {
+            x: inner.x + (inner.width - cols) / 2,
+            y: inner.y + (inner.height - rows) / 2,
+            width: cols,
+            height: rows,
+        };
+        let colors = TextStyle::new().fg(Color::Rgb(bar[0], bar[1], bar[2])).bg(Color::Rgb(bg[0], bg[1], bg[2]));
+        frame.render_widget(Paragraph::new(lines.into_iter().map(Line::raw).collect::<Vec<_>>()).style(colors), canvas);
+    }
+}
+
+/// `items` in `block`, the selected one marked.
+fn choice_list<'a, T: Into<ListItem<'a>>>(items: impl IntoIterator<Item = T>, block: Block<'a>) -> List<'a> {
+    List::new(items).block(block).highlight_symbol("› ").highlight_style(TextStyle::new().bold())
+}
+
+/// The index after (or before) `i` among `len` choices, wrapping around.
+fn step_index(i: usize, len: usize, down: bool) -> usize {
+    if down { (i + 1) % len } else { (i + len - 1) % len }
+}
+
+/// A made-up spectrum level in 0..=1 for bar `i` of `n`: falling towards the treble, with some ripple.
+fn sample_level(i: usize, n: usize) -> f32 {
+    let x = i as f32 / n.max(1) as f32;
+    ((1.0 - 0.7 * x) * (0.45 + 0.55 * (x * 23.0).sin().abs())).clamp(0.05, 1.0)
+}
+
+/// Character sketch of a `resolution` frame drawn with `style`, as large as fits in `cols` x `rows` terminal cells
+/// (taken to be twice as tall as wide). The spectrum band sits at the bottom like the default layout; radial and
+/// ring are drawn around the frame's center.
+fn sketch(style: Style, resolution: (u32, u32), cols: usize, rows: usize) -> Vec<String> {
+    let (width, height) = (resolution.0.max(1) as f32, resolution.1.max(1) as f32);
+    let (mut w, mut h) = (cols as f32, cols as f32 * height / width / 2.0);
+    if h > rows as f32 {
+        (w, h) = (rows as f32 * 2.0 * width / height, rows as f32);
+    }
+    let (w, h) = ((w.round() as usize).clamp(1, cols.max(1)), (h.round() as usize).clamp(1, rows.max(1)));
+    let mut grid = vec![vec![' '; w]; h];
+    let band = ((SPECTRUM_HEIGHT as f32 / height * h as f32).round() as usize).clamp(1, h);
+    let top = h - band;
+    let mid = top + band / 2;
+    let level_rows = |x: usize| ((sample_level(x, w) * band as f32).round() as usize).clamp(1, band);
+    match style {
+        Style::Bars | Style::Mirror | Style::Led => {
+            for x in (0..w).step_by(2) {
+                let len = level_rows(x);
+                let (start, c) = match style {
+                    Style::Bars => (top + (band - len) / 2, '█'),
+                    Style::Led => (h - len, '■'),
+                    _ => (mid.saturating_sub(len / 2), '█'),
+                };
+                for (y, row) in grid.iter_mut().enumerate().skip(start).take(len) {
+                    row[x] = if style == Style::Mirror && y > mid { '▒' } else { c };
+                }
+            }
+        }
+        Style::Line | Style::Area | Style::Dots => {
+            for x in 0..w {
+                let y = h - level_rows(x);
+                match style {
+                    Style::Line => grid[y][x] = '•',
+                    Style::Dots if x % 2 == 0 => grid[y][x] = '●',
+                    Style::Area => grid[y..].iter_mut().for_each(|row| row[x] = '█'),
+                    _ => {}
+                }
+            }
+        }
+        Style::Waveform | Style::Timeline => {
+            let swing = |x: usize| (x as f32 / w as f32 * std::f32::consts::TAU * 3.0).sin() * sample_level(x, w);
+            let ys: Vec<usize> = (0..w).map(|x| (mid as f32 - swing(x) * band as f32 / 2.0).round() as usize).collect();
+            for (x, y) in ys.into_iter().enumerate() {
+                grid[y.clamp(top, h - 1)][x] = '•';
+            }
+            if style == Style::Timeline {
+                grid[top..].iter_mut().for_each(|row| row[w / 3] = '│');
+            }
+        }
+        Style::Spectrogram => {
+            const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+            for (y, row) in grid.iter_mut().enumerate().skip(top) {
+                for (x, cell) in row.iter_mut().enumerate() {
+                    let heat = sample_level(x + y, w + band) * (y + 1 - top) as f32 / band as f32;
+                    *cell = SHADES[((heat * SHADES.len() as f32) as usize).min(SHADES.len() - 1)];
+                }
+            }
+        }
+        Style::Radial | Style::Ring => {
+            // Pixel units: the inner radius is a sixth of the shorter side, as in `draw::radial_geometry`.
+            let (cx, cy, r0) = (width / 2.0, height / 2.0, width.min(height) / 6.0);
+            let spokes = 48;
+            for i in 0..spokes {
+                let theta = i as f32 / spokes as f32 * std::f32::consts::TAU;
+                let level = sample_level(i, spokes);
+                let (from, to) = match style {
+                    Style::Radial => (r0, r0 + level * SPECTRUM_HEIGHT as f32),
+                    _ => (r0 + (level - 0.5) * SPECTRUM_HEIGHT as f32 * 0.5, 0.0),
+                };
+                let mut r = from;
+                loop {
+                    let x = ((cx + r * theta.sin()) / width * w as f32) as usize;
+                    let y = ((cy - r * theta.cos()) / height * h as f32) as usize;
+                    if x < w && y < h {
+                        grid[y][x] = if style == Style::Radial { '█' } else { '•' };
+                    }
+                    r += height / h as f32 / 2.0;
+                    if r > to {
+                        break;
+                    }
+                }
+            }
+        }
+        Style::Vectorscope => {
+            let (cx, half) = (w / 2, band as f32 / 2.0);
+            for i in 0..band * 6 {
+                let t = i as f32 * 2.399;
+                let spread = (i as f32 / (band * 6) as f32).sqrt();
+                let x = cx as f32 + t.cos() * spread * half * 2.0;
+                let y = mid as f32 + t.sin() * spread * half;
+                if (x as usize) < w {
+                    grid[(y as usize).clamp(top, h - 1)][x as usize] = '·';
+                }
+            }
+        }
+    }
+    grid.into_iter().map(|row| row.into_iter().collect()).collect()
+}
+
+/// The command line for `answers`; with a config file, the options are in the file and only the input is given.
+fn command_line(answers: &Answers, config: Option<&Path>) -> String {
+    let mut words = vec!["audio-spectrum-generator".to_string()];
+    match config {
+        Some(path) => words.extend(["--config".into(), path.display().to_string()]),
+        None => {
+            for (flag, value) in config_table(answers) {
+                words.push(format!("--{}", flag));
+                words.push(value.as_str().unwrap_or_default().to_string());
+            }
+        }
+    }
+    words.push(answers.input.display().to_string());
+    words.iter().map(|word| shell_word(word)).collect::<Vec<_>>().join(" ")
+}
+
+/// The options in `answers` other than the input (which a config file can't hold), keyed by long flag name.
+fn config_table(answers: &Answers) -> toml::Table {
+    let style = answers.style.to_possible_value().expect("no hidden styles");
+    let (width, height) = answers.resolution;
+    let mut table = toml::Table::new();
+    table.insert("output".into(), answers.output.display().to_string().into());
+    table.insert("style".into(), style.get_name().into());
+    table.insert("resolution".into(), format!("{}x{}", width, height).into());
+    table.insert("bar-color".into(), answers.bar_color.clone().into());
+    table.insert("bg-color".into(), answers.bg_color.clone().into());
+    table
+}
+
+/// `word` as the shell reads it back: as is when it only has safe characters, else in single quotes.
+fn shell_word(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_line, config_table, sketch, Answers};
+    use crate::config::Style;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn sketch_keeps_the_aspect_and_puts_the_band_at_the_bottom() {
+        let lines = sketch(Style::Bars, (1920, 1080), 80, 40);
+        assert_eq!(lines.len(), 23);
+        assert!(lines.iter().all(|line| line.chars().count() == 80));
+        // A 200 px band in 1080 px is 4 of 23 rows.
+        assert!(lines[..19].iter().all(|line| line.trim().is_empty()));
+        assert!(lines[19..].iter().any(|line| line.contains('█')));
+
+        let portrait = sketch(Style::Radial, (1080, 1920), 80, 40);
+        assert_eq!((portrait.len(), portrait[0].chars().count()), (40, 45));
+        assert!(portrait[20].contains('█'));
+    }
+
+    #[test]
+    fn command_line_quotes_words_and_config_holds_the_rest() {
+        let answers = Answers {
+            input: PathBuf::from("my song.mp3"),
+            output: PathBuf::from("out.mp4"),
+            style: Style::Mirror,
+            bar_color: "#ff6600".into(),
+            bg_color: "1a1a2e".into(),
+            resolution: (1280, 720),
+        };
+        assert_eq!(
+            command_line(&answers, None),
+            "audio-spectrum-generator --bar-color '#ff6600' --bg-color 1a1a2e --output out.mp4 --resolution 1280x720 \
+             --style mirror 'my song.mp3'"
+        );
+        assert_eq!(
+            command_line(&answers, Some(Path::new("spectrum.toml"))),
+            "audio-spectrum-generator --config spectrum.toml 'my song.mp3'"
+        );
+        let table = config_table(&answers);
+        assert_eq!(table.keys().collect::<Vec<_>>(), ["bar-color", "bg-color", "output", "resolution", "style"]);
+    }
+}